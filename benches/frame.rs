@@ -0,0 +1,191 @@
+//! The stages of the frame pipeline on the [`Scene`]s: tessellation, buffer updates, and the
+//! encoding and submission of the render pass, then whole headless frames.
+//!
+//! `cargo bench --bench frame -- shapes` runs one scene. Without a GPU, the GPU stages run on
+//! the software adapter, if any.
+
+use std::iter;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use egui_wgpu_x11::egui::{self, ClippedPrimitive, Context, RawInput, Rect};
+use egui_wgpu_x11::renderer::{self, Renderer, ScreenDescriptor};
+use egui_wgpu_x11::scenes::Scene;
+use egui_wgpu_x11::{wgpu, GpuOptions, HeadlessOverlay, OverlayApp};
+
+const SIZE: [u32; 2] = [1280, 720];
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Bgra8UnormSrgb;
+
+/// The shapes of a frame of `scene`, once its textures are loaded and windows sized.
+fn scene_shapes(ctx: &Context, scene: Scene) -> egui::FullOutput {
+    let mut app = scene.app();
+    let input = || RawInput {
+        screen_rect: Some(Rect::from_min_size(
+            Default::default(),
+            egui::vec2(SIZE[0] as f32, SIZE[1] as f32),
+        )),
+        ..Default::default()
+    };
+    // windows take a frame to size themselves
+    let mut first = ctx.run(input(), |ctx| app.ui(ctx));
+    let mut output = ctx.run(input(), |ctx| app.ui(ctx));
+    // all the textures, for the renderers
+    first.textures_delta.append(output.textures_delta);
+    output.textures_delta = first.textures_delta;
+    output
+}
+
+fn tessellate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tessellate");
+    for scene in Scene::ALL {
+        let ctx = Context::default();
+        let output = scene_shapes(&ctx, scene);
+        group.bench_function(scene.name(), |b| {
+            b.iter_batched(
+                || output.shapes.clone(),
+                |shapes| ctx.tessellate(shapes),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+/// A device, on the software adapter when there is no GPU.
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(wgpu::Backends::all());
+    let adapter = [false, true]
+        .into_iter()
+        .find_map(|force_fallback_adapter| {
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                force_fallback_adapter,
+                compatible_surface: None,
+            }))
+        })?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None)).ok()
+}
+
+/// A renderer with the textures of a scene, its paint jobs and a target to draw them into.
+struct GpuScene {
+    renderer: Box<dyn Renderer>,
+    paint_jobs: Vec<ClippedPrimitive>,
+    view: wgpu::TextureView,
+}
+
+impl GpuScene {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, scene: Scene) -> Self {
+        let ctx = Context::default();
+        let output = scene_shapes(&ctx, scene);
+        let mut renderer = renderer::egui_wgpu_backend(device, FORMAT, 1);
+        renderer
+            .update_textures(device, queue, &output.textures_delta)
+            .expect("textures uploaded");
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("bench target"),
+            size: wgpu::Extent3d {
+                width: SIZE[0],
+                height: SIZE[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        });
+        Self {
+            renderer,
+            paint_jobs: ctx.tessellate(output.shapes),
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+
+    fn encode_submit(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("bench encoder"),
+        });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("bench pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.renderer
+                .render(&mut pass, &self.paint_jobs, &SCREEN)
+                .expect("render pass recorded");
+        }
+        queue.submit(iter::once(encoder.finish()));
+        // the GPU time counts too
+        device.poll(wgpu::Maintain::Wait);
+    }
+}
+
+const SCREEN: ScreenDescriptor = ScreenDescriptor {
+    size_in_pixels: SIZE,
+    pixels_per_point: 1.,
+};
+
+fn gpu_stages(c: &mut Criterion) {
+    let Some((device, queue)) = device() else {
+        eprintln!("no adapter, skipping the GPU stages");
+        return;
+    };
+    let mut scenes: Vec<_> = Scene::ALL
+        .into_iter()
+        .map(|scene| (scene, GpuScene::new(&device, &queue, scene)))
+        .collect();
+
+    let mut group = c.benchmark_group("update_buffers");
+    for (scene, gpu_scene) in &mut scenes {
+        group.bench_function(scene.name(), |b| {
+            b.iter(|| {
+                gpu_scene
+                    .renderer
+                    .update_buffers(&device, &queue, &gpu_scene.paint_jobs, &SCREEN)
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("encode_submit");
+    for (scene, gpu_scene) in &scenes {
+        group.bench_function(scene.name(), |b| {
+            b.iter(|| gpu_scene.encode_submit(&device, &queue))
+        });
+    }
+    group.finish();
+}
+
+/// Whole frames, from the UI to the submission.
+fn headless_frame(c: &mut Criterion) {
+    let overlay = HeadlessOverlay::new(SIZE[0], SIZE[1]).or_else(|_| {
+        let gpu = GpuOptions {
+            force_fallback_adapter: true,
+            ..Default::default()
+        };
+        HeadlessOverlay::with_gpu(SIZE[0], SIZE[1], gpu)
+    });
+    let Ok(mut overlay) = overlay else {
+        eprintln!("no adapter, skipping the headless frames");
+        return;
+    };
+    let mut group = c.benchmark_group("frame");
+    for scene in Scene::ALL {
+        let mut app = scene.app();
+        overlay.render(&mut app).expect("frame rendered");
+        group.bench_function(scene.name(), |b| {
+            b.iter(|| overlay.render(&mut app).expect("frame rendered"))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tessellate, gpu_stages, headless_frame);
+criterion_main!(benches);
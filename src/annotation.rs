@@ -0,0 +1,198 @@
+//! Drawing on the screen, like gromit-mpx: freehand strokes, arrows and rectangles over all
+//! the windows, toggled by a hotkey.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::annotation::Annotation;
+//! use egui_wgpu_x11::{egui::Modifiers, keysyms, Overlay};
+//!
+//! let mut overlay = Overlay::fullscreen_overlay()?;
+//! Annotation::toggle_with(&mut overlay, Modifiers::NONE, keysyms::KEY_F9)?;
+//! overlay.run(Annotation::default())?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! Between the toggles, input goes through to the windows underneath, the drawings stay.
+
+use anyhow::Result;
+use egui::{
+    Align2, Color32, Context, Frame, Modifiers, Painter, Pos2, Rect, Sense, Shape, Stroke, Vec2,
+};
+
+use crate::{InputShape, Overlay, OverlayApp};
+
+/// What dragging the pointer draws.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tool {
+    #[default]
+    Pen,
+    Arrow,
+    Rectangle,
+}
+
+/// A finished or ongoing drawing.
+struct Mark {
+    tool: Tool,
+    stroke: Stroke,
+    /// every point of pen strokes, the start and end points otherwise
+    points: Vec<Pos2>,
+}
+
+impl Mark {
+    fn paint(&self, painter: &Painter) {
+        match (self.tool, &self.points[..]) {
+            (Tool::Pen, [point]) => {
+                painter.circle_filled(*point, self.stroke.width / 2., self.stroke.color)
+            }
+            (Tool::Pen, points) => {
+                painter.add(Shape::line(points.to_vec(), self.stroke));
+            }
+            (Tool::Arrow, [start, end, ..]) => paint_arrow(painter, *start, *end, self.stroke),
+            (Tool::Rectangle, [start, end, ..]) => {
+                painter.rect_stroke(Rect::from_two_pos(*start, *end), 0., self.stroke)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The annotation app: draws while the overlay catches input, see [`Annotation::toggle_with`].
+pub struct Annotation {
+    marks: Vec<Mark>,
+    /// being dragged
+    current: Option<Mark>,
+    tool: Tool,
+    stroke: Stroke,
+    /// whether the overlay catches input, known after each frame
+    drawing: bool,
+}
+
+impl Default for Annotation {
+    fn default() -> Self {
+        Self {
+            marks: Vec::new(),
+            current: None,
+            tool: Tool::default(),
+            stroke: Stroke::new(4., Color32::RED),
+            drawing: false,
+        }
+    }
+}
+
+impl Annotation {
+    /// Makes `overlay` click-through, and `modifiers` + `keysym` toggle drawing on it.
+    pub fn toggle_with(overlay: &mut Overlay, modifiers: Modifiers, keysym: u32) -> Result<()> {
+        overlay.set_input_shape(InputShape::Window)?;
+        overlay.set_input_passthrough(true)?;
+        overlay.register_hotkey(modifiers, keysym, |overlay| {
+            overlay.set_input_passthrough(!overlay.input_passthrough())?;
+            overlay.context().request_repaint();
+            Ok(())
+        })
+    }
+
+    /// Removes all the drawings.
+    pub fn clear(&mut self) {
+        self.marks.clear();
+        self.current = None;
+    }
+
+    fn toolbar(&mut self, ctx: &Context) {
+        egui::Window::new("Annotate")
+            .anchor(Align2::CENTER_TOP, Vec2::new(0., 8.))
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut self.tool, Tool::Pen, "✏ Pen");
+                    ui.selectable_value(&mut self.tool, Tool::Arrow, "➡ Arrow");
+                    ui.selectable_value(&mut self.tool, Tool::Rectangle, "⬜ Rectangle");
+                    ui.separator();
+                    ui.color_edit_button_srgba(&mut self.stroke.color);
+                    ui.add(egui::Slider::new(&mut self.stroke.width, 1.0..=20.0).text("width"));
+                    ui.separator();
+                    if ui.button("Undo").clicked() {
+                        self.marks.pop();
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.clear();
+                    }
+                });
+            });
+    }
+}
+
+impl OverlayApp for Annotation {
+    fn ui(&mut self, ctx: &Context) {
+        if self.drawing {
+            self.toolbar(ctx);
+        }
+        egui::CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                let sense = if self.drawing {
+                    Sense::drag()
+                } else {
+                    Sense::hover()
+                };
+                let (response, painter) = ui.allocate_painter(ui.available_size(), sense);
+                if self.drawing {
+                    // tells that clicks don't reach the windows underneath
+                    painter.rect_filled(response.rect, 0., Color32::from_black_alpha(24));
+                }
+
+                if let Some(pos) = response.interact_pointer_pos() {
+                    if response.drag_started() {
+                        self.current = Some(Mark {
+                            tool: self.tool,
+                            stroke: self.stroke,
+                            points: vec![pos],
+                        });
+                    } else if let Some(mark) = &mut self.current {
+                        match mark.tool {
+                            // not every pixel, the line looks the same
+                            Tool::Pen if mark.points.last().unwrap().distance(pos) < 1. => {}
+                            Tool::Pen => mark.points.push(pos),
+                            Tool::Arrow | Tool::Rectangle => {
+                                mark.points.truncate(1);
+                                mark.points.push(pos);
+                            }
+                        }
+                    }
+                }
+                if response.drag_released() {
+                    self.marks.extend(self.current.take());
+                }
+
+                for mark in self.marks.iter().chain(&self.current) {
+                    mark.paint(&painter);
+                }
+            });
+    }
+
+    fn update(&mut self, overlay: &mut Overlay) -> Result<()> {
+        let drawing = !overlay.input_passthrough();
+        if drawing != self.drawing {
+            self.drawing = drawing;
+            // with the toolbar, or without
+            overlay.context().request_repaint();
+        }
+        Ok(())
+    }
+}
+
+/// a line from `start` with a head at `end`, sized after the stroke rather than the length
+fn paint_arrow(painter: &Painter, start: Pos2, end: Pos2, stroke: Stroke) {
+    painter.line_segment([start, end], stroke);
+    let vec = end - start;
+    if vec.length() < 1. {
+        return;
+    }
+    let head = vec.normalized() * (stroke.width * 4.).max(12.);
+    let rotate = |angle: f32| {
+        let (sin, cos) = angle.sin_cos();
+        Vec2::new(head.x * cos - head.y * sin, head.x * sin + head.y * cos)
+    };
+    let spread = std::f32::consts::FRAC_PI_6;
+    painter.line_segment([end, end - rotate(spread)], stroke);
+    painter.line_segment([end, end - rotate(-spread)], stroke);
+}
@@ -0,0 +1,310 @@
+//! Overlays following the window of another application, the building block of per-app HUDs
+//! like trainers or subtitles, see [`OverlayBuilder::attach`](crate::OverlayBuilder::attach).
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, EventMask, MapState, Rectangle, Window,
+};
+use x11rb::protocol::Event;
+use x11rb::NONE;
+
+use crate::geometry::Edge;
+use crate::x11::{select_more_events, unselect_events};
+
+/// The window an overlay follows, see [`Attachment`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttachTarget {
+    Window(Window),
+    /// Whichever window has the focus, from `_NET_ACTIVE_WINDOW` as set by EWMH window
+    /// managers. The overlay itself getting it doesn't count.
+    Active,
+}
+
+/// Where an attached overlay goes, placed there by its
+/// [`Geometry`](crate::geometry::Geometry).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AttachSide {
+    /// Over the window, e.g. subtitles at its bottom.
+    #[default]
+    Over,
+    /// Beside the window along its edge, up to the screen edge, e.g. a panel docked to its
+    /// right.
+    Beside(Edge),
+}
+
+/// A window of another application an overlay sticks to: moved and resized with it, hidden
+/// while it is unmapped, e.g. on another workspace or minimized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attachment {
+    pub target: AttachTarget,
+    pub side: AttachSide,
+}
+
+impl Attachment {
+    /// Covers `window`, or the part of it picked by the overlay's geometry.
+    pub fn over(window: Window) -> Self {
+        Self {
+            target: AttachTarget::Window(window),
+            side: AttachSide::Over,
+        }
+    }
+
+    /// Docks the overlay next to `edge` of `window`.
+    pub fn beside(window: Window, edge: Edge) -> Self {
+        Self {
+            target: AttachTarget::Window(window),
+            side: AttachSide::Beside(edge),
+        }
+    }
+
+    /// Covers the focused window, moving along as the focus does.
+    pub fn active() -> Self {
+        Self {
+            target: AttachTarget::Active,
+            side: AttachSide::Over,
+        }
+    }
+
+    pub fn side(mut self, side: AttachSide) -> Self {
+        self.side = side;
+        self
+    }
+
+    /// the area the overlay's geometry places it in, beside `rect` up to the edge of `screen`
+    pub(crate) fn area(&self, rect: Rectangle, screen: Rectangle) -> Rectangle {
+        let (left, top) = (i32::from(rect.x), i32::from(rect.y));
+        let (right, bottom) = (left + i32::from(rect.width), top + i32::from(rect.height));
+        let (screen_right, screen_bottom) = (
+            i32::from(screen.x) + i32::from(screen.width),
+            i32::from(screen.y) + i32::from(screen.height),
+        );
+        let span = |from: i32, to: i32| (to - from).clamp(0, i32::from(u16::MAX)) as u16;
+        match self.side {
+            AttachSide::Over => rect,
+            AttachSide::Beside(Edge::Left) => Rectangle {
+                x: screen.x,
+                width: span(screen.x.into(), left),
+                ..rect
+            },
+            AttachSide::Beside(Edge::Right) => Rectangle {
+                x: right.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+                width: span(right, screen_right),
+                ..rect
+            },
+            AttachSide::Beside(Edge::Top) => Rectangle {
+                y: screen.y,
+                height: span(screen.y.into(), top),
+                ..rect
+            },
+            AttachSide::Beside(Edge::Bottom) => Rectangle {
+                y: bottom.clamp(i16::MIN.into(), i16::MAX.into()) as i16,
+                height: span(bottom, screen_bottom),
+                ..rect
+            },
+        }
+    }
+}
+
+/// Follows the geometry and visibility of the attached window through the `ConfigureNotify`,
+/// `MapNotify` and `UnmapNotify` of it and of its frame, the top-level window reparenting
+/// window managers put it in.
+pub(crate) struct WindowFollower {
+    pub attachment: Attachment,
+    root: Window,
+    win_id: Window,
+    net_active_window: Atom,
+    /// its structure changes selected, `NONE` if gone
+    window: Window,
+    frame: Window,
+    /// where it is in the root window, `None` while not viewable
+    pub rect: Option<Rectangle>,
+}
+
+impl WindowFollower {
+    pub fn new<Conn>(
+        conn: &Conn,
+        root: Window,
+        win_id: Window,
+        attachment: Attachment,
+    ) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let net_active_window = conn
+            .intern_atom(false, b"_NET_ACTIVE_WINDOW")?
+            .reply()?
+            .atom;
+        let mut follower = Self {
+            attachment,
+            root,
+            win_id,
+            net_active_window,
+            window: NONE,
+            frame: NONE,
+            rect: None,
+        };
+        let window = match attachment.target {
+            AttachTarget::Window(window) => window,
+            AttachTarget::Active => {
+                select_more_events(conn, root, EventMask::PROPERTY_CHANGE)?;
+                follower.active_window(conn)?
+            }
+        };
+        follower.follow(conn, window)?;
+        Ok(follower)
+    }
+
+    /// Stops following the window.
+    pub fn stop<Conn>(mut self, conn: &Conn) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        self.unselect(conn);
+        Ok(())
+    }
+
+    /// Returns whether `rect` changed.
+    pub fn handle_event<Conn>(&mut self, conn: &Conn, event: &Event) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        let rect = self.rect;
+        match event {
+            Event::PropertyNotify(e)
+                if self.attachment.target == AttachTarget::Active
+                    && e.window == self.root
+                    && e.atom == self.net_active_window =>
+            {
+                let active = self.active_window(conn)?;
+                self.follow(conn, active)?
+            }
+            Event::ConfigureNotify(e) if self.follows(e.window) => self.rect = self.query(conn)?,
+            Event::MapNotify(e) if self.follows(e.window) => self.rect = self.query(conn)?,
+            Event::UnmapNotify(e) if self.follows(e.window) => self.rect = None,
+            Event::ReparentNotify(e) if e.window == self.window => {
+                // framed, or unframed, by the window manager
+                let window = self.window;
+                self.unselect(conn);
+                self.follow(conn, window)?
+            }
+            Event::DestroyNotify(e) if e.window == self.window => {
+                self.unselect(conn);
+                self.rect = None;
+            }
+            _ => {}
+        }
+        Ok(rect != self.rect)
+    }
+
+    fn follows(&self, window: Window) -> bool {
+        window != NONE && (window == self.window || window == self.frame)
+    }
+
+    /// selects the structure changes of `window` and its frame, instead of the previous ones'
+    fn follow<Conn>(&mut self, conn: &Conn, window: Window) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        // the overlay getting the focus keeps it on the previous window
+        if window == self.window || window == self.win_id {
+            return Ok(());
+        }
+        self.unselect(conn);
+        self.rect = None;
+        if window == NONE {
+            return Ok(());
+        }
+        // gone meanwhile
+        if select_more_events(conn, window, EventMask::STRUCTURE_NOTIFY).is_err() {
+            return Ok(());
+        }
+        self.window = window;
+        let Some(frame) = self.top_level(conn, window)? else {
+            return Ok(());
+        };
+        if frame != window && select_more_events(conn, frame, EventMask::STRUCTURE_NOTIFY).is_ok() {
+            self.frame = frame;
+        }
+        self.rect = self.query(conn)?;
+        Ok(())
+    }
+
+    /// drops the events selected on the windows of another client, which may be gone already
+    fn unselect<Conn>(&mut self, conn: &Conn)
+    where
+        Conn: Connection,
+    {
+        for window in [self.window, self.frame] {
+            if window != NONE {
+                let _ = unselect_events(conn, window, EventMask::STRUCTURE_NOTIFY);
+            }
+        }
+        self.window = NONE;
+        self.frame = NONE;
+    }
+
+    /// where the window is in the root window, `None` unless viewable
+    fn query<Conn>(&self, conn: &Conn) -> Result<Option<Rectangle>>
+    where
+        Conn: Connection,
+    {
+        // errors for windows gone meanwhile
+        let Ok(attributes) = conn.get_window_attributes(self.window)?.reply() else {
+            return Ok(None);
+        };
+        if attributes.map_state != MapState::VIEWABLE {
+            return Ok(None);
+        }
+        let Ok(geometry) = conn.get_geometry(self.window)?.reply() else {
+            return Ok(None);
+        };
+        let Ok(position) = conn
+            .translate_coordinates(self.window, self.root, 0, 0)?
+            .reply()
+        else {
+            return Ok(None);
+        };
+        Ok(Some(Rectangle {
+            x: position.dst_x,
+            y: position.dst_y,
+            width: geometry.width,
+            height: geometry.height,
+        }))
+    }
+
+    /// the ancestor of `window` right under the root window, `None` if gone
+    fn top_level<Conn>(&self, conn: &Conn, mut window: Window) -> Result<Option<Window>>
+    where
+        Conn: Connection,
+    {
+        loop {
+            let Ok(tree) = conn.query_tree(window)?.reply() else {
+                return Ok(None);
+            };
+            if tree.parent == self.root || tree.parent == NONE {
+                return Ok(Some(window));
+            }
+            window = tree.parent;
+        }
+    }
+
+    fn active_window<Conn>(&self, conn: &Conn) -> Result<Window>
+    where
+        Conn: Connection,
+    {
+        Ok(conn
+            .get_property(
+                false,
+                self.root,
+                self.net_active_window,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()?
+            .value32()
+            .and_then(|mut windows| windows.next())
+            .unwrap_or(NONE))
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet};
+
+use egui::epaint::{ImageDelta, Primitive};
+use egui::{ClippedPrimitive, TextureId, TexturesDelta};
+
+/// GPU memory of the egui textures, see [`Overlay::set_texture_budget`].
+///
+/// [`Overlay::set_texture_budget`]: crate::Overlay::set_texture_budget
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureMemory {
+    /// Of the textures on the GPU, 4 per texel.
+    pub bytes: usize,
+    pub budget: Option<usize>,
+    /// Images freed from the GPU, uploaded again when drawn.
+    pub evicted: usize,
+}
+
+/// told when the textures drawn go over the budget, see
+/// [`Overlay::set_texture_budget_handler`](crate::Overlay::set_texture_budget_handler)
+pub(crate) type BudgetHandler = Box<dyn FnMut(&TextureMemory)>;
+
+/// Frees the least recently drawn egui images from the GPU past the budget, their copies kept
+/// to upload them again once drawn. The font atlas stays.
+pub(crate) struct TextureBudget {
+    /// in bytes, `None` for no eviction
+    pub budget: Option<usize>,
+    frame: u64,
+    last_drawn: HashMap<TextureId, u64>,
+    evicted: HashSet<TextureId>,
+    /// reported once, until back under the budget
+    over: bool,
+}
+
+impl TextureBudget {
+    pub fn new() -> Self {
+        Self {
+            budget: None,
+            frame: 0,
+            last_drawn: HashMap::new(),
+            evicted: HashSet::new(),
+            over: false,
+        }
+    }
+
+    /// Uploads again, through `delta`, the evicted textures drawn by `paint_jobs` or updated,
+    /// then evicts the least recently drawn ones while over the budget. Returns the memory
+    /// when the textures drawn alone go over it.
+    pub fn apply(
+        &mut self,
+        paint_jobs: &[ClippedPrimitive],
+        delta: &mut TexturesDelta,
+        textures: &HashMap<TextureId, ImageDelta>,
+    ) -> Option<TextureMemory> {
+        self.frame += 1;
+        // freed by egui, not to be freed twice
+        let evicted = &mut self.evicted;
+        delta.free.retain(|id| !evicted.remove(id));
+        for id in &delta.free {
+            self.last_drawn.remove(id);
+        }
+
+        // the wgpu textures are the app's
+        let drawn = paint_jobs.iter().filter_map(|job| match &job.primitive {
+            Primitive::Mesh(mesh) if matches!(mesh.texture_id, TextureId::Managed(_)) => {
+                Some(mesh.texture_id)
+            }
+            _ => None,
+        });
+        for id in drawn {
+            self.last_drawn.insert(id, self.frame);
+        }
+        let uploads: Vec<TextureId> = self
+            .evicted
+            .iter()
+            .copied()
+            .filter(|id| self.last_drawn.get(id) == Some(&self.frame) || updates(delta, *id))
+            .collect();
+        for id in uploads {
+            // whole, partial updates need the texture on the GPU
+            delta.set.retain(|(set, _)| *set != id);
+            if let Some(texture) = textures.get(&id) {
+                delta.set.push((id, texture.clone()));
+            }
+            self.evicted.remove(&id);
+        }
+
+        let Some(budget) = self.budget else {
+            self.over = false;
+            return None;
+        };
+        let mut bytes = self.bytes(textures);
+        if bytes > budget {
+            let mut candidates: Vec<(u64, TextureId)> = textures
+                .keys()
+                .filter(|id| self.evictable(**id, delta))
+                .map(|id| (self.last_drawn.get(id).copied().unwrap_or(0), *id))
+                .collect();
+            candidates.sort_unstable();
+            for (_, id) in candidates {
+                if bytes <= budget {
+                    break;
+                }
+                bytes -= texture_bytes(&textures[&id]);
+                self.evicted.insert(id);
+                delta.free.push(id);
+            }
+        }
+        let over = bytes > budget;
+        let exceeded = over && !self.over;
+        self.over = over;
+        exceeded.then(|| self.memory(textures))
+    }
+
+    /// the images on the GPU, not drawn in this frame nor updated
+    fn evictable(&self, id: TextureId, delta: &TexturesDelta) -> bool {
+        // 0 is the font atlas
+        matches!(id, TextureId::Managed(n) if n != 0)
+            && !self.evicted.contains(&id)
+            && self.last_drawn.get(&id) != Some(&self.frame)
+            && !updates(delta, id)
+    }
+
+    /// of the textures on the GPU
+    pub fn bytes(&self, textures: &HashMap<TextureId, ImageDelta>) -> usize {
+        textures
+            .iter()
+            .filter(|(id, _)| !self.evicted.contains(id))
+            .map(|(_, texture)| texture_bytes(texture))
+            .sum()
+    }
+
+    pub fn memory(&self, textures: &HashMap<TextureId, ImageDelta>) -> TextureMemory {
+        TextureMemory {
+            bytes: self.bytes(textures),
+            budget: self.budget,
+            evicted: self.evicted.len(),
+        }
+    }
+
+    /// After all the textures were uploaded again, to a new device.
+    pub fn reset(&mut self) {
+        self.evicted.clear();
+        self.over = false;
+    }
+}
+
+fn updates(delta: &TexturesDelta, id: TextureId) -> bool {
+    delta.set.iter().any(|(set, _)| *set == id)
+}
+
+fn texture_bytes(texture: &ImageDelta) -> usize {
+    texture.image.width() * texture.image.height() * 4
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::epaint::Mesh;
+    use egui::{Color32, ColorImage, Rect, TextureFilter};
+
+    use super::*;
+
+    const FONT: TextureId = TextureId::Managed(0);
+
+    fn id(n: u64) -> TextureId {
+        TextureId::Managed(n)
+    }
+
+    /// the font atlas, 256 bytes, and images 1 to 4, 64 bytes each
+    fn textures() -> HashMap<TextureId, ImageDelta> {
+        std::iter::once((FONT, [8, 8]))
+            .chain((1..=4).map(|n| (id(n), [4, 4])))
+            .map(|(id, size)| {
+                let image = ColorImage::new(size, Color32::WHITE);
+                (id, ImageDelta::full(image, TextureFilter::Linear))
+            })
+            .collect()
+    }
+
+    fn draw(ids: &[TextureId]) -> Vec<ClippedPrimitive> {
+        ids.iter()
+            .map(|&id| ClippedPrimitive {
+                clip_rect: Rect::EVERYTHING,
+                primitive: Primitive::Mesh(Mesh::with_texture(id)),
+            })
+            .collect()
+    }
+
+    /// a frame drawing `ids`, with the textures freed from the GPU
+    fn frame(
+        budget: &mut TextureBudget,
+        ids: &[TextureId],
+        textures: &HashMap<TextureId, ImageDelta>,
+    ) -> (TexturesDelta, Option<TextureMemory>) {
+        let mut delta = TexturesDelta::default();
+        let exceeded = budget.apply(&draw(ids), &mut delta, textures);
+        (delta, exceeded)
+    }
+
+    #[test]
+    fn least_recently_drawn_evicted_first() {
+        let textures = textures();
+        let mut budget = TextureBudget::new();
+        let _ = frame(&mut budget, &[FONT, id(1), id(2), id(3), id(4)], &textures);
+        let _ = frame(&mut budget, &[id(3), id(1)], &textures);
+        let _ = frame(&mut budget, &[id(4)], &textures);
+
+        // room for the font and two images
+        budget.budget = Some(256 + 128);
+        let (delta, exceeded) = frame(&mut budget, &[id(4)], &textures);
+        assert_eq!(delta.free, [id(2), id(1)]);
+        assert!(exceeded.is_none());
+        assert_eq!(budget.bytes(&textures), 256 + 128);
+
+        // uploaded again when drawn, evicting the next least recently drawn
+        let (delta, _) = frame(&mut budget, &[id(2)], &textures);
+        assert!(delta.set.iter().map(|(id, _)| *id).eq([id(2)]));
+        assert_eq!(delta.free, [id(3)]);
+        assert_eq!(budget.memory(&textures).evicted, 2);
+    }
+
+    #[test]
+    fn drawn_textures_and_the_font_stay() {
+        let textures = textures();
+        let mut budget = TextureBudget::new();
+        budget.budget = Some(64);
+        let (delta, exceeded) = frame(&mut budget, &[id(1), id(2)], &textures);
+        // the images not drawn, the font atlas staying over the budget
+        let mut freed = delta.free;
+        freed.sort_unstable_by_key(|id| format!("{:?}", id));
+        assert_eq!(freed, [id(3), id(4)]);
+        let memory = exceeded.expect("over the budget");
+        assert_eq!(memory.bytes, 256 + 128);
+        assert_eq!(memory.evicted, 2);
+
+        // reported once, until back under the budget
+        let (_, exceeded) = frame(&mut budget, &[id(1), id(2)], &textures);
+        assert!(exceeded.is_none());
+        budget.budget = Some(1024);
+        let _ = frame(&mut budget, &[], &textures);
+        budget.budget = Some(64);
+        let (_, exceeded) = frame(&mut budget, &[id(1), id(2)], &textures);
+        assert!(exceeded.is_some());
+    }
+
+    #[test]
+    fn freed_by_egui_once() {
+        let mut textures = textures();
+        let mut budget = TextureBudget::new();
+        budget.budget = Some(256 + 192);
+        let _ = frame(&mut budget, &[id(2), id(3), id(4)], &textures);
+        assert_eq!(budget.memory(&textures).evicted, 1);
+        let mut delta = TexturesDelta {
+            set: vec![],
+            free: vec![id(1), id(2)],
+        };
+        textures.retain(|texture, _| !delta.free.contains(texture));
+        budget.apply(&[], &mut delta, &textures);
+        // the evicted image already off the GPU
+        assert_eq!(delta.free, [id(2)]);
+        assert_eq!(budget.memory(&textures).evicted, 0);
+    }
+}
@@ -0,0 +1,123 @@
+use std::num::NonZeroU32;
+use std::sync::mpsc;
+
+use anyhow::{bail, Result};
+use egui::{Color32, ColorImage};
+
+/// A texture to render into and copy from, as the surface textures can't be read back.
+pub(crate) fn offscreen_texture(
+    device: &wgpu::Device,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen"),
+        size: wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+    })
+}
+
+/// Copies `texture` to the CPU, blocking until the GPU is done with it.
+/// The colors stay premultiplied by their alpha, like egui's.
+pub(crate) fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+) -> Result<ColorImage> {
+    let pixel_size = match format {
+        wgpu::TextureFormat::Rgba16Float => 8,
+        wgpu::TextureFormat::Bgra8Unorm
+        | wgpu::TextureFormat::Bgra8UnormSrgb
+        | wgpu::TextureFormat::Rgba8Unorm
+        | wgpu::TextureFormat::Rgba8UnormSrgb => 4,
+        _ => bail!("can't read back {:?} textures", format),
+    };
+    // copied rows are padded to the alignment
+    let row_size = size.0 * pixel_size;
+    let padded_row_size =
+        row_size.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback"),
+        size: u64::from(padded_row_size * size.1),
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("readback encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_row_size),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+
+    let data = slice.get_mapped_range();
+    let pixels = data
+        .chunks_exact(padded_row_size as usize)
+        .flat_map(|row| row[..row_size as usize].chunks_exact(pixel_size as usize))
+        .map(|pixel| match format {
+            wgpu::TextureFormat::Rgba16Float => {
+                let [r, g, b, a] = [0, 2, 4, 6].map(|i| {
+                    let value = f16_to_f32(u16::from_le_bytes([pixel[i], pixel[i + 1]]));
+                    (value.clamp(0., 1.) * 255.).round() as u8
+                });
+                Color32::from_rgba_premultiplied(r, g, b, a)
+            }
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                Color32::from_rgba_premultiplied(pixel[2], pixel[1], pixel[0], pixel[3])
+            }
+            _ => Color32::from_rgba_premultiplied(pixel[0], pixel[1], pixel[2], pixel[3]),
+        })
+        .collect();
+    drop(data);
+    buffer.unmap();
+
+    Ok(ColorImage {
+        size: [size.0 as usize, size.1 as usize],
+        pixels,
+    })
+}
+
+/// the half-precision float of the `Rgba16Float` channels
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = if half & 0x8000 == 0 { 1. } else { -1. };
+    let exponent = i32::from((half >> 10) & 0x1f);
+    let mantissa = f32::from(half & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0. => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1. + mantissa / 1024.) * 2f32.powi(exponent - 15),
+    }
+}
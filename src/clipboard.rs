@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt as _, EventMask, PropMode, Property,
+    SelectionNotifyEvent, SelectionRequestEvent, Window, SELECTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::Event;
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::{CURRENT_TIME, NONE};
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        CLIPBOARD,
+        PRIMARY,
+        TARGETS,
+        UTF8_STRING,
+        TEXT,
+        INCR,
+        // where the selection owners write what we request
+        EGUI_OVERLAY_SELECTION,
+    }
+}
+
+/// The X selection pasted from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Selection {
+    /// Ctrl+C / Ctrl+V
+    Clipboard,
+    /// selected text, pasted with the middle button
+    Primary,
+}
+
+/// an outgoing transfer too large for a single request, sent in chunks (INCR protocol)
+struct IncrSend {
+    requestor: Window,
+    property: Atom,
+    type_: Atom,
+    data: Vec<u8>,
+    sent: usize,
+}
+
+impl IncrSend {
+    /// The next chunk of at most `chunk_size` bytes.
+    /// The last chunk is empty, it tells the requestor the transfer is over.
+    fn next_chunk(&mut self, chunk_size: usize) -> &[u8] {
+        let start = self.sent;
+        self.sent = (start + chunk_size).min(self.data.len());
+        &self.data[start..self.sent]
+    }
+}
+
+/// Copy and paste through the X selections, on behalf of the overlay window.
+pub(crate) struct Clipboard {
+    win_id: Window,
+    atoms: Atoms,
+    /// text served for each selection we own
+    owned: HashMap<Atom, String>,
+    sending: Vec<IncrSend>,
+    /// incoming INCR transfer
+    receiving: Option<Vec<u8>>,
+    chunk_size: usize,
+}
+
+impl Clipboard {
+    pub fn new<Conn>(conn: &Conn, win_id: Window) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        Ok(Self {
+            win_id,
+            atoms: Atoms::new(conn)?.reply()?,
+            owned: HashMap::new(),
+            sending: Vec::new(),
+            receiving: None,
+            // leaves room for the request header
+            chunk_size: conn.maximum_request_bytes() / 4,
+        })
+    }
+
+    /// Owns both selections, serving `text`.
+    pub fn copy<Conn>(&mut self, conn: &Conn, text: String) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        for selection in [self.atoms.CLIPBOARD, self.atoms.PRIMARY] {
+            conn.set_selection_owner(self.win_id, selection, CURRENT_TIME)?;
+            self.owned.insert(selection, text.clone());
+        }
+        Ok(())
+    }
+
+    /// Asks the selection owner for its text.
+    /// Returns it directly if we own the selection, otherwise it arrives through `handle_event`.
+    pub fn paste<Conn>(&mut self, conn: &Conn, selection: Selection) -> Result<Option<String>>
+    where
+        Conn: Connection,
+    {
+        let selection = self.atom(selection);
+        if let Some(text) = self.owned.get(&selection) {
+            return Ok(Some(text.clone()));
+        }
+        conn.convert_selection(
+            self.win_id,
+            selection,
+            self.atoms.UTF8_STRING,
+            self.atoms.EGUI_OVERLAY_SELECTION,
+            CURRENT_TIME,
+        )?;
+        Ok(None)
+    }
+
+    /// Serves and receives selections. Returns the pasted text once fully received.
+    pub fn handle_event<Conn>(&mut self, conn: &Conn, event: &Event) -> Result<Option<String>>
+    where
+        Conn: Connection,
+    {
+        match event {
+            Event::SelectionRequest(e) => self.serve(conn, e)?,
+            Event::SelectionClear(e) => {
+                self.owned.remove(&e.selection);
+            }
+            Event::SelectionNotify(e) if e.requestor == self.win_id => {
+                if e.property == NONE {
+                    // refused, or nobody owns the selection
+                    return Ok(None);
+                }
+                let reply = conn
+                    .get_property(true, self.win_id, e.property, AtomEnum::ANY, 0, u32::MAX)?
+                    .reply()?;
+                if reply.type_ == self.atoms.INCR {
+                    // deleting the property asked for the first chunk
+                    self.receiving = Some(Vec::new());
+                } else {
+                    return Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()));
+                }
+            }
+            Event::PropertyNotify(e)
+                if e.window == self.win_id
+                    && e.atom == self.atoms.EGUI_OVERLAY_SELECTION
+                    && e.state == Property::NEW_VALUE =>
+            {
+                if let Some(received) = &mut self.receiving {
+                    let chunk = conn
+                        .get_property(true, self.win_id, e.atom, AtomEnum::ANY, 0, u32::MAX)?
+                        .reply()?
+                        .value;
+                    // an empty chunk ends the transfer
+                    if chunk.is_empty() {
+                        let received = self.receiving.take().unwrap_or_default();
+                        return Ok(Some(String::from_utf8_lossy(&received).into_owned()));
+                    }
+                    received.extend(chunk);
+                }
+            }
+            Event::PropertyNotify(e) if e.state == Property::DELETE => {
+                self.send_next_chunk(conn, e.window, e.atom)?
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn atom(&self, selection: Selection) -> Atom {
+        match selection {
+            Selection::Clipboard => self.atoms.CLIPBOARD,
+            Selection::Primary => self.atoms.PRIMARY,
+        }
+    }
+
+    fn serve<Conn>(&mut self, conn: &Conn, e: &SelectionRequestEvent) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        // obsolete clients leave the property to us
+        let property = if e.property == NONE {
+            e.target
+        } else {
+            e.property
+        };
+        let text_targets = [
+            self.atoms.UTF8_STRING,
+            AtomEnum::STRING.into(),
+            self.atoms.TEXT,
+        ];
+
+        let served = match self.owned.get(&e.selection) {
+            Some(_) if e.target == self.atoms.TARGETS => {
+                let mut targets = vec![self.atoms.TARGETS];
+                targets.extend(text_targets);
+                conn.change_property32(
+                    PropMode::REPLACE,
+                    e.requestor,
+                    property,
+                    AtomEnum::ATOM,
+                    &targets,
+                )?;
+                true
+            }
+            Some(text) if text_targets.contains(&e.target) => {
+                let type_ = if e.target == self.atoms.TEXT {
+                    self.atoms.UTF8_STRING
+                } else {
+                    e.target
+                };
+                let data = text.as_bytes().to_vec();
+                if data.len() > self.chunk_size {
+                    // the requestor deletes the property to ask for each chunk
+                    conn.change_window_attributes(
+                        e.requestor,
+                        &ChangeWindowAttributesAux::new().event_mask(EventMask::PROPERTY_CHANGE),
+                    )?;
+                    conn.change_property32(
+                        PropMode::REPLACE,
+                        e.requestor,
+                        property,
+                        self.atoms.INCR,
+                        &[data.len() as u32],
+                    )?;
+                    self.sending.push(IncrSend {
+                        requestor: e.requestor,
+                        property,
+                        type_,
+                        data,
+                        sent: 0,
+                    });
+                } else {
+                    conn.change_property8(PropMode::REPLACE, e.requestor, property, type_, &data)?;
+                }
+                true
+            }
+            _ => false,
+        };
+
+        let notify = SelectionNotifyEvent {
+            response_type: SELECTION_NOTIFY_EVENT,
+            sequence: 0,
+            time: e.time,
+            requestor: e.requestor,
+            selection: e.selection,
+            target: e.target,
+            property: if served { property } else { NONE },
+        };
+        conn.send_event(false, e.requestor, EventMask::NO_EVENT, notify)?;
+        Ok(())
+    }
+
+    fn send_next_chunk<Conn>(&mut self, conn: &Conn, window: Window, property: Atom) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        let Some(i) = self
+            .sending
+            .iter()
+            .position(|send| send.requestor == window && send.property == property)
+        else {
+            return Ok(());
+        };
+
+        let chunk_size = self.chunk_size;
+        let send = &mut self.sending[i];
+        let type_ = send.type_;
+        let chunk = send.next_chunk(chunk_size);
+        conn.change_property8(PropMode::REPLACE, window, property, type_, chunk)?;
+        if chunk.is_empty() {
+            self.sending.remove(i);
+        }
+        Ok(())
+    }
+}
+
+/// Ctrl+V or Shift+Insert
+pub(crate) fn is_paste_shortcut(event: &egui::Event) -> bool {
+    match event {
+        egui::Event::Key {
+            key,
+            pressed: true,
+            modifiers,
+        } => {
+            (modifiers.command && *key == egui::Key::V)
+                || (modifiers.shift && *key == egui::Key::Insert)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Key, Modifiers};
+
+    use super::*;
+
+    fn key(key: Key, modifiers: Modifiers, pressed: bool) -> egui::Event {
+        egui::Event::Key {
+            key,
+            pressed,
+            modifiers,
+        }
+    }
+
+    #[test]
+    fn incr_chunks() {
+        let mut send = IncrSend {
+            requestor: 1,
+            property: 2,
+            type_: 3,
+            data: (0..10).collect(),
+            sent: 0,
+        };
+        assert_eq!(send.next_chunk(4), [0, 1, 2, 3]);
+        assert_eq!(send.next_chunk(4), [4, 5, 6, 7]);
+        assert_eq!(send.next_chunk(4), [8, 9]);
+        // then the empty chunk ending the transfer, for as long as asked
+        assert!(send.next_chunk(4).is_empty());
+        assert!(send.next_chunk(4).is_empty());
+        assert_eq!(send.sent, 10);
+    }
+
+    #[test]
+    fn incr_chunks_of_exact_size() {
+        let mut send = IncrSend {
+            requestor: 1,
+            property: 2,
+            type_: 3,
+            data: vec![7; 8],
+            sent: 0,
+        };
+        assert_eq!(send.next_chunk(4).len(), 4);
+        assert_eq!(send.next_chunk(4).len(), 4);
+        assert!(send.next_chunk(4).is_empty());
+    }
+
+    #[test]
+    fn paste_shortcuts() {
+        let ctrl = Modifiers::COMMAND;
+        assert!(is_paste_shortcut(&key(Key::V, ctrl, true)));
+        assert!(is_paste_shortcut(&key(Key::Insert, Modifiers::SHIFT, true)));
+        // on press only
+        assert!(!is_paste_shortcut(&key(Key::V, ctrl, false)));
+        assert!(!is_paste_shortcut(&key(Key::V, Modifiers::NONE, true)));
+        assert!(!is_paste_shortcut(&key(Key::C, ctrl, true)));
+        assert!(!is_paste_shortcut(&key(Key::Insert, ctrl, true)));
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(any(feature = "ipc", feature = "dbus"))]
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::toasts::Toast;
+use crate::Overlay;
+
+/// What a running overlay is asked to do by other processes, e.g. `{"command":"hide"}`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    Show,
+    Hide,
+    Toggle,
+    /// Makes the overlay click-through, or restores its input shape, see
+    /// [`Overlay::set_input_passthrough`].
+    TogglePassthrough,
+    /// See [`Overlay::set_opacity`].
+    SetOpacity {
+        opacity: f32,
+    },
+    /// Applies the config file again, see [`Config::update`].
+    ReloadConfig,
+    Quit,
+    /// Shows a [`Toast`].
+    Notify {
+        summary: String,
+        #[serde(default)]
+        body: String,
+        /// 5 seconds by default
+        #[serde(default)]
+        timeout_ms: Option<u32>,
+    },
+}
+
+impl Command {
+    pub(crate) fn run(self, overlay: &mut Overlay) -> Result<()> {
+        match self {
+            Self::Show => overlay.set_visible(true),
+            Self::Hide => overlay.set_visible(false),
+            Self::Toggle => overlay.set_visible(!overlay.visible()),
+            Self::TogglePassthrough => overlay.set_input_passthrough(!overlay.input_passthrough()),
+            Self::SetOpacity { opacity } => overlay.set_opacity(opacity),
+            Self::ReloadConfig => Config::load()?.update(overlay),
+            Self::Quit => {
+                overlay.close();
+                Ok(())
+            }
+            Self::Notify {
+                summary,
+                body,
+                timeout_ms,
+            } => {
+                let mut toast = Toast::new(summary).body(body);
+                if let Some(ms) = timeout_ms {
+                    toast = toast.duration(Duration::from_millis(ms.into()));
+                }
+                overlay.push_toast(toast);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// a command received from another thread, with where to send how it went
+#[cfg(any(feature = "ipc", feature = "dbus"))]
+pub(crate) type Request = (Command, Sender<Result<(), String>>);
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use tracing::warn;
+use x11rb::connection::Connection;
+use x11rb::protocol::xfixes::{ConnectionExt as _, SelectionEventMask};
+use x11rb::protocol::xproto::{Atom, ConnectionExt as _, Window};
+use x11rb::protocol::Event;
+use x11rb::NONE;
+
+/// Whether a compositing manager runs. Without one, the transparent parts of the overlay
+/// render as black.
+pub(crate) struct Compositor {
+    /// `_NET_WM_CM_Sn`, owned by the compositing manager of screen n
+    selection: Atom,
+    running: bool,
+}
+
+impl Compositor {
+    /// Also subscribes `win_id` to the compositor starting or stopping.
+    pub fn new<Conn>(conn: &Conn, screen_num: usize, win_id: Window) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let name = format!("_NET_WM_CM_S{}", screen_num);
+        let selection = conn.intern_atom(false, name.as_bytes())?.reply()?.atom;
+        conn.xfixes_select_selection_input(
+            win_id,
+            selection,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )?;
+        let running = conn.get_selection_owner(selection)?.reply()?.owner != NONE;
+        if !running {
+            warn!("no compositor running, the overlay background will be opaque");
+        }
+        Ok(Self { selection, running })
+    }
+
+    pub fn running(&self) -> bool {
+        self.running
+    }
+
+    /// Follows the compositor starting or stopping.
+    pub fn handle_event(&mut self, event: &Event) {
+        if let Event::XfixesSelectionNotify(e) = event {
+            if e.selection == self.selection {
+                self.running = e.owner != NONE;
+            }
+        }
+    }
+}
@@ -0,0 +1,467 @@
+//! Overlay settings from `$XDG_CONFIG_HOME/egui-overlay/config.toml`, to tune an overlay
+//! without recompiling it.
+//!
+//! ```toml
+//! anchor = "bottom-right"
+//! width = "400px"
+//! height = "50%"
+//! margin = 20
+//! monitor = "HDMI-1"   # or "primary", or an index
+//! scale-factor = 1.5
+//! max-fps = 30         # 0 for uncapped
+//! ui-rate = 10         # UI runs per second, exposures presenting the last frame, 0 for all
+//! msaa = 4             # samples per pixel, 1 for none
+//! color-space = "srgb" # or "linear"
+//! input-shape = "content"
+//! fullscreen-policy = "hide" # or "show", "pause", or fps while a fullscreen app is focused
+//! workspaces = "0,1"   # desktops to show on from 0, "!2" to hide on some, or "all"
+//! theme = "system"
+//! opacity = 0.8
+//! backend = "vulkan"
+//! power-preference = "low"
+//!
+//! [[widgets]]          # see the widgets module
+//! kind = "cpu"
+//! ```
+//!
+//! Every setting is optional, the overlay's own defaults apply to the missing ones.
+//! [`OverlayBuilder::watch_config`] applies the file again while the overlay runs, whenever
+//! it's saved.
+
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::{anyhow, Context as _, Result};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+use serde::{Deserialize, Deserializer};
+use tracing::{debug, warn};
+
+use crate::geometry::{Anchor, Length, Margin};
+use crate::monitors::MonitorSelector;
+use crate::state::{parse_backends, parse_power_preference};
+use crate::widgets::WidgetConfig;
+use crate::{
+    ColorSpace, FullscreenPolicy, InputShape, Overlay, OverlayBuilder, OverlayError, Theme, Waker,
+    Workspaces,
+};
+
+/// The settings of the config file, applied to an [`OverlayBuilder`] with [`Config::apply`].
+/// Command line flags can override them by setting the fields in between.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(deserialize_with = "parsed")]
+    pub anchor: Option<Anchor>,
+    #[serde(deserialize_with = "parsed")]
+    pub width: Option<Length>,
+    #[serde(deserialize_with = "parsed")]
+    pub height: Option<Length>,
+    pub margin: Option<u16>,
+    #[serde(deserialize_with = "parsed")]
+    pub monitor: Option<MonitorSelector>,
+    pub scale_factor: Option<f32>,
+    /// 0 for uncapped.
+    pub max_fps: Option<f32>,
+    /// 0 for every frame, see [`Overlay::set_ui_rate`].
+    pub ui_rate: Option<f32>,
+    /// Samples per pixel, see [`OverlayBuilder::msaa_samples`].
+    pub msaa: Option<u32>,
+    #[serde(deserialize_with = "parsed")]
+    pub color_space: Option<ColorSpace>,
+    #[serde(deserialize_with = "parsed")]
+    pub input_shape: Option<InputShape>,
+    #[serde(deserialize_with = "parsed")]
+    pub fullscreen_policy: Option<FullscreenPolicy>,
+    #[serde(deserialize_with = "parsed")]
+    pub workspaces: Option<Workspaces>,
+    #[serde(deserialize_with = "parsed")]
+    pub theme: Option<Theme>,
+    /// From 0, transparent, to 1.
+    pub opacity: Option<f32>,
+    #[serde(deserialize_with = "backends")]
+    pub backend: Option<wgpu::Backends>,
+    #[serde(deserialize_with = "power_preference")]
+    pub power_preference: Option<wgpu::PowerPreference>,
+    /// The HUD, see [`WidgetRegistry::build`](crate::widgets::WidgetRegistry::build).
+    pub widgets: Vec<WidgetConfig>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/egui-overlay/config.toml`, or `~/.config/...` without
+    /// `XDG_CONFIG_HOME`.
+    pub fn default_path() -> Result<PathBuf> {
+        let config_home = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let home = env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+                PathBuf::from(home).join(".config")
+            }
+        };
+        Ok(config_home.join("egui-overlay/config.toml"))
+    }
+
+    /// The config at [`Config::default_path`], empty if there is no such file.
+    pub fn load() -> Result<Self, OverlayError> {
+        let path = Self::default_path().map_err(|e| OverlayError::config("HOME", e))?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::from_path(&path)
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, OverlayError> {
+        let invalid = |e: anyhow::Error| OverlayError::config(path.display(), e);
+        let config = fs::read_to_string(path).map_err(|e| invalid(e.into()))?;
+        toml::from_str(&config).map_err(|e| invalid(e.into()))
+    }
+
+    /// Overrides the settings of `builder` found in the config.
+    pub fn apply(&self, mut builder: OverlayBuilder) -> OverlayBuilder {
+        let geometry = &mut builder.geometry;
+        if let Some(anchor) = self.anchor {
+            geometry.anchor = anchor;
+        }
+        if let Some(width) = self.width {
+            geometry.width = width;
+        }
+        if let Some(height) = self.height {
+            geometry.height = height;
+        }
+        if let Some(margin) = self.margin {
+            builder = builder.margin(margin);
+        }
+        if let Some(monitor) = &self.monitor {
+            builder = builder.monitor(monitor.clone());
+        }
+        if let Some(scale_factor) = self.scale_factor {
+            builder = builder.scale_factor(scale_factor);
+        }
+        if let Some(max_fps) = self.max_fps {
+            builder = builder.max_fps((max_fps > 0.).then_some(max_fps));
+        }
+        if let Some(ui_rate) = self.ui_rate.filter(|rate| *rate > 0.) {
+            builder = builder.ui_rate(ui_rate);
+        }
+        if let Some(msaa) = self.msaa {
+            builder = builder.msaa_samples(msaa);
+        }
+        if let Some(color_space) = self.color_space {
+            builder = builder.color_space(color_space);
+        }
+        if let Some(input_shape) = self.input_shape {
+            builder = builder.input_shape(input_shape);
+        }
+        if let Some(fullscreen_policy) = self.fullscreen_policy {
+            builder = builder.fullscreen_policy(fullscreen_policy);
+        }
+        if let Some(workspaces) = &self.workspaces {
+            builder = builder.workspaces(workspaces.clone());
+        }
+        if let Some(theme) = self.theme {
+            builder = builder.theme(theme);
+        }
+        if let Some(opacity) = self.opacity {
+            builder = builder.opacity(opacity);
+        }
+        if let Some(backends) = self.backend {
+            builder = builder.backends(backends);
+        }
+        if let Some(power_preference) = self.power_preference {
+            builder = builder.power_preference(power_preference);
+        }
+        builder
+    }
+
+    /// Applies the settings to a running overlay, e.g. after editing the file. The ones
+    /// needing a new window or device stay as built: `msaa`, `color-space`, `backend` and
+    /// `power-preference`.
+    pub fn update(&self, overlay: &mut Overlay) -> Result<()> {
+        if let Some(monitor) = &self.monitor {
+            overlay.set_monitor(monitor.clone())?;
+        }
+        let mut geometry = overlay.layout();
+        if let Some(anchor) = self.anchor {
+            geometry.anchor = anchor;
+        }
+        if let Some(width) = self.width {
+            geometry.width = width;
+        }
+        if let Some(height) = self.height {
+            geometry.height = height;
+        }
+        if let Some(margin) = self.margin {
+            geometry.margin = Margin::uniform(margin);
+        }
+        if geometry != overlay.layout() {
+            overlay.set_layout(geometry)?;
+        }
+        if let Some(scale_factor) = self.scale_factor {
+            overlay.set_scale_factor(Some(scale_factor))?;
+        }
+        if let Some(max_fps) = self.max_fps {
+            overlay.set_max_fps((max_fps > 0.).then_some(max_fps));
+        }
+        if let Some(ui_rate) = self.ui_rate {
+            overlay.set_ui_rate(Some(ui_rate))?;
+        }
+        if let Some(input_shape) = self.input_shape {
+            overlay.set_input_shape(input_shape)?;
+        }
+        if let Some(fullscreen_policy) = self.fullscreen_policy {
+            overlay.set_fullscreen_policy(fullscreen_policy)?;
+        }
+        if let Some(workspaces) = &self.workspaces {
+            overlay.set_workspaces(workspaces.clone())?;
+        }
+        if let Some(theme) = self.theme {
+            overlay.set_theme(theme);
+        }
+        if let Some(opacity) = self.opacity {
+            overlay.set_opacity(opacity)?;
+        }
+        Ok(())
+    }
+}
+
+/// Notices the changes of a config file, see [`OverlayBuilder::watch_config`].
+pub(crate) struct ConfigWatcher {
+    path: PathBuf,
+    changed: Arc<AtomicBool>,
+    inotify: Inotify,
+    watch: WatchDescriptor,
+}
+
+impl ConfigWatcher {
+    /// Watches the directory of `path` on its own thread, editors often save files by
+    /// replacing them, waking the overlay up when the file changes.
+    pub fn start(path: &Path, waker: Waker) -> Result<Self> {
+        let dir = path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("no config file in {}", path.display()))?
+            .to_owned();
+        // to notice the file once created
+        fs::create_dir_all(dir)?;
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC)?;
+        let watch = inotify
+            .add_watch(
+                dir,
+                AddWatchFlags::IN_CLOSE_WRITE
+                    | AddWatchFlags::IN_MOVED_TO
+                    | AddWatchFlags::IN_DELETE,
+            )
+            .with_context(|| format!("watching {}", dir.display()))?;
+
+        let changed = Arc::new(AtomicBool::new(false));
+        thread::Builder::new()
+            .name("egui-overlay-config".to_owned())
+            .spawn({
+                let changed = changed.clone();
+                move || {
+                    loop {
+                        let events = match inotify.read_events() {
+                            Ok(events) => events,
+                            Err(e) => {
+                                warn!("watching the config: {}", e);
+                                break;
+                            }
+                        };
+                        // the watch removed, by the drop or with the directory
+                        if events
+                            .iter()
+                            .any(|event| event.mask.contains(AddWatchFlags::IN_IGNORED))
+                        {
+                            break;
+                        }
+                        if events
+                            .iter()
+                            .any(|event| event.name.as_ref() == Some(&name))
+                        {
+                            changed.store(true, Ordering::Relaxed);
+                            waker.wake();
+                        }
+                    }
+                    let _ = nix::unistd::close(inotify.as_raw_fd());
+                }
+            })?;
+        Ok(Self {
+            path: path.to_owned(),
+            changed,
+            inotify,
+            watch,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The config, empty if there is no such file.
+    pub fn load(&self) -> Result<Config, OverlayError> {
+        if !self.path.exists() {
+            return Ok(Config::default());
+        }
+        Config::from_path(&self.path)
+    }
+
+    /// Whether the file changed since the last call.
+    pub fn changed(&self) -> bool {
+        self.changed.swap(false, Ordering::Relaxed)
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        // ends the thread
+        let _ = self.inotify.rm_watch(self.watch);
+        debug!("no longer watching {}", self.path.display());
+    }
+}
+
+/// a setting written as a string, or a number, e.g. `margin = 20` or `monitor = 1`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Text(String),
+    Integer(i64),
+    Float(f64),
+}
+
+impl Value {
+    fn into_string(self) -> String {
+        match self {
+            Self::Text(text) => text,
+            Self::Integer(integer) => integer.to_string(),
+            Self::Float(float) => float.to_string(),
+        }
+    }
+}
+
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = Value::deserialize(deserializer)?.into_string();
+    value.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
+fn backends<'de, D>(deserializer: D) -> Result<Option<wgpu::Backends>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse_backends(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn power_preference<'de, D>(deserializer: D) -> Result<Option<wgpu::PowerPreference>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse_power_preference(&value)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `config` written to a file of its own, then loaded
+    fn load(name: &str, config: &str) -> (PathBuf, Result<Config, OverlayError>) {
+        let path = env::temp_dir().join(format!("egui-overlay-{}-{}", std::process::id(), name));
+        fs::write(&path, config).unwrap();
+        let loaded = Config::from_path(&path);
+        fs::remove_file(&path).unwrap();
+        (path, loaded)
+    }
+
+    /// the message of the invalid config at `path`
+    fn invalid(path: &Path, loaded: Result<Config, OverlayError>) -> String {
+        match loaded {
+            Err(OverlayError::ConfigInvalid { origin, source }) => {
+                assert_eq!(origin, path.display().to_string());
+                source.to_string()
+            }
+            other => panic!("expected an invalid config, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn valid_file() {
+        let (_, loaded) = load(
+            "valid.toml",
+            r#"
+            anchor = "bottom-right"
+            width = "400px"
+            height = "50%"
+            margin = 20
+            monitor = 1
+            max-fps = 30
+            msaa = 4
+            color-space = "linear"
+            input-shape = "content"
+            fullscreen-policy = "hide"
+            opacity = 0.8
+            power-preference = "low"
+            "#,
+        );
+        let config = loaded.unwrap();
+        assert_eq!(
+            config,
+            Config {
+                anchor: Some(Anchor::BottomRight),
+                width: Some(Length::Pixels(400)),
+                height: Some(Length::Percent(50.)),
+                margin: Some(20),
+                monitor: Some(MonitorSelector::Index(1)),
+                max_fps: Some(30.),
+                msaa: Some(4),
+                color_space: Some(ColorSpace::Linear),
+                input_shape: Some(InputShape::Content),
+                fullscreen_policy: Some(FullscreenPolicy::Hide),
+                opacity: Some(0.8),
+                power_preference: Some(wgpu::PowerPreference::LowPower),
+                ..Config::default()
+            }
+        );
+        let (_, loaded) = load("empty.toml", "");
+        assert_eq!(loaded.unwrap(), Config::default());
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let (path, loaded) = load("unknown.toml", "anchor = \"top\"\nanchr = \"top\"\n");
+        let message = invalid(&path, loaded);
+        assert!(message.contains("anchr"), "{}", message);
+    }
+
+    #[test]
+    fn bad_values() {
+        let (path, loaded) = load("anchor.toml", "anchor = \"middle\"\n");
+        let message = invalid(&path, loaded);
+        assert!(message.contains("unknown anchor"), "{}", message);
+
+        let (path, loaded) = load("margin.toml", "margin = -20\n");
+        invalid(&path, loaded);
+        let (path, loaded) = load("syntax.toml", "width = \n");
+        invalid(&path, loaded);
+
+        let path = env::temp_dir().join("egui-overlay-missing.toml");
+        invalid(&path, Config::from_path(&path));
+    }
+}
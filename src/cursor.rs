@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use egui::CursorIcon;
+use x11rb::connection::Connection;
+use x11rb::cursor::Handle;
+use x11rb::protocol::xproto::{
+    ChangeWindowAttributesAux, ConnectionExt as _, CreateGCAux, Cursor, Rectangle, Window,
+};
+use x11rb::resource_manager;
+use x11rb::NONE;
+
+/// The mouse cursor over the overlay window, following egui's `CursorIcon`.
+pub(crate) struct Cursors {
+    handle: Handle,
+    win_id: Window,
+    /// cursors loaded from the theme, by name
+    loaded: HashMap<&'static str, Cursor>,
+    current: CursorIcon,
+}
+
+impl Cursors {
+    pub fn new<Conn>(conn: &Conn, screen_num: usize, win_id: Window) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        // the theme and size come from the X resources, `Xcursor.theme` and `Xcursor.size`
+        let database = resource_manager::new_from_default(conn)?;
+        let handle = Handle::new(conn, screen_num, &database)?.reply()?;
+        Ok(Self {
+            handle,
+            win_id,
+            loaded: HashMap::new(),
+            current: CursorIcon::Default,
+        })
+    }
+
+    /// Shows `icon` over the window, loading it from the cursor theme on first use.
+    pub fn set<Conn>(&mut self, conn: &Conn, icon: CursorIcon) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        if icon == self.current {
+            return Ok(());
+        }
+        let cursor = self.get(conn, icon)?;
+        conn.change_window_attributes(
+            self.win_id,
+            &ChangeWindowAttributesAux::new().cursor(cursor),
+        )?;
+        self.current = icon;
+        Ok(())
+    }
+
+    /// The cursor for `icon`, loaded from the theme on first use, e.g. for pointer grabs.
+    pub fn get<Conn>(&mut self, conn: &Conn, icon: CursorIcon) -> Result<Cursor>
+    where
+        Conn: Connection,
+    {
+        let (name, fallback) = cursor_names(icon);
+        if let Some(&cursor) = self.loaded.get(name) {
+            return Ok(cursor);
+        }
+        let cursor = if icon == CursorIcon::None {
+            blank_cursor(conn, self.win_id)?
+        } else {
+            self.load(conn, name, fallback)?
+        };
+        self.loaded.insert(name, cursor);
+        Ok(cursor)
+    }
+
+    /// Tries the CSS name of modern themes, then the core cursor font name.
+    fn load<Conn>(&self, conn: &Conn, name: &str, fallback: &str) -> Result<Cursor>
+    where
+        Conn: Connection,
+    {
+        let cursor = self.handle.load_cursor(conn, name)?;
+        if cursor != NONE {
+            return Ok(cursor);
+        }
+        Ok(self.handle.load_cursor(conn, fallback)?)
+    }
+}
+
+/// an invisible cursor, from an empty 1x1 bitmap
+fn blank_cursor<Conn>(conn: &Conn, win_id: Window) -> Result<Cursor>
+where
+    Conn: Connection,
+{
+    let pixmap = conn.generate_id()?;
+    conn.create_pixmap(1, pixmap, win_id, 1, 1)?;
+    let gc = conn.generate_id()?;
+    conn.create_gc(gc, pixmap, &CreateGCAux::new().foreground(0))?;
+    let rect = Rectangle {
+        x: 0,
+        y: 0,
+        width: 1,
+        height: 1,
+    };
+    conn.poly_fill_rectangle(pixmap, gc, &[rect])?;
+    let cursor = conn.generate_id()?;
+    conn.create_cursor(cursor, pixmap, pixmap, 0, 0, 0, 0, 0, 0, 0, 0)?;
+    conn.free_gc(gc)?;
+    conn.free_pixmap(pixmap)?;
+    Ok(cursor)
+}
+
+/// the CSS cursor name, and its closest match in the core cursor font
+fn cursor_names(icon: CursorIcon) -> (&'static str, &'static str) {
+    match icon {
+        CursorIcon::Default => ("default", "left_ptr"),
+        CursorIcon::None => ("none", "none"),
+        CursorIcon::ContextMenu => ("context-menu", "left_ptr"),
+        CursorIcon::Help => ("help", "question_arrow"),
+        CursorIcon::PointingHand => ("pointer", "hand2"),
+        CursorIcon::Progress => ("progress", "watch"),
+        CursorIcon::Wait => ("wait", "watch"),
+        CursorIcon::Cell => ("cell", "plus"),
+        CursorIcon::Crosshair => ("crosshair", "crosshair"),
+        CursorIcon::Text => ("text", "xterm"),
+        CursorIcon::VerticalText => ("vertical-text", "xterm"),
+        CursorIcon::Alias => ("alias", "left_ptr"),
+        CursorIcon::Copy => ("copy", "left_ptr"),
+        CursorIcon::Move => ("move", "fleur"),
+        CursorIcon::NoDrop => ("no-drop", "pirate"),
+        CursorIcon::NotAllowed => ("not-allowed", "pirate"),
+        CursorIcon::Grab => ("grab", "hand1"),
+        CursorIcon::Grabbing => ("grabbing", "fleur"),
+        CursorIcon::AllScroll => ("all-scroll", "fleur"),
+        CursorIcon::ResizeHorizontal => ("ew-resize", "sb_h_double_arrow"),
+        CursorIcon::ResizeNeSw => ("nesw-resize", "fleur"),
+        CursorIcon::ResizeNwSe => ("nwse-resize", "fleur"),
+        CursorIcon::ResizeVertical => ("ns-resize", "sb_v_double_arrow"),
+        CursorIcon::ResizeEast => ("e-resize", "right_side"),
+        CursorIcon::ResizeSouthEast => ("se-resize", "bottom_right_corner"),
+        CursorIcon::ResizeSouth => ("s-resize", "bottom_side"),
+        CursorIcon::ResizeSouthWest => ("sw-resize", "bottom_left_corner"),
+        CursorIcon::ResizeWest => ("w-resize", "left_side"),
+        CursorIcon::ResizeNorthWest => ("nw-resize", "top_left_corner"),
+        CursorIcon::ResizeNorth => ("n-resize", "top_side"),
+        CursorIcon::ResizeNorthEast => ("ne-resize", "top_right_corner"),
+        CursorIcon::ResizeColumn => ("col-resize", "sb_h_double_arrow"),
+        CursorIcon::ResizeRow => ("row-resize", "sb_v_double_arrow"),
+        CursorIcon::ZoomIn => ("zoom-in", "plus"),
+        CursorIcon::ZoomOut => ("zoom-out", "plus"),
+    }
+}
@@ -0,0 +1,329 @@
+//! Control of running overlays over the D-Bus session bus, for desktop integration, see
+//! [`OverlayBuilder::dbus_name`](crate::OverlayBuilder::dbus_name).
+//!
+//! The overlay serves the `org.makemeunsee.Overlay` interface at [`OBJECT_PATH`], with a
+//! method per [`Command`]: `Show`, `Hide`, `Toggle`, `TogglePassthrough`, `SetOpacity(d)`,
+//! `ReloadConfig`, `Quit` and `Notify(s summary, s body, i timeout_ms)`, a negative timeout
+//! for the default one. The `VisibilityChanged(b)` and `PassthroughChanged(b)` signals follow
+//! the overlay.
+//!
+//! ```sh
+//! busctl --user call org.makemeunsee.Overlay /org/makemeunsee/Overlay \
+//!     org.makemeunsee.Overlay Notify ssi "Build" "finished" -1
+//! ```
+//!
+//! Overlays can also be the notification daemon of the desktop, showing the notifications
+//! of other applications as toasts, see
+//! [`OverlayBuilder::notification_daemon`](crate::OverlayBuilder::notification_daemon).
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use anyhow::{Context as _, Result};
+use egui::ColorImage;
+use tracing::trace;
+use zbus::blocking::{Connection, ConnectionBuilder};
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{dbus_interface, fdo, SignalContext};
+
+use crate::command::{Command, Request};
+use crate::toasts::{CloseReason, Toast, ToastEvent, ToastKind, ToastSender};
+use crate::{Overlay, Waker};
+
+/// The bus name of a single overlay.
+pub const SERVICE_NAME: &str = "org.makemeunsee.Overlay";
+/// Where overlays serve their interface.
+pub const OBJECT_PATH: &str = "/org/makemeunsee/Overlay";
+
+/// Owns the bus name, handing the method calls over to the overlay loop through its waker.
+pub(crate) struct DbusService {
+    connection: Connection,
+    requests: Receiver<Request>,
+    /// as last signaled, `None` until the first changes
+    visible: Option<bool>,
+    passthrough: Option<bool>,
+}
+
+impl DbusService {
+    pub fn connect(name: &str, waker: Waker) -> Result<Self> {
+        let (sender, requests) = mpsc::channel();
+        let connection = ConnectionBuilder::session()?
+            .name(name)?
+            .serve_at(OBJECT_PATH, OverlayInterface { sender, waker })?
+            .build()
+            .with_context(|| format!("owning {} on the session bus", name))?;
+        Ok(Self {
+            connection,
+            requests,
+            visible: None,
+            passthrough: None,
+        })
+    }
+
+    /// The method calls received since the last call.
+    pub fn requests(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+
+    /// Signals the changes of the overlay since the last call.
+    pub fn signal_changes(&mut self, overlay: &Overlay) -> Result<()> {
+        let context = SignalContext::new(self.connection.inner(), OBJECT_PATH)?;
+        let visible = overlay.visible();
+        if self
+            .visible
+            .replace(visible)
+            .is_some_and(|was| was != visible)
+        {
+            zbus::block_on(OverlayInterface::visibility_changed(&context, visible))?;
+        }
+        let passthrough = overlay.input_passthrough();
+        if self
+            .passthrough
+            .replace(passthrough)
+            .is_some_and(|was| was != passthrough)
+        {
+            zbus::block_on(OverlayInterface::passthrough_changed(&context, passthrough))?;
+        }
+        Ok(())
+    }
+}
+
+struct OverlayInterface {
+    sender: Sender<Request>,
+    waker: Waker,
+}
+
+impl OverlayInterface {
+    /// runs `command` on the overlay loop, blocking the bus thread meanwhile
+    fn run(&self, command: Command) -> fdo::Result<()> {
+        let (reply, outcome) = mpsc::channel();
+        self.sender
+            .send((command, reply))
+            .map_err(|_| fdo::Error::Failed("the overlay stopped".to_owned()))?;
+        self.waker.wake();
+        outcome
+            .recv()
+            .unwrap_or_else(|_| Err("the overlay stopped".to_owned()))
+            .map_err(fdo::Error::Failed)
+    }
+}
+
+#[dbus_interface(name = "org.makemeunsee.Overlay")]
+impl OverlayInterface {
+    fn show(&self) -> fdo::Result<()> {
+        self.run(Command::Show)
+    }
+
+    fn hide(&self) -> fdo::Result<()> {
+        self.run(Command::Hide)
+    }
+
+    fn toggle(&self) -> fdo::Result<()> {
+        self.run(Command::Toggle)
+    }
+
+    fn toggle_passthrough(&self) -> fdo::Result<()> {
+        self.run(Command::TogglePassthrough)
+    }
+
+    fn set_opacity(&self, opacity: f64) -> fdo::Result<()> {
+        self.run(Command::SetOpacity {
+            opacity: opacity as f32,
+        })
+    }
+
+    fn reload_config(&self) -> fdo::Result<()> {
+        self.run(Command::ReloadConfig)
+    }
+
+    fn quit(&self) -> fdo::Result<()> {
+        self.run(Command::Quit)
+    }
+
+    fn notify(&self, summary: String, body: String, timeout_ms: i32) -> fdo::Result<()> {
+        self.run(Command::Notify {
+            summary,
+            body,
+            timeout_ms: u32::try_from(timeout_ms).ok(),
+        })
+    }
+
+    #[dbus_interface(signal)]
+    async fn visibility_changed(context: &SignalContext<'_>, visible: bool) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn passthrough_changed(
+        context: &SignalContext<'_>,
+        passthrough: bool,
+    ) -> zbus::Result<()>;
+}
+
+/// The name of notification daemons, see the [Desktop Notifications
+/// Specification](https://specifications.freedesktop.org/notification-spec/latest/).
+pub const NOTIFICATIONS_NAME: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+/// of `urgency` hints
+const CRITICAL: u8 = 2;
+
+/// Shows the desktop notifications as toasts of the overlay.
+pub(crate) struct NotificationDaemon {
+    connection: Connection,
+    events: Receiver<ToastEvent>,
+}
+
+impl NotificationDaemon {
+    pub fn start(toasts: ToastSender) -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let notifications = Notifications {
+            toasts,
+            events: sender,
+            next_id: AtomicU32::new(1),
+        };
+        let connection = ConnectionBuilder::session()?
+            .name(NOTIFICATIONS_NAME)?
+            .serve_at(NOTIFICATIONS_PATH, notifications)?
+            .build()
+            .context("owning org.freedesktop.Notifications, is another daemon running?")?;
+        Ok(Self { connection, events })
+    }
+
+    /// Signals the actions invoked and the notifications closed since the last call.
+    pub fn signal_events(&self) -> Result<()> {
+        let context = SignalContext::new(self.connection.inner(), NOTIFICATIONS_PATH)?;
+        for event in self.events.try_iter() {
+            match event {
+                ToastEvent::Action { id, key } => {
+                    zbus::block_on(Notifications::action_invoked(&context, id as u32, &key))?
+                }
+                ToastEvent::Closed { id, reason } => {
+                    let reason = match reason {
+                        CloseReason::Expired => 1,
+                        CloseReason::Dismissed => 2,
+                        CloseReason::Closed => 3,
+                        CloseReason::Dropped => 4,
+                    };
+                    zbus::block_on(Notifications::notification_closed(
+                        &context, id as u32, reason,
+                    ))?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Notifications {
+    toasts: ToastSender,
+    events: Sender<ToastEvent>,
+    next_id: AtomicU32,
+}
+
+#[dbus_interface(name = "org.freedesktop.Notifications")]
+impl Notifications {
+    fn get_capabilities(&self) -> Vec<&'static str> {
+        vec!["actions", "body", "icon-static", "persistence"]
+    }
+
+    /// Icons are shown from image data hints only, not from names or files.
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: String,
+        replaces_id: u32,
+        app_icon: String,
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        hints: HashMap<String, OwnedValue>,
+        expire_timeout: i32,
+    ) -> u32 {
+        trace!(app_name, app_icon, "notification");
+        let id = match replaces_id {
+            0 => self.next_id.fetch_add(1, Ordering::Relaxed),
+            id => id,
+        };
+        let mut toast = Toast::new(summary)
+            .body(body)
+            .id(id.into())
+            .events(self.events.clone());
+        for action in actions.chunks_exact(2) {
+            toast = toast.action(&action[0], &action[1]);
+        }
+        let critical = matches!(
+            hints.get("urgency").map(|urgency| &**urgency),
+            Some(Value::U8(CRITICAL))
+        );
+        if critical {
+            toast = toast.kind(ToastKind::Error);
+        }
+        toast = match expire_timeout {
+            0 => toast.persistent(),
+            // critical notifications stay until dismissed
+            ..=-1 if critical => toast.persistent(),
+            ..=-1 => toast,
+            ms => toast.duration(std::time::Duration::from_millis(ms as u64)),
+        };
+        let image = ["image-data", "image_data", "icon_data"]
+            .iter()
+            .find_map(|hint| hints.get(*hint).and_then(image_data));
+        if let Some(image) = image {
+            toast = toast.icon(image);
+        }
+        self.toasts.push(toast);
+        id
+    }
+
+    fn close_notification(&self, id: u32) {
+        self.toasts.close(id.into());
+    }
+
+    fn get_server_information(&self) -> (&'static str, &'static str, &'static str, &'static str) {
+        (
+            "egui-overlay",
+            "makemeunsee",
+            env!("CARGO_PKG_VERSION"),
+            "1.2",
+        )
+    }
+
+    #[dbus_interface(signal)]
+    async fn notification_closed(
+        context: &SignalContext<'_>,
+        id: u32,
+        reason: u32,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn action_invoked(
+        context: &SignalContext<'_>,
+        id: u32,
+        action_key: &str,
+    ) -> zbus::Result<()>;
+}
+
+/// the image of an `(iiibiiay)` hint: width, height, row stride, alpha, bits per sample,
+/// channels and 8-bit RGB or RGBA data
+fn image_data(value: &OwnedValue) -> Option<ColorImage> {
+    type ImageData = (i32, i32, i32, bool, i32, i32, Vec<u8>);
+    let (width, height, stride, _, bits, channels, data) =
+        ImageData::try_from(value.clone()).ok()?;
+    if bits != 8 || !(3..=4).contains(&channels) {
+        return None;
+    }
+    let (width, height, stride, channels) = (
+        usize::try_from(width).ok()?,
+        usize::try_from(height).ok()?,
+        usize::try_from(stride).ok()?,
+        channels as usize,
+    );
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = data.get(y * stride + x * channels..)?;
+            let alpha = if channels == 4 { *pixel.get(3)? } else { 255 };
+            rgba.extend_from_slice(&[*pixel.first()?, *pixel.get(1)?, *pixel.get(2)?, alpha]);
+        }
+    }
+    Some(ColorImage::from_rgba_unmultiplied([width, height], &rgba))
+}
@@ -0,0 +1,135 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use egui::plot::{Bar, BarChart, Plot};
+use x11rb::protocol::Event;
+
+use crate::OverlayApp;
+
+/// frames kept for the statistics
+const HISTORY: usize = 240;
+/// width of the frame time histogram buckets
+const BUCKET_MS: f64 = 2.;
+
+/// Frame timing and event counts, shown in the built-in debug panel.
+#[derive(Default)]
+pub(crate) struct DebugStats {
+    /// start and CPU time of the last frames, oldest first
+    frames: VecDeque<(Instant, Duration)>,
+    events: BTreeMap<&'static str, u64>,
+    texture_bytes: usize,
+}
+
+impl DebugStats {
+    pub fn record_frame(&mut self, start: Instant, texture_bytes: usize) {
+        if self.frames.len() == HISTORY {
+            self.frames.pop_front();
+        }
+        self.frames.push_back((start, start.elapsed()));
+        self.texture_bytes = texture_bytes;
+    }
+
+    pub fn count_event(&mut self, event: &Event) {
+        *self.events.entry(event_kind(event)).or_default() += 1;
+    }
+
+    /// Draws the panel over `app`.
+    pub fn wrap<'a>(&'a self, app: &'a mut dyn OverlayApp) -> DebugPanel<'a> {
+        DebugPanel { stats: self, app }
+    }
+
+    fn ui(&self, ctx: &egui::Context) {
+        egui::Window::new("Overlay debug")
+            .default_pos((10., 10.))
+            .show(ctx, |ui| {
+                let fps = match (self.frames.front(), self.frames.back()) {
+                    (Some((first, _)), Some((last, _))) if self.frames.len() > 1 => {
+                        (self.frames.len() - 1) as f32 / (*last - *first).as_secs_f32()
+                    }
+                    _ => 0.,
+                };
+                ui.label(format!("{:.1} fps", fps));
+
+                let times = self
+                    .frames
+                    .iter()
+                    .map(|(_, time)| time.as_secs_f64() * 1000.);
+                let max = times.clone().fold(0., f64::max);
+                let mean = times.clone().sum::<f64>() / self.frames.len().max(1) as f64;
+                ui.label(format!("frame time {:.2} ms, max {:.2} ms", mean, max));
+
+                let mut buckets = BTreeMap::<u32, u32>::new();
+                for time in times {
+                    *buckets.entry((time / BUCKET_MS) as u32).or_default() += 1;
+                }
+                let bars = buckets
+                    .into_iter()
+                    .map(|(bucket, count)| {
+                        Bar::new((f64::from(bucket) + 0.5) * BUCKET_MS, count.into())
+                            .width(BUCKET_MS)
+                    })
+                    .collect();
+                Plot::new("frame times")
+                    .height(80.)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .show(ui, |plot| plot.bar_chart(BarChart::new(bars).name("ms")));
+
+                ui.label(format!(
+                    "textures {:.1} MiB",
+                    self.texture_bytes as f64 / (1024. * 1024.)
+                ));
+
+                ui.separator();
+                egui::Grid::new("events").show(ui, |ui| {
+                    for (kind, count) in &self.events {
+                        ui.label(*kind);
+                        ui.label(count.to_string());
+                        ui.end_row();
+                    }
+                });
+            });
+    }
+}
+
+pub(crate) struct DebugPanel<'a> {
+    stats: &'a DebugStats,
+    app: &'a mut dyn OverlayApp,
+}
+
+impl OverlayApp for DebugPanel<'_> {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.app.ui(ctx);
+        self.stats.ui(ctx);
+    }
+}
+
+fn event_kind(event: &Event) -> &'static str {
+    match event {
+        Event::KeyPress(_) | Event::KeyRelease(_) => "key",
+        Event::ButtonPress(_)
+        | Event::ButtonRelease(_)
+        | Event::MotionNotify(_)
+        | Event::EnterNotify(_)
+        | Event::LeaveNotify(_) => "pointer",
+        Event::XinputRawMotion(_)
+        | Event::XinputRawButtonPress(_)
+        | Event::XinputRawButtonRelease(_) => "raw pointer",
+        Event::SelectionRequest(_)
+        | Event::SelectionClear(_)
+        | Event::SelectionNotify(_)
+        | Event::PropertyNotify(_)
+        | Event::XfixesSelectionNotify(_) => "selection",
+        Event::ConfigureNotify(_)
+        | Event::MapNotify(_)
+        | Event::UnmapNotify(_)
+        | Event::CirculateNotify(_)
+        | Event::CreateNotify(_)
+        | Event::DestroyNotify(_)
+        | Event::ReparentNotify(_)
+        | Event::GravityNotify(_) => "window",
+        Event::RandrScreenChangeNotify(_) | Event::RandrNotify(_) => "screen",
+        Event::Error(_) => "error",
+        _ => "other",
+    }
+}
@@ -0,0 +1,152 @@
+use std::{error, fmt, io};
+
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
+
+use crate::monitors::MonitorSelector;
+use crate::GpuError;
+
+/// Why an overlay, or its window, couldn't be set up or drawn, for the library functions to
+/// be matched on. Converts into `anyhow::Error` like any error.
+///
+/// ```no_run
+/// use egui_wgpu_x11::{Overlay, OverlayError};
+///
+/// match Overlay::builder().build() {
+///     Ok(overlay) => {}
+///     Err(OverlayError::NoAdapter(e)) => eprintln!("no GPU, tried {:?}", e.attempts),
+///     Err(OverlayError::Connect(_)) => eprintln!("not on X11"),
+///     Err(e) => eprintln!("{}", e),
+/// }
+/// ```
+#[derive(Debug)]
+pub enum OverlayError {
+    /// `$DISPLAY` is unset, or the X server refused the connection.
+    Connect(ConnectError),
+    /// The connection broke, or the server refused a request.
+    X11Protocol(ReplyOrIdError),
+    /// The screen has no 32-bit ARGB visual, the X server can't do transparent windows.
+    NoArgbVisual,
+    /// The screen has no true color visual this deep.
+    NoVisual { depth: u8 },
+    /// No monitor matches [`OverlayBuilder::monitor`](crate::OverlayBuilder::monitor).
+    NoMonitor(MonitorSelector),
+    /// No GPU could draw the overlay.
+    NoAdapter(GpuError),
+    /// The surface was lost, and drawing on a new device failed too.
+    SurfaceLost,
+    /// The surface failed otherwise, e.g. out of memory.
+    Surface(wgpu::SurfaceError),
+    /// A config file, or an environment variable, couldn't be read or parsed.
+    ConfigInvalid {
+        /// The path of the file, or the name of the variable, e.g. `OVERLAY_WGPU_BACKEND`.
+        origin: String,
+        source: Box<dyn error::Error + Send + Sync>,
+    },
+    /// Anything else, e.g. the IPC socket or D-Bus name being taken.
+    Other(anyhow::Error),
+}
+
+impl OverlayError {
+    pub(crate) fn config(origin: impl fmt::Display, source: impl Into<anyhow::Error>) -> Self {
+        Self::ConfigInvalid {
+            origin: origin.to_string(),
+            source: source.into().into(),
+        }
+    }
+}
+
+impl fmt::Display for OverlayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(e) => write!(f, "can't connect to the X server: {}", e),
+            Self::X11Protocol(e) => e.fmt(f),
+            Self::NoArgbVisual => write!(
+                f,
+                "no 32-bit ARGB visual, the X server can't do transparent windows"
+            ),
+            Self::NoVisual { depth } => write!(f, "no {}-bit true color visual", depth),
+            Self::NoMonitor(selector) => write!(f, "no monitor matching {:?}", selector),
+            Self::NoAdapter(e) => e.fmt(f),
+            Self::SurfaceLost => write!(f, "the surface was lost, and no GPU could draw again"),
+            Self::Surface(e) => e.fmt(f),
+            Self::ConfigInvalid { origin, source } => {
+                write!(f, "invalid config {}: {}", origin, source)
+            }
+            Self::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for OverlayError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Connect(e) => Some(e),
+            Self::X11Protocol(e) => Some(e),
+            Self::NoAdapter(e) => Some(e),
+            Self::Surface(e) => Some(e),
+            Self::ConfigInvalid { source, .. } => Some(source.as_ref()),
+            Self::Other(e) => Some(e.as_ref()),
+            Self::NoArgbVisual | Self::NoVisual { .. } | Self::NoMonitor(_) | Self::SurfaceLost => {
+                None
+            }
+        }
+    }
+}
+
+impl From<ConnectError> for OverlayError {
+    fn from(e: ConnectError) -> Self {
+        Self::Connect(e)
+    }
+}
+
+impl From<ReplyOrIdError> for OverlayError {
+    fn from(e: ReplyOrIdError) -> Self {
+        Self::X11Protocol(e)
+    }
+}
+
+impl From<ReplyError> for OverlayError {
+    fn from(e: ReplyError) -> Self {
+        Self::X11Protocol(e.into())
+    }
+}
+
+impl From<ConnectionError> for OverlayError {
+    fn from(e: ConnectionError) -> Self {
+        Self::X11Protocol(e.into())
+    }
+}
+
+impl From<io::Error> for OverlayError {
+    fn from(e: io::Error) -> Self {
+        Self::Other(e.into())
+    }
+}
+
+impl From<GpuError> for OverlayError {
+    fn from(e: GpuError) -> Self {
+        Self::NoAdapter(e)
+    }
+}
+
+impl From<wgpu::SurfaceError> for OverlayError {
+    fn from(e: wgpu::SurfaceError) -> Self {
+        match e {
+            wgpu::SurfaceError::Lost => Self::SurfaceLost,
+            e => Self::Surface(e),
+        }
+    }
+}
+
+/// the typed error back when it went through `anyhow`, e.g. from a helper of the overlay
+impl From<anyhow::Error> for OverlayError {
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast() {
+            Ok(e) => e,
+            Err(e) => match e.downcast() {
+                Ok(e) => Self::NoAdapter(e),
+                Err(e) => Self::Other(e),
+            },
+        }
+    }
+}
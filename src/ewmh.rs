@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, PropMode, Window};
+use x11rb::wrapper::ConnectionExt as _;
+
+/// What the overlay is to the window manager, as `_NET_WM_WINDOW_TYPE`.
+///
+/// Window managers and compositors pick their stacking, focus, decoration, shadow and
+/// animation rules from it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowType {
+    /// A panel or bar.
+    Dock,
+    /// A notification bubble.
+    Notification,
+    /// A persistent tool window, like a palette.
+    Utility,
+    /// A transient on-screen display like a volume indicator, KDE's own type, notification
+    /// elsewhere.
+    OnScreenDisplay,
+}
+
+impl WindowType {
+    /// most specific first, window managers take the first they know
+    fn atom_names(self) -> &'static [&'static str] {
+        match self {
+            Self::Dock => &["_NET_WM_WINDOW_TYPE_DOCK"],
+            Self::Notification => &["_NET_WM_WINDOW_TYPE_NOTIFICATION"],
+            Self::Utility => &["_NET_WM_WINDOW_TYPE_UTILITY"],
+            Self::OnScreenDisplay => &[
+                "_KDE_NET_WM_WINDOW_TYPE_ON_SCREEN_DISPLAY",
+                "_NET_WM_WINDOW_TYPE_NOTIFICATION",
+            ],
+        }
+    }
+}
+
+/// The desktops, or workspaces, an overlay shows on, see
+/// [`Overlay::set_workspaces`](crate::Overlay::set_workspaces). Numbered from 0, as in
+/// `_NET_CURRENT_DESKTOP`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Workspaces {
+    #[default]
+    All,
+    Only(Vec<u32>),
+    /// All the desktops but these.
+    Except(Vec<u32>),
+}
+
+impl Workspaces {
+    /// Whether the overlay shows on `desktop`, `None` without EWMH window manager.
+    pub fn shows_on(&self, desktop: Option<u32>) -> bool {
+        match (self, desktop) {
+            (Self::All, _) | (_, None) => true,
+            (Self::Only(desktops), Some(desktop)) => desktops.contains(&desktop),
+            (Self::Except(desktops), Some(desktop)) => !desktops.contains(&desktop),
+        }
+    }
+}
+
+impl std::str::FromStr for Workspaces {
+    type Err = anyhow::Error;
+
+    /// `all`, desktops comma separated, e.g. `0,1`, or `!` then the desktops to hide on
+    fn from_str(workspaces: &str) -> Result<Self> {
+        if workspaces == "all" {
+            return Ok(Self::All);
+        }
+        let (except, list) = match workspaces.strip_prefix('!') {
+            Some(list) => (true, list),
+            None => (false, workspaces),
+        };
+        let desktops = list
+            .split(',')
+            .map(|desktop| desktop.trim().parse())
+            .collect::<Result<Vec<u32>, _>>()
+            .map_err(|_| {
+                anyhow!(
+                    "expected all, desktops like 0,1, or !2 to hide on some, got {:?}",
+                    workspaces
+                )
+            })?;
+        Ok(if except {
+            Self::Except(desktops)
+        } else {
+            Self::Only(desktops)
+        })
+    }
+}
+
+/// The window properties read by window managers, set before mapping.
+#[derive(Clone, Debug)]
+pub(crate) struct WindowHints {
+    pub window_type: Option<WindowType>,
+    /// `_NET_WM_STATE_ABOVE`
+    pub above: bool,
+    /// `_NET_WM_STATE_FULLSCREEN`, over the panels
+    pub fullscreen: bool,
+    /// on all the desktops
+    pub sticky: bool,
+    pub skip_taskbar: bool,
+    pub skip_pager: bool,
+    /// `WM_CLASS` instance and class names, matched by window manager and compositor rules
+    pub class: Option<(String, String)>,
+    /// `WM_NAME` and `_NET_WM_NAME`
+    pub title: Option<String>,
+}
+
+impl Default for WindowHints {
+    fn default() -> Self {
+        Self {
+            window_type: None,
+            above: true,
+            fullscreen: false,
+            sticky: true,
+            skip_taskbar: true,
+            skip_pager: true,
+            class: None,
+            title: None,
+        }
+    }
+}
+
+impl WindowHints {
+    pub fn apply<Conn>(&self, conn: &Conn, win_id: Window) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        if let Some(window_type) = self.window_type {
+            let types = window_type
+                .atom_names()
+                .iter()
+                .map(|name| atom(conn, name))
+                .collect::<Result<Vec<_>>>()?;
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                atom(conn, "_NET_WM_WINDOW_TYPE")?,
+                AtomEnum::ATOM,
+                &types,
+            )?;
+        }
+
+        let mut states = Vec::new();
+        if self.above {
+            states.push("_NET_WM_STATE_ABOVE");
+        }
+        if self.fullscreen {
+            states.push("_NET_WM_STATE_FULLSCREEN");
+        }
+        if self.sticky {
+            states.push("_NET_WM_STATE_STICKY");
+            // all the desktops
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                atom(conn, "_NET_WM_DESKTOP")?,
+                AtomEnum::CARDINAL,
+                &[u32::MAX],
+            )?;
+        }
+        if self.skip_taskbar {
+            states.push("_NET_WM_STATE_SKIP_TASKBAR");
+        }
+        if self.skip_pager {
+            states.push("_NET_WM_STATE_SKIP_PAGER");
+        }
+        let states = states
+            .into_iter()
+            .map(|name| atom(conn, name))
+            .collect::<Result<Vec<_>>>()?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            win_id,
+            atom(conn, "_NET_WM_STATE")?,
+            AtomEnum::ATOM,
+            &states,
+        )?;
+
+        if let Some((instance, class)) = &self.class {
+            let value = format!("{}\0{}\0", instance, class);
+            conn.change_property8(
+                PropMode::REPLACE,
+                win_id,
+                AtomEnum::WM_CLASS,
+                AtomEnum::STRING,
+                value.as_bytes(),
+            )?;
+        }
+
+        if let Some(title) = &self.title {
+            conn.change_property8(
+                PropMode::REPLACE,
+                win_id,
+                AtomEnum::WM_NAME,
+                AtomEnum::STRING,
+                title.as_bytes(),
+            )?;
+            conn.change_property8(
+                PropMode::REPLACE,
+                win_id,
+                atom(conn, "_NET_WM_NAME")?,
+                atom(conn, "UTF8_STRING")?,
+                title.as_bytes(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn atom<Conn>(conn: &Conn, name: &str) -> Result<Atom>
+where
+    Conn: Connection,
+{
+    Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+}
@@ -0,0 +1,33 @@
+use std::time::{Duration, Instant};
+
+/// A fade in or out of the whole window, from one opacity to another.
+pub(crate) struct Fade {
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Fade {
+    pub fn new(from: f32, to: f32, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            start: Instant::now(),
+            duration,
+        }
+    }
+
+    /// Eased in and out.
+    pub fn opacity(&self, now: Instant) -> f32 {
+        let t =
+            now.saturating_duration_since(self.start).as_secs_f32() / self.duration.as_secs_f32();
+        let t = t.min(1.);
+        let eased = t * t * (3. - 2. * t);
+        self.from + (self.to - self.from) * eased
+    }
+
+    pub fn done(&self, now: Instant) -> bool {
+        now >= self.start + self.duration
+    }
+}
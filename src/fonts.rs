@@ -0,0 +1,47 @@
+//! Fonts from the system, e.g. for CJK text or icons missing from egui's default fonts.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::{egui::FontDefinitions, fonts, OverlayBuilder};
+//!
+//! let mut definitions = FontDefinitions::default();
+//! fonts::add_fallback(&mut definitions, "Noto Sans CJK JP")?;
+//! let overlay = OverlayBuilder::new().fonts(definitions).build()?;
+//! # anyhow::Ok(())
+//! ```
+
+use anyhow::{anyhow, Context as _, Result};
+use egui::{FontData, FontDefinitions, FontFamily};
+use fontconfig::Fontconfig;
+
+/// The font file fontconfig matches with `family`, e.g. `DejaVu Sans` or `monospace`.
+///
+/// Fontconfig always finds a match, the closest font installed: check its name in
+/// `fc-match` if glyphs are missing.
+pub fn system_font(family: &str) -> Result<FontData> {
+    let fontconfig = Fontconfig::new().ok_or_else(|| anyhow!("fontconfig failed to initialize"))?;
+    let font = fontconfig
+        .find(family, None)
+        .ok_or_else(|| anyhow!("no font matching {:?}", family))?;
+    let bytes = std::fs::read(&font.path)
+        .with_context(|| format!("reading the font {}", font.path.display()))?;
+
+    let mut data = FontData::from_owned(bytes);
+    // of the font collections, e.g. `.ttc`
+    data.index = font.index.unwrap_or(0).max(0) as u32;
+    Ok(data)
+}
+
+/// Adds the system font matching `family` after the others, for the characters they lack,
+/// in both the proportional and monospace families.
+pub fn add_fallback(definitions: &mut FontDefinitions, family: &str) -> Result<()> {
+    let data = system_font(family)?;
+    definitions.font_data.insert(family.to_owned(), data);
+    for font_family in [FontFamily::Proportional, FontFamily::Monospace] {
+        definitions
+            .families
+            .entry(font_family)
+            .or_default()
+            .push(family.to_owned());
+    }
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, EventMask, Window};
+use x11rb::protocol::Event;
+use x11rb::NONE;
+
+use crate::x11::{intern_atoms, select_more_events, unselect_events};
+
+/// What an overlay does while a fullscreen window has the focus, e.g. a game.
+///
+/// Drawing over fullscreen windows keeps compositors from unredirecting them, which costs
+/// games latency and may make them stutter.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum FullscreenPolicy {
+    /// Stays shown and drawn as usual.
+    #[default]
+    Show,
+    /// Hidden until the window leaves fullscreen or loses the focus.
+    Hide,
+    /// Shown but not drawn again meanwhile, its last frame stays.
+    Pause,
+    /// Drawn at most this many times per second meanwhile.
+    Throttle(f32),
+}
+
+impl std::str::FromStr for FullscreenPolicy {
+    type Err = anyhow::Error;
+
+    /// `show`, `hide`, `pause` or frames per second
+    fn from_str(policy: &str) -> Result<Self> {
+        match policy {
+            "show" => Ok(Self::Show),
+            "hide" => Ok(Self::Hide),
+            "pause" => Ok(Self::Pause),
+            fps => fps.parse().map(Self::Throttle).map_err(|_| {
+                anyhow!(
+                    "unknown fullscreen policy {:?}, expected show, hide, pause or fps",
+                    policy
+                )
+            }),
+        }
+    }
+}
+
+/// Follows whether the focused window is fullscreen, from `_NET_ACTIVE_WINDOW` and its
+/// `_NET_WM_STATE`, as set by EWMH window managers.
+pub(crate) struct FullscreenWatch {
+    root: Window,
+    win_id: Window,
+    net_active_window: Atom,
+    net_wm_state: Atom,
+    net_wm_state_fullscreen: Atom,
+    /// its state changes selected
+    active: Window,
+    pub fullscreen: bool,
+}
+
+impl FullscreenWatch {
+    pub fn new<Conn>(conn: &Conn, root: Window, win_id: Window) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let [net_active_window, net_wm_state, net_wm_state_fullscreen] = intern_atoms(
+            conn,
+            [
+                "_NET_ACTIVE_WINDOW",
+                "_NET_WM_STATE",
+                "_NET_WM_STATE_FULLSCREEN",
+            ],
+        )?;
+        select_more_events(conn, root, EventMask::PROPERTY_CHANGE)?;
+        let mut watch = Self {
+            root,
+            win_id,
+            net_active_window,
+            net_wm_state,
+            net_wm_state_fullscreen,
+            active: NONE,
+            fullscreen: false,
+        };
+        watch.follow_active(conn)?;
+        Ok(watch)
+    }
+
+    /// Stops following the focused window.
+    pub fn stop<Conn>(self, conn: &Conn) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        if self.active != self.win_id {
+            unselect(conn, self.active)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether `fullscreen` changed.
+    pub fn handle_event<Conn>(&mut self, conn: &Conn, event: &Event) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        let fullscreen = self.fullscreen;
+        match event {
+            Event::PropertyNotify(e)
+                if e.window == self.root && e.atom == self.net_active_window =>
+            {
+                self.follow_active(conn)?
+            }
+            Event::PropertyNotify(e) if e.window == self.active && e.atom == self.net_wm_state => {
+                self.fullscreen = self.is_fullscreen(conn)?
+            }
+            _ => {}
+        }
+        Ok(fullscreen != self.fullscreen)
+    }
+
+    /// selects the state changes of the focused window, instead of the previous one's
+    fn follow_active<Conn>(&mut self, conn: &Conn) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        let active = conn
+            .get_property(
+                false,
+                self.root,
+                self.net_active_window,
+                AtomEnum::WINDOW,
+                0,
+                1,
+            )?
+            .reply()?
+            .value32()
+            .and_then(|mut windows| windows.next())
+            .unwrap_or(NONE);
+        if active == self.active {
+            return Ok(());
+        }
+        if self.active != self.win_id {
+            unselect(conn, self.active)?;
+        }
+        self.active = active;
+        // the overlay being fullscreen itself doesn't count
+        if active == NONE || active == self.win_id {
+            self.fullscreen = false;
+            return Ok(());
+        }
+        // alongside the events selected by other parts of the overlay
+        let selected = select_more_events(conn, active, EventMask::PROPERTY_CHANGE);
+        // gone meanwhile
+        self.fullscreen = selected.is_ok() && self.is_fullscreen(conn)?;
+        Ok(())
+    }
+
+    fn is_fullscreen<Conn>(&self, conn: &Conn) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        let states = conn
+            .get_property(
+                false,
+                self.active,
+                self.net_wm_state,
+                AtomEnum::ATOM,
+                0,
+                u32::MAX,
+            )?
+            .reply();
+        Ok(states.is_ok_and(|states| {
+            states
+                .value32()
+                .is_some_and(|mut states| states.any(|state| state == self.net_wm_state_fullscreen))
+        }))
+    }
+}
+
+/// drops the events selected on another client's window, which may be gone already
+fn unselect<Conn>(conn: &Conn, window: Window) -> Result<()>
+where
+    Conn: Connection,
+{
+    if window != NONE {
+        let _ = unselect_events(conn, window, EventMask::PROPERTY_CHANGE);
+    }
+    Ok(())
+}
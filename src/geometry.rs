@@ -0,0 +1,451 @@
+//! Where the overlay window sits, relative to the screen or its monitor.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use x11rb::protocol::xproto::Rectangle;
+
+/// The point of the screen the overlay sticks to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// horizontal and vertical alignment: 0 start, 1 center, 2 end
+    fn alignment(self) -> (i32, i32) {
+        match self {
+            Self::TopLeft => (0, 0),
+            Self::Top => (1, 0),
+            Self::TopRight => (2, 0),
+            Self::Left => (0, 1),
+            Self::Center => (1, 1),
+            Self::Right => (2, 1),
+            Self::BottomLeft => (0, 2),
+            Self::Bottom => (1, 2),
+            Self::BottomRight => (2, 2),
+        }
+    }
+}
+
+impl FromStr for Anchor {
+    type Err = Error;
+
+    /// e.g. `top-left`, `center`, `bottom`
+    fn from_str(anchor: &str) -> Result<Self, Error> {
+        Ok(match anchor.to_lowercase().as_str() {
+            "top-left" => Self::TopLeft,
+            "top" => Self::Top,
+            "top-right" => Self::TopRight,
+            "left" => Self::Left,
+            "center" => Self::Center,
+            "right" => Self::Right,
+            "bottom-left" => Self::BottomLeft,
+            "bottom" => Self::Bottom,
+            "bottom-right" => Self::BottomRight,
+            _ => return Err(anyhow!("unknown anchor {:?}", anchor)),
+        })
+    }
+}
+
+/// A width or a height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    Pixels(u16),
+    /// Of the space left inside the margins.
+    Percent(f32),
+}
+
+impl Length {
+    fn resolve(self, available: u16) -> u16 {
+        match self {
+            Self::Pixels(pixels) => pixels,
+            Self::Percent(percent) => (available as f32 * percent / 100.).round() as u16,
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = Error;
+
+    /// Pixels, e.g. `400` or `400px`, or a percentage, e.g. `50%`.
+    fn from_str(length: &str) -> Result<Self, Error> {
+        let invalid = || anyhow!("expected pixels or a percentage, got {:?}", length);
+        let length = length.trim();
+        match length.strip_suffix('%') {
+            Some(percent) => Ok(Self::Percent(
+                percent.trim().parse().map_err(|_| invalid())?,
+            )),
+            None => Ok(Self::Pixels(
+                length
+                    .trim_end_matches("px")
+                    .parse()
+                    .map_err(|_| invalid())?,
+            )),
+        }
+    }
+}
+
+/// Space kept between the overlay and the screen edges, in pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Margin {
+    pub left: u16,
+    pub top: u16,
+    pub right: u16,
+    pub bottom: u16,
+}
+
+impl Margin {
+    pub fn uniform(margin: u16) -> Self {
+        Self {
+            left: margin,
+            top: margin,
+            right: margin,
+            bottom: margin,
+        }
+    }
+}
+
+/// Size and position of the overlay, recomputed when the screen or monitor changes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Geometry {
+    pub anchor: Anchor,
+    pub width: Length,
+    pub height: Length,
+    pub margin: Margin,
+}
+
+impl Default for Geometry {
+    /// The whole screen but a 100 pixels margin.
+    fn default() -> Self {
+        Self {
+            anchor: Anchor::TopLeft,
+            width: Length::Percent(100.),
+            height: Length::Percent(100.),
+            margin: Margin::uniform(100),
+        }
+    }
+}
+
+impl Geometry {
+    /// At `(x, y)` from the top-left corner, `width` x `height` pixels.
+    pub fn absolute(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self {
+            anchor: Anchor::TopLeft,
+            width: Length::Pixels(width),
+            height: Length::Pixels(height),
+            margin: Margin {
+                left: x,
+                top: y,
+                ..Margin::default()
+            },
+        }
+    }
+
+    /// The whole area.
+    pub fn fill() -> Self {
+        Self {
+            margin: Margin::default(),
+            ..Self::default()
+        }
+    }
+
+    /// Anchored with a margin on every side, e.g. a HUD in a corner.
+    pub fn anchored(anchor: Anchor, width: Length, height: Length, margin: u16) -> Self {
+        Self {
+            anchor,
+            width,
+            height,
+            margin: Margin::uniform(margin),
+        }
+    }
+
+    /// The window rectangle inside `area`, the screen or a monitor.
+    pub(crate) fn place(&self, area: Rectangle) -> Rectangle {
+        let available_width = area
+            .width
+            .saturating_sub(self.margin.left)
+            .saturating_sub(self.margin.right);
+        let available_height = area
+            .height
+            .saturating_sub(self.margin.top)
+            .saturating_sub(self.margin.bottom);
+        // X rejects empty windows
+        let width = self.width.resolve(available_width).max(1);
+        let height = self.height.resolve(available_height).max(1);
+
+        let (align_x, align_y) = self.anchor.alignment();
+        let x = i32::from(area.x)
+            + i32::from(self.margin.left)
+            + (i32::from(available_width) - i32::from(width)) * align_x / 2;
+        let y = i32::from(area.y)
+            + i32::from(self.margin.top)
+            + (i32::from(available_height) - i32::from(height)) * align_y / 2;
+
+        Rectangle {
+            x: x as i16,
+            y: y as i16,
+            width,
+            height,
+        }
+    }
+}
+
+/// The part of the overlay window holding the egui screen, and the zoom of its UI, for
+/// letterboxed or inset UIs. The rest of the window stays transparent and egui takes no input
+/// there. Pointer positions and [`Overlay::screen_to_points`](crate::Overlay::screen_to_points)
+/// stay in the points of the whole window, from its top-left corner: the egui screen, see
+/// `egui::InputState::screen_rect`, starts at the offset of the viewport.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// Places the egui screen in the window, like the window in the screen.
+    pub geometry: Geometry,
+    /// Multiplies the scale factor of the overlay, 1 by default.
+    pub scale: f32,
+}
+
+impl Default for Viewport {
+    /// The whole window.
+    fn default() -> Self {
+        Self {
+            geometry: Geometry::fill(),
+            scale: 1.,
+        }
+    }
+}
+
+impl Viewport {
+    /// `inset` pixels from the window edges.
+    pub fn inset(inset: u16) -> Self {
+        Self {
+            geometry: Geometry {
+                margin: Margin::uniform(inset),
+                ..Geometry::fill()
+            },
+            ..Self::default()
+        }
+    }
+
+    /// `width` x `height` pixels in the middle of the window, e.g. letterboxing a 16:9 UI.
+    pub fn centered(width: u16, height: u16) -> Self {
+        Self {
+            geometry: Geometry::anchored(
+                Anchor::Center,
+                Length::Pixels(width),
+                Length::Pixels(height),
+                0,
+            ),
+            ..Self::default()
+        }
+    }
+
+    pub fn scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+}
+
+/// A screen edge, along which the overlay can reserve space like a panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// `_NET_WM_STRUT_PARTIAL` reserving the extent of `window` along `edge` of the screen:
+/// left, right, top, bottom, then the start and end of each along its edge
+pub(crate) fn strut_partial(edge: Edge, window: Rectangle, screen: (u16, u16)) -> [u32; 12] {
+    let (x, y) = (window.x.max(0) as u32, window.y.max(0) as u32);
+    let (width, height) = (u32::from(window.width), u32::from(window.height));
+    let (screen_width, screen_height) = (u32::from(screen.0), u32::from(screen.1));
+
+    let mut strut = [0; 12];
+    match edge {
+        Edge::Left => {
+            strut[0] = x + width;
+            strut[4] = y;
+            strut[5] = y + height - 1;
+        }
+        Edge::Right => {
+            strut[1] = screen_width.saturating_sub(x);
+            strut[6] = y;
+            strut[7] = y + height - 1;
+        }
+        Edge::Top => {
+            strut[2] = y + height;
+            strut[8] = x;
+            strut[9] = x + width - 1;
+        }
+        Edge::Bottom => {
+            strut[3] = screen_height.saturating_sub(y);
+            strut[10] = x;
+            strut[11] = x + width - 1;
+        }
+    }
+    strut
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i16, y: i16, width: u16, height: u16) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn place_at_each_anchor() {
+        let screen = rect(0, 0, 1000, 800);
+        let hud = |anchor| Geometry::anchored(anchor, Length::Pixels(200), Length::Pixels(100), 10);
+        for (anchor, placed) in [
+            (Anchor::TopLeft, rect(10, 10, 200, 100)),
+            (Anchor::Top, rect(400, 10, 200, 100)),
+            (Anchor::TopRight, rect(790, 10, 200, 100)),
+            (Anchor::Left, rect(10, 350, 200, 100)),
+            (Anchor::Center, rect(400, 350, 200, 100)),
+            (Anchor::Right, rect(790, 350, 200, 100)),
+            (Anchor::BottomLeft, rect(10, 690, 200, 100)),
+            (Anchor::Bottom, rect(400, 690, 200, 100)),
+            (Anchor::BottomRight, rect(790, 690, 200, 100)),
+        ] {
+            assert_eq!(hud(anchor).place(screen), placed, "{:?}", anchor);
+        }
+    }
+
+    #[test]
+    fn place_in_monitors() {
+        let half = Geometry::anchored(
+            Anchor::BottomRight,
+            Length::Percent(50.),
+            Length::Percent(50.),
+            20,
+        );
+        for (monitor, placed) in [
+            (rect(0, 0, 1920, 1080), rect(960, 540, 940, 520)),
+            // right of the first one
+            (rect(1920, 0, 1280, 1024), rect(2560, 512, 620, 492)),
+            // below it, and left of the origin
+            (rect(0, 1080, 1920, 1080), rect(960, 1620, 940, 520)),
+            (rect(-1280, 0, 1280, 1024), rect(-640, 512, 620, 492)),
+        ] {
+            assert_eq!(half.place(monitor), placed, "{:?}", monitor);
+        }
+        let margin = Margin {
+            left: 5,
+            top: 30,
+            right: 15,
+            bottom: 0,
+        };
+        let panel = Geometry {
+            margin,
+            ..Geometry::fill()
+        };
+        assert_eq!(
+            panel.place(rect(1920, 0, 1280, 1024)),
+            rect(1925, 30, 1260, 994)
+        );
+    }
+
+    #[test]
+    fn place_with_margins_larger_than_the_area() {
+        let monitor = rect(1920, 0, 100, 80);
+        // one pixel past the left and top margins, X rejecting empty windows
+        let inset = Geometry {
+            margin: Margin::uniform(60),
+            ..Geometry::fill()
+        };
+        assert_eq!(inset.place(monitor), rect(1980, 60, 1, 1));
+        // the pixel sizes are kept, overflowing the area from the anchor
+        let hud = Geometry::anchored(Anchor::Center, Length::Pixels(200), Length::Pixels(100), 0);
+        assert_eq!(hud.place(monitor), rect(1870, -10, 200, 100));
+        let corner = Geometry::anchored(
+            Anchor::BottomRight,
+            Length::Pixels(50),
+            Length::Pixels(50),
+            200,
+        );
+        assert_eq!(corner.place(monitor), rect(2070, 150, 50, 50));
+    }
+
+    #[test]
+    fn struts_along_each_edge() {
+        let screen = (1920, 1080);
+        // left, right, top, bottom, left_start_y, left_end_y, right_start_y, right_end_y,
+        // top_start_x, top_end_x, bottom_start_x, bottom_end_x, as in the EWMH spec
+        for (edge, window, strut) in [
+            (
+                Edge::Left,
+                rect(0, 100, 40, 800),
+                [40, 0, 0, 0, 100, 899, 0, 0, 0, 0, 0, 0],
+            ),
+            (
+                Edge::Right,
+                rect(1880, 0, 40, 1080),
+                [0, 40, 0, 0, 0, 0, 0, 1079, 0, 0, 0, 0],
+            ),
+            (
+                Edge::Top,
+                rect(0, 0, 1920, 30),
+                [0, 0, 30, 0, 0, 0, 0, 0, 0, 1919, 0, 0],
+            ),
+            (
+                Edge::Bottom,
+                rect(200, 1050, 600, 30),
+                [0, 0, 0, 30, 0, 0, 0, 0, 0, 0, 200, 799],
+            ),
+        ] {
+            assert_eq!(strut_partial(edge, window, screen), strut, "{:?}", edge);
+        }
+    }
+
+    #[test]
+    fn struts_from_the_screen_edges() {
+        // a monitor right of a taller 1920x1200 one, the screen being 3200x1200
+        let screen = (3200, 1200);
+        let panels = [
+            // from the left edge of the screen, across the first monitor
+            (
+                Edge::Left,
+                rect(1920, 0, 40, 1024),
+                [1960, 0, 0, 0, 0, 1023],
+            ),
+            (Edge::Right, rect(3160, 0, 40, 1024), [0, 40, 0, 0, 0, 0]),
+            // from the bottom edge of the screen, below the shorter monitor
+            (
+                Edge::Bottom,
+                rect(1920, 994, 1280, 30),
+                [0, 0, 0, 206, 0, 0],
+            ),
+        ];
+        for (edge, window, start) in panels {
+            assert_eq!(
+                strut_partial(edge, window, screen)[..6],
+                start,
+                "{:?}",
+                edge
+            );
+        }
+        let bottom = strut_partial(Edge::Bottom, rect(1920, 994, 1280, 30), screen);
+        assert_eq!(bottom[10..], [1920, 3199]);
+        // off the screen, e.g. a stale monitor layout
+        let strut = strut_partial(Edge::Right, rect(3300, -10, 40, 100), screen);
+        assert_eq!(strut[1], 0);
+        assert_eq!(strut[6..8], [0, 99]);
+    }
+}
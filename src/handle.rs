@@ -0,0 +1,144 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, Result};
+
+use crate::{Overlay, OverlayApp, OverlayBuilder, Toast, Waker};
+
+enum Command<A> {
+    SetVisible(bool),
+    PushToast(Toast),
+    Update(Box<dyn FnOnce(&mut A) + Send>),
+    Close,
+}
+
+/// Controls an overlay running on its own thread, see [`OverlayBuilder::spawn`].
+///
+/// Dropping the handle leaves the overlay running, until the process exits.
+pub struct Handle<A> {
+    commands: Sender<Command<A>>,
+    waker: Waker,
+    thread: JoinHandle<Result<()>>,
+}
+
+impl<A> Handle<A> {
+    pub fn show(&self) {
+        self.send(Command::SetVisible(true));
+    }
+
+    pub fn hide(&self) {
+        self.send(Command::SetVisible(false));
+    }
+
+    pub fn push_toast(&self, toast: Toast) {
+        self.send(Command::PushToast(toast));
+    }
+
+    /// Runs `update` on the app, on the overlay thread, then renders a frame.
+    ///
+    /// ```no_run
+    /// # use egui_wgpu_x11::{egui, OverlayApp, OverlayBuilder};
+    /// #[derive(Default)]
+    /// struct Hud {
+    ///     fps: f32,
+    /// }
+    /// # impl OverlayApp for Hud {
+    /// #     fn ui(&mut self, ctx: &egui::Context) {}
+    /// # }
+    ///
+    /// let hud = OverlayBuilder::new().spawn(Hud::default)?;
+    /// hud.update(|hud| hud.fps = 144.);
+    /// # anyhow::Ok(())
+    /// ```
+    pub fn update(&self, update: impl FnOnce(&mut A) + Send + 'static) {
+        self.send(Command::Update(Box::new(update)));
+    }
+
+    /// Renders a frame, e.g. after changing data shared with the app.
+    pub fn request_repaint(&self) {
+        self.waker.wake();
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.thread.is_finished()
+    }
+
+    /// Stops the overlay and waits for its thread, returning how it ended.
+    pub fn close(self) -> Result<()> {
+        self.send(Command::Close);
+        self.thread
+            .join()
+            .map_err(|_| anyhow!("the overlay thread panicked"))?
+    }
+
+    fn send(&self, command: Command<A>) {
+        // the overlay already stopped otherwise, `close` tells why
+        if self.commands.send(command).is_ok() {
+            self.waker.wake();
+        }
+    }
+}
+
+/// Runs the commands of the [`Handle`] on the overlay thread, when woken.
+struct Commanded<A> {
+    app: A,
+    commands: Receiver<Command<A>>,
+}
+
+impl<A: OverlayApp> OverlayApp for Commanded<A> {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.app.ui(ctx);
+    }
+
+    fn update(&mut self, overlay: &mut Overlay) -> Result<()> {
+        self.app.update(overlay)
+    }
+
+    fn woken(&mut self, overlay: &mut Overlay) -> Result<()> {
+        for command in self.commands.try_iter() {
+            match command {
+                Command::SetVisible(visible) => overlay.set_visible(visible)?,
+                Command::PushToast(toast) => overlay.push_toast(toast),
+                Command::Update(update) => update(&mut self.app),
+                Command::Close => overlay.close(),
+            }
+        }
+        self.app.woken(overlay)
+    }
+}
+
+pub(crate) fn spawn<A, F>(builder: OverlayBuilder, make_app: F) -> Result<Handle<A>>
+where
+    A: OverlayApp + 'static,
+    F: FnOnce() -> A + Send + 'static,
+{
+    let (commands, receiver) = mpsc::channel();
+    let (started, waker) = mpsc::sync_channel(1);
+    let thread = thread::Builder::new()
+        .name("egui-overlay".to_owned())
+        .spawn(move || {
+            let overlay = match builder.build() {
+                Ok(overlay) => overlay,
+                Err(e) => {
+                    let _ = started.send(Err(e));
+                    return Ok(());
+                }
+            };
+            let _ = started.send(Ok(overlay.waker()));
+            overlay.run(Commanded {
+                app: make_app(),
+                commands: receiver,
+            })
+        })?;
+
+    let waker = match waker.recv() {
+        Ok(waker) => waker?,
+        // panicked while building
+        Err(_) => return Err(anyhow!("the overlay thread panicked")),
+    };
+    Ok(Handle {
+        commands,
+        waker,
+        thread,
+    })
+}
@@ -0,0 +1,112 @@
+use crate::geometry::Viewport;
+use crate::{state::State, GpuOptions, OverlayApp, OverlayError};
+
+type Result<T, E = OverlayError> = std::result::Result<T, E>;
+
+/// Renders an [`OverlayApp`] offscreen, without any display server, e.g. to test overlay UIs
+/// in CI. Input is injected with [`HeadlessOverlay::push_event`], frames are read back with
+/// [`HeadlessOverlay::capture_frame`].
+///
+/// [`OverlayApp::update`] is not called as there is no [`Overlay`](crate::Overlay). The panics
+/// of [`OverlayApp::ui`] are not caught either, so that tests fail on them.
+///
+/// ```no_run
+/// # use egui_wgpu_x11::{egui, HeadlessOverlay, OverlayApp};
+/// struct Hello;
+///
+/// impl OverlayApp for Hello {
+///     fn ui(&mut self, ctx: &egui::Context) {
+///         egui::Window::new("Hello").show(ctx, |ui| ui.label("world"));
+///     }
+/// }
+///
+/// let mut overlay = HeadlessOverlay::new(640, 480)?;
+/// overlay.render(&mut Hello)?;
+/// let frame = overlay.capture_frame()?;
+/// assert_eq!(frame.size, [640, 480]);
+/// # anyhow::Ok(())
+/// ```
+pub struct HeadlessOverlay {
+    state: State,
+    /// before the zoom of the viewport
+    pixels_per_point: f32,
+    viewport: Viewport,
+}
+
+impl HeadlessOverlay {
+    /// A `width` x `height` pixels overlay, on the GPU picked by the default [`GpuOptions`]
+    /// and their environment overrides.
+    pub fn new(width: u32, height: u32) -> Result<Self> {
+        Self::with_gpu(width, height, GpuOptions::default())
+    }
+
+    /// Like [`HeadlessOverlay::new`], e.g. with `force_fallback_adapter` for machines without
+    /// a GPU.
+    pub fn with_gpu(width: u32, height: u32, gpu: GpuOptions) -> Result<Self> {
+        let state = State::headless((width, height), &gpu.with_env()?, 1.)?;
+        Ok(Self {
+            state,
+            pixels_per_point: 1.,
+            viewport: Viewport::default(),
+        })
+    }
+
+    pub fn context(&self) -> &egui::Context {
+        self.state.context()
+    }
+
+    /// Queues `event` for the next frame, e.g. a click, in points.
+    pub fn push_event(&mut self, event: egui::Event) {
+        self.state.raw_input.events.push(event);
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.state.size
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.state.resize((width, height));
+    }
+
+    /// The scale factor, 1 by default, zoomed further by the viewport.
+    pub fn pixels_per_point(&self) -> f32 {
+        self.pixels_per_point
+    }
+
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.pixels_per_point = pixels_per_point;
+        self.state
+            .set_pixels_per_point(pixels_per_point * self.viewport.scale);
+    }
+
+    /// Places the egui screen in the frame and zooms it, see
+    /// [`Overlay::set_viewport`](crate::Overlay::set_viewport).
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+        self.state.set_viewport(viewport.geometry);
+        self.set_pixels_per_point(self.pixels_per_point);
+    }
+
+    /// Runs `app` for one frame and draws it. Animations may take several frames to settle.
+    /// If the GPU device was lost, draws again on a new one, or fails with
+    /// [`OverlayError::SurfaceLost`].
+    pub fn render(&mut self, app: &mut dyn OverlayApp) -> Result<()> {
+        match self.state.render(app) {
+            Err(OverlayError::SurfaceLost) => {
+                self.state
+                    .recover()
+                    .map_err(|_| OverlayError::SurfaceLost)?;
+                self.state.render(app)?;
+            }
+            rendered => {
+                rendered?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The last rendered frame, see [`Overlay::capture_frame`](crate::Overlay::capture_frame).
+    pub fn capture_frame(&mut self) -> Result<egui::ColorImage> {
+        Ok(self.state.capture()?)
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::{anyhow, bail, Result};
+use tracing::warn;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt as _, GrabMode, ModMask, Window};
+
+use crate::Overlay;
+
+pub(crate) type HotkeyCallback = Box<dyn FnMut(&mut Overlay) -> Result<()>>;
+
+struct Hotkey {
+    keysym: u32,
+    modifiers: ModMask,
+    keycodes: Vec<u8>,
+    callback: HotkeyCallback,
+}
+
+/// Global shortcuts, grabbed on the root window so they fire whichever window has the focus.
+#[derive(Default)]
+pub(crate) struct Hotkeys(Vec<Hotkey>);
+
+impl Hotkeys {
+    /// Fails if another client already grabbed the same combination.
+    pub fn register<Conn>(
+        &mut self,
+        conn: &Conn,
+        root: Window,
+        modifiers: egui::Modifiers,
+        keysym: u32,
+        keycodes: Vec<u8>,
+        callback: HotkeyCallback,
+    ) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        if keycodes.is_empty() {
+            bail!("no key produces the keysym {:#x}", keysym);
+        }
+        let modifiers = mod_mask(modifiers);
+        for (i, &keycode) in keycodes.iter().enumerate() {
+            if let Err(e) = grab(conn, root, modifiers, keycode) {
+                // left as it was
+                for &keycode in &keycodes[..i] {
+                    ungrab(conn, root, modifiers, keycode)?;
+                }
+                return Err(e);
+            }
+        }
+        self.0.push(Hotkey {
+            keysym,
+            modifiers,
+            keycodes,
+            callback,
+        });
+        Ok(())
+    }
+
+    /// Returns whether such a hotkey was registered.
+    pub fn unregister<Conn>(
+        &mut self,
+        conn: &Conn,
+        root: Window,
+        modifiers: egui::Modifiers,
+        keysym: u32,
+    ) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        let modifiers = mod_mask(modifiers);
+        let Some(i) = self
+            .0
+            .iter()
+            .position(|hotkey| hotkey.keysym == keysym && hotkey.modifiers == modifiers)
+        else {
+            return Ok(false);
+        };
+        let hotkey = self.0.remove(i);
+        for keycode in hotkey.keycodes {
+            ungrab(conn, root, modifiers, keycode)?;
+        }
+        Ok(true)
+    }
+
+    /// Grabs the hotkeys again on a new connection, with their keycodes from `keycodes`.
+    /// Those taken by another client meanwhile are dropped.
+    pub fn regrab<Conn>(
+        &mut self,
+        conn: &Conn,
+        root: Window,
+        keycodes: impl Fn(u32) -> Vec<u8>,
+    ) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        for mut hotkey in std::mem::take(&mut self.0) {
+            hotkey.keycodes = keycodes(hotkey.keysym);
+            let grabbed = hotkey
+                .keycodes
+                .iter()
+                .try_for_each(|&keycode| grab(conn, root, hotkey.modifiers, keycode));
+            match grabbed {
+                Ok(()) if !hotkey.keycodes.is_empty() => self.0.push(hotkey),
+                Ok(()) => warn!("hotkey {:#x} dropped, no key produces it", hotkey.keysym),
+                Err(e) => warn!("hotkey {:#x} dropped: {}", hotkey.keysym, e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Grabs the hotkeys again after a keymap change, on the keys producing them now.
+    pub fn remap<Conn>(
+        &mut self,
+        conn: &Conn,
+        root: Window,
+        keycodes: impl Fn(u32) -> Vec<u8>,
+    ) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        for hotkey in &self.0 {
+            for &keycode in &hotkey.keycodes {
+                ungrab(conn, root, hotkey.modifiers, keycode)?;
+            }
+        }
+        self.regrab(conn, root, keycodes)
+    }
+
+    /// Runs the callbacks of the hotkeys matching a `KeyPress` on the root window.
+    /// They are taken out of `overlay` meanwhile, so they can use it.
+    pub fn trigger(overlay: &mut Overlay, keycode: u8, state: u16) -> Result<()> {
+        let state = state & !u16::from(ModMask::LOCK | ModMask::M2);
+
+        let mut hotkeys = std::mem::take(&mut overlay.hotkeys);
+        let result = hotkeys
+            .0
+            .iter_mut()
+            .filter(|hotkey| {
+                u16::from(hotkey.modifiers) == state && hotkey.keycodes.contains(&keycode)
+            })
+            .try_for_each(|hotkey| (hotkey.callback)(overlay));
+        // keeping those registered by the callbacks
+        hotkeys.0.append(&mut overlay.hotkeys.0);
+        overlay.hotkeys = hotkeys;
+        result
+    }
+}
+
+fn mod_mask(modifiers: egui::Modifiers) -> ModMask {
+    let mut mask = ModMask::from(0u16);
+    if modifiers.ctrl || modifiers.command {
+        mask |= ModMask::CONTROL;
+    }
+    if modifiers.shift {
+        mask |= ModMask::SHIFT;
+    }
+    if modifiers.alt {
+        mask |= ModMask::M1;
+    }
+    mask
+}
+
+/// Lock modifiers the grabs ignore, each combination needing its own grab:
+/// caps lock, and num lock which is usually `Mod2`
+fn ignored_modifiers() -> [ModMask; 4] {
+    [
+        ModMask::from(0u16),
+        ModMask::LOCK,
+        ModMask::M2,
+        ModMask::LOCK | ModMask::M2,
+    ]
+}
+
+fn grab<Conn>(conn: &Conn, root: Window, modifiers: ModMask, keycode: u8) -> Result<()>
+where
+    Conn: Connection,
+{
+    for ignored in ignored_modifiers() {
+        conn.grab_key(
+            false,
+            root,
+            modifiers | ignored,
+            keycode,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+        )?
+        .check()
+        .map_err(|e| anyhow!("hotkey already taken by another client: {:?}", e))?;
+    }
+    Ok(())
+}
+
+fn ungrab<Conn>(conn: &Conn, root: Window, modifiers: ModMask, keycode: u8) -> Result<()>
+where
+    Conn: Connection,
+{
+    for ignored in ignored_modifiers() {
+        conn.ungrab_key(keycode, root, modifiers | ignored)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! Control of running overlays by scripts and window manager key bindings: JSON commands
+//! over a Unix socket, see [`OverlayBuilder::ipc_socket`](crate::OverlayBuilder::ipc_socket).
+//!
+//! Each line sent is a [`Command`], answered by a line once run: `{"ok":true}`, or
+//! `{"ok":false,"error":"..."}`.
+//!
+//! ```sh
+//! echo '{"command":"set-opacity","opacity":0.5}' \
+//!     | socat - UNIX-CONNECT:$XDG_RUNTIME_DIR/egui-overlay/hud.sock
+//! ```
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::command::Request;
+use crate::Waker;
+
+pub use crate::command::Command;
+
+#[derive(Deserialize, Serialize)]
+struct Reply {
+    ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Sends `command` to the overlay listening on `socket`, found like
+/// [`OverlayBuilder::ipc_socket`](crate::OverlayBuilder::ipc_socket) does, and waits until
+/// it ran.
+pub fn send(socket: impl AsRef<Path>, command: &Command) -> Result<()> {
+    let path = socket_path(socket.as_ref())?;
+    let mut stream = UnixStream::connect(&path)
+        .with_context(|| format!("connecting to the overlay at {}", path.display()))?;
+    let mut line = serde_json::to_string(command)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    let reply: Reply = serde_json::from_str(&reply)?;
+    match reply.error {
+        Some(error) => Err(anyhow!(error)),
+        None => Ok(()),
+    }
+}
+
+/// Listens on the socket on its own thread, handing the commands over to the overlay loop
+/// through its waker.
+pub(crate) struct IpcServer {
+    path: PathBuf,
+    requests: Receiver<Request>,
+    closed: Arc<AtomicBool>,
+}
+
+impl IpcServer {
+    pub fn bind(path: &Path, waker: Waker) -> Result<Self> {
+        let path = socket_path(path)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // left by an overlay that crashed, unless another one still listens there
+        if path.exists() {
+            if UnixStream::connect(&path).is_ok() {
+                bail!("another overlay listens on {}", path.display());
+            }
+            fs::remove_file(&path)?;
+        }
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("listening on {}", path.display()))?;
+
+        let (sender, requests) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        thread::Builder::new()
+            .name("egui-overlay-ipc".to_owned())
+            .spawn({
+                let closed = closed.clone();
+                move || serve(listener, sender, waker, closed)
+            })?;
+        Ok(Self {
+            path,
+            requests,
+            closed,
+        })
+    }
+
+    /// The commands received since the last call.
+    pub fn requests(&self) -> impl Iterator<Item = Request> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+        // wakes the listening thread up, to notice
+        let _ = UnixStream::connect(&self.path);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// `path` if absolute, otherwise under `$XDG_RUNTIME_DIR/egui-overlay`
+fn socket_path(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_owned());
+    }
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(env::temp_dir, PathBuf::from);
+    Ok(runtime_dir.join("egui-overlay").join(path))
+}
+
+fn serve(listener: UnixListener, requests: Sender<Request>, waker: Waker, closed: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if closed.load(Ordering::Relaxed) {
+            break;
+        }
+        match stream {
+            Ok(stream) => {
+                let requests = requests.clone();
+                let waker = waker.clone();
+                // clients may stay connected, e.g. `socat` reading a script
+                thread::spawn(move || {
+                    if let Err(e) = serve_client(stream, &requests, &waker) {
+                        debug!("IPC client: {:#}", e);
+                    }
+                });
+            }
+            Err(e) => warn!("accepting an IPC client: {}", e),
+        }
+    }
+}
+
+fn serve_client(stream: UnixStream, requests: &Sender<Request>, waker: &Waker) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let outcome = match serde_json::from_str::<Command>(&line) {
+            Ok(command) => {
+                let (reply, outcome) = mpsc::channel();
+                if requests.send((command, reply)).is_err() {
+                    return Ok(());
+                }
+                waker.wake();
+                outcome
+                    .recv()
+                    .unwrap_or_else(|_| Err("the overlay stopped".to_owned()))
+            }
+            Err(e) => Err(format!("invalid command: {}", e)),
+        };
+        let reply = Reply {
+            ok: outcome.is_ok(),
+            error: outcome.err(),
+        };
+        let mut reply = serde_json::to_string(&reply)?;
+        reply.push('\n');
+        writer.write_all(reply.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::waker::wake_channel;
+
+    fn socket(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("egui-overlay-{}-{}.sock", std::process::id(), name))
+    }
+
+    /// the next request, once the client sent it
+    fn next_request(server: &IpcServer) -> Request {
+        loop {
+            if let Some(request) = server.requests().next() {
+                return request;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn commands_and_replies() {
+        let (waker, woken) = wake_channel().unwrap();
+        let path = socket("commands");
+        let server = IpcServer::bind(&path, waker).unwrap();
+        let client = thread::spawn({
+            let path = path.clone();
+            move || {
+                let opacity = send(&path, &Command::SetOpacity { opacity: 0.5 });
+                (opacity, send(&path, &Command::Quit))
+            }
+        });
+
+        let (command, reply) = next_request(&server);
+        assert_eq!(command, Command::SetOpacity { opacity: 0.5 });
+        assert!(woken.drain());
+        reply.send(Ok(())).unwrap();
+        let (command, reply) = next_request(&server);
+        assert_eq!(command, Command::Quit);
+        reply.send(Err("already quitting".to_owned())).unwrap();
+
+        let (opacity, quit) = client.join().unwrap();
+        assert!(opacity.is_ok());
+        assert_eq!(quit.unwrap_err().to_string(), "already quitting");
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let (waker, _woken) = wake_channel().unwrap();
+        let path = socket("invalid");
+        let server = IpcServer::bind(&path, waker).unwrap();
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        // blank lines are skipped
+        stream.write_all(b"\n{\"command\":\"fly\"}\n").unwrap();
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply).unwrap();
+        let reply: Reply = serde_json::from_str(&reply).unwrap();
+        assert!(!reply.ok);
+        assert!(reply.error.unwrap().starts_with("invalid command"));
+        assert!(server.requests().next().is_none());
+    }
+
+    #[test]
+    fn one_overlay_per_socket() {
+        let path = socket("taken");
+        let (waker, _woken) = wake_channel().unwrap();
+        let server = IpcServer::bind(&path, waker.clone()).unwrap();
+        let error = IpcServer::bind(&path, waker.clone()).err().unwrap();
+        assert!(error.to_string().starts_with("another overlay listens"));
+
+        // removed once closed, and taken over when left by a crash
+        drop(server);
+        assert!(!path.exists());
+        UnixListener::bind(&path).unwrap();
+        assert!(IpcServer::bind(&path, waker).is_ok());
+    }
+
+    #[test]
+    fn relative_socket_paths() {
+        let path = Path::new("/tmp/hud.sock");
+        assert_eq!(socket_path(path).unwrap(), path);
+        let path = socket_path(Path::new("hud.sock")).unwrap();
+        assert!(path.is_absolute());
+        assert!(path.ends_with("egui-overlay/hud.sock"));
+    }
+}
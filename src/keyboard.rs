@@ -0,0 +1,536 @@
+use std::collections::HashSet;
+use std::env;
+
+use anyhow::{bail, Result};
+use egui::{Event, Key, Modifiers};
+use x11rb::protocol::xkb::{
+    self as xkb_proto, BoolCtrl, ConnectionExt as _, EventType, MapPart, PerClientFlag,
+    SelectEventsAux, ID,
+};
+use x11rb::protocol::xproto::Mapping;
+use x11rb::protocol::Event as XEvent;
+use x11rb::xcb_ffi::XCBConnection;
+use xkbcommon::xkb::{self, compose, keysyms as ks, Keycode, Keysym};
+
+/// Translates X key events into egui key and text events, through xkbcommon. The modifiers
+/// and layout follow the server, see [`Keyboard::handle_event`].
+pub(crate) struct Keyboard {
+    context: xkb::Context,
+    /// of the core keyboard, changing with it
+    device_id: i32,
+    keymap: xkb::Keymap,
+    state: xkb::State,
+    /// dead keys and compose sequences, if the locale provides a compose table
+    compose: Option<compose::State>,
+    /// the keys of the compose sequence in progress, shown while composing
+    preedit: Option<String>,
+    /// to tell repeats from presses, forgotten as the focus changes
+    pressed: HashSet<u8>,
+}
+
+impl Keyboard {
+    pub fn new(conn: &XCBConnection) -> Result<Self> {
+        let (mut major, mut minor, mut base_event, mut base_error) = (0, 0, 0, 0);
+        if !xkb::x11::setup_xkb_extension(
+            conn,
+            xkb::x11::MIN_MAJOR_XKB_VERSION,
+            xkb::x11::MIN_MINOR_XKB_VERSION,
+            xkb::x11::SetupXkbExtensionFlags::NoFlags,
+            &mut major,
+            &mut minor,
+            &mut base_event,
+            &mut base_error,
+        ) {
+            bail!("XKB extension not available");
+        }
+
+        // held keys then repeat as consecutive KeyPress, without the fake KeyRelease in between
+        conn.xkb_per_client_flags(
+            ID::USE_CORE_KBD.into(),
+            PerClientFlag::DETECTABLE_AUTO_REPEAT,
+            PerClientFlag::DETECTABLE_AUTO_REPEAT,
+            BoolCtrl::default(),
+            BoolCtrl::default(),
+            BoolCtrl::default(),
+        )?
+        .reply()?;
+        // the state and keymap changes, whatever window has the focus
+        let map_parts = MapPart::KEY_TYPES
+            | MapPart::KEY_SYMS
+            | MapPart::MODIFIER_MAP
+            | MapPart::EXPLICIT_COMPONENTS
+            | MapPart::KEY_ACTIONS
+            | MapPart::KEY_BEHAVIORS
+            | MapPart::VIRTUAL_MODS
+            | MapPart::VIRTUAL_MOD_MAP;
+        conn.xkb_select_events(
+            ID::USE_CORE_KBD.into(),
+            EventType::from(0u16),
+            EventType::NEW_KEYBOARD_NOTIFY | EventType::MAP_NOTIFY | EventType::STATE_NOTIFY,
+            map_parts,
+            map_parts,
+            &SelectEventsAux::new(),
+        )?
+        .check()?;
+
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let device_id = xkb::x11::get_core_keyboard_device_id(conn);
+        if device_id == -1 {
+            bail!("no core keyboard device");
+        }
+        let keymap = xkb::x11::keymap_new_from_device(
+            &context,
+            conn,
+            device_id,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        );
+        let state = xkb::x11::state_new_from_device(&keymap, conn, device_id);
+
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(env::var_os)
+            .find(|value| !value.is_empty())
+            .unwrap_or_else(|| "C".into());
+        let compose = compose::Table::new_from_locale(&context, &locale, compose::COMPILE_NO_FLAGS)
+            .ok()
+            .map(|table| compose::State::new(&table, compose::STATE_NO_FLAGS));
+
+        Ok(Self {
+            context,
+            device_id,
+            keymap,
+            state,
+            compose,
+            preedit: None,
+            pressed: HashSet::new(),
+        })
+    }
+
+    /// Follows the modifiers and layout of the server through `XkbStateNotify`, so that a
+    /// release missed while another window had the focus leaves nothing stuck, and the keymap
+    /// through `XkbNewKeyboardNotify`, `XkbMapNotify` and `MappingNotify`. Returns whether the
+    /// keymap changed, the hotkeys then need grabbing again.
+    pub fn handle_event(&mut self, conn: &XCBConnection, event: &XEvent) -> bool {
+        match event {
+            XEvent::XkbStateNotify(e) => {
+                self.update_state(e);
+                false
+            }
+            XEvent::XkbNewKeyboardNotify(_) | XEvent::XkbMapNotify(_) => {
+                self.reload(conn);
+                true
+            }
+            XEvent::MappingNotify(e) if e.request != Mapping::POINTER => {
+                self.reload(conn);
+                true
+            }
+            XEvent::FocusIn(_) | XEvent::FocusOut(_) => {
+                self.pressed.clear();
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn update_state(&mut self, e: &xkb_proto::StateNotifyEvent) {
+        // negative base or latched groups wrap around, xkbcommon adding them up to the locked one
+        self.state.update_mask(
+            e.base_mods.into(),
+            e.latched_mods.into(),
+            e.locked_mods.into(),
+            e.base_group as u32,
+            e.latched_group as u32,
+            e.locked_group.into(),
+        );
+    }
+
+    /// the keymap and state of the core keyboard, a new device possibly
+    fn reload(&mut self, conn: &XCBConnection) {
+        let device_id = xkb::x11::get_core_keyboard_device_id(conn);
+        if device_id == -1 {
+            return;
+        }
+        self.device_id = device_id;
+        self.keymap = xkb::x11::keymap_new_from_device(
+            &self.context,
+            conn,
+            device_id,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        );
+        self.state = xkb::x11::state_new_from_device(&self.keymap, conn, device_id);
+        self.pressed.clear();
+    }
+
+    pub fn modifiers(&self) -> Modifiers {
+        let active = |name: &str| {
+            self.state
+                .mod_name_is_active(name, xkb::STATE_MODS_EFFECTIVE)
+        };
+        let ctrl = active(xkb::MOD_NAME_CTRL);
+        Modifiers {
+            alt: active(xkb::MOD_NAME_ALT),
+            ctrl,
+            shift: active(xkb::MOD_NAME_SHIFT),
+            mac_cmd: false,
+            command: ctrl,
+        }
+    }
+
+    /// Handles a `KeyPress` (`pressed`) or `KeyRelease` of the X keycode `detail`, in the
+    /// state last reported by the server.
+    pub fn handle_key(&mut self, detail: u8, pressed: bool, events: &mut Vec<Event>) {
+        let keycode = Keycode::new(detail.into());
+
+        let repeat = if pressed {
+            !self.pressed.insert(detail)
+        } else {
+            self.pressed.remove(&detail);
+            false
+        };
+        if repeat && !self.keymap.key_repeats(keycode) {
+            return;
+        }
+
+        let modifiers = self.modifiers();
+        let keysym = self.state.key_get_one_sym(keycode);
+        if let Some(key) = egui_key(keysym) {
+            events.push(Event::Key {
+                key,
+                pressed,
+                modifiers,
+            });
+
+            // pasting needs the clipboard content first, see `clipboard::is_paste_shortcut`
+            if pressed {
+                match key {
+                    Key::C if modifiers.command => events.push(Event::Copy),
+                    Key::Insert if modifiers.command => events.push(Event::Copy),
+                    Key::X if modifiers.command => events.push(Event::Cut),
+                    Key::Delete if modifiers.shift => events.push(Event::Cut),
+                    _ => {}
+                }
+            }
+        }
+
+        // shortcuts are handled by egui through the key events, they produce no text
+        if pressed && !modifiers.ctrl && !modifiers.alt {
+            self.text(keycode, keysym, events);
+        }
+    }
+
+    /// The keycodes producing `keysym` at any level of the first layout.
+    pub fn keycodes(&self, keysym: u32) -> Vec<u8> {
+        let mut keycodes = Vec::new();
+        self.keymap.key_for_each(|keymap, keycode| {
+            let produces = (0..keymap.num_levels_for_key(keycode, 0)).any(|level| {
+                keymap
+                    .key_get_syms_by_level(keycode, 0, level)
+                    .iter()
+                    .any(|sym| sym.raw() == keysym)
+            });
+            if let (true, Ok(keycode)) = (produces, u8::try_from(keycode.raw())) {
+                keycodes.push(keycode);
+            }
+        });
+        keycodes
+    }
+
+    /// The text typed by the key, going through dead keys and compose sequences, which text
+    /// fields show as an IME composition until the sequence ends.
+    fn text(&mut self, keycode: Keycode, keysym: Keysym, events: &mut Vec<Event>) {
+        let Some(compose) = &mut self.compose else {
+            push_text(self.state.key_get_utf8(keycode), events);
+            return;
+        };
+        // modifiers, they don't break sequences
+        if matches!(compose.feed(keysym), compose::FeedResult::Ignored) {
+            return;
+        }
+        match compose.status() {
+            compose::Status::Composing => {
+                if self.preedit.is_none() {
+                    events.push(Event::CompositionStart);
+                }
+                let preedit = self.preedit.get_or_insert_with(String::new);
+                preedit.push_str(&preedit_text(&self.state, keycode, keysym));
+                events.push(Event::CompositionUpdate(preedit.clone()));
+            }
+            compose::Status::Composed => {
+                let text = compose.utf8().unwrap_or_default();
+                compose.reset();
+                self.end_composition(text, events);
+            }
+            // egui can't take the preedit text back, the keys are typed as shown
+            compose::Status::Cancelled => {
+                compose.reset();
+                let preedit = self.preedit.clone().unwrap_or_default();
+                self.end_composition(preedit, events);
+            }
+            compose::Status::Nothing => push_text(self.state.key_get_utf8(keycode), events),
+        }
+    }
+
+    fn end_composition(&mut self, text: String, events: &mut Vec<Event>) {
+        match self.preedit.take() {
+            Some(_) => events.push(Event::CompositionEnd(text)),
+            None => push_text(text, events),
+        }
+    }
+}
+
+fn push_text(text: String, events: &mut Vec<Event>) {
+    if !text.is_empty() && text.chars().all(is_printable) {
+        events.push(Event::Text(text));
+    }
+}
+
+/// how a key of a compose sequence shows: dead keys as their spacing accent
+fn preedit_text(state: &xkb::State, keycode: Keycode, keysym: Keysym) -> String {
+    let accent = match keysym.raw() {
+        ks::KEY_dead_grave => "`",
+        ks::KEY_dead_acute => "´",
+        ks::KEY_dead_circumflex => "^",
+        ks::KEY_dead_tilde => "~",
+        ks::KEY_dead_macron => "¯",
+        ks::KEY_dead_breve => "˘",
+        ks::KEY_dead_abovedot => "˙",
+        ks::KEY_dead_diaeresis => "¨",
+        ks::KEY_dead_abovering => "˚",
+        ks::KEY_dead_doubleacute => "˝",
+        ks::KEY_dead_caron => "ˇ",
+        ks::KEY_dead_cedilla => "¸",
+        ks::KEY_dead_ogonek => "˛",
+        ks::KEY_Multi_key => "·",
+        _ => {
+            let text = state.key_get_utf8(keycode);
+            return if text.chars().all(is_printable) {
+                text
+            } else {
+                String::new()
+            };
+        }
+    };
+    accent.to_owned()
+}
+
+/// Control characters and the private use area (where some keysyms land) are not text.
+fn is_printable(c: char) -> bool {
+    !c.is_control() && !('\u{e000}'..='\u{f8ff}').contains(&c)
+}
+
+fn egui_key(keysym: Keysym) -> Option<Key> {
+    const LETTERS: [Key; 26] = [
+        Key::A,
+        Key::B,
+        Key::C,
+        Key::D,
+        Key::E,
+        Key::F,
+        Key::G,
+        Key::H,
+        Key::I,
+        Key::J,
+        Key::K,
+        Key::L,
+        Key::M,
+        Key::N,
+        Key::O,
+        Key::P,
+        Key::Q,
+        Key::R,
+        Key::S,
+        Key::T,
+        Key::U,
+        Key::V,
+        Key::W,
+        Key::X,
+        Key::Y,
+        Key::Z,
+    ];
+    const DIGITS: [Key; 10] = [
+        Key::Num0,
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+    ];
+    const FUNCTIONS: [Key; 20] = [
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::F13,
+        Key::F14,
+        Key::F15,
+        Key::F16,
+        Key::F17,
+        Key::F18,
+        Key::F19,
+        Key::F20,
+    ];
+
+    let raw = keysym.raw();
+    Some(match raw {
+        ks::KEY_Down | ks::KEY_KP_Down => Key::ArrowDown,
+        ks::KEY_Left | ks::KEY_KP_Left => Key::ArrowLeft,
+        ks::KEY_Right | ks::KEY_KP_Right => Key::ArrowRight,
+        ks::KEY_Up | ks::KEY_KP_Up => Key::ArrowUp,
+        ks::KEY_Escape => Key::Escape,
+        ks::KEY_Tab | ks::KEY_ISO_Left_Tab => Key::Tab,
+        ks::KEY_BackSpace => Key::Backspace,
+        ks::KEY_Return | ks::KEY_KP_Enter => Key::Enter,
+        ks::KEY_space => Key::Space,
+        ks::KEY_Insert | ks::KEY_KP_Insert => Key::Insert,
+        ks::KEY_Delete | ks::KEY_KP_Delete => Key::Delete,
+        ks::KEY_Home | ks::KEY_KP_Home => Key::Home,
+        ks::KEY_End | ks::KEY_KP_End => Key::End,
+        ks::KEY_Page_Up | ks::KEY_KP_Page_Up => Key::PageUp,
+        ks::KEY_Page_Down | ks::KEY_KP_Page_Down => Key::PageDown,
+        ks::KEY_a..=ks::KEY_z => LETTERS[(raw - ks::KEY_a) as usize],
+        ks::KEY_A..=ks::KEY_Z => LETTERS[(raw - ks::KEY_A) as usize],
+        ks::KEY_0..=ks::KEY_9 => DIGITS[(raw - ks::KEY_0) as usize],
+        ks::KEY_KP_0..=ks::KEY_KP_9 => DIGITS[(raw - ks::KEY_KP_0) as usize],
+        ks::KEY_F1..=ks::KEY_F20 => FUNCTIONS[(raw - ks::KEY_F1) as usize],
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::protocol::xproto::ModMask;
+
+    use super::*;
+
+    /// evdev keycodes, as X reports them
+    const KEY_A: u8 = 38;
+    const KEY_1: u8 = 10;
+
+    /// a US keyboard, if the xkeyboard-config data is installed
+    fn keyboard() -> Option<Keyboard> {
+        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+        let keymap = xkb::Keymap::new_from_names(
+            &context,
+            "evdev",
+            "pc105",
+            "us",
+            "",
+            None,
+            xkb::KEYMAP_COMPILE_NO_FLAGS,
+        )?;
+        let state = xkb::State::new(&keymap);
+        Some(Keyboard {
+            context,
+            device_id: 0,
+            keymap,
+            state,
+            compose: None,
+            preedit: None,
+            pressed: HashSet::new(),
+        })
+    }
+
+    fn server_state(base_mods: ModMask) -> xkb_proto::StateNotifyEvent {
+        xkb_proto::StateNotifyEvent {
+            base_mods,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn egui_keys() {
+        let key = |raw| egui_key(Keysym::new(raw));
+        assert_eq!(key(ks::KEY_a), Some(Key::A));
+        assert_eq!(key(ks::KEY_z), Some(Key::Z));
+        assert_eq!(key(ks::KEY_A), Some(Key::A));
+        assert_eq!(key(ks::KEY_Q), Some(Key::Q));
+        assert_eq!(key(ks::KEY_0), Some(Key::Num0));
+        assert_eq!(key(ks::KEY_7), Some(Key::Num7));
+        assert_eq!(key(ks::KEY_KP_0), Some(Key::Num0));
+        assert_eq!(key(ks::KEY_KP_9), Some(Key::Num9));
+        assert_eq!(key(ks::KEY_F1), Some(Key::F1));
+        assert_eq!(key(ks::KEY_F12), Some(Key::F12));
+        assert_eq!(key(ks::KEY_F20), Some(Key::F20));
+        assert_eq!(key(ks::KEY_KP_Up), Some(Key::ArrowUp));
+        assert_eq!(key(ks::KEY_KP_Enter), Some(Key::Enter));
+        assert_eq!(key(ks::KEY_ISO_Left_Tab), Some(Key::Tab));
+        // next to the ranges
+        assert_eq!(key(ks::KEY_F21), None);
+        assert_eq!(key(ks::KEY_exclam), None);
+        assert_eq!(key(ks::KEY_KP_Add), None);
+        assert_eq!(key(ks::KEY_Shift_L), None);
+    }
+
+    #[test]
+    fn modifiers_follow_the_server() {
+        let Some(mut keyboard) = keyboard() else {
+            eprintln!("no xkeyboard-config data, skipping");
+            return;
+        };
+        keyboard.update_state(&server_state(ModMask::SHIFT));
+        assert!(keyboard.modifiers().shift);
+        let mut events = Vec::new();
+        keyboard.handle_key(KEY_A, true, &mut events);
+        keyboard.handle_key(KEY_A, false, &mut events);
+        assert!(events.contains(&Event::Text("A".to_owned())));
+
+        // the shift release went to another window, the server tells all the same
+        keyboard.update_state(&server_state(ModMask::from(0u16)));
+        assert!(!keyboard.modifiers().shift);
+        let mut events = Vec::new();
+        keyboard.handle_key(KEY_A, true, &mut events);
+        assert_eq!(
+            events,
+            [
+                Event::Key {
+                    key: Key::A,
+                    pressed: true,
+                    modifiers: Modifiers::NONE,
+                },
+                Event::Text("a".to_owned()),
+            ]
+        );
+
+        keyboard.update_state(&server_state(ModMask::CONTROL));
+        let mut events = Vec::new();
+        keyboard.handle_key(KEY_1, true, &mut events);
+        assert_eq!(
+            events,
+            [Event::Key {
+                key: Key::Num1,
+                pressed: true,
+                modifiers: Modifiers::CTRL | Modifiers::COMMAND,
+            }]
+        );
+    }
+
+    #[test]
+    fn repeats() {
+        let Some(mut keyboard) = keyboard() else {
+            eprintln!("no xkeyboard-config data, skipping");
+            return;
+        };
+        let mut events = Vec::new();
+        keyboard.handle_key(KEY_A, true, &mut events);
+        keyboard.handle_key(KEY_A, true, &mut events);
+        assert_eq!(
+            events
+                .iter()
+                .filter(|e| **e == Event::Text("a".to_owned()))
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,3323 @@
+//! egui + WGPU inside a raw X11 overlay window.
+//!
+//! Implement [`OverlayApp`] and hand it to [`Overlay::run`]:
+//!
+//! ```no_run
+//! use egui_wgpu_x11::{Overlay, OverlayApp};
+//!
+//! struct Hello;
+//!
+//! impl OverlayApp for Hello {
+//!     fn ui(&mut self, ctx: &egui::Context) {
+//!         egui::Window::new("Hello").show(ctx, |ui| ui.label("world"));
+//!     }
+//! }
+//!
+//! fn main() -> anyhow::Result<()> {
+//!     Overlay::new()?.run(Hello)
+//! }
+//! ```
+
+pub mod annotation;
+mod attach;
+mod budget;
+mod capture;
+mod clipboard;
+#[cfg(any(feature = "ipc", feature = "dbus", feature = "scripting"))]
+mod command;
+mod compositor;
+pub mod config;
+mod cursor;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+mod debug;
+mod error;
+mod ewmh;
+mod fade;
+pub mod fonts;
+mod fullscreen;
+pub mod geometry;
+mod handle;
+mod headless;
+mod hotkeys;
+#[cfg(feature = "ipc")]
+pub mod ipc;
+mod keyboard;
+pub mod magnifier;
+mod mirror;
+pub mod monitors;
+mod occlusion;
+mod paint;
+pub mod panels;
+mod panics;
+#[cfg(feature = "persistence")]
+mod persistence;
+mod picker;
+mod pointer;
+#[cfg(feature = "recorder")]
+pub mod recorder;
+pub mod renderer;
+mod runner;
+mod scale;
+pub mod scenes;
+mod screen;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+mod scroll;
+mod signals;
+mod stacking;
+mod state;
+mod stats;
+mod theme;
+pub mod toasts;
+mod touch;
+#[cfg(feature = "dbus")]
+pub mod tray;
+mod uploads;
+mod url;
+mod waker;
+mod watchdog;
+#[cfg(feature = "wayland")]
+mod wayland;
+pub mod widgets;
+pub mod window_capture;
+pub mod wm_query;
+pub mod x11;
+mod xerror;
+
+use std::{
+    cell::Cell,
+    ffi::c_void,
+    mem::ManuallyDrop,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use anyhow::Result;
+use attach::WindowFollower;
+use budget::BudgetHandler;
+use clipboard::{is_paste_shortcut, Clipboard, Selection};
+use compositor::Compositor;
+use cursor::Cursors;
+use debug::DebugStats;
+use ewmh::WindowHints;
+use fade::Fade;
+use fullscreen::FullscreenWatch;
+use geometry::{strut_partial, Anchor, Edge, Geometry, Length, Margin, Viewport};
+use hotkeys::Hotkeys;
+use keyboard::Keyboard;
+use mirror::{mirror_rect, MirrorWindow};
+use monitors::{monitors, randr_init, Monitor, MonitorSelector};
+use occlusion::Occlusion;
+use panels::WorkArea;
+use picker::{pick_event, window_at, ColorPicker, PickEvent, WindowPicker};
+use pointer::{is_middle_press, pointer_event, PointerTracker};
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, XcbDisplayHandle,
+    XcbWindowHandle,
+};
+use renderer::{Renderer, RendererFactory};
+use runner::{Runner, Turn};
+use scale::detect_scale_factor;
+use scroll::{select_xi2_events, SmoothScroll};
+use stacking::Stacking;
+use state::{Gpu, State, SurfaceOptions};
+use touch::Touches;
+use tracing::{debug, trace, trace_span, warn};
+use url::UrlHandler;
+use waker::{wake_channel, WakeReceiver};
+use wm_query::WmWatch;
+use x11::{
+    always_on_top, argb_visuals, create_overlay_window, input_events, intern_atoms, overlay_events,
+    select_more_events, set_bounding_region, set_input_region, set_strut, set_window_geometry,
+    set_wm_protocols, visual_for_depth, wait_for_event, write_blur_region, write_window_opacity,
+    xfixes_init, OverlayWindow, XAuth,
+};
+use x11rb::{
+    connection::Connection,
+    protocol::{
+        randr::Rotation,
+        xproto::{
+            Atom, Colormap, ConnectionExt, EventMask, GrabMode, GrabStatus, InputFocus, Rectangle,
+            Visualid, Window,
+        },
+        Event,
+    },
+    xcb_ffi::XCBConnection,
+    CURRENT_TIME,
+};
+use xerror::{log_x_error, XErrorHandler};
+
+pub use attach::{AttachSide, AttachTarget, Attachment};
+pub use budget::TextureMemory;
+#[cfg(any(feature = "ipc", feature = "dbus", feature = "scripting"))]
+pub use command::Command;
+pub use egui;
+pub use error::OverlayError;
+pub use ewmh::{WindowType, Workspaces};
+pub use fullscreen::FullscreenPolicy;
+pub use handle::Handle;
+pub use headless::HeadlessOverlay;
+pub use mirror::Mirrors;
+pub use paint::{paint_callback, CallbackContext, UserTexture};
+pub use picker::PickedWindow;
+pub use state::{ColorSpace, GpuAttempt, GpuError, GpuFailure, GpuOptions};
+pub use stats::FrameStats;
+pub use theme::Theme;
+pub use toasts::{Toast, ToastSender};
+pub use waker::Waker;
+pub use watchdog::Watchdog;
+pub use wgpu;
+pub use x11rb;
+pub use xerror::XError;
+pub use xkbcommon::xkb::keysyms;
+
+/// The egui application drawn inside the overlay.
+pub trait OverlayApp {
+    /// Builds the UI, called once per frame.
+    fn ui(&mut self, ctx: &egui::Context);
+
+    /// Called after each frame, to drive the overlay itself.
+    ///
+    /// ```no_run
+    /// # use egui_wgpu_x11::{egui, Overlay, OverlayApp};
+    /// # struct Hud;
+    /// impl OverlayApp for Hud {
+    ///     # fn ui(&mut self, ctx: &egui::Context) {}
+    ///     fn update(&mut self, overlay: &mut Overlay) -> anyhow::Result<()> {
+    ///         if overlay.context().input().key_pressed(egui::Key::F1) {
+    ///             overlay.set_input_passthrough(!overlay.input_passthrough())?;
+    ///         }
+    ///         Ok(())
+    ///     }
+    /// }
+    /// ```
+    fn update(&mut self, _overlay: &mut Overlay) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when the [`Overlay::waker`] fires, before rendering, even while hidden.
+    fn woken(&mut self, _overlay: &mut Overlay) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Which parts of the overlay catch mouse input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InputShape {
+    /// All input goes through to the windows underneath.
+    #[default]
+    Passthrough,
+    /// The whole window catches input.
+    Window,
+    /// The areas painted by egui catch input, the rest goes through.
+    /// Follows the UI, updated after each frame.
+    Content,
+}
+
+impl std::str::FromStr for InputShape {
+    type Err = anyhow::Error;
+
+    /// `passthrough`, `window` or `content`
+    fn from_str(input_shape: &str) -> Result<Self> {
+        match input_shape.to_lowercase().as_str() {
+            "passthrough" => Ok(Self::Passthrough),
+            "window" => Ok(Self::Window),
+            "content" => Ok(Self::Content),
+            _ => bail!(
+                "expected passthrough, window or content, got {:?}",
+                input_shape
+            ),
+        }
+    }
+}
+
+struct MyWindow {
+    pub window: u32,
+    pub visual_id: u32,
+    pub connection: *mut c_void,
+    pub screen: i32,
+}
+
+unsafe impl HasRawWindowHandle for MyWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        let mut handle = XcbWindowHandle::empty();
+        handle.visual_id = self.visual_id;
+        handle.window = self.window;
+        RawWindowHandle::Xcb(handle)
+    }
+}
+unsafe impl HasRawDisplayHandle for MyWindow {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        let mut handle = XcbDisplayHandle::empty();
+        handle.connection = self.connection;
+        handle.screen = self.screen;
+        RawDisplayHandle::Xcb(handle)
+    }
+}
+
+/// An X11 overlay window with its wgpu surface and egui context.
+pub struct Overlay {
+    /// dropped first, the surface must go before its window and the connection
+    state: ManuallyDrop<State>,
+    keyboard: Keyboard,
+    clipboard: Clipboard,
+    cursors: Cursors,
+    compositor: Compositor,
+    waker: Waker,
+    wake_receiver: WakeReceiver,
+    /// shared with the sibling overlays
+    conn: Rc<XCBConnection>,
+    screen_num: usize,
+    root: u32,
+    win_id: u32,
+    /// of the window, shared by the mirrors
+    visual: Visualid,
+    depth: u8,
+    colormap: Colormap,
+    /// sent by window managers to close the window, e.g. from its taskbar entry
+    wm_delete_window: Atom,
+    /// interned once, set again by every frame fading or painting elsewhere
+    opacity_atom: Atom,
+    blur_atom: Atom,
+    /// set once run, the runner then flushes the requests at the end of every frame and turn
+    running: bool,
+    /// nesting of [`Overlay::with_batch`], the outermost one flushes
+    batch_depth: Cell<u32>,
+    input_shape: InputShape,
+    /// overrides `input_shape` with full click-through when set
+    input_passthrough: bool,
+    /// areas painted by the last frame, the input region in `InputShape::Content` mode
+    input_region: Vec<Rectangle>,
+    /// placement in the screen or monitor, reapplied when they change
+    geometry: Geometry,
+    /// where `geometry` puts the window, before cropping
+    placed: Rectangle,
+    /// shrinks the window to the painted areas
+    crop_to_content: bool,
+    /// the part of `placed` covered by the window when cropped
+    crop: Option<Rectangle>,
+    /// blurs behind the painted areas, under compositors that can
+    blur_behind: bool,
+    /// set by the last frame, relative to the window
+    blur_region: Vec<Rectangle>,
+    /// shapes the window itself to the painted areas
+    shape_to_content: bool,
+    /// set by the last frame, relative to the window, `None` when unshaped
+    bounding_region: Option<Vec<Rectangle>>,
+    /// the monitor holding the overlay, followed across monitor changes
+    monitor: Option<MonitorSelector>,
+    /// see [`Overlay::set_mirrors`]
+    mirrors: Mirrors,
+    /// showing the frames on the other monitors, in the order of the mirrors of `state`
+    mirror_windows: Vec<MirrorWindow>,
+    /// the window of another application the overlay sticks to, see [`Overlay::attach`]
+    follower: Option<WindowFollower>,
+    /// the desktops the overlay shows on, see [`Overlay::set_workspaces`]
+    workspaces: Workspaces,
+    /// see [`Overlay::set_viewport`]
+    viewport: Viewport,
+    /// overrides the detected scale factor
+    scale_factor: Option<f32>,
+    /// software frame rate cap, zero for none
+    min_frame_time: Duration,
+    /// see [`Overlay::set_ui_rate`]
+    ui_rate: Option<f32>,
+    /// screen edge along which the overlay reserves space, like a panel
+    strut: Option<Edge>,
+    /// follows the pointer even when the overlay gets no pointer events
+    pointer_tracker: Option<PointerTracker>,
+    /// `None` without XInput 2.1, wheels then scroll by notches
+    smooth_scroll: Option<SmoothScroll>,
+    /// `None` without XInput 2.2
+    touches: Option<Touches>,
+    hotkeys: Hotkeys,
+    /// mapped, or about to be once faded in
+    visible: bool,
+    /// grabs the keyboard while egui wants keyboard input
+    keyboard_grab: bool,
+    keyboard_grabbed: bool,
+    /// of `set_visible`, zero for none
+    fade_duration: Duration,
+    fade: Option<Fade>,
+    /// kept above the other windows, by `stacking`
+    always_on_top: bool,
+    stacking: Stacking,
+    /// frames are skipped while nobody can see them
+    occlusion: Occlusion,
+    /// shared with the [`panels`] through the egui context
+    work_area: WorkArea,
+    fullscreen_policy: FullscreenPolicy,
+    /// following the focused window, unless shown anyway
+    fullscreen_watch: Option<FullscreenWatch>,
+    /// by the fullscreen policy, the attached window or the workspaces, shown again after
+    hidden_by_rules: bool,
+    /// opacity of the ongoing or last fade, 1 when fully shown
+    fade_level: f32,
+    /// of the whole window, multiplied by `fade_level`
+    opacity: f32,
+    x_error_handler: XErrorHandler,
+    /// opens the clicked links, if any
+    url_command: Option<String>,
+    url_handler: Option<UrlHandler>,
+    /// told when the textures drawn go over the budget, logged otherwise
+    texture_budget_handler: Option<BudgetHandler>,
+    /// stops the loop after the current turn
+    closed: bool,
+    exit_on_signal: bool,
+    /// drawn over the app when set
+    debug: Option<DebugStats>,
+    /// while picking a color, see [`Overlay::pick_color`]
+    picker: Option<ColorPicker>,
+    /// while picking a window, see [`Overlay::pick_window`]
+    window_picker: Option<WindowPicker>,
+    /// reports the window manager changes to the [`wm_query::WmQuery`]s, once one is made
+    wm_watch: Option<WmWatch>,
+    /// where the egui memory is saved when dropped
+    #[cfg(feature = "persistence")]
+    memory_path: Option<std::path::PathBuf>,
+    /// see [`Overlay::start_recording`]
+    #[cfg(feature = "recorder")]
+    recording: Option<recorder::Recording>,
+    /// runs the commands of scripts, see [`OverlayBuilder::ipc_socket`]
+    #[cfg(feature = "ipc")]
+    ipc: Option<ipc::IpcServer>,
+    /// runs the method calls, see [`OverlayBuilder::dbus_name`]
+    #[cfg(feature = "dbus")]
+    dbus: Option<dbus::DbusService>,
+    /// see [`OverlayBuilder::notification_daemon`]
+    #[cfg(feature = "dbus")]
+    notifications: Option<dbus::NotificationDaemon>,
+    /// see [`Overlay::set_tray`]
+    #[cfg(feature = "dbus")]
+    tray: Option<tray::TrayIcon>,
+    toasts: toasts::Toasts,
+    /// see [`OverlayBuilder::watch_config`]
+    config_watcher: Option<config::ConfigWatcher>,
+    /// last applied by the watcher
+    config: Option<config::Config>,
+    /// built with, to build it again after reconnecting
+    settings: OverlayBuilder,
+}
+
+/// Configures an [`Overlay`] before its window is mapped.
+///
+/// ```no_run
+/// use egui_wgpu_x11::geometry::{Anchor, Length};
+/// use egui_wgpu_x11::OverlayBuilder;
+///
+/// let overlay = OverlayBuilder::new()
+///     .anchor(Anchor::BottomRight)
+///     .size(Length::Pixels(400), Length::Percent(50.))
+///     .margin(20)
+///     .build()?;
+/// # anyhow::Ok(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct OverlayBuilder {
+    display: Option<String>,
+    xauth: XAuth,
+    geometry: Geometry,
+    monitor: Option<MonitorSelector>,
+    mirrors: Mirrors,
+    attachment: Option<Attachment>,
+    workspaces: Workspaces,
+    input_shape: InputShape,
+    event_mask: Option<EventMask>,
+    scale_factor: Option<f32>,
+    gpu: GpuOptions,
+    present_mode: wgpu::PresentMode,
+    msaa_samples: u32,
+    color_space: ColorSpace,
+    renderer: RendererFactory,
+    max_fps: Option<f32>,
+    ui_rate: Option<f32>,
+    texture_budget: Option<usize>,
+    watchdog: Option<Watchdog>,
+    strut: Option<Edge>,
+    track_pointer: bool,
+    visible: bool,
+    fullscreen_policy: FullscreenPolicy,
+    keyboard_grab: bool,
+    crop_to_content: bool,
+    blur_behind: bool,
+    shape_to_content: bool,
+    fade_duration: Duration,
+    opacity: f32,
+    hints: WindowHints,
+    url_command: Option<String>,
+    managed: bool,
+    viewport: Viewport,
+    debug_panel: bool,
+    toast_corner: egui::Align2,
+    exit_on_signal: bool,
+    fonts: Option<egui::FontDefinitions>,
+    style: Option<egui::Style>,
+    theme: Option<Theme>,
+    #[cfg(feature = "persistence")]
+    memory_path: Option<std::path::PathBuf>,
+    #[cfg(feature = "ipc")]
+    ipc_socket: Option<std::path::PathBuf>,
+    #[cfg(feature = "dbus")]
+    dbus_name: Option<String>,
+    #[cfg(feature = "dbus")]
+    notification_daemon: bool,
+    watch_config: Option<std::path::PathBuf>,
+}
+
+impl Default for OverlayBuilder {
+    fn default() -> Self {
+        Self {
+            display: None,
+            xauth: XAuth::Default,
+            geometry: Geometry::default(),
+            monitor: None,
+            mirrors: Mirrors::None,
+            attachment: None,
+            workspaces: Workspaces::All,
+            input_shape: InputShape::default(),
+            event_mask: None,
+            scale_factor: None,
+            gpu: GpuOptions::default(),
+            present_mode: wgpu::PresentMode::Fifo,
+            msaa_samples: 1,
+            color_space: ColorSpace::Srgb,
+            renderer: RendererFactory::default(),
+            max_fps: Some(60.),
+            ui_rate: None,
+            texture_budget: None,
+            watchdog: Some(Watchdog::default()),
+            strut: None,
+            track_pointer: false,
+            visible: true,
+            fullscreen_policy: FullscreenPolicy::Show,
+            keyboard_grab: false,
+            crop_to_content: false,
+            blur_behind: false,
+            shape_to_content: false,
+            fade_duration: Duration::ZERO,
+            url_command: Some(url::DEFAULT_URL_COMMAND.to_owned()),
+            opacity: 1.,
+            hints: WindowHints::default(),
+            managed: false,
+            viewport: Viewport::default(),
+            debug_panel: false,
+            toast_corner: egui::Align2::RIGHT_TOP,
+            exit_on_signal: true,
+            fonts: None,
+            style: None,
+            theme: None,
+            #[cfg(feature = "persistence")]
+            memory_path: None,
+            #[cfg(feature = "ipc")]
+            ipc_socket: None,
+            #[cfg(feature = "dbus")]
+            dbus_name: None,
+            #[cfg(feature = "dbus")]
+            notification_daemon: false,
+            watch_config: None,
+        }
+    }
+}
+
+impl OverlayBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Covers the whole screen, panels included, or the whole monitor if one is picked with
+    /// [`OverlayBuilder::monitor`], letting all input through: the usual game or stream HUD.
+    pub fn fullscreen_overlay() -> Self {
+        Self::new()
+            .geometry(Geometry::fill())
+            .input_shape(InputShape::Passthrough)
+            .fullscreen(true)
+    }
+
+    pub fn geometry(mut self, geometry: Geometry) -> Self {
+        self.geometry = geometry;
+        self
+    }
+
+    pub fn anchor(mut self, anchor: Anchor) -> Self {
+        self.geometry.anchor = anchor;
+        self
+    }
+
+    pub fn size(mut self, width: Length, height: Length) -> Self {
+        self.geometry.width = width;
+        self.geometry.height = height;
+        self
+    }
+
+    /// The same margin on every side, see [`Geometry::margin`] for distinct ones.
+    pub fn margin(mut self, margin: u16) -> Self {
+        self.geometry.margin = Margin::uniform(margin);
+        self
+    }
+
+    /// Places the overlay in a monitor rather than in the whole screen.
+    pub fn monitor(mut self, monitor: MonitorSelector) -> Self {
+        self.monitor = Some(monitor);
+        self
+    }
+
+    /// Shows copies of the overlay on other monitors, see [`Mirrors`]. Off by default, see
+    /// [`Overlay::set_mirrors`].
+    pub fn mirrors(mut self, mirrors: Mirrors) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Sticks the overlay to the window of another application, see [`Attachment`]. Off by
+    /// default, see [`Overlay::attach`].
+    pub fn attach(mut self, attachment: Attachment) -> Self {
+        self.attachment = Some(attachment);
+        self
+    }
+
+    pub fn input_shape(mut self, input_shape: InputShape) -> Self {
+        self.input_shape = input_shape;
+        self
+    }
+
+    /// Sets the events selected on the window, instead of the ones derived from the input
+    /// shape: [`x11::overlay_events`], with [`x11::input_events`] unless the input goes
+    /// through. Input events are added anyway once the overlay catches input.
+    pub fn event_mask(mut self, event_mask: EventMask) -> Self {
+        self.event_mask = Some(event_mask);
+        self
+    }
+
+    /// Overrides the detected scale factor.
+    pub fn scale_factor(mut self, scale_factor: f32) -> Self {
+        self.scale_factor = Some(scale_factor);
+        self
+    }
+
+    /// The X server to connect to, `$DISPLAY` by default, e.g. `:1` for a nested Xephyr or
+    /// `host:0` for a remote one over TCP. Ignored by siblings, on the connection of theirs.
+    pub fn display(mut self, display: impl Into<String>) -> Self {
+        self.display = Some(display.into());
+        self
+    }
+
+    /// How to authenticate to the X server, [`XAuth::Default`] by default.
+    pub fn xauth(mut self, xauth: XAuth) -> Self {
+        self.xauth = xauth;
+        self
+    }
+
+    /// Picks the GPU adapter, ignored by siblings which share the GPU of their sibling.
+    pub fn gpu(mut self, gpu: GpuOptions) -> Self {
+        self.gpu = gpu;
+        self
+    }
+
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.gpu.backends = Some(backends);
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.gpu.power_preference = power_preference;
+        self
+    }
+
+    /// `Fifo` (vsync) by default, see [`Overlay::set_present_mode`].
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Multisampling of the egui shapes: 1 (off, the default), 2 or 4 samples per pixel.
+    /// Smooths lines and rounded corners, which egui's feathering leaves jagged at scale
+    /// factor 1, for more GPU memory and fill rate. Off when the surface format doesn't
+    /// support it.
+    pub fn msaa_samples(mut self, samples: u32) -> Self {
+        self.msaa_samples = match samples {
+            0 | 1 => 1,
+            2 | 3 => 2,
+            _ => 4,
+        };
+        self
+    }
+
+    /// sRGB by default, see [`ColorSpace`].
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Draws the UI with another renderer than [`renderer::egui_wgpu_backend`], built by
+    /// `renderer` for the device, surface format and MSAA sample count, see
+    /// [`renderer::Renderer`].
+    pub fn renderer(
+        mut self,
+        renderer: impl Fn(&wgpu::Device, wgpu::TextureFormat, u32) -> Box<dyn Renderer>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.renderer = RendererFactory::new(renderer);
+        self
+    }
+
+    /// 60 by default, see [`Overlay::set_max_fps`].
+    pub fn max_fps(mut self, max_fps: Option<f32>) -> Self {
+        self.max_fps = max_fps;
+        self
+    }
+
+    /// Off by default, see [`Overlay::set_ui_rate`].
+    pub fn ui_rate(mut self, ui_rate: f32) -> Self {
+        self.ui_rate = Some(ui_rate);
+        self
+    }
+
+    /// Off by default, see [`Overlay::set_texture_budget`].
+    pub fn texture_budget(mut self, bytes: usize) -> Self {
+        self.texture_budget = Some(bytes);
+        self
+    }
+
+    /// [`Watchdog::default`] by default, see [`Overlay::set_watchdog`].
+    pub fn watchdog(mut self, watchdog: Option<Watchdog>) -> Self {
+        self.watchdog = watchdog;
+        self
+    }
+
+    /// Reserves the space of the overlay along a screen edge, see [`Overlay::set_strut`].
+    pub fn strut(mut self, edge: Edge) -> Self {
+        self.strut = Some(edge);
+        self
+    }
+
+    /// Off by default, see [`Overlay::set_pointer_tracking`].
+    pub fn track_pointer(mut self, track_pointer: bool) -> Self {
+        self.track_pointer = track_pointer;
+        self
+    }
+
+    /// Shown by default, `false` creates it hidden, see [`Overlay::set_visible`].
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.visible = visible;
+        self
+    }
+
+    /// Opens the links clicked in egui with `command` and the URL as last argument,
+    /// `xdg-open` by default. The command may hold arguments, e.g. `firefox --new-tab`.
+    /// `None` leaves them unopened, see also [`Overlay::set_url_handler`].
+    pub fn url_command(mut self, command: Option<String>) -> Self {
+        self.url_command = command;
+        self
+    }
+
+    /// Shown as usual by default, see [`Overlay::set_fullscreen_policy`].
+    pub fn fullscreen_policy(mut self, policy: FullscreenPolicy) -> Self {
+        self.fullscreen_policy = policy;
+        self
+    }
+
+    /// Off by default, see [`Overlay::set_keyboard_grab`].
+    pub fn keyboard_grab(mut self, keyboard_grab: bool) -> Self {
+        self.keyboard_grab = keyboard_grab;
+        self
+    }
+
+    /// Off by default, see [`Overlay::set_blur_behind`].
+    pub fn blur_behind(mut self, blur_behind: bool) -> Self {
+        self.blur_behind = blur_behind;
+        self
+    }
+
+    /// Off by default, see [`Overlay::set_shape_to_content`].
+    pub fn shape_to_content(mut self, shape_to_content: bool) -> Self {
+        self.shape_to_content = shape_to_content;
+        self
+    }
+
+    /// Off by default, see [`Overlay::set_crop_to_content`].
+    pub fn crop_to_content(mut self, crop_to_content: bool) -> Self {
+        self.crop_to_content = crop_to_content;
+        self
+    }
+
+    /// None by default, see [`Overlay::set_fade_duration`].
+    pub fn fade_duration(mut self, fade_duration: Duration) -> Self {
+        self.fade_duration = fade_duration;
+        self
+    }
+
+    /// Opaque by default, see [`Overlay::set_opacity`].
+    pub fn opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity.clamp(0., 1.);
+        self
+    }
+
+    /// Above the other windows by default, kept there even against override-redirect
+    /// windows and window managers ignoring `_NET_WM_STATE_ABOVE`. `false` leaves the
+    /// stacking to the window manager, e.g. for a desktop widget.
+    pub fn always_on_top(mut self, always_on_top: bool) -> Self {
+        self.hints.above = always_on_top;
+        self
+    }
+
+    /// Off by default. Asks the window manager to show the overlay over the panels too, with
+    /// `_NET_WM_STATE_FULLSCREEN`, the geometry should then fill the screen or monitor.
+    /// Override-redirect windows cover the panels anyway, when above them.
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.hints.fullscreen = fullscreen;
+        self
+    }
+
+    /// Space between the window edges and the egui screen, in pixels, none by default. Shorthand
+    /// for [`Viewport::inset`] keeping the scale of the viewport.
+    pub fn inset(mut self, inset: u16) -> Self {
+        self.viewport.geometry = Viewport::inset(inset).geometry;
+        self
+    }
+
+    /// The egui screen covers the whole window by default, see [`Overlay::set_viewport`].
+    pub fn viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    /// None by default. Window managers and compositors pick their rules from it.
+    pub fn window_type(mut self, window_type: WindowType) -> Self {
+        self.hints.window_type = Some(window_type);
+        self
+    }
+
+    /// On all the desktops, by default.
+    pub fn sticky(mut self, sticky: bool) -> Self {
+        self.hints.sticky = sticky;
+        self
+    }
+
+    /// The desktops a sticky overlay shows on. All of them by default, see
+    /// [`Overlay::set_workspaces`].
+    pub fn workspaces(mut self, workspaces: Workspaces) -> Self {
+        self.workspaces = workspaces;
+        self
+    }
+
+    /// Out of taskbars, by default.
+    pub fn skip_taskbar(mut self, skip_taskbar: bool) -> Self {
+        self.hints.skip_taskbar = skip_taskbar;
+        self
+    }
+
+    /// Out of pagers and workspace overviews, by default.
+    pub fn skip_pager(mut self, skip_pager: bool) -> Self {
+        self.hints.skip_pager = skip_pager;
+        self
+    }
+
+    /// `WM_CLASS`, e.g. to match the overlay in compositor rules.
+    pub fn class(mut self, instance: impl Into<String>, class: impl Into<String>) -> Self {
+        self.hints.class = Some((instance.into(), class.into()));
+        self
+    }
+
+    /// The window title.
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.hints.title = Some(title.into());
+        self
+    }
+
+    /// Lets the window manager manage the overlay window, instead of bypassing it with
+    /// override-redirect. Off by default.
+    ///
+    /// The window then asks to be undecorated, above the others and not focused when mapped,
+    /// and shows in alt-tab or overviews unless skipped, see [`OverlayBuilder::skip_taskbar`].
+    /// Some window managers (KDE, GNOME) handle managed transparent windows better, others may
+    /// tile, move or decorate it anyway.
+    pub fn managed(mut self, managed: bool) -> Self {
+        self.managed = managed;
+        self
+    }
+
+    /// egui's default fonts unless given, see [`fonts`] to add system fonts. Also settable
+    /// later through [`Overlay::context`].
+    pub fn fonts(mut self, fonts: egui::FontDefinitions) -> Self {
+        self.fonts = Some(fonts);
+        self
+    }
+
+    /// egui's default style unless given. Its visuals are replaced by those of the
+    /// [`OverlayBuilder::theme`], if any.
+    pub fn style(mut self, style: egui::Style) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    /// The visuals of the style by default, see [`Overlay::set_theme`].
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    /// Restores the egui memory, e.g. window positions and collapsed headers, from `path`
+    /// when the overlay starts, and saves it there when it stops. Relative paths are under
+    /// `$XDG_DATA_HOME/egui-overlay`, e.g. `my-hud.ron`. Not persisted by default.
+    ///
+    /// Each overlay needs its own file, siblings included.
+    #[cfg(feature = "persistence")]
+    pub fn persist_memory(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.memory_path = Some(path.into());
+        self
+    }
+
+    /// Listens for [`ipc::Command`]s on a Unix socket at `path`, e.g. sent by window manager
+    /// key bindings. Relative paths are under `$XDG_RUNTIME_DIR/egui-overlay`, e.g.
+    /// `hud.sock`. Off by default.
+    ///
+    /// Each overlay needs its own socket, siblings included.
+    #[cfg(feature = "ipc")]
+    pub fn ipc_socket(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.ipc_socket = Some(path.into());
+        self
+    }
+
+    /// Serves the [`dbus`] interface on the session bus as `name`, e.g.
+    /// [`dbus::SERVICE_NAME`], or `org.makemeunsee.Overlay.Hud` to tell several overlays
+    /// apart. Off by default.
+    #[cfg(feature = "dbus")]
+    pub fn dbus_name(mut self, name: impl Into<String>) -> Self {
+        self.dbus_name = Some(name.into());
+        self
+    }
+
+    /// Owns `org.freedesktop.Notifications` on the session bus, showing the notifications of
+    /// the desktop as toasts, with their actions, image icons and urgency. Fails when another
+    /// notification daemon runs. Off by default.
+    #[cfg(feature = "dbus")]
+    pub fn notification_daemon(mut self, notification_daemon: bool) -> Self {
+        self.notification_daemon = notification_daemon;
+        self
+    }
+
+    /// Applies the config file at `path` again whenever it's saved, e.g. at
+    /// [`Config::default_path`](config::Config::default_path), with
+    /// [`Config::update`](config::Config::update). Invalid settings are shown as error toasts,
+    /// the previous ones staying. Off by default, see [`Overlay::config`].
+    pub fn watch_config(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.watch_config = Some(path.into());
+        self
+    }
+
+    /// SIGINT and SIGTERM stop the loop by default, like [`Overlay::close`], so that the
+    /// overlay is torn down and [`Overlay::run`] returns. A second signal kills the process.
+    /// `false` leaves the signals to the program.
+    pub fn exit_on_signal(mut self, exit_on_signal: bool) -> Self {
+        self.exit_on_signal = exit_on_signal;
+        self
+    }
+
+    /// Hidden by default, see [`Overlay::set_debug_panel`].
+    pub fn debug_panel(mut self, debug_panel: bool) -> Self {
+        self.debug_panel = debug_panel;
+        self
+    }
+
+    /// Top-right by default, see [`Overlay::set_toast_corner`].
+    pub fn toast_corner(mut self, corner: egui::Align2) -> Self {
+        self.toast_corner = corner;
+        self
+    }
+
+    /// Connects to the [`OverlayBuilder::display`] and maps the overlay window.
+    pub fn build(self) -> Result<Overlay, OverlayError> {
+        let (conn, screen_num) = connect(&self)?;
+        Overlay::with_connection(Rc::new(conn), screen_num, None, self)
+    }
+
+    /// Builds the overlay and runs `app` in it, see [`Overlay::run`].
+    ///
+    /// With the `wayland` feature, when `WAYLAND_DISPLAY` is set, the overlay is a layer-shell
+    /// surface of the Wayland compositor instead. [`OverlayApp::update`] is not called there.
+    pub fn run(self, app: impl OverlayApp) -> Result<()> {
+        #[cfg(feature = "wayland")]
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            let mut app = app;
+            return wayland::run(self, &mut app);
+        }
+        self.build()?.run(app)
+    }
+
+    /// Builds and runs the overlay on its own thread, controlled through the returned
+    /// [`Handle`], e.g. to embed it in a program whose main thread is taken.
+    ///
+    /// The app is made by `make_app` on that thread, so it does not need to be `Send`.
+    pub fn spawn<A, F>(self, make_app: F) -> Result<Handle<A>>
+    where
+        A: OverlayApp + 'static,
+        F: FnOnce() -> A + Send + 'static,
+    {
+        handle::spawn(self, make_app)
+    }
+
+    /// Maps the overlay window on the X connection and GPU device of `sibling`,
+    /// see [`Overlay::new_sibling`].
+    pub fn build_sibling(self, sibling: &Overlay) -> Result<Overlay, OverlayError> {
+        Overlay::with_connection(
+            sibling.conn.clone(),
+            sibling.screen_num,
+            Some(sibling.state.gpu().clone()),
+            self,
+        )
+    }
+
+    pub(crate) fn surface_options(&self) -> SurfaceOptions {
+        SurfaceOptions {
+            present_mode: self.present_mode,
+            msaa_samples: self.msaa_samples,
+            color_space: self.color_space,
+            renderer: self.renderer.clone(),
+            transparent: true,
+            watchdog: self.watchdog,
+        }
+    }
+
+    /// applies the egui settings to the context of a new overlay
+    pub(crate) fn setup_context(&self, context: &egui::Context) -> Result<()> {
+        if let Some(fonts) = &self.fonts {
+            context.set_fonts(fonts.clone());
+        }
+        if let Some(style) = &self.style {
+            context.set_style(style.clone());
+        }
+        if let Some(theme) = self.theme {
+            context.set_visuals(theme.visuals());
+        }
+        #[cfg(feature = "persistence")]
+        if let Some(path) = &self.memory_path {
+            persistence::load_memory(context, &persistence::memory_path(path)?);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Overlay {
+    fn drop(&mut self) {
+        #[cfg(feature = "persistence")]
+        if let Some(path) = &self.memory_path {
+            let saved = persistence::memory_path(path)
+                .and_then(|path| persistence::save_memory(self.state.context(), &path));
+            if let Err(e) = saved {
+                tracing::warn!("saving the egui memory: {:#}", e);
+            }
+        }
+
+        #[cfg(feature = "recorder")]
+        if let Err(e) = self.stop_recording() {
+            tracing::warn!("finishing the recording: {:#}", e);
+        }
+
+        // SAFETY: not used afterwards
+        unsafe { ManuallyDrop::drop(&mut self.state) };
+        // freed by the server with the connection otherwise, which siblings keep open
+        for mirror in &self.mirror_windows {
+            let _ = self.conn.destroy_window(mirror.window);
+        }
+        let _ = self.conn.destroy_window(self.win_id);
+        let _ = self.conn.free_colormap(self.colormap);
+        let _ = self.conn.flush();
+    }
+}
+
+impl Overlay {
+    /// Connects to `$DISPLAY` and maps the overlay window, with the default settings.
+    pub fn new() -> Result<Self, OverlayError> {
+        OverlayBuilder::new().build()
+    }
+
+    pub fn builder() -> OverlayBuilder {
+        OverlayBuilder::new()
+    }
+
+    /// Connects to `$DISPLAY` and maps an overlay covering the whole screen, see
+    /// [`OverlayBuilder::fullscreen_overlay`].
+    pub fn fullscreen_overlay() -> Result<Self, OverlayError> {
+        OverlayBuilder::fullscreen_overlay().build()
+    }
+
+    /// Maps another overlay window, on the same X connection and GPU device.
+    /// Each overlay has its own egui context, drive them together with [`Overlay::run_all`].
+    ///
+    /// egui 0.19 has no viewports: popups stay inside their overlay, panels to show elsewhere
+    /// on screen go into siblings.
+    pub fn new_sibling(&self) -> Result<Self, OverlayError> {
+        OverlayBuilder::new().build_sibling(self)
+    }
+
+    fn with_connection(
+        conn: Rc<XCBConnection>,
+        screen_num: usize,
+        gpu: Option<Rc<Gpu>>,
+        builder: OverlayBuilder,
+    ) -> Result<Self, OverlayError> {
+        let settings = builder.clone();
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+
+        let monitors = monitors(&*conn, root)?;
+        let monitor = match &builder.monitor {
+            Some(selector) => Some(
+                selector
+                    .select(&monitors)
+                    .ok_or_else(|| OverlayError::NoMonitor(selector.clone()))?,
+            ),
+            None => None,
+        };
+        let area = monitor.map_or(
+            Rectangle {
+                x: 0,
+                y: 0,
+                width: screen.width_in_pixels,
+                height: screen.height_in_pixels,
+            },
+            Monitor::rect,
+        );
+        let rect = builder.geometry.place(area);
+        let scale_factor = match builder.scale_factor {
+            Some(scale_factor) => scale_factor,
+            None => {
+                let monitor = monitor.or_else(|| MonitorSelector::Primary.select(&monitors));
+                detect_scale_factor(&*conn, root, monitor)?
+            }
+        } * builder.viewport.scale;
+
+        // the surfaces of some ARGB visuals can only be opaque, e.g. with some Vulkan
+        // drivers: the window is then created again with the next one, down to an opaque
+        // window on the root visual, e.g. on Xvnc servers without 32-bit visuals
+        let gpu_options = builder.gpu.with_env()?;
+        let mut visuals = argb_visuals(screen)
+            .into_iter()
+            .map(|visual| (visual, 32))
+            .chain(visual_for_depth(screen, 24).map(|visual| (visual, 24)))
+            .peekable();
+        let (win_id, visual, depth, colormap, mut state) = loop {
+            let (visual, depth) = visuals.next().ok_or(OverlayError::NoVisual { depth: 24 })?;
+            if depth != 32 {
+                warn!("no transparent visual, drawing opaque frames shaped to the content");
+            }
+            let window_options = x11::WindowOptions {
+                depth,
+                visual: Some(visual),
+                event_mask: builder
+                    .event_mask
+                    .unwrap_or_else(|| match builder.input_shape {
+                        InputShape::Passthrough => overlay_events(),
+                        _ => overlay_events() | input_events(),
+                    }),
+                colormap: None,
+                managed: builder.managed,
+            };
+            let OverlayWindow {
+                window: win_id,
+                colormap,
+                ..
+            } = create_overlay_window(&*conn, screen, rect, &window_options)?;
+            let window = MyWindow {
+                window: win_id,
+                visual_id: visual,
+                connection: conn.get_raw_xcb_connection(),
+                screen: screen_num as i32,
+            };
+            match State::new(
+                &window,
+                (rect.width.into(), rect.height.into()),
+                gpu.clone(),
+                &gpu_options,
+                SurfaceOptions {
+                    transparent: depth == 32,
+                    ..builder.surface_options()
+                },
+                scale_factor,
+            ) {
+                Ok(state) => break (win_id, visual, depth, colormap, ManuallyDrop::new(state)),
+                Err(e) if e.opaque() && visuals.peek().is_some() => {
+                    debug!("visual {:#x}: {}, trying the next one", visual, e);
+                    conn.destroy_window(win_id)?;
+                    conn.free_colormap(colormap)?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+        let transparent = state.transparent();
+        let wm_delete_window = set_wm_protocols(&*conn, win_id)?;
+        let [opacity_atom, blur_atom] = intern_atoms(&*conn, [x11::OPACITY, x11::BLUR_REGION])?;
+        if let Some(edge) = builder.strut {
+            let screen_size = (screen.width_in_pixels, screen.height_in_pixels);
+            set_strut(&*conn, win_id, Some(strut_partial(edge, rect, screen_size)))?;
+        }
+
+        builder.hints.apply(&*conn, win_id)?;
+        if builder.hints.above {
+            always_on_top(&*conn, root, win_id)?;
+        }
+        if builder.opacity < 1. {
+            write_window_opacity(&*conn, win_id, opacity_atom, builder.opacity)?;
+        }
+        if builder.visible {
+            conn.map_window(win_id)?;
+        }
+        conn.flush()?;
+
+        state.set_viewport(builder.viewport.geometry);
+        let keyboard = Keyboard::new(&conn)?;
+        let clipboard = Clipboard::new(&*conn, win_id)?;
+        let cursors = Cursors::new(&*conn, screen_num, win_id)?;
+        let compositor = Compositor::new(&*conn, screen_num, win_id)?;
+        let (waker, wake_receiver) = wake_channel()?;
+        let toasts = toasts::Toasts::new(waker.clone(), builder.toast_corner);
+        #[cfg(feature = "ipc")]
+        let ipc = builder
+            .ipc_socket
+            .as_deref()
+            .map(|path| ipc::IpcServer::bind(path, waker.clone()))
+            .transpose()?;
+        #[cfg(feature = "dbus")]
+        let dbus = builder
+            .dbus_name
+            .as_deref()
+            .map(|name| dbus::DbusService::connect(name, waker.clone()))
+            .transpose()?;
+        #[cfg(feature = "dbus")]
+        let notifications = builder
+            .notification_daemon
+            .then(|| dbus::NotificationDaemon::start(toasts.sender.clone()))
+            .transpose()?;
+        let config_watcher = builder
+            .watch_config
+            .as_deref()
+            .map(|path| config::ConfigWatcher::start(path, waker.clone()))
+            .transpose()?;
+        let config = config_watcher
+            .as_ref()
+            .map(config::ConfigWatcher::load)
+            .transpose()?;
+        builder.setup_context(state.context())?;
+        // `request_repaint` from other threads, through a clone of the context
+        state.context().set_request_repaint_callback({
+            let waker = waker.clone();
+            move || waker.wake()
+        });
+        let stacking = Stacking::new(&*conn, root, builder.managed)?;
+        let occlusion = Occlusion::new(&*conn, root, win_id, builder.visible)?;
+        let work_area = WorkArea::new(&*conn, root, occlusion.current_desktop)?;
+        let pointer_tracker = builder
+            .track_pointer
+            .then(|| PointerTracker::new(&*conn, root))
+            .transpose()?;
+        let (smooth_scroll, touches) = match select_xi2_events(&*conn, win_id) {
+            Ok(touch) => (
+                Some(SmoothScroll::new(&*conn)?),
+                touch.then(Touches::default),
+            ),
+            Err(e) => {
+                debug!("no smooth scrolling nor touch input: {:#}", e);
+                (None, None)
+            }
+        };
+
+        let mut overlay = Self {
+            state,
+            keyboard,
+            clipboard,
+            cursors,
+            compositor,
+            waker,
+            wake_receiver,
+            conn,
+            screen_num,
+            root,
+            win_id,
+            visual,
+            depth,
+            colormap,
+            wm_delete_window,
+            opacity_atom,
+            blur_atom,
+            running: false,
+            batch_depth: Cell::new(0),
+            exit_on_signal: builder.exit_on_signal,
+            settings,
+            input_shape: builder.input_shape,
+            input_passthrough: false,
+            input_region: Vec::new(),
+            geometry: builder.geometry,
+            monitor: builder.monitor,
+            mirrors: Mirrors::None,
+            mirror_windows: Vec::new(),
+            follower: None,
+            workspaces: builder.workspaces.clone(),
+            viewport: builder.viewport,
+            scale_factor: builder.scale_factor,
+            min_frame_time: min_frame_time(builder.max_fps),
+            ui_rate: None,
+            strut: builder.strut,
+            pointer_tracker,
+            smooth_scroll,
+            touches,
+            hotkeys: Hotkeys::default(),
+            visible: builder.visible,
+            keyboard_grab: builder.keyboard_grab,
+            keyboard_grabbed: false,
+            placed: rect,
+            crop_to_content: builder.crop_to_content,
+            crop: None,
+            blur_behind: builder.blur_behind,
+            blur_region: Vec::new(),
+            // the windows underneath show through only outside the painted areas
+            shape_to_content: builder.shape_to_content || !transparent,
+            bounding_region: None,
+            fade_duration: builder.fade_duration,
+            fade: None,
+            always_on_top: builder.hints.above,
+            stacking,
+            occlusion,
+            work_area,
+            fullscreen_policy: FullscreenPolicy::Show,
+            fullscreen_watch: None,
+            hidden_by_rules: false,
+            fade_level: 1.,
+            opacity: builder.opacity,
+            x_error_handler: Box::new(log_x_error),
+            url_command: builder.url_command.clone(),
+            url_handler: None,
+            texture_budget_handler: None,
+            closed: false,
+            debug: builder.debug_panel.then(DebugStats::default),
+            picker: None,
+            window_picker: None,
+            wm_watch: None,
+            #[cfg(feature = "persistence")]
+            memory_path: builder.memory_path,
+            #[cfg(feature = "recorder")]
+            recording: None,
+            #[cfg(feature = "ipc")]
+            ipc,
+            #[cfg(feature = "dbus")]
+            dbus,
+            #[cfg(feature = "dbus")]
+            notifications,
+            #[cfg(feature = "dbus")]
+            tray: None,
+            toasts,
+            config_watcher,
+            config,
+        };
+        overlay.apply_input_region()?;
+        overlay.set_ui_rate(builder.ui_rate)?;
+        overlay.set_texture_budget(builder.texture_budget);
+        overlay.share_work_area();
+        overlay.set_fullscreen_policy(builder.fullscreen_policy)?;
+        overlay.set_mirrors(builder.mirrors)?;
+        overlay.attach(builder.attachment)?;
+        Ok(overlay)
+    }
+
+    /// Shows an icon for the overlay in the panel, replacing the previous one, `None` removes
+    /// it, see [`tray`]. Fails without a tray following the StatusNotifierItem specification.
+    #[cfg(feature = "dbus")]
+    pub fn set_tray(&mut self, tray: Option<tray::Tray>) -> Result<()> {
+        // its bus name released before another one is registered
+        self.tray = None;
+        self.tray = tray
+            .map(|tray| tray::TrayIcon::start(tray, self.waker.clone(), self.visible))
+            .transpose()?;
+        Ok(())
+    }
+
+    /// runs the clicks on the tray icon and its menu
+    #[cfg(feature = "dbus")]
+    pub(crate) fn handle_tray(&mut self) -> Result<()> {
+        let Some(mut tray) = self.tray.take() else {
+            return Ok(());
+        };
+        let handled = tray.handle_clicks(self);
+        // unless a click replaced it
+        self.tray.get_or_insert(tray);
+        handled
+    }
+
+    /// signals what happened to the notifications shown in the frame
+    #[cfg(feature = "dbus")]
+    pub(crate) fn signal_notifications(&self) -> Result<()> {
+        match &self.notifications {
+            Some(notifications) => notifications.signal_events(),
+            None => Ok(()),
+        }
+    }
+
+    /// The config last applied by [`OverlayBuilder::watch_config`], e.g. for the app to
+    /// follow its own settings. `None` unless watching.
+    pub fn config(&self) -> Option<&config::Config> {
+        self.config.as_ref()
+    }
+
+    /// applies the watched config again if it changed, its errors shown as toasts
+    pub(crate) fn handle_config_changes(&mut self) {
+        let Some(watcher) = self
+            .config_watcher
+            .as_ref()
+            .filter(|watcher| watcher.changed())
+        else {
+            return;
+        };
+        let path = watcher.path().to_owned();
+        let updated = watcher.load().and_then(|config| {
+            config.update(self)?;
+            Ok(config)
+        });
+        match updated {
+            Ok(config) => {
+                debug!("applied {}", path.display());
+                self.config = Some(config);
+            }
+            Err(e) => {
+                warn!("{:#}", e);
+                self.push_toast(Toast::error("Invalid config").body(format!("{:#}", e)));
+            }
+        }
+    }
+
+    /// runs the commands received on the IPC socket and D-Bus, answering how they went
+    #[cfg(any(feature = "ipc", feature = "dbus"))]
+    pub(crate) fn handle_commands(&mut self) -> Result<()> {
+        let mut requests = Vec::new();
+        #[cfg(feature = "ipc")]
+        if let Some(ipc) = &self.ipc {
+            requests.extend(ipc.requests());
+        }
+        #[cfg(feature = "dbus")]
+        if let Some(dbus) = &self.dbus {
+            requests.extend(dbus.requests());
+        }
+        for (command, reply) in requests {
+            let outcome = command.run(self).map_err(|e| format!("{:#}", e));
+            // the client may be gone already
+            let _ = reply.send(outcome);
+        }
+        #[cfg(feature = "dbus")]
+        if let Some(mut dbus) = self.dbus.take() {
+            let signaled = dbus.signal_changes(self);
+            self.dbus = Some(dbus);
+            signaled?;
+        }
+        Ok(())
+    }
+
+    /// The egui context, e.g. to check input from [`OverlayApp::update`].
+    pub fn context(&self) -> &egui::Context {
+        self.state.context()
+    }
+
+    /// The last rendered frame, e.g. from [`OverlayApp::update`] to save a screenshot or
+    /// compare it to a reference. Blank before the first frame.
+    ///
+    /// The frame is painted again into an offscreen texture, surfaces can't be read back.
+    /// Its colors are premultiplied by their alpha, as composited over the desktop.
+    pub fn capture_frame(&mut self) -> Result<egui::ColorImage> {
+        self.state.capture()
+    }
+
+    /// Records the frames to `path`, a `.gif`, `.webm` or `.mp4` file, until
+    /// [`Overlay::stop_recording`], see [`recorder`]. Replaces the ongoing recording, if any.
+    #[cfg(feature = "recorder")]
+    pub fn start_recording(&mut self, path: impl Into<std::path::PathBuf>) -> Result<()> {
+        self.stop_recording()?;
+        self.recording = Some(recorder::Recording::start(path.into())?);
+        // the first frame right away
+        self.context().request_repaint();
+        Ok(())
+    }
+
+    /// Waits for the ongoing recording to be written, and returns its path, `None` if there
+    /// is none.
+    #[cfg(feature = "recorder")]
+    pub fn stop_recording(&mut self) -> Result<Option<std::path::PathBuf>> {
+        self.recording
+            .take()
+            .map(recorder::Recording::finish)
+            .transpose()
+    }
+
+    #[cfg(feature = "recorder")]
+    pub fn recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// reads back the frame drawn at `now` for the recording, if any, which stops on errors
+    #[cfg(feature = "recorder")]
+    pub(crate) fn record_frame(&mut self, now: Instant) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+        if !recording.wants_frame(now) {
+            return;
+        }
+        let recorded = self
+            .state
+            .capture()
+            .and_then(|frame| recording.push(frame, now));
+        if let Err(e) = recorded {
+            warn!("recording stopped: {:#}", e);
+            if let Err(e) = self.stop_recording() {
+                warn!("{:#}", e);
+            }
+        }
+    }
+
+    /// Reads `width` x `height` pixels of the screen at `(x, y)`, the windows on it included,
+    /// overlays too: keep what they paint out of the way. Clamped to the screen.
+    pub fn screen_image(
+        &self,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> Result<egui::ColorImage> {
+        let rect = Rectangle {
+            x,
+            y,
+            width,
+            height,
+        };
+        screen::grab_screen(&*self.conn, self.screen_num, rect)
+    }
+
+    /// The pointer position on the screen in pixels, `None` when on another X screen. Combined
+    /// with [`OverlayBuilder::track_pointer`], follows the pointer anywhere every frame.
+    pub fn screen_pointer(&self) -> Result<Option<(i16, i16)>> {
+        let pointer = self.conn.query_pointer(self.root)?.reply()?;
+        Ok(pointer
+            .same_screen
+            .then_some((pointer.root_x, pointer.root_y)))
+    }
+
+    /// Picks a color anywhere on the screen: the pointer is grabbed, a tooltip next to it
+    /// shows the color underneath, and a primary click calls `on_pick` with it. The other
+    /// buttons cancel, `on_pick` then gets `None`.
+    ///
+    /// ```no_run
+    /// use egui_wgpu_x11::Overlay;
+    ///
+    /// let mut overlay = Overlay::fullscreen_overlay()?;
+    /// overlay.pick_color(|_, color| {
+    ///     println!("{:?}", color);
+    ///     Ok(())
+    /// })?;
+    /// # anyhow::Ok(())
+    /// ```
+    ///
+    /// The tooltip shows over the overlay only, a fullscreen one shows it everywhere. Fails
+    /// while another pick is going on, or as another client grabs the pointer.
+    pub fn pick_color(
+        &mut self,
+        on_pick: impl FnOnce(&mut Overlay, Option<egui::Color32>) -> Result<()> + 'static,
+    ) -> Result<()> {
+        self.grab_pick_pointer()?;
+        self.picker = Some(ColorPicker::new(Box::new(on_pick), self.screen_pointer()?));
+        self.context().request_repaint();
+        Ok(())
+    }
+
+    /// Whether [`Overlay::pick_color`] is going on.
+    pub fn picking_color(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    /// Picks the window of another application, e.g. to attach the overlay to it with
+    /// [`Overlay::attach`]: the pointer is grabbed, the window underneath is outlined with its
+    /// class and title next to the pointer, and a primary click calls `on_pick` with it. The
+    /// other buttons cancel, `on_pick` then gets `None`.
+    ///
+    /// ```no_run
+    /// use egui_wgpu_x11::{Attachment, Overlay};
+    ///
+    /// let mut overlay = Overlay::fullscreen_overlay()?;
+    /// overlay.pick_window(|overlay, window| match window {
+    ///     Some(window) => overlay.attach(Some(Attachment::over(window.window))),
+    ///     None => Ok(()),
+    /// })?;
+    /// # anyhow::Ok(())
+    /// ```
+    ///
+    /// Like for [`Overlay::pick_color`], the outline shows over the overlay only. Overlays and
+    /// the other windows letting input through are skipped.
+    pub fn pick_window(
+        &mut self,
+        on_pick: impl FnOnce(&mut Overlay, Option<PickedWindow>) -> Result<()> + 'static,
+    ) -> Result<()> {
+        self.grab_pick_pointer()?;
+        self.window_picker = Some(WindowPicker::new(Box::new(on_pick), self.screen_pointer()?));
+        self.context().request_repaint();
+        Ok(())
+    }
+
+    /// Whether [`Overlay::pick_window`] is going on.
+    pub fn picking_window(&self) -> bool {
+        self.window_picker.is_some()
+    }
+
+    /// grabs the pointer for a pick, with a crosshair
+    fn grab_pick_pointer(&mut self) -> Result<()> {
+        if self.picker.is_some() || self.window_picker.is_some() {
+            bail!("already picking");
+        }
+        let cursor = self.cursors.get(&*self.conn, egui::CursorIcon::Crosshair)?;
+        let status = self
+            .conn
+            .grab_pointer(
+                false,
+                self.win_id,
+                EventMask::BUTTON_PRESS | EventMask::POINTER_MOTION,
+                GrabMode::ASYNC,
+                GrabMode::ASYNC,
+                x11rb::NONE,
+                cursor,
+                CURRENT_TIME,
+            )?
+            .reply()?
+            .status;
+        if status != GrabStatus::SUCCESS {
+            bail!("could not grab the pointer: {:?}", status);
+        }
+        Ok(())
+    }
+
+    /// Where a screen position in pixels, e.g. from [`Overlay::screen_pointer`], is in the
+    /// egui coordinates of the overlay.
+    pub fn screen_to_points(&self, x: i16, y: i16) -> egui::Pos2 {
+        let pixels_per_point = self.state.pixels_per_point();
+        egui::pos2(
+            (i32::from(x) - i32::from(self.placed.x)) as f32 / pixels_per_point,
+            (i32::from(y) - i32::from(self.placed.y)) as f32 / pixels_per_point,
+        )
+    }
+
+    pub fn set_input_shape(&mut self, input_shape: InputShape) -> Result<()> {
+        self.input_shape = input_shape;
+        self.apply_input_region()
+    }
+
+    /// Makes the whole overlay click-through, or restores its [`InputShape`].
+    /// Unlike `InputShape::Passthrough`, the shape is kept and restored when toggled back.
+    pub fn set_input_passthrough(&mut self, passthrough: bool) -> Result<()> {
+        if self.input_passthrough != passthrough {
+            self.input_passthrough = passthrough;
+            self.apply_input_region()?;
+        }
+        Ok(())
+    }
+
+    pub fn input_passthrough(&self) -> bool {
+        self.input_passthrough
+    }
+
+    /// Wakes the loop to render a frame, e.g. when a background thread has new data to show.
+    /// [`egui::Context::request_repaint`] on a clone of the [`Overlay::context`] does the same.
+    pub fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    /// The name of the window manager, when it follows EWMH.
+    pub fn window_manager(&self) -> Option<&str> {
+        self.stacking.wm_name()
+    }
+
+    /// Whether a compositing manager runs, followed as it starts or stops.
+    /// Without one the transparent parts of the overlay are black, apps may want to draw less.
+    pub fn compositing(&self) -> bool {
+        self.compositor.running()
+    }
+
+    /// The active monitors.
+    pub fn monitors(&self) -> Result<Vec<Monitor>> {
+        monitors(&*self.conn, self.root)
+    }
+
+    /// Moves the overlay to a single monitor, placed in it by its [`Geometry`].
+    /// The overlay keeps following that monitor when monitors are reconfigured or replugged.
+    pub fn set_monitor(&mut self, monitor: MonitorSelector) -> Result<()> {
+        if monitor.select(&self.monitors()?).is_none() {
+            bail!("no monitor matching {:?}", monitor);
+        }
+        self.monitor = Some(monitor);
+        self.follow_monitor()
+    }
+
+    /// Shows copies of the overlay on other monitors, or none, see [`Mirrors`]. They follow
+    /// the overlay's layout, visibility and opacity, and the monitor changes.
+    pub fn set_mirrors(&mut self, mirrors: Mirrors) -> Result<()> {
+        self.mirrors = mirrors;
+        self.layout_mirrors()
+    }
+
+    pub fn mirrors(&self) -> &Mirrors {
+        &self.mirrors
+    }
+
+    /// Sticks the overlay to the window of another application, or to none. The overlay is
+    /// placed by its [`Geometry`] over that window or beside it, follows it as it moves or
+    /// resizes, and hides while it is unmapped.
+    pub fn attach(&mut self, attachment: Option<Attachment>) -> Result<()> {
+        if let Some(follower) = self.follower.take() {
+            follower.stop(&*self.conn)?;
+        }
+        if let Some(attachment) = attachment {
+            self.follower = Some(WindowFollower::new(
+                &*self.conn,
+                self.root,
+                self.win_id,
+                attachment,
+            )?);
+            return self.follow_window();
+        }
+        self.set_layout(self.geometry)?;
+        self.apply_hide_rules()
+    }
+
+    pub fn attachment(&self) -> Option<Attachment> {
+        self.follower.as_ref().map(|follower| follower.attachment)
+    }
+
+    /// Shows the overlay on some desktops only, hiding it on the others. The window manager
+    /// keeps [sticky](OverlayBuilder::sticky) overlays on all the desktops, the overlay maps
+    /// and unmaps itself as `_NET_CURRENT_DESKTOP` changes.
+    pub fn set_workspaces(&mut self, workspaces: Workspaces) -> Result<()> {
+        self.workspaces = workspaces;
+        self.apply_hide_rules()
+    }
+
+    pub fn workspaces(&self) -> &Workspaces {
+        &self.workspaces
+    }
+
+    /// Moves and resizes the overlay to `width` x `height` pixels at `(x, y)`, from the top-left
+    /// corner of the screen, or of its monitor if it has one. Cheap enough to call every frame,
+    /// e.g. from [`OverlayApp::update`] to slide the overlay in from an edge.
+    ///
+    /// The surface is resized right away, for the next frame. Replaces the [`Geometry`], so
+    /// screen and monitor changes keep the overlay at that place.
+    pub fn set_geometry(&mut self, x: u16, y: u16, width: u16, height: u16) -> Result<()> {
+        self.set_layout(Geometry::absolute(x, y, width, height))
+    }
+
+    /// Places the overlay by `geometry` in the screen, or in its monitor, like
+    /// [`OverlayBuilder::geometry`]. The surface is resized right away, for the next frame.
+    pub fn set_layout(&mut self, geometry: Geometry) -> Result<()> {
+        self.geometry = geometry;
+        let area = self.area()?;
+        self.place(area)?;
+        let rect = self.geometry.place(area);
+        let size = (rect.width.into(), rect.height.into());
+        if size != self.state.size {
+            self.state.resize(size);
+        }
+        Ok(())
+    }
+
+    /// How the overlay is placed, see [`Overlay::set_layout`].
+    pub fn layout(&self) -> Geometry {
+        self.geometry
+    }
+
+    /// Size of an egui point in pixels, zoomed by the scale of the [`Viewport`].
+    pub fn scale_factor(&self) -> f32 {
+        self.state.pixels_per_point()
+    }
+
+    /// Overrides the scale factor, `None` restores the detected one.
+    ///
+    /// Detection looks at `GDK_SCALE`/`QT_SCALE_FACTOR`, then `Xft.dpi`, then the physical size
+    /// of the overlay monitor.
+    pub fn set_scale_factor(&mut self, scale_factor: Option<f32>) -> Result<()> {
+        self.scale_factor = scale_factor;
+        self.update_scale_factor()
+    }
+
+    /// Places the egui screen in the window and zooms it, e.g. to letterbox a UI made for
+    /// another aspect ratio. egui keeps the coordinates of the whole window, so the pointer
+    /// positions stay in the same points: [`Viewport`] tells more.
+    pub fn set_viewport(&mut self, viewport: Viewport) -> Result<()> {
+        self.viewport = viewport;
+        self.state.set_viewport(viewport.geometry);
+        self.update_scale_factor()
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    /// The message of the last panic of [`OverlayApp::ui`]. The overlay catches them and shows
+    /// the message in a window instead of the app, until retried from there.
+    pub fn app_panic(&self) -> Option<&str> {
+        self.state.panics.message.as_deref()
+    }
+
+    /// Timing of the frames of the overlay, as of the last one, e.g. to show in a HUD.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.state.frames.stats()
+    }
+
+    /// How frames are queued for display: `Fifo` waits for vsync, `Mailbox` replaces the queued
+    /// frame without tearing, `Immediate` may tear. Falls back to `Fifo` when unsupported.
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.state.set_present_mode(present_mode);
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.state.present_mode()
+    }
+
+    /// The device drawing the overlay, to create textures for [`Overlay::register_texture`].
+    /// Sibling overlays share it. It changes after a GPU reset, see
+    /// [`Overlay::device_generation`].
+    pub fn device(&self) -> &wgpu::Device {
+        self.state.device()
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        self.state.queue()
+    }
+
+    /// Bumped when the overlay moves to a new device, after the former one was lost. Textures
+    /// registered with [`Overlay::register_texture`] are then blank until created on the new
+    /// [`Overlay::device`] and passed to [`Overlay::update_texture`].
+    pub fn device_generation(&self) -> u32 {
+        self.state.device_generation()
+    }
+
+    /// Makes a texture of [`Overlay::device`] drawable by egui, e.g. with [`egui::Ui::image`],
+    /// without copying it to the CPU: video frames, screen captures, or other wgpu renderings.
+    /// Needs `TEXTURE_BINDING` usage, and a filterable float format.
+    ///
+    /// DMA-BUF frames, e.g. from PipeWire or VA-API, are imported as wgpu textures with
+    /// `wgpu::Device::create_texture_from_hal` and Vulkan external memory.
+    pub fn register_texture(
+        &mut self,
+        texture: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+    ) -> egui::TextureId {
+        self.state.register_texture(texture, filter)
+    }
+
+    /// Points a registered texture to another one, e.g. the next video frame.
+    pub fn update_texture(
+        &mut self,
+        id: egui::TextureId,
+        texture: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+    ) -> Result<()> {
+        self.state.update_texture(id, texture, filter)
+    }
+
+    /// Releases a texture registered with [`Overlay::register_texture`], or a [`UserTexture`]
+    /// by its id.
+    pub fn free_texture(&mut self, id: egui::TextureId) -> Result<()> {
+        self.state.free_texture(id)
+    }
+
+    /// A texture of `size` pixels to render to with wgpu, drawable by egui. `format` is
+    /// usually `Rgba8UnormSrgb`, as egui's own textures.
+    ///
+    /// Simpler than [`paint_callback`] to render less often than the overlay, e.g. plots
+    /// updated on new data.
+    pub fn create_user_texture(
+        &mut self,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+    ) -> Result<UserTexture> {
+        self.state.create_user_texture(size, format)
+    }
+
+    /// Replaces `texture` by a blank one of `size`, keeping its id. Also recreates it on the
+    /// new device after a GPU reset, see [`Overlay::device_generation`].
+    pub fn resize_user_texture(
+        &mut self,
+        texture: &mut UserTexture,
+        size: (u32, u32),
+    ) -> Result<()> {
+        self.state.resize_user_texture(texture, size)
+    }
+
+    /// Caps the frame rate independently of the present mode, `None` for no cap.
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.min_frame_time = min_frame_time(max_fps);
+    }
+
+    /// Runs the UI at most `ui_rate` times per second, e.g. 10 for a status HUD, `None` for
+    /// every frame. The frames are drawn into a texture, presented again when the window is
+    /// exposed, e.g. by the compositor, rather than drawn again: mostly static overlays use
+    /// less GPU. Input also waits for the next UI run.
+    pub fn set_ui_rate(&mut self, ui_rate: Option<f32>) -> Result<()> {
+        let ui_rate = ui_rate.filter(|rate| *rate > 0.);
+        self.ui_rate = ui_rate;
+        self.state.set_caching(ui_rate.is_some())
+    }
+
+    pub fn ui_rate(&self) -> Option<f32> {
+        self.ui_rate
+    }
+
+    /// Keeps the egui textures on the GPU under `budget` bytes, `None` for no limit: the
+    /// images loaded with [`egui::Context::load_texture`] least recently drawn are freed from
+    /// the GPU, and uploaded again from their copy when drawn. The font atlas and the wgpu
+    /// textures stay. Long-running overlays showing many images then keep their VRAM use in
+    /// check, see [`Overlay::set_texture_budget_handler`] when the images drawn alone exceed
+    /// it.
+    pub fn set_texture_budget(&mut self, budget: Option<usize>) {
+        self.state.set_texture_budget(budget);
+    }
+
+    pub fn texture_budget(&self) -> Option<usize> {
+        self.state.texture_memory().budget
+    }
+
+    /// What the egui textures take on the GPU.
+    pub fn texture_memory(&self) -> TextureMemory {
+        self.state.texture_memory()
+    }
+
+    /// Called when the textures drawn in a frame go over the budget, once until they are back
+    /// under it, e.g. to drop images the app holds. Logged otherwise.
+    pub fn set_texture_budget_handler(&mut self, handler: impl FnMut(&TextureMemory) + 'static) {
+        self.texture_budget_handler = Some(Box::new(handler));
+    }
+
+    /// Draws on a new GPU device after repeated surface timeouts or a frame waiting too long
+    /// on the GPU, e.g. hung by a driver bug, `None` to keep waiting on it. The overlays
+    /// sharing the device move to the new one too.
+    pub fn set_watchdog(&mut self, watchdog: Option<Watchdog>) {
+        self.state.watchdog.watchdog = watchdog;
+    }
+
+    pub fn watchdog(&self) -> Option<Watchdog> {
+        self.state.watchdog.watchdog
+    }
+
+    pub(crate) fn texture_budget_exceeded(&mut self, memory: &TextureMemory) {
+        match &mut self.texture_budget_handler {
+            Some(handler) => handler(memory),
+            None => warn!(
+                "{} bytes of textures drawn, over the budget of {:?}",
+                memory.bytes, memory.budget
+            ),
+        }
+    }
+
+    /// Hides, pauses or slows down the overlay while the focused window is fullscreen, e.g. a
+    /// game, unless the HUD is wanted on top of it. Needs an EWMH window manager.
+    pub fn set_fullscreen_policy(&mut self, policy: FullscreenPolicy) -> Result<()> {
+        self.fullscreen_policy = policy;
+        match (policy, self.fullscreen_watch.take()) {
+            (FullscreenPolicy::Show, Some(watch)) => watch.stop(&*self.conn)?,
+            (FullscreenPolicy::Show, None) => {}
+            (_, Some(watch)) => self.fullscreen_watch = Some(watch),
+            (_, None) => {
+                self.fullscreen_watch =
+                    Some(FullscreenWatch::new(&*self.conn, self.root, self.win_id)?)
+            }
+        }
+        self.apply_fullscreen_policy()
+    }
+
+    pub fn fullscreen_policy(&self) -> FullscreenPolicy {
+        self.fullscreen_policy
+    }
+
+    /// Reserves the space of the overlay along a screen `edge` with `_NET_WM_STRUT_PARTIAL`,
+    /// so maximized windows stay clear of it like of a panel. `None` floats over them again.
+    ///
+    /// The reserved extent goes from that screen edge to the far side of the overlay, and follows
+    /// its geometry. Only window managers managing the overlay window take it into account, most
+    /// ignore struts on override-redirect windows, see [`OverlayBuilder::managed`].
+    pub fn set_strut(&mut self, edge: Option<Edge>) -> Result<()> {
+        self.strut = edge;
+        let geometry = self.conn.get_geometry(self.win_id)?.reply()?;
+        self.apply_strut(Rectangle {
+            x: geometry.x,
+            y: geometry.y,
+            width: geometry.width,
+            height: geometry.height,
+        })?;
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Follows the pointer over the whole screen, even when the overlay is click-through or
+    /// the pointer is over other windows, e.g. to draw a crosshair. Its position then reaches
+    /// egui as usual, see [`egui::Context::pointer_hover_pos`], possibly outside the overlay.
+    ///
+    /// Clicks are not sent to egui, as they are meant for the windows below, but their state is
+    /// available with [`Overlay::tracked_button_down`]. Needs XInput 2.1.
+    pub fn set_pointer_tracking(&mut self, track: bool) -> Result<()> {
+        match (track, self.pointer_tracker.take()) {
+            (true, None) => {
+                self.pointer_tracker = Some(PointerTracker::new(&*self.conn, self.root)?)
+            }
+            (false, Some(tracker)) => tracker.stop(&*self.conn)?,
+            (_, tracker) => self.pointer_tracker = tracker,
+        }
+        Ok(())
+    }
+
+    pub fn pointer_tracking(&self) -> bool {
+        self.pointer_tracker.is_some()
+    }
+
+    /// Whether `button` is held anywhere on the screen, while tracking the pointer.
+    pub fn tracked_button_down(&self, button: egui::PointerButton) -> bool {
+        self.pointer_tracker
+            .as_ref()
+            .is_some_and(|tracker| tracker.is_down(button))
+    }
+
+    /// Calls `callback` when `keysym` is pressed with exactly `modifiers`, whichever window has
+    /// the focus, e.g. to toggle the overlay. Caps lock and num lock are ignored.
+    ///
+    /// ```no_run
+    /// use egui_wgpu_x11::{egui::Modifiers, keysyms, Overlay};
+    ///
+    /// let mut overlay = Overlay::new()?;
+    /// let ctrl_alt = Modifiers {
+    ///     ctrl: true,
+    ///     alt: true,
+    ///     ..Modifiers::default()
+    /// };
+    /// overlay.register_hotkey(ctrl_alt, keysyms::KEY_o, |overlay| {
+    ///     overlay.set_input_passthrough(!overlay.input_passthrough())
+    /// })?;
+    /// # anyhow::Ok(())
+    /// ```
+    ///
+    /// Fails when another client, often the window manager, already grabbed the combination.
+    pub fn register_hotkey(
+        &mut self,
+        modifiers: egui::Modifiers,
+        keysym: u32,
+        callback: impl FnMut(&mut Overlay) -> Result<()> + 'static,
+    ) -> Result<()> {
+        let keycodes = self.keyboard.keycodes(keysym);
+        self.hotkeys.register(
+            &*self.conn,
+            self.root,
+            modifiers,
+            keysym,
+            keycodes,
+            Box::new(callback),
+        )
+    }
+
+    /// Releases a hotkey, returns whether it was registered.
+    pub fn unregister_hotkey(&mut self, modifiers: egui::Modifiers, keysym: u32) -> Result<bool> {
+        self.hotkeys
+            .unregister(&*self.conn, self.root, modifiers, keysym)
+    }
+
+    /// Gives the keyboard focus to the overlay, e.g. before typing into a text field. Unless
+    /// managed, the overlay never gets it otherwise, its keys then go to the focused window.
+    /// The focus goes back to the root window once the overlay is hidden.
+    pub fn set_input_focus(&self) -> Result<()> {
+        self.conn
+            .set_input_focus(InputFocus::PARENT, self.win_id, CURRENT_TIME)?;
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Grabs the keyboard while an egui widget wants keyboard input, e.g. a focused text field,
+    /// and releases it once none does. Keys then reach the overlay without it having the focus,
+    /// hotkeys don't fire meanwhile. Off by default.
+    pub fn set_keyboard_grab(&mut self, keyboard_grab: bool) -> Result<()> {
+        self.keyboard_grab = keyboard_grab;
+        self.update_keyboard_grab()
+    }
+
+    /// Blurs the windows behind the areas painted by egui, for frosted-glass panels with
+    /// translucent fills, e.g. `Visuals::window_fill` with a low alpha. Follows the UI, updated
+    /// after each frame. Off by default.
+    ///
+    /// Needs a compositor honoring `_KDE_NET_WM_BLUR_BEHIND_REGION`, like KWin. picom has no
+    /// per-window blur region, its `blur-background` option blurs behind whole translucent
+    /// windows instead.
+    pub fn set_blur_behind(&mut self, blur_behind: bool) -> Result<()> {
+        self.blur_behind = blur_behind;
+        if blur_behind {
+            // set by the next frame
+            self.context().request_repaint();
+        } else if !self.blur_region.is_empty() {
+            self.blur_region.clear();
+            write_blur_region(&*self.conn, self.win_id, self.blur_atom, &[])?;
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Whether the window has per-pixel transparency. Without a 32-bit visual, e.g. on some
+    /// Xvnc servers, frames are opaque instead, and the window is shaped to the content, see
+    /// [`Overlay::set_shape_to_content`].
+    pub fn transparent(&self) -> bool {
+        self.state.transparent()
+    }
+
+    /// Shapes the window to the areas painted by egui, with the XShape bounding region: the
+    /// rest of the overlay is not part of the window at all. Without a compositor, the windows
+    /// underneath then show through instead of black, and other clients see only the painted
+    /// areas as covered. Input goes through outside of them too. Follows the UI, updated after
+    /// each frame. Off by default.
+    pub fn set_shape_to_content(&mut self, shape_to_content: bool) -> Result<()> {
+        self.shape_to_content = shape_to_content;
+        if shape_to_content {
+            // set by the next frame
+            self.context().request_repaint();
+        } else if self.bounding_region.take().is_some() {
+            set_bounding_region(&*self.conn, self.win_id, None)?;
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Shrinks the window to the bounding box of what egui paints, following it frame by frame,
+    /// so a HUD in a corner of a 4K overlay renders and composites only that corner. egui still
+    /// lays out over the whole geometry. Off by default.
+    ///
+    /// Outside the box, input goes through as with [`InputShape::Content`]. Content growing
+    /// out of the box, e.g. a menu opening, shows one frame late.
+    pub fn set_crop_to_content(&mut self, crop_to_content: bool) -> Result<()> {
+        self.crop_to_content = crop_to_content;
+        if !crop_to_content && self.crop.is_some() {
+            self.apply_crop(None)?;
+            // for the input region
+            self.context().request_repaint();
+        }
+        Ok(())
+    }
+
+    /// Shows or hides the overlay, fading it in or out when [`Overlay::set_fade_duration`]
+    /// is set. Hidden overlays do not render, their app is not called until shown again.
+    pub fn set_visible(&mut self, visible: bool) -> Result<()> {
+        if visible == self.visible {
+            return Ok(());
+        }
+        let mapped = self.visible || self.fade.is_some();
+        self.visible = visible;
+        self.update_keyboard_grab()?;
+        // fading needs the compositor to blend the window
+        let fading = !self.fade_duration.is_zero() && self.compositor.running();
+
+        if visible {
+            self.stacking.recheck();
+        }
+        if visible && !mapped {
+            if fading {
+                self.fade_level = 0.;
+                self.apply_opacity()?;
+            }
+            self.map_windows(true)?;
+        }
+        let target = if visible { 1. } else { 0. };
+        if fading {
+            self.fade = Some(Fade::new(self.fade_level, target, self.fade_duration));
+        } else {
+            self.fade = None;
+            if !visible {
+                self.map_windows(false)?;
+            }
+            if self.fade_level != 1. {
+                self.fade_level = 1.;
+                self.apply_opacity()?;
+            }
+        }
+        self.flush()?;
+        #[cfg(feature = "dbus")]
+        if let Some(tray) = &self.tray {
+            tray.set_visible(visible)?;
+        }
+        Ok(())
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Whether nobody can see the overlay though visible: unmapped by the window manager, e.g.
+    /// minimized, on another workspace, or covered by other windows without a compositor.
+    /// Frames are skipped meanwhile, [`OverlayApp::update`] not called.
+    pub fn occluded(&self) -> bool {
+        self.occlusion.hidden()
+    }
+
+    /// How long [`Overlay::set_visible`] takes to fade the overlay in or out, zero to show
+    /// and hide it at once. Fading needs a compositing manager, without one it is immediate.
+    pub fn set_fade_duration(&mut self, fade_duration: Duration) {
+        self.fade_duration = fade_duration;
+    }
+
+    /// Dims the whole overlay with `_NET_WM_WINDOW_OPACITY`, from 0 transparent to 1 opaque,
+    /// on top of the transparency of what egui paints. Needs a compositing manager honouring it.
+    pub fn set_opacity(&mut self, opacity: f32) -> Result<()> {
+        self.opacity = opacity.clamp(0., 1.);
+        self.apply_opacity()?;
+        self.flush()?;
+        Ok(())
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    /// Sends the X requests of `f` on the connection of the overlay as one batch, flushed once
+    /// `f` returns, or at the end of the frame while the overlay runs, e.g. from
+    /// [`OverlayApp::update`]. The setters of the overlay called meanwhile join the batch.
+    ///
+    /// Requests without a reply aren't checked, their errors reach the
+    /// [X error handler](Overlay::set_x_error_handler) later on. Call `check()` on the cookies
+    /// of those whose failure matters, a round trip flushing the batch so far.
+    ///
+    /// ```no_run
+    /// use egui_wgpu_x11::x11rb::protocol::xproto::{ConfigureWindowAux, ConnectionExt};
+    /// # use egui_wgpu_x11::Overlay;
+    /// # fn batch(overlay: &Overlay, windows: &[u32]) -> anyhow::Result<()> {
+    /// overlay.with_batch(|conn| {
+    ///     for (i, window) in windows.iter().enumerate() {
+    ///         let at = ConfigureWindowAux::new().x(i as i32 * 100);
+    ///         conn.configure_window(*window, &at)?;
+    ///     }
+    ///     Ok(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_batch<T>(&self, f: impl FnOnce(&XCBConnection) -> Result<T>) -> Result<T> {
+        self.batch_depth.set(self.batch_depth.get() + 1);
+        let result = f(&self.conn);
+        self.batch_depth.set(self.batch_depth.get() - 1);
+        self.flush()?;
+        result
+    }
+
+    /// flushes the requests sent, unless batched or left to the runner
+    fn flush(&self) -> Result<()> {
+        if !self.running && self.batch_depth.get() == 0 {
+            self.conn.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Called with the X errors of the requests sent without waiting for their reply, which
+    /// are otherwise logged. On siblings, the overlay whose window caused the error reports
+    /// it, or the first one run.
+    pub fn set_x_error_handler(&mut self, handler: impl FnMut(&XError) + 'static) {
+        self.x_error_handler = Box::new(handler);
+    }
+
+    /// Called with the links clicked in egui, e.g. [`egui::Ui::hyperlink`], before they are
+    /// opened by the [`OverlayBuilder::url_command`]. Returning `true` stops them there, e.g.
+    /// to show the page in the overlay itself.
+    pub fn set_url_handler(&mut self, handler: impl FnMut(&str) -> bool + 'static) {
+        self.url_handler = Some(Box::new(handler));
+    }
+
+    /// The command opening links, see [`OverlayBuilder::url_command`].
+    pub fn set_url_command(&mut self, command: Option<String>) {
+        self.url_command = command;
+    }
+
+    /// Switches to dark or light visuals, keeping the rest of the style. [`Theme::System`]
+    /// queries the desktop once, call it again to follow a change.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.state.context().set_visuals(theme.visuals());
+    }
+
+    /// Shows a window over the app with the frame rate, a histogram of the frame times, the
+    /// texture memory and the count of X events by kind. The statistics start when shown.
+    ///
+    /// Timings of frames, X events and GPU submissions are also traced with [`tracing`] spans.
+    pub fn set_debug_panel(&mut self, debug_panel: bool) {
+        if debug_panel != self.debug.is_some() {
+            self.debug = debug_panel.then(DebugStats::default);
+        }
+    }
+
+    pub fn debug_panel(&self) -> bool {
+        self.debug.is_some()
+    }
+
+    /// Shows `toast` over the app, below the ones still shown, see [`toasts`].
+    pub fn push_toast(&mut self, toast: Toast) {
+        self.toasts.push(toast);
+        self.state.context().request_repaint();
+    }
+
+    /// Pushes toasts from other threads.
+    pub fn toast_sender(&self) -> ToastSender {
+        self.toasts.sender.clone()
+    }
+
+    /// Closes the toasts with this [`Toast::id`].
+    pub fn close_toast(&mut self, id: u64) {
+        self.toasts.close(id, toasts::CloseReason::Closed);
+    }
+
+    /// Where the toasts are stacked, e.g. [`egui::Align2::CENTER_BOTTOM`] for OSD-style
+    /// messages.
+    pub fn set_toast_corner(&mut self, corner: egui::Align2) {
+        self.toasts.corner = corner;
+    }
+
+    /// Stops the loop running the overlay, and its siblings, once the current frame is done.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    /// Runs the render loop with `app` until [`Overlay::close`] or the GPU gives up.
+    /// Also stops on SIGINT and SIGTERM, see [`OverlayBuilder::exit_on_signal`], and when the
+    /// window manager asks to close the window. The overlay is then torn down.
+    ///
+    /// If the connection to the X server breaks, e.g. as it restarts, the loop connects again
+    /// for up to 30 seconds, then builds the overlay again with its current settings and egui
+    /// memory. The thread blocks meanwhile.
+    ///
+    /// Frames are rendered when egui asks for a repaint, when X events arrive or when the
+    /// [`Overlay::waker`] fires, at most [`Overlay::set_max_fps`] per second. In between,
+    /// the loop sleeps until the earliest of them.
+    pub fn run(self, mut app: impl OverlayApp) -> Result<()> {
+        Self::run_all(vec![(self, &mut app)])
+    }
+
+    /// Like [`Overlay::run`], for overlays created with [`Overlay::new_sibling`], each with its app.
+    /// Each overlay only renders when its own events arrive or its egui context asks for it.
+    pub fn run_all(overlays: Vec<(Overlay, &mut dyn OverlayApp)>) -> Result<()> {
+        let Some(mut runner) = Runner::new(overlays)? else {
+            return Ok(());
+        };
+        let wakers = runner.wakers();
+        while let Turn::Wait(wake_at) = runner.turn()? {
+            let waited = wait_for_event(
+                runner.conn(),
+                &wakers,
+                wake_at.map(|at| at.saturating_duration_since(Instant::now())),
+            );
+            // the next turn finds the connection broken too, and reconnects
+            match waited {
+                Err(e) if !runner::is_connection_error(&e) => return Err(e),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`Overlay::run`], as a future for a tokio runtime, so the overlay runs alongside
+    /// other tasks of its thread, e.g. streaming stats into a HUD.
+    ///
+    /// The overlay is not `Send`: await it from `#[tokio::main]` or `block_on`, or spawn it on
+    /// a `tokio::task::LocalSet`. Rendering a frame still blocks the thread, until vsync with
+    /// the `Fifo` present mode.
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(self, mut app: impl OverlayApp) -> Result<()> {
+        Self::run_all_async(vec![(self, &mut app)]).await
+    }
+
+    /// Like [`Overlay::run_all`], see [`Overlay::run_async`].
+    #[cfg(feature = "tokio")]
+    pub async fn run_all_async(overlays: Vec<(Overlay, &mut dyn OverlayApp)>) -> Result<()> {
+        use std::os::unix::io::AsRawFd;
+        use tokio::io::unix::AsyncFd;
+
+        let Some(mut runner) = Runner::new(overlays)? else {
+            return Ok(());
+        };
+        let async_fds = |runner: &Runner| {
+            std::iter::once(runner.conn().as_raw_fd())
+                .chain(runner.wakers())
+                .map(AsyncFd::new)
+                .collect::<std::io::Result<Vec<_>>>()
+        };
+        let mut fds = async_fds(&runner)?;
+        while let Turn::Wait(wake_at) = runner.turn()? {
+            // a new connection after reconnecting
+            if fds[0].as_raw_fd() != runner.conn().as_raw_fd() {
+                drop(fds);
+                fds = async_fds(&runner)?;
+            }
+            runner::wait_async(&fds, wake_at).await?;
+        }
+        Ok(())
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<()> {
+        let _span = trace_span!("event", window = self.win_id).entered();
+        if let Some(debug) = &mut self.debug {
+            debug.count_event(&event);
+        }
+        if is_input(&event) {
+            self.state.frames.input(Instant::now());
+        }
+        let desktop = self.occlusion.current_desktop;
+        if self.occlusion.handle_event(&*self.conn, &event)? {
+            // skipped meanwhile
+            self.context().request_repaint();
+        }
+        if self.occlusion.current_desktop != desktop {
+            self.apply_hide_rules()?;
+            if self
+                .work_area
+                .update(&*self.conn, self.occlusion.current_desktop)?
+            {
+                self.share_work_area();
+            }
+        }
+        let desktop = self.occlusion.current_desktop;
+        if self.work_area.handle_event(&*self.conn, &event, desktop)? {
+            self.share_work_area();
+        }
+        if let Some(watch) = &mut self.fullscreen_watch {
+            if watch.handle_event(&*self.conn, &event)? {
+                self.apply_fullscreen_policy()?;
+            }
+        }
+        if let Some(follower) = &mut self.follower {
+            if follower.handle_event(&*self.conn, &event)? {
+                self.follow_window()?;
+            }
+        }
+        if let Some(watch) = &mut self.wm_watch {
+            if watch.handle_event(&*self.conn, &event)? {
+                // for the app to see
+                self.context().request_repaint();
+            }
+        }
+        if let Some(picker) = &mut self.picker {
+            match pick_event(&event) {
+                // egui still sees the pointer move, for the tooltip
+                Some(PickEvent::Moved(x, y)) => picker.moved = Some((x, y)),
+                Some(PickEvent::Picked(x, y)) => {
+                    let color = self.screen_image(x, y, 1, 1)?.pixels.first().copied();
+                    return self.finish_pick(color);
+                }
+                Some(PickEvent::Cancelled) => return self.finish_pick(None),
+                None => {}
+            }
+        }
+        if let Some(picker) = &mut self.window_picker {
+            match pick_event(&event) {
+                Some(PickEvent::Moved(x, y)) => picker.moved = Some((x, y)),
+                Some(PickEvent::Picked(x, y)) => {
+                    let window = window_at(&*self.conn, self.root, (x, y), &self.own_windows())?;
+                    return self.finish_window_pick(window.map(|(window, _)| window));
+                }
+                Some(PickEvent::Cancelled) => return self.finish_window_pick(None),
+                None => {}
+            }
+        }
+        match event {
+            // the surface follows the crop instead
+            Event::ConfigureNotify(e) if e.window == self.win_id && self.crop.is_none() => {
+                let size = (e.width.into(), e.height.into());
+                if size != self.state.size {
+                    self.state.resize(size);
+                }
+            }
+            Event::RandrScreenChangeNotify(e) => {
+                // the reported size ignores the rotation
+                let (width, height) = if e
+                    .rotation
+                    .intersects(Rotation::ROTATE90 | Rotation::ROTATE270)
+                {
+                    (e.height, e.width)
+                } else {
+                    (e.width, e.height)
+                };
+                self.follow_screen(width, height)?;
+            }
+            Event::RandrNotify(_) => self.follow_monitor()?,
+            Event::XfixesSelectionNotify(_) => self.compositor.handle_event(&event),
+            Event::Error(e) => (self.x_error_handler)(&XError::from(&e)),
+            Event::ClientMessage(e)
+                if e.format == 32 && e.data.as_data32()[0] == self.wm_delete_window =>
+            {
+                self.close()
+            }
+            // other windows changing, on the root
+            Event::ConfigureNotify(_)
+            | Event::MapNotify(_)
+            | Event::CirculateNotify(_)
+            | Event::CreateNotify(_)
+            | Event::DestroyNotify(_)
+            | Event::UnmapNotify(_)
+            | Event::ReparentNotify(_)
+            | Event::GravityNotify(_) => self.stacking.handle_event(&event, self.root),
+            Event::XinputRawMotion(_)
+            | Event::XinputRawButtonPress(_)
+            | Event::XinputRawButtonRelease(_) => {
+                if let Some(tracker) = &mut self.pointer_tracker {
+                    tracker.handle_event(&event);
+                }
+            }
+            // grabbed by a hotkey
+            Event::KeyPress(e) if e.event == self.root => {
+                Hotkeys::trigger(self, e.detail, e.state.into())?
+            }
+            Event::KeyRelease(e) if e.event == self.root => {}
+            Event::KeyPress(e) => {
+                self.keyboard
+                    .handle_key(e.detail, true, &mut self.state.raw_input.events);
+                if self
+                    .state
+                    .raw_input
+                    .events
+                    .last()
+                    .is_some_and(is_paste_shortcut)
+                {
+                    self.paste(Selection::Clipboard)?;
+                }
+            }
+            Event::KeyRelease(e) => {
+                self.keyboard
+                    .handle_key(e.detail, false, &mut self.state.raw_input.events)
+            }
+            Event::XkbStateNotify(_)
+            | Event::XkbNewKeyboardNotify(_)
+            | Event::XkbMapNotify(_)
+            | Event::MappingNotify(_)
+            | Event::FocusIn(_)
+            | Event::FocusOut(_) => {
+                if self.keyboard.handle_event(&self.conn, &event) {
+                    let keyboard = &self.keyboard;
+                    self.hotkeys
+                        .remap(&*self.conn, self.root, |keysym| keyboard.keycodes(keysym))?;
+                }
+            }
+            Event::SelectionRequest(_)
+            | Event::SelectionClear(_)
+            | Event::SelectionNotify(_)
+            | Event::PropertyNotify(_) => {
+                if let Some(text) = self.clipboard.handle_event(&*self.conn, &event)? {
+                    self.state.raw_input.events.push(egui::Event::Paste(text));
+                }
+            }
+            Event::XinputTouchBegin(_) | Event::XinputTouchUpdate(_) | Event::XinputTouchEnd(_) => {
+                let pixels_per_point = self.state.pixels_per_point();
+                let modifiers = self.keyboard.modifiers();
+                if let Some(touches) = &mut self.touches {
+                    touches.handle_event(
+                        &event,
+                        pixels_per_point,
+                        modifiers,
+                        &mut self.state.raw_input.events,
+                    );
+                }
+            }
+            event => {
+                if is_middle_press(&event) {
+                    self.paste(Selection::Primary)?;
+                }
+                let pixels_per_point = self.state.pixels_per_point();
+                let modifiers = self.keyboard.modifiers();
+                if let Some(smooth_scroll) = &mut self.smooth_scroll {
+                    if let Some(scroll) =
+                        smooth_scroll.handle_event(&*self.conn, &event, modifiers)?
+                    {
+                        self.state.raw_input.events.push(scroll);
+                    }
+                }
+                match pointer_event(&event, pixels_per_point, modifiers) {
+                    Some(event) => self.state.raw_input.events.push(event),
+                    None => trace!("unhandled event {:?}", event),
+                }
+            }
+        }
+        self.state.raw_input.modifiers = self.keyboard.modifiers();
+        Ok(())
+    }
+
+    /// Builds the overlay again on `conn`, a new connection after the previous one broke,
+    /// e.g. as the X server restarted. The egui memory and style, the settings changed
+    /// since it was built, the waker, the hotkeys and the handlers carry over.
+    pub(crate) fn reconnect(&mut self, conn: Rc<XCBConnection>, screen_num: usize) -> Result<()> {
+        let mut builder = self.settings.clone();
+        builder.geometry = self.geometry;
+        builder.monitor = self.monitor.clone();
+        builder.mirrors = self.mirrors.clone();
+        builder.attachment = self.attachment();
+        builder.workspaces = self.workspaces.clone();
+        builder.input_shape = self.input_shape;
+        builder.scale_factor = self.scale_factor;
+        builder.viewport = self.viewport;
+        builder.ui_rate = self.ui_rate;
+        builder.texture_budget = self.texture_budget();
+        builder.watchdog = self.watchdog();
+        builder.present_mode = self.state.present_mode();
+        builder.strut = self.strut;
+        builder.track_pointer = self.pointer_tracker.is_some();
+        builder.visible = self.visible;
+        builder.keyboard_grab = self.keyboard_grab;
+        builder.fullscreen_policy = self.fullscreen_policy;
+        builder.crop_to_content = self.crop_to_content;
+        builder.blur_behind = self.blur_behind;
+        builder.shape_to_content = self.shape_to_content;
+        builder.opacity = self.opacity;
+        builder.hints.above = self.always_on_top;
+        builder.debug_panel = false;
+        // kept listening, woken through the waker kept below
+        #[cfg(feature = "ipc")]
+        {
+            builder.ipc_socket = None;
+        }
+        #[cfg(feature = "dbus")]
+        {
+            builder.dbus_name = None;
+            builder.notification_daemon = false;
+        }
+        builder.watch_config = None;
+        let gpu = self.state.gpu().clone();
+        let mut old = std::mem::replace(
+            self,
+            Overlay::with_connection(conn, screen_num, Some(gpu), builder)?,
+        );
+
+        let (context, old_context) = (self.state.context(), old.state.context());
+        *context.memory() = old_context.memory().clone();
+        context.set_style(old_context.style());
+        // the clones given out keep working
+        std::mem::swap(&mut self.waker, &mut old.waker);
+        std::mem::swap(&mut self.wake_receiver, &mut old.wake_receiver);
+        context.set_request_repaint_callback({
+            let waker = self.waker.clone();
+            move || waker.wake()
+        });
+        self.input_passthrough = old.input_passthrough;
+        self.running = old.running;
+        self.apply_input_region()?;
+        self.min_frame_time = old.min_frame_time;
+        self.fade_duration = old.fade_duration;
+        self.exit_on_signal = old.exit_on_signal;
+        self.debug = old.debug.take();
+        self.x_error_handler = std::mem::replace(&mut old.x_error_handler, Box::new(log_x_error));
+        self.url_handler = old.url_handler.take();
+        self.texture_budget_handler = old.texture_budget_handler.take();
+        self.state.panics.message = old.state.panics.message.take();
+        self.url_command = old.url_command.take();
+        #[cfg(feature = "recorder")]
+        {
+            self.recording = old.recording.take();
+        }
+        #[cfg(feature = "ipc")]
+        {
+            self.ipc = old.ipc.take();
+        }
+        #[cfg(feature = "dbus")]
+        {
+            self.dbus = old.dbus.take();
+            self.notifications = old.notifications.take();
+            self.tray = old.tray.take();
+        }
+        std::mem::swap(&mut self.toasts, &mut old.toasts);
+        self.config_watcher = old.config_watcher.take();
+        self.config = old.config.take();
+        self.hotkeys = std::mem::take(&mut old.hotkeys);
+        let keyboard = &self.keyboard;
+        self.hotkeys
+            .regrab(&*self.conn, self.root, |keysym| keyboard.keycodes(keysym))?;
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Fits the overlay to the new screen size, or to its monitor.
+    /// The surface follows through the resulting `ConfigureNotify`.
+    fn follow_screen(&mut self, screen_width: u16, screen_height: u16) -> Result<()> {
+        if self.follower.is_some() {
+            return self.follow_window();
+        }
+        if self.monitor.is_some() {
+            return self.follow_monitor();
+        }
+        self.place(Rectangle {
+            x: 0,
+            y: 0,
+            width: screen_width,
+            height: screen_height,
+        })
+    }
+
+    /// Moves and resizes the window to its geometry inside `area`, uncropped until the next
+    /// frame.
+    fn place(&mut self, area: Rectangle) -> Result<()> {
+        let rect = self.geometry.place(area);
+        self.placed = rect;
+        if self.crop.take().is_some() {
+            self.state.crop(None);
+        }
+        set_window_geometry(
+            &*self.conn,
+            self.win_id,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+        )?;
+        if self.strut.is_some() {
+            self.apply_strut(rect)?;
+        }
+        self.share_work_area();
+        self.layout_mirrors()?;
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Places the mirrors like the overlay in their monitors, at their scale factor. Their
+    /// windows are created again when the mirrored monitors change.
+    fn layout_mirrors(&mut self) -> Result<()> {
+        if self.mirrors == Mirrors::None && self.mirror_windows.is_empty() {
+            return Ok(());
+        }
+        let monitors = monitors(&*self.conn, self.root)?;
+        let own_scale_factor = self.state.pixels_per_point();
+        let mut placements = Vec::new();
+        for monitor in self.mirrors.select(&monitors, self.placed) {
+            let scale_factor = match self.scale_factor {
+                Some(scale_factor) => scale_factor,
+                None => detect_scale_factor(&*self.conn, self.root, Some(monitor))?,
+            } * self.viewport.scale;
+            let rect = mirror_rect(
+                &self.geometry,
+                self.placed,
+                scale_factor / own_scale_factor,
+                monitor.rect(),
+            );
+            placements.push((rect, scale_factor));
+        }
+
+        if placements.len() != self.mirror_windows.len() {
+            self.state.remove_mirrors();
+            for mirror in self.mirror_windows.drain(..) {
+                self.conn.destroy_window(mirror.window)?;
+            }
+            for (rect, scale_factor) in placements {
+                self.create_mirror(rect, scale_factor)?;
+            }
+        } else {
+            for (i, (rect, scale_factor)) in placements.into_iter().enumerate() {
+                let mirror = &mut self.mirror_windows[i];
+                if mirror.rect == rect && mirror.scale_factor == scale_factor {
+                    continue;
+                }
+                mirror.rect = rect;
+                mirror.scale_factor = scale_factor;
+                let window = mirror.window;
+                set_window_geometry(&*self.conn, window, rect.x, rect.y, rect.width, rect.height)?;
+                let size = (rect.width.into(), rect.height.into());
+                self.state.resize_mirror(i, size, scale_factor)?;
+            }
+        }
+        self.flush()?;
+        Ok(())
+    }
+
+    /// maps a window at `rect` drawn with the frames of the overlay
+    fn create_mirror(&mut self, rect: Rectangle, scale_factor: f32) -> Result<()> {
+        let screen = &self.conn.setup().roots[self.screen_num];
+        let options = x11::WindowOptions {
+            depth: self.depth,
+            visual: Some(self.visual),
+            event_mask: EventMask::NO_EVENT,
+            colormap: Some(self.colormap),
+            managed: false,
+        };
+        let OverlayWindow { window, .. } =
+            create_overlay_window(&*self.conn, screen, rect, &options)?;
+        let handle = MyWindow {
+            window,
+            visual_id: self.visual,
+            connection: self.conn.get_raw_xcb_connection(),
+            screen: self.screen_num as i32,
+        };
+        let size = (rect.width.into(), rect.height.into());
+        if let Err(e) = self.state.add_mirror(&handle, size, scale_factor) {
+            self.conn.destroy_window(window)?;
+            return Err(e);
+        }
+        self.mirror_windows.push(MirrorWindow {
+            window,
+            rect,
+            scale_factor,
+        });
+        if self.opacity * self.fade_level < 1. {
+            let opacity = self.opacity * self.fade_level;
+            write_window_opacity(&*self.conn, window, self.opacity_atom, opacity)?;
+        }
+        if self.visible || self.fade.is_some() {
+            self.conn.map_window(window)?;
+        }
+        Ok(())
+    }
+
+    /// maps or unmaps the overlay window and its mirrors
+    fn map_windows(&self, mapped: bool) -> Result<()> {
+        let mirrors = self.mirror_windows.iter().map(|mirror| mirror.window);
+        for window in std::iter::once(self.win_id).chain(mirrors) {
+            if mapped {
+                self.conn.map_window(window)?;
+            } else {
+                self.conn.unmap_window(window)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_opacity(&self) -> Result<()> {
+        let opacity = self.opacity * self.fade_level;
+        write_window_opacity(&*self.conn, self.win_id, self.opacity_atom, opacity)?;
+        for mirror in &self.mirror_windows {
+            write_window_opacity(&*self.conn, mirror.window, self.opacity_atom, opacity)?;
+        }
+        Ok(())
+    }
+
+    /// Advances the ongoing fade, unmapping the window once faded out.
+    fn step_fade(&mut self, now: Instant) -> Result<()> {
+        let Some(fade) = &self.fade else {
+            return Ok(());
+        };
+        self.fade_level = fade.opacity(now);
+        self.apply_opacity()?;
+        if fade.done(now) {
+            self.fade = None;
+            if !self.visible {
+                self.map_windows(false)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the tracked pointer position to egui, once per frame however fast it moves.
+    fn update_tracked_pointer(&mut self) -> Result<()> {
+        let Some(tracker) = &mut self.pointer_tracker else {
+            return Ok(());
+        };
+        let pixels_per_point = self.state.pixels_per_point();
+        if let Some(pos) = tracker.moved(&*self.conn, self.win_id, pixels_per_point)? {
+            self.state
+                .raw_input
+                .events
+                .push(egui::Event::PointerMoved(pos));
+        }
+        Ok(())
+    }
+
+    /// Reads the color under the pointer, once per frame however fast it moves.
+    fn update_picked_color(&mut self) -> Result<()> {
+        let Some((x, y)) = self.picker.as_ref().and_then(|picker| picker.moved) else {
+            return Ok(());
+        };
+        let color = self.screen_image(x, y, 1, 1)?.pixels.first().copied();
+        if let Some(picker) = &mut self.picker {
+            picker.moved = None;
+            picker.color = color;
+        }
+        Ok(())
+    }
+
+    /// Finds the window under the pointer, once per frame however fast it moves.
+    fn update_picked_window(&mut self) -> Result<()> {
+        let Some(pointer) = self.window_picker.as_ref().and_then(|picker| picker.moved) else {
+            return Ok(());
+        };
+        let hovered = window_at(&*self.conn, self.root, pointer, &self.own_windows())?.map(
+            |(window, frame)| {
+                let min = self.screen_to_points(frame.x, frame.y);
+                let pixels_per_point = self.state.pixels_per_point();
+                let size = egui::vec2(frame.width.into(), frame.height.into()) / pixels_per_point;
+                (window, egui::Rect::from_min_size(min, size))
+            },
+        );
+        if let Some(picker) = &mut self.window_picker {
+            picker.moved = None;
+            picker.hovered = hovered;
+        }
+        Ok(())
+    }
+
+    /// the overlay window and its mirrors
+    fn own_windows(&self) -> Vec<Window> {
+        let mirrors = self.mirror_windows.iter().map(|mirror| mirror.window);
+        std::iter::once(self.win_id).chain(mirrors).collect()
+    }
+
+    /// releases the pointer and calls back the window picker with `window`
+    fn finish_window_pick(&mut self, window: Option<PickedWindow>) -> Result<()> {
+        let Some(picker) = self.window_picker.take() else {
+            return Ok(());
+        };
+        self.conn.ungrab_pointer(CURRENT_TIME)?;
+        self.flush()?;
+        // without the outline
+        self.context().request_repaint();
+        (picker.on_pick)(self, window)
+    }
+
+    /// releases the pointer and calls back the picker with `color`
+    fn finish_pick(&mut self, color: Option<egui::Color32>) -> Result<()> {
+        let Some(picker) = self.picker.take() else {
+            return Ok(());
+        };
+        self.conn.ungrab_pointer(CURRENT_TIME)?;
+        self.flush()?;
+        // without the tooltip
+        self.context().request_repaint();
+        (picker.on_pick)(self, color)
+    }
+
+    /// Places the overlay at the attached window, or hides it while that window is not
+    /// viewable.
+    fn follow_window(&mut self) -> Result<()> {
+        let Some(follower) = &self.follower else {
+            return Ok(());
+        };
+        if follower.rect.is_some() {
+            self.set_layout(self.geometry)?;
+        }
+        self.apply_hide_rules()
+    }
+
+    /// whether the fullscreen policy, the attached window or the workspaces hide the overlay
+    fn hidden_by_rules(&self) -> bool {
+        let fullscreen =
+            self.fullscreen_policy == FullscreenPolicy::Hide && self.fullscreen_focused();
+        let detached = self
+            .follower
+            .as_ref()
+            .is_some_and(|follower| follower.rect.is_none());
+        let away = !self.workspaces.shows_on(self.occlusion.current_desktop);
+        fullscreen || detached || away
+    }
+
+    /// Hides the overlay or shows it again as its rules change, see
+    /// [`Overlay::hidden_by_rules`]. Overlays hidden otherwise stay so.
+    fn apply_hide_rules(&mut self) -> Result<()> {
+        let hide = self.hidden_by_rules();
+        if hide && self.visible {
+            self.set_visible(false)?;
+            self.hidden_by_rules = true;
+        } else if !hide && self.hidden_by_rules {
+            self.hidden_by_rules = false;
+            self.set_visible(true)?;
+        }
+        Ok(())
+    }
+
+    /// whether the focused window is fullscreen, if followed
+    fn fullscreen_focused(&self) -> bool {
+        self.fullscreen_watch
+            .as_ref()
+            .is_some_and(|watch| watch.fullscreen)
+    }
+
+    /// Hides the overlay or shows it again, as the focused window enters or leaves
+    /// fullscreen. Pausing and throttling are up to the loop.
+    fn apply_fullscreen_policy(&mut self) -> Result<()> {
+        self.apply_hide_rules()?;
+        // resumed at full speed
+        self.context().request_repaint();
+        Ok(())
+    }
+
+    /// Whether frames are skipped, as the focused window is fullscreen.
+    pub(crate) fn paused(&self) -> bool {
+        self.fullscreen_policy == FullscreenPolicy::Pause && self.fullscreen_focused()
+    }
+
+    /// Between frames at least, slower while throttled for a fullscreen window.
+    pub(crate) fn frame_interval(&self) -> Duration {
+        let interval = self.min_frame_time.max(min_frame_time(self.ui_rate));
+        match self.fullscreen_policy {
+            FullscreenPolicy::Throttle(fps) if self.fullscreen_focused() => {
+                interval.max(min_frame_time(Some(fps)))
+            }
+            _ => interval,
+        }
+    }
+
+    /// Sets the strut for the window at `rect`, or removes it.
+    fn apply_strut(&self, rect: Rectangle) -> Result<()> {
+        let strut = match self.strut {
+            Some(edge) => {
+                let root = self.conn.get_geometry(self.root)?.reply()?;
+                Some(strut_partial(edge, rect, (root.width, root.height)))
+            }
+            None => None,
+        };
+        set_strut(&*self.conn, self.win_id, strut)?;
+        Ok(())
+    }
+
+    /// Pastes right away if we own the selection, otherwise once the owner answers.
+    fn paste(&mut self, selection: Selection) -> Result<()> {
+        if let Some(text) = self.clipboard.paste(&*self.conn, selection)? {
+            self.state.raw_input.events.push(egui::Event::Paste(text));
+        }
+        Ok(())
+    }
+
+    /// The attached window or the space beside it, the monitor of the overlay if it has one
+    /// and it is plugged in, or the screen.
+    fn area(&self) -> Result<Rectangle> {
+        if let Some(follower) = &self.follower {
+            if let Some(rect) = follower.rect {
+                let root = self.conn.get_geometry(self.root)?.reply()?;
+                let screen = Rectangle {
+                    x: 0,
+                    y: 0,
+                    width: root.width,
+                    height: root.height,
+                };
+                return Ok(follower.attachment.area(rect, screen));
+            }
+        }
+        if let Some(selector) = &self.monitor {
+            if let Some(monitor) = selector.select(&monitors(&*self.conn, self.root)?) {
+                return Ok(monitor.rect());
+            }
+        }
+        let root = self.conn.get_geometry(self.root)?.reply()?;
+        Ok(Rectangle {
+            x: 0,
+            y: 0,
+            width: root.width,
+            height: root.height,
+        })
+    }
+
+    /// Places the overlay in the selected monitor again, if any and still plugged in.
+    fn follow_monitor(&mut self) -> Result<()> {
+        let Some(selector) = &self.monitor else {
+            return Ok(());
+        };
+        let monitors = monitors(&*self.conn, self.root)?;
+        if self.follower.is_some() {
+            self.follow_window()?;
+        } else if let Some(monitor) = selector.select(&monitors) {
+            self.place(monitor.rect())?;
+        }
+        // the new monitor may have another DPI
+        self.update_scale_factor()
+    }
+
+    fn update_scale_factor(&mut self) -> Result<()> {
+        let scale_factor = match self.scale_factor {
+            Some(scale_factor) => scale_factor,
+            None => {
+                let selector = self.monitor.clone().unwrap_or_default();
+                let monitors = monitors(&*self.conn, self.root)?;
+                detect_scale_factor(&*self.conn, self.root, selector.select(&monitors))?
+            }
+        };
+        self.state
+            .set_pixels_per_point(scale_factor * self.viewport.scale);
+        self.share_work_area();
+        self.layout_mirrors()
+    }
+
+    /// the part of the work area over the window, in points, for the [`panels`]
+    fn share_work_area(&self) {
+        let work_area = self.work_area.rect.map(|area| {
+            let min = self.screen_to_points(area.x, area.y);
+            let size =
+                egui::vec2(area.width.into(), area.height.into()) / self.state.pixels_per_point();
+            egui::Rect::from_min_size(min, size)
+        });
+        panels::set_work_area(self.context(), work_area);
+        self.context().request_repaint();
+    }
+
+    fn apply_input_region(&self) -> Result<()> {
+        let rects = match self.input_shape {
+            _ if self.input_passthrough => Some(&[][..]),
+            InputShape::Passthrough => Some(&[][..]),
+            InputShape::Window => None,
+            InputShape::Content => Some(&self.input_region[..]),
+        };
+        // left out of the window events while all the input went through
+        if rects != Some(&[]) {
+            select_more_events(&*self.conn, self.win_id, input_events())?;
+        }
+        set_input_region(&*self.conn, self.win_id, rects)?;
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Through the URL handler, then the URL command.
+    fn open_url(&mut self, url: &str) {
+        if let Some(handler) = &mut self.url_handler {
+            if handler(url) {
+                return;
+            }
+        }
+        if let Some(command) = &self.url_command {
+            url::open(command, url);
+        }
+    }
+
+    /// Grabs or releases the keyboard, as egui wants keyboard input or not.
+    /// A failed grab, e.g. as another client holds one, is tried again after the next frame.
+    fn update_keyboard_grab(&mut self) -> Result<()> {
+        let grab = self.keyboard_grab && self.visible && self.context().wants_keyboard_input();
+        if grab == self.keyboard_grabbed {
+            return Ok(());
+        }
+        if grab {
+            let status = self
+                .conn
+                .grab_keyboard(
+                    true,
+                    self.win_id,
+                    CURRENT_TIME,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                )?
+                .reply()?
+                .status;
+            if status != GrabStatus::SUCCESS {
+                debug!("keyboard grab failed: {:?}", status);
+                return Ok(());
+            }
+        } else {
+            self.conn.ungrab_keyboard(CURRENT_TIME)?;
+            self.flush()?;
+        }
+        self.keyboard_grabbed = grab;
+        Ok(())
+    }
+
+    /// Crops the window to the bounding box of `painted`, if cropping. Returns whether the
+    /// crop changed, the next frame then drawing what this one clipped.
+    fn update_crop(&mut self, painted: &[egui::Rect]) -> Result<bool> {
+        if !self.crop_to_content {
+            return Ok(false);
+        }
+        let bounds = pixel_rects(painted, self.state.pixels_per_point())
+            .into_iter()
+            .map(|rect| {
+                (
+                    i32::from(rect.x) - CROP_MARGIN,
+                    i32::from(rect.y) - CROP_MARGIN,
+                    i32::from(rect.x) + i32::from(rect.width) + CROP_MARGIN,
+                    i32::from(rect.y) + i32::from(rect.height) + CROP_MARGIN,
+                )
+            })
+            .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)));
+        let (width, height) = (i32::from(self.placed.width), i32::from(self.placed.height));
+        let crop = match bounds {
+            Some((x0, y0, x1, y1)) => {
+                let (x0, y0) = (x0.clamp(0, width - 1), y0.clamp(0, height - 1));
+                let (x1, y1) = (x1.clamp(x0 + 1, width), y1.clamp(y0 + 1, height));
+                Rectangle {
+                    x: x0 as i16,
+                    y: y0 as i16,
+                    width: (x1 - x0) as u16,
+                    height: (y1 - y0) as u16,
+                }
+            }
+            // windows can't be empty
+            None => Rectangle {
+                x: 0,
+                y: 0,
+                width: 1,
+                height: 1,
+            },
+        };
+        if self.crop == Some(crop) {
+            return Ok(false);
+        }
+        self.apply_crop(Some(crop))?;
+        Ok(true)
+    }
+
+    /// Moves and resizes the window to `crop` of its placement, all of it if `None`.
+    fn apply_crop(&mut self, crop: Option<Rectangle>) -> Result<()> {
+        let rect = crop.unwrap_or(Rectangle {
+            x: 0,
+            y: 0,
+            width: self.placed.width,
+            height: self.placed.height,
+        });
+        set_window_geometry(
+            &*self.conn,
+            self.win_id,
+            self.placed.x + rect.x,
+            self.placed.y + rect.y,
+            rect.width,
+            rect.height,
+        )?;
+        self.crop = crop;
+        self.state.crop(crop);
+        self.flush()?;
+        Ok(())
+    }
+
+    /// Tracks the painted areas, so switching to `InputShape::Content` applies them at once.
+    fn update_input_region(&mut self, painted: &[egui::Rect]) -> Result<()> {
+        let mut rects = pixel_rects(painted, self.state.pixels_per_point());
+        // relative to the window
+        if let Some(crop) = self.crop {
+            for rect in &mut rects {
+                rect.x -= crop.x;
+                rect.y -= crop.y;
+            }
+        }
+        if self.shape_to_content && self.bounding_region.as_ref() != Some(&rects) {
+            set_bounding_region(&*self.conn, self.win_id, Some(&rects))?;
+            self.flush()?;
+            self.bounding_region = Some(rects.clone());
+        }
+        if self.blur_behind && rects != self.blur_region {
+            write_blur_region(&*self.conn, self.win_id, self.blur_atom, &rects)?;
+            self.flush()?;
+            self.blur_region.clone_from(&rects);
+        }
+        if rects != self.input_region {
+            self.input_region = rects;
+            if self.input_shape == InputShape::Content && !self.input_passthrough {
+                self.apply_input_region()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// pixels kept around the content when cropping, not to resize on every small change
+const CROP_MARGIN: i32 = 16;
+
+/// `rects` in points, rounded out to whole pixels
+fn pixel_rects(rects: &[egui::Rect], pixels_per_point: f32) -> Vec<Rectangle> {
+    rects
+        .iter()
+        .map(|rect| {
+            let (x0, y0) = (
+                (rect.min.x * pixels_per_point).floor(),
+                (rect.min.y * pixels_per_point).floor(),
+            );
+            let (x1, y1) = (
+                (rect.max.x * pixels_per_point).ceil(),
+                (rect.max.y * pixels_per_point).ceil(),
+            );
+            Rectangle {
+                x: x0 as i16,
+                y: y0 as i16,
+                width: (x1 - x0) as u16,
+                height: (y1 - y0) as u16,
+            }
+        })
+        .collect()
+}
+
+/// When an overlay renders next, in [`Overlay::run_all`].
+struct Schedule {
+    /// `None`: no repaint requested
+    repaint_at: Option<Instant>,
+    last_frame: Option<Instant>,
+    /// the cached frame is presented again, see [`Overlay::set_ui_rate`]
+    exposed: bool,
+}
+
+impl Schedule {
+    fn new() -> Self {
+        Self {
+            repaint_at: Some(Instant::now()),
+            last_frame: None,
+            exposed: false,
+        }
+    }
+
+    /// the requested repaint, delayed to respect the frame rate cap
+    fn frame_at(&self, min_frame_time: Duration) -> Option<Instant> {
+        self.repaint_at.map(|at| {
+            self.last_frame
+                .map_or(at, |last| at.max(last + min_frame_time))
+        })
+    }
+}
+
+/// to the display of `builder`, with the extensions the overlays use
+fn connect(builder: &OverlayBuilder) -> Result<(XCBConnection, usize), OverlayError> {
+    let (conn, screen_num) = x11::connect(builder.display.as_deref(), &builder.xauth)?;
+
+    xfixes_init(&conn)?;
+    randr_init(&conn, conn.setup().roots[screen_num].root)?;
+
+    Ok((conn, screen_num))
+}
+
+fn min_frame_time(max_fps: Option<f32>) -> Duration {
+    match max_fps {
+        Some(fps) if fps > 0. => Duration::from_secs_f32(1. / fps),
+        _ => Duration::ZERO,
+    }
+}
+
+/// whether the overlay may look different after `event`, not when only other windows changed
+fn repaints(event: &Event) -> bool {
+    match event {
+        Event::ConfigureNotify(e) => e.event == e.window,
+        Event::MapNotify(_)
+        | Event::CirculateNotify(_)
+        | Event::CreateNotify(_)
+        | Event::DestroyNotify(_)
+        | Event::UnmapNotify(_)
+        | Event::ReparentNotify(_)
+        | Event::GravityNotify(_)
+        | Event::Error(_) => false,
+        _ => true,
+    }
+}
+
+/// whether `event` comes from the user, for the input latency
+fn is_input(event: &Event) -> bool {
+    matches!(
+        event,
+        Event::KeyPress(_)
+            | Event::KeyRelease(_)
+            | Event::ButtonPress(_)
+            | Event::ButtonRelease(_)
+            | Event::MotionNotify(_)
+            | Event::XinputMotion(_)
+            | Event::XinputButtonPress(_)
+            | Event::XinputButtonRelease(_)
+            | Event::XinputTouchBegin(_)
+            | Event::XinputTouchUpdate(_)
+            | Event::XinputTouchEnd(_)
+            | Event::XinputRawMotion(_)
+            | Event::XinputRawButtonPress(_)
+            | Event::XinputRawButtonRelease(_)
+    )
+}
+
+/// the window an event is addressed to, `None` for screen-wide events
+fn event_window(event: &Event) -> Option<Window> {
+    match event {
+        Event::ConfigureNotify(e) => Some(e.event),
+        Event::KeyPress(e) | Event::KeyRelease(e) => Some(e.event),
+        Event::ButtonPress(e) | Event::ButtonRelease(e) => Some(e.event),
+        Event::MotionNotify(e) => Some(e.event),
+        Event::XinputMotion(e) => Some(e.event),
+        Event::XinputButtonPress(e) | Event::XinputButtonRelease(e) => Some(e.event),
+        Event::XinputTouchBegin(e) | Event::XinputTouchUpdate(e) | Event::XinputTouchEnd(e) => {
+            Some(e.event)
+        }
+        Event::EnterNotify(e) | Event::LeaveNotify(e) => Some(e.event),
+        Event::FocusIn(e) | Event::FocusOut(e) => Some(e.event),
+        Event::SelectionRequest(e) => Some(e.owner),
+        Event::SelectionClear(e) => Some(e.owner),
+        Event::SelectionNotify(e) => Some(e.requestor),
+        Event::PropertyNotify(e) => Some(e.window),
+        Event::XfixesSelectionNotify(e) => Some(e.window),
+        _ => None,
+    }
+}
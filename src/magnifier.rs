@@ -0,0 +1,132 @@
+//! A zooming lens following the pointer over the whole screen.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::magnifier::Magnifier;
+//! use egui_wgpu_x11::{egui, Overlay, OverlayApp, OverlayBuilder};
+//!
+//! struct Lens(Magnifier);
+//!
+//! impl OverlayApp for Lens {
+//!     fn ui(&mut self, ctx: &egui::Context) {
+//!         self.0.ui(ctx);
+//!     }
+//!
+//!     fn update(&mut self, overlay: &mut Overlay) -> anyhow::Result<()> {
+//!         self.0.update(overlay)
+//!     }
+//! }
+//!
+//! OverlayBuilder::fullscreen_overlay()
+//!     .track_pointer(true)
+//!     .run(Lens(Magnifier::new(4., 200)))?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! The screen is read back with `GetImage` every frame: a lens of a few hundred pixels is
+//! cheap, a full screen one is not.
+
+use anyhow::Result;
+use egui::{
+    pos2, vec2, Color32, Context, Id, LayerId, Mesh, Order, Pos2, Rect, Stroke, TextureFilter,
+    TextureHandle, Vec2,
+};
+
+use crate::Overlay;
+
+/// Shows the screen around the pointer, zoomed, next to it. Drive it from
+/// [`OverlayApp::update`](crate::OverlayApp::update) and [`OverlayApp::ui`](crate::OverlayApp::ui).
+pub struct Magnifier {
+    zoom: f32,
+    /// side of the lens, in pixels
+    size: u16,
+    /// where the pointer is, in points
+    pointer: Option<Pos2>,
+    texture: Option<TextureHandle>,
+}
+
+impl Magnifier {
+    /// A square lens of `size` pixels, magnifying `zoom` times.
+    pub fn new(zoom: f32, size: u16) -> Self {
+        Self {
+            zoom: zoom.max(1.),
+            size,
+            pointer: None,
+            texture: None,
+        }
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(1.);
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Reads the screen around the pointer, for the next frame. The overlay draws the lens
+    /// away from the pointer, not to magnify itself.
+    pub fn update(&mut self, overlay: &mut Overlay) -> Result<()> {
+        let Some((x, y)) = overlay.screen_pointer()? else {
+            self.pointer = None;
+            return Ok(());
+        };
+        let side = (f32::from(self.size) / self.zoom).ceil() as u16;
+        let half = (side / 2) as i16;
+        let image = overlay.screen_image(x - half, y - half, side, side)?;
+        match &mut self.texture {
+            Some(texture) => texture.set(image, TextureFilter::Nearest),
+            None => {
+                self.texture = Some(overlay.context().load_texture(
+                    "magnifier",
+                    image,
+                    TextureFilter::Nearest,
+                ))
+            }
+        }
+        self.pointer = Some(overlay.screen_to_points(x, y));
+        // the content under the lens changes without any input
+        overlay.context().request_repaint();
+        Ok(())
+    }
+
+    /// Draws the lens below and right of the pointer, or on the other sides near the edges.
+    pub fn ui(&self, ctx: &Context) {
+        let (Some(pointer), Some(texture)) = (self.pointer, &self.texture) else {
+            return;
+        };
+        let ppp = ctx.pixels_per_point();
+        // less is grabbed near the screen edges, shown at the same zoom
+        let [width, height] = texture.size();
+        let size = vec2(width as f32, height as f32) * self.zoom / ppp;
+        // out of the grabbed square, not to magnify the lens itself
+        let gap = Vec2::splat(f32::from(self.size) / self.zoom / 2. / ppp + 8.);
+        let screen = ctx.input().screen_rect();
+        let mut min = pointer + gap;
+        if min.x + size.x > screen.max.x {
+            min.x = pointer.x - gap.x - size.x;
+        }
+        if min.y + size.y > screen.max.y {
+            min.y = pointer.y - gap.y - size.y;
+        }
+        let rect = Rect::from_min_size(min, size);
+
+        let painter = ctx.layer_painter(LayerId::new(Order::Tooltip, Id::new("magnifier")));
+        let mut mesh = Mesh::with_texture(texture.id());
+        mesh.add_rect_with_uv(
+            rect,
+            Rect::from_min_max(Pos2::ZERO, pos2(1., 1.)),
+            Color32::WHITE,
+        );
+        painter.add(mesh);
+        // the pixel under the pointer
+        let pixel = Rect::from_center_size(rect.center(), Vec2::splat(self.zoom / ppp));
+        painter.rect_stroke(pixel, 0., Stroke::new(1., Color32::WHITE));
+        painter.rect_stroke(rect, 0., Stroke::new(2., Color32::BLACK));
+    }
+}
+
+impl Default for Magnifier {
+    fn default() -> Self {
+        Self::new(4., 200)
+    }
+}
@@ -1,257 +1,294 @@
-mod x11;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use std::{ffi::c_void, iter, time::Duration};
-
-use anyhow::Result;
-use egui::{vec2, Context, Pos2, RawInput};
+use anyhow::{anyhow, bail, Result};
+use clap::builder::BoolishValueParser;
+use clap::{ArgAction, Parser};
 use egui_demo_lib::DemoWindows;
-use egui_wgpu_backend::{RenderPass, ScreenDescriptor};
-use raw_window_handle::{
-    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle, XcbDisplayHandle,
-    XcbWindowHandle,
+use egui_wgpu_x11::{
+    annotation::Annotation,
+    config::Config,
+    egui::{Context, Modifiers},
+    geometry::{Geometry, Viewport},
+    keysyms,
+    monitors::MonitorSelector,
+    scenes::{Scene, SceneApp},
+    wgpu,
+    widgets::WidgetRegistry,
+    x11::XAuth,
+    Attachment, InputShape, Mirrors, Overlay, OverlayApp, OverlayBuilder,
 };
-use x11::{create_overlay_window, raise_if_not_top, xfixes_init};
-use x11rb::{connection::Connection, protocol::xproto::ConnectionExt};
 
-struct MyWindow {
-    pub window: u32,
-    pub visual_id: u32,
-    pub connection: *mut c_void,
-    pub screen: i32,
-    pub width: u32,
-    pub height: u32,
-}
+/// Display the demo application that ships with egui.
+#[derive(Default)]
+struct Demo(DemoWindows);
 
-struct State {
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: (u32, u32),
-    context: Context,
-    raw_input: RawInput,
-    demo_app: DemoWindows,
-    egui_rpass: RenderPass,
+impl OverlayApp for Demo {
+    fn ui(&mut self, ctx: &Context) {
+        self.0.ui(ctx);
+    }
 }
 
-impl State {
-    fn new(window: &MyWindow) -> Self {
-        let size = (window.width, window.height);
-
-        // wgpu stuff
-
-        // The instance is a handle to our GPU
-        // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
-        let surface = unsafe { instance.create_surface(window) };
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        }))
-        .unwrap();
-
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::default(),
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        ))
-        .unwrap();
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
-            width: size.0,
-            height: size.1,
-            present_mode: wgpu::PresentMode::Fifo,
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-        };
-        surface.configure(&device, &config);
-
-        // egui stuff
-
-        let scale_factor = 2.;
-        let raw_input = egui::RawInput {
-            screen_rect: Some(egui::Rect::from_min_size(
-                Pos2::new(50., 50.),
-                vec2(size.0 as f32 - 100., size.1 as f32 - 100.) / scale_factor,
-            )),
-            pixels_per_point: Some(scale_factor),
-            ..Default::default()
-        };
-
-        let surface_format = surface.get_supported_formats(&adapter)[0];
-        // We use the egui_wgpu_backend crate as the render backend.
-        let egui_rpass = RenderPass::new(&device, surface_format, 1);
-
-        // Display the demo application that ships with egui.
-        let demo_app = egui_demo_lib::DemoWindows::default();
-
-        let context = Context::default();
-        // context.set_fonts(_);
-        // context.set_style(_);
+/// A benchmark scene, its frame timings printed every second.
+struct SceneBench {
+    app: SceneApp,
+    printed: Instant,
+}
 
-        Self {
-            surface,
-            device,
-            queue,
-            config,
-            size,
-            context,
-            raw_input,
-            demo_app,
-            egui_rpass,
-        }
+impl OverlayApp for SceneBench {
+    fn ui(&mut self, ctx: &Context) {
+        self.app.ui(ctx);
     }
 
-    pub fn resize(&mut self, new_size: (u32, u32)) {
-        if new_size.0 > 0 && new_size.1 > 0 {
-            self.size = new_size;
-            self.config.width = new_size.0;
-            self.config.height = new_size.1;
-            self.surface.configure(&self.device, &self.config);
+    fn update(&mut self, overlay: &mut Overlay) -> Result<()> {
+        if self.printed.elapsed() >= Duration::from_secs(1) {
+            self.printed = Instant::now();
+            let stats = overlay.frame_stats();
+            println!(
+                "{}: {:.0} fps, cpu {:?}, submit {:?}, present {:?}, {} dropped",
+                self.app.scene(),
+                stats.fps,
+                stats.cpu_time,
+                stats.submit_time,
+                stats.present_latency.unwrap_or_default(),
+                stats.dropped_frames,
+            );
         }
+        Ok(())
     }
+}
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output_frame = self.surface.get_current_texture().unwrap();
-        let output_view = output_frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        // Begin to draw the UI frame.
-        let scale_factor = self.raw_input.pixels_per_point.unwrap_or(1.);
-        self.context.begin_frame(self.raw_input.take());
-        self.raw_input.pixels_per_point = Some(scale_factor);
-
-        // Draw the demo application.
-        self.demo_app.ui(&self.context);
-
-        // End the UI frame. We could now handle the output and draw the UI with the backend.
-        let full_output = self.context.end_frame();
-        let paint_jobs = self.context.tessellate(full_output.shapes);
-
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("encoder"),
-            });
-
-        // Upload all resources for the GPU.
-        let screen_descriptor = ScreenDescriptor {
-            physical_width: self.config.width,
-            physical_height: self.config.height,
-            scale_factor,
-        };
-        let tdelta: egui::TexturesDelta = full_output.textures_delta;
-        self.egui_rpass
-            .add_textures(&self.device, &self.queue, &tdelta)
-            .expect("add texture ok");
-        self.egui_rpass
-            .update_buffers(&self.device, &self.queue, &paint_jobs, &screen_descriptor);
-
-        // Record all render passes.
-        self.egui_rpass
-            .execute(
-                &mut encoder,
-                &output_view,
-                &paint_jobs,
-                &screen_descriptor,
-                Some(wgpu::Color {
-                    r: 0.2,
-                    g: 0.1,
-                    b: 0.3,
-                    a: 0.2,
-                }),
-            )
-            .unwrap();
-        // Submit the commands.
-        self.queue.submit(iter::once(encoder.finish()));
+/// The egui demo windows in an overlay, to try overlays on a window manager.
+///
+/// The flags override `$XDG_CONFIG_HOME/egui-overlay/config.toml`.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// The X server, `$DISPLAY` by default, e.g. `:1` for a nested Xephyr or `host:0` over TCP
+    #[arg(long)]
+    display: Option<String>,
+    /// An Xauthority file with the cookie of the display, e.g. written by `xauth -f`
+    #[arg(long)]
+    xauthority: Option<PathBuf>,
+    /// `primary`, an index, or an output name, e.g. `HDMI-1`
+    #[arg(long)]
+    monitor: Option<MonitorSelector>,
+    /// X geometry of the overlay, e.g. `800x600+100+50`
+    #[arg(long, value_parser = parse_geometry)]
+    geometry: Option<Geometry>,
+    /// e.g. `1.5`, detected from `Xft.dpi` or the monitor otherwise
+    #[arg(long)]
+    scale: Option<f32>,
+    /// Let all input through to the windows underneath
+    #[arg(long)]
+    passthrough: bool,
+    /// `vulkan`, `gl`, or both comma separated
+    #[arg(long, value_parser = parse_backends)]
+    backend: Option<wgpu::Backends>,
+    /// Frame rate cap, 0 for none
+    #[arg(long)]
+    fps: Option<f32>,
+    /// `off` leaves the stacking to the window manager
+    #[arg(
+        long,
+        action = ArgAction::Set,
+        value_parser = BoolishValueParser::new(),
+        default_value = "on",
+        value_name = "on|off"
+    )]
+    always_on_top: bool,
+    /// Draw on the screen instead, toggled with F9
+    #[arg(long)]
+    annotate: bool,
+    /// Show the overlay on the other monitors too
+    #[arg(long)]
+    mirror: bool,
+    /// Stick to another window, `active` or a window id from `xwininfo`, e.g. `0x3a00007`
+    #[arg(long, value_parser = parse_attachment)]
+    attach: Option<Attachment>,
+    /// Where the demo goes in the overlay: pixels from its edges, e.g. `50`, or the size of a
+    /// letterbox in its middle, e.g. `1280x720`
+    #[arg(long, value_parser = parse_viewport, default_value = "50")]
+    viewport: Viewport,
+    /// Zoom of the demo, on top of the scale factor
+    #[arg(long, default_value_t = 1.)]
+    zoom: f32,
+    /// Draw `text`, `widgets`, `shapes` or `images` instead of the demo, printing the frame
+    /// timings every second, e.g. with `--fps 0`
+    #[arg(long)]
+    bench_scene: Option<Scene>,
+}
 
-        // Redraw egui
-        output_frame.present();
+/// `WxH+X+Y`, the offsets defaulting to 0, from the top-left corner of the screen
+fn parse_geometry(geometry: &str) -> Result<Geometry> {
+    let invalid = || anyhow!("expected WxH+X+Y, got {:?}", geometry);
+    let (size, offset) = match geometry.split_once('+') {
+        Some((size, offset)) => (size, offset),
+        None => (geometry, "0+0"),
+    };
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let (x, y) = offset.split_once('+').ok_or_else(invalid)?;
+    let [x, y, width, height] = [x, y, width, height].map(|n| n.parse::<u16>());
+    let (Ok(x), Ok(y), Ok(width), Ok(height)) = (x, y, width, height) else {
+        return Err(invalid());
+    };
+    if width == 0 || height == 0 {
+        bail!("the overlay can't be empty, got {:?}", geometry);
+    }
+    Ok(Geometry::absolute(x, y, width, height))
+}
 
-        self.egui_rpass
-            .remove_textures(tdelta)
-            .expect("remove texture ok");
+/// `N` pixels inset, or `WxH` centered
+fn parse_viewport(viewport: &str) -> Result<Viewport> {
+    let invalid = || anyhow!("expected N or WxH, got {:?}", viewport);
+    match viewport.split_once('x') {
+        Some((width, height)) => Ok(Viewport::centered(
+            width.parse().map_err(|_| invalid())?,
+            height.parse().map_err(|_| invalid())?,
+        )),
+        None => Ok(Viewport::inset(viewport.parse().map_err(|_| invalid())?)),
+    }
+}
 
-        Ok(())
+fn parse_attachment(target: &str) -> Result<Attachment> {
+    if target == "active" {
+        return Ok(Attachment::active());
     }
+    let window = match target.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => target.parse(),
+    };
+    let window = window.map_err(|_| anyhow!("expected active or a window id, got {:?}", target))?;
+    Ok(Attachment::over(window))
 }
 
-unsafe impl HasRawWindowHandle for MyWindow {
-    fn raw_window_handle(&self) -> RawWindowHandle {
-        let mut handle = XcbWindowHandle::empty();
-        handle.visual_id = self.visual_id;
-        handle.window = self.window;
-        RawWindowHandle::Xcb(handle)
+fn parse_backends(backends: &str) -> Result<wgpu::Backends> {
+    let parsed = wgpu::util::parse_backends_from_comma_list(&backends.to_lowercase());
+    if parsed.is_empty() {
+        return Err(anyhow!("unknown backend {:?}", backends));
     }
+    Ok(parsed)
 }
-unsafe impl HasRawDisplayHandle for MyWindow {
-    fn raw_display_handle(&self) -> RawDisplayHandle {
-        let mut handle = XcbDisplayHandle::empty();
-        handle.connection = self.connection;
-        handle.screen = self.screen;
-        RawDisplayHandle::Xcb(handle)
+
+/// on the X server of the flags
+fn connection(
+    mut builder: OverlayBuilder,
+    display: Option<&str>,
+    xauthority: Option<&Path>,
+) -> OverlayBuilder {
+    if let Some(display) = display {
+        builder = builder.display(display);
+    }
+    if let Some(path) = xauthority {
+        builder = builder.xauth(XAuth::File(path.to_owned()));
     }
+    builder
 }
 
 fn main() -> Result<()> {
-    let (conn, screen_num) = x11rb::xcb_ffi::XCBConnection::connect(None)?;
-
-    xfixes_init(&conn);
-
-    let screen = &conn.setup().roots[screen_num];
-
-    let win_id = create_overlay_window(
-        &conn,
-        screen,
-        100,
-        100,
-        screen.width_in_pixels - 200,
-        screen.height_in_pixels - 200,
-    )?;
-
-    conn.map_window(win_id)?;
-    conn.flush()?;
+    // `RUST_LOG=egui_wgpu_x11=debug` shows the overlay logs
+    env_logger::init();
+    let args = Args::parse();
+    let (display, xauthority) = (args.display.as_deref(), args.xauthority.as_deref());
+
+    let mut config = Config::load()?;
+    config.monitor = args.monitor.or(config.monitor);
+    config.scale_factor = args.scale.or(config.scale_factor);
+    config.backend = args.backend.or(config.backend);
+    config.max_fps = args.fps.or(config.max_fps);
+    if args.passthrough {
+        config.input_shape = Some(InputShape::Passthrough);
+    }
 
-    let window = MyWindow {
-        window: win_id,
-        visual_id: screen.root_visual,
-        connection: conn.get_raw_xcb_connection(),
-        screen: screen_num as i32,
-        width: screen.width_in_pixels as u32 - 200,
-        height: screen.height_in_pixels as u32 - 200,
-    };
+    if args.annotate {
+        let mut builder = connection(OverlayBuilder::fullscreen_overlay(), display, xauthority);
+        if args.mirror {
+            // drawn on one monitor, copied on the others
+            let monitor = config.monitor.clone().unwrap_or_default();
+            builder = builder.monitor(monitor).mirrors(Mirrors::All);
+        } else if let Some(monitor) = config.monitor {
+            builder = builder.monitor(monitor);
+        }
+        let mut overlay = builder.build()?;
+        // like gromit-mpx
+        Annotation::toggle_with(&mut overlay, Modifiers::NONE, keysyms::KEY_F9)?;
+        return overlay.run(Annotation::default());
+    }
 
-    let mut state = State::new(&window);
+    let mut builder = connection(Overlay::builder(), display, xauthority)
+        .input_shape(InputShape::Content)
+        .always_on_top(args.always_on_top)
+        .viewport(args.viewport.scale(args.zoom));
+    if args.mirror {
+        builder = builder.mirrors(Mirrors::All);
+    }
+    // tweaked live
+    let mut builder = config.apply(builder).watch_config(Config::default_path()?);
+    if let Some(geometry) = args.geometry {
+        builder = builder.geometry(geometry);
+    }
+    if let Some(attachment) = args.attach {
+        builder = builder.attach(attachment);
+    }
+    if let Some(scene) = args.bench_scene {
+        return builder.run(SceneBench {
+            app: scene.app(),
+            printed: Instant::now(),
+        });
+    }
+    // a HUD instead of the demo windows when the config lists widgets
+    if !config.widgets.is_empty() {
+        let mut overlay = builder.build()?;
+        let hud = WidgetRegistry::builtin().build(&config.widgets, &mut overlay)?;
+        return overlay.run(hud);
+    }
+    // the demo windows open where they were left
+    #[cfg(feature = "persistence")]
+    let builder = builder.persist_memory("demo.ron");
+    builder.run(Demo::default())
+}
 
-    const STACK_CHECK_DELAY: u32 = 30;
-    let mut i = 1;
-    loop {
-        match state.render() {
-            Ok(_) => {}
-            // Reconfigure the surface if it's lost or outdated
-            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                state.resize(state.size)
-            }
-            // The system is out of memory, we should probably quit
-            Err(wgpu::SurfaceError::OutOfMemory) => break,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometry() {
+        assert_eq!(
+            parse_geometry("800x600+10+20").unwrap(),
+            Geometry::absolute(10, 20, 800, 600)
+        );
+        // at the top-left corner
+        assert_eq!(
+            parse_geometry("800x600").unwrap(),
+            Geometry::absolute(0, 0, 800, 600)
+        );
+        assert_eq!(
+            parse_geometry("65535x1+0+0").unwrap(),
+            Geometry::absolute(0, 0, 65535, 1)
+        );
+    }
 
-            Err(wgpu::SurfaceError::Timeout) => println!("Surface timeout"),
+    #[test]
+    fn invalid_geometry() {
+        for geometry in [
+            // offsets are from the top-left corner
+            "800x600+-10+20",
+            "800x600-10-20",
+            // both offsets or none
+            "800x600+10",
+            "800x600+10+20+30",
+            "0x600+10+20",
+            "800x0",
+            "65536x600",
+            "800x600+70000+0",
+            "",
+            "800",
+            "800x",
+            "axb+c+d",
+            " 800x600",
+        ] {
+            assert!(parse_geometry(geometry).is_err(), "{:?}", geometry);
         }
-        if let Some(event) = conn.poll_for_event().unwrap() {
-            println!("Event: {:?}", event);
-        } else if i == 0 {
-            raise_if_not_top(&conn, screen.root, win_id)?;
-        }
-
-        i = (i + 1) % STACK_CHECK_DELAY;
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
-
-    Ok(())
 }
@@ -0,0 +1,82 @@
+use x11rb::protocol::xproto::Rectangle;
+
+use crate::geometry::{Geometry, Length, Margin};
+use crate::monitors::{Monitor, MonitorSelector};
+
+/// The monitors showing a copy of an overlay besides its own, see
+/// [`OverlayBuilder::mirrors`](crate::OverlayBuilder::mirrors).
+///
+/// The UI runs and is tessellated once per frame, then drawn on each monitor at its own scale
+/// factor, e.g. for presenter tools showing the same annotations on every output. Copies are
+/// placed like the overlay in their monitor and let input through, only the overlay itself
+/// takes it. Overlays spanning the whole screen rather than one monitor, see
+/// [`OverlayBuilder::monitor`](crate::OverlayBuilder::monitor), cover the mirrors' monitors
+/// already.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Mirrors {
+    #[default]
+    None,
+    /// Every other monitor, the ones plugged in later included.
+    All,
+    /// The other monitors matching one of the selectors.
+    Only(Vec<MonitorSelector>),
+}
+
+impl Mirrors {
+    /// the monitors to copy an overlay placed at `own` on, not the one holding it
+    pub(crate) fn select<'a>(&self, monitors: &'a [Monitor], own: Rectangle) -> Vec<&'a Monitor> {
+        let center = (
+            i32::from(own.x) + i32::from(own.width) / 2,
+            i32::from(own.y) + i32::from(own.height) / 2,
+        );
+        let others = monitors
+            .iter()
+            .filter(|monitor| !contains(monitor.rect(), center));
+        match self {
+            Self::None => Vec::new(),
+            Self::All => others.collect(),
+            Self::Only(selectors) => others
+                .filter(|monitor| {
+                    selectors
+                        .iter()
+                        .any(|selector| selector.select(monitors) == Some(*monitor))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A window of another monitor drawn with the frames of the overlay.
+pub(crate) struct MirrorWindow {
+    pub window: u32,
+    pub rect: Rectangle,
+    pub scale_factor: f32,
+}
+
+/// where the copy of an overlay placed by `geometry` at `placed` goes in `monitor`, `scale`
+/// times larger
+pub(crate) fn mirror_rect(
+    geometry: &Geometry,
+    placed: Rectangle,
+    scale: f32,
+    monitor: Rectangle,
+) -> Rectangle {
+    let scaled = |pixels: u16| (f32::from(pixels) * scale).round().min(f32::from(u16::MAX)) as u16;
+    Geometry {
+        width: Length::Pixels(scaled(placed.width)),
+        height: Length::Pixels(scaled(placed.height)),
+        margin: Margin {
+            left: scaled(geometry.margin.left),
+            top: scaled(geometry.margin.top),
+            right: scaled(geometry.margin.right),
+            bottom: scaled(geometry.margin.bottom),
+        },
+        ..*geometry
+    }
+    .place(monitor)
+}
+
+fn contains(rect: Rectangle, (x, y): (i32, i32)) -> bool {
+    let (left, top) = (i32::from(rect.x), i32::from(rect.y));
+    x >= left && y >= top && x < left + i32::from(rect.width) && y < top + i32::from(rect.height)
+}
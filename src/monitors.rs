@@ -0,0 +1,116 @@
+//! Monitor geometries from XRandR.
+
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{ConnectionExt as _, NotifyMask};
+use x11rb::protocol::xproto::{ConnectionExt as _, Rectangle, Window};
+
+/// A monitor, as laid out in the X screen.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Monitor {
+    /// Output name, e.g. `DP-1`.
+    pub name: String,
+    pub primary: bool,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    /// Physical size, 0 when unknown.
+    pub width_mm: u32,
+    pub height_mm: u32,
+}
+
+/// Which monitor the overlay spans.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum MonitorSelector {
+    /// The primary monitor, or the first one if none is primary.
+    #[default]
+    Primary,
+    /// The n-th monitor, in XRandR order.
+    Index(usize),
+    /// The monitor with this output name, e.g. `HDMI-1`.
+    Name(String),
+}
+
+impl Monitor {
+    pub(crate) fn rect(&self) -> Rectangle {
+        Rectangle {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+impl MonitorSelector {
+    pub fn select<'a>(&self, monitors: &'a [Monitor]) -> Option<&'a Monitor> {
+        match self {
+            Self::Primary => monitors
+                .iter()
+                .find(|m| m.primary)
+                .or_else(|| monitors.first()),
+            Self::Index(i) => monitors.get(*i),
+            Self::Name(name) => monitors.iter().find(|m| &m.name == name),
+        }
+    }
+}
+
+impl FromStr for MonitorSelector {
+    type Err = Infallible;
+
+    /// `primary`, an index, or an output name.
+    fn from_str(monitor: &str) -> Result<Self, Infallible> {
+        Ok(match monitor.parse() {
+            Ok(index) => Self::Index(index),
+            Err(_) if monitor.eq_ignore_ascii_case("primary") => Self::Primary,
+            Err(_) => Self::Name(monitor.to_owned()),
+        })
+    }
+}
+
+/// also subscribes `root` to monitor changes: (un)plugging, mode or layout changes
+pub(crate) fn randr_init<Conn>(conn: &Conn, root: Window) -> Result<()>
+where
+    Conn: Connection,
+{
+    conn.randr_query_version(1, 5)?.reply()?;
+    conn.randr_select_input(
+        root,
+        NotifyMask::SCREEN_CHANGE | NotifyMask::CRTC_CHANGE | NotifyMask::OUTPUT_CHANGE,
+    )?;
+    Ok(())
+}
+
+/// The active monitors, requires XRandR 1.5.
+pub fn monitors<Conn>(conn: &Conn, root: Window) -> Result<Vec<Monitor>>
+where
+    Conn: Connection,
+{
+    let infos = conn.randr_get_monitors(root, true)?.reply()?.monitors;
+    // send all the name requests before waiting for any reply
+    let names = infos
+        .iter()
+        .map(|info| conn.get_atom_name(info.name))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    infos
+        .into_iter()
+        .zip(names)
+        .map(|(info, name)| {
+            Ok(Monitor {
+                name: String::from_utf8_lossy(&name.reply()?.name).into_owned(),
+                primary: info.primary,
+                x: info.x,
+                y: info.y,
+                width: info.width,
+                height: info.height,
+                width_mm: info.width_in_millimeters,
+                height_mm: info.height_in_millimeters,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, EventMask, Visibility, Window};
+use x11rb::protocol::Event;
+
+use crate::x11::{intern_atoms, select_more_events};
+
+/// `_NET_WM_DESKTOP` of windows shown on all the workspaces
+const ALL_DESKTOPS: u32 = 0xffff_ffff;
+
+/// Whether anyone can see the overlay, frames being skipped otherwise: unmapped by the window
+/// manager, e.g. minimized, on another workspace, or covered by other windows.
+///
+/// Compositing managers redirect the windows offscreen, the X server then reports them
+/// unobscured whatever covers them.
+pub(crate) struct Occlusion {
+    root: Window,
+    win_id: Window,
+    net_current_desktop: Atom,
+    net_wm_desktop: Atom,
+    /// by the X server, the overlay unmapping it itself being known from its visibility
+    mapped: bool,
+    obscured: bool,
+    pub current_desktop: Option<u32>,
+    /// of the overlay, `None` for unmanaged overlays, shown everywhere
+    desktop: Option<u32>,
+}
+
+impl Occlusion {
+    pub fn new<Conn>(conn: &Conn, root: Window, win_id: Window, mapped: bool) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let [net_current_desktop, net_wm_desktop] =
+            intern_atoms(conn, ["_NET_CURRENT_DESKTOP", "_NET_WM_DESKTOP"])?;
+        select_more_events(conn, root, EventMask::PROPERTY_CHANGE)?;
+        Ok(Self {
+            root,
+            win_id,
+            net_current_desktop,
+            net_wm_desktop,
+            mapped,
+            obscured: false,
+            current_desktop: desktop(conn, root, net_current_desktop)?,
+            desktop: desktop(conn, win_id, net_wm_desktop)?,
+        })
+    }
+
+    /// Whether nobody can see the overlay.
+    pub fn hidden(&self) -> bool {
+        let away = match (self.desktop, self.current_desktop) {
+            (Some(ALL_DESKTOPS), _) => false,
+            (Some(desktop), Some(current)) => desktop != current,
+            _ => false,
+        };
+        !self.mapped || self.obscured || away
+    }
+
+    /// Follows the mapping, visibility and workspaces. Returns whether the overlay was hidden
+    /// and can now be seen.
+    pub fn handle_event<Conn>(&mut self, conn: &Conn, event: &Event) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        let hidden = self.hidden();
+        match event {
+            Event::MapNotify(e) if e.window == self.win_id => self.mapped = true,
+            Event::UnmapNotify(e) if e.window == self.win_id => self.mapped = false,
+            Event::VisibilityNotify(e) if e.window == self.win_id => {
+                self.obscured = e.state == Visibility::FULLY_OBSCURED
+            }
+            Event::PropertyNotify(e)
+                if e.window == self.root && e.atom == self.net_current_desktop =>
+            {
+                self.current_desktop = desktop(conn, self.root, self.net_current_desktop)?
+            }
+            Event::PropertyNotify(e)
+                if e.window == self.win_id && e.atom == self.net_wm_desktop =>
+            {
+                self.desktop = desktop(conn, self.win_id, self.net_wm_desktop)?
+            }
+            _ => {}
+        }
+        Ok(hidden && !self.hidden())
+    }
+}
+
+/// a workspace number property of `window`
+fn desktop<Conn>(conn: &Conn, window: Window, property: Atom) -> Result<Option<u32>>
+where
+    Conn: Connection,
+{
+    let reply = conn
+        .get_property(false, window, property, AtomEnum::CARDINAL, 0, 1)?
+        .reply()?;
+    Ok(reply.value32().and_then(|mut values| values.next()))
+}
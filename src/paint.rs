@@ -0,0 +1,139 @@
+//! Custom wgpu rendering inside egui UIs: into textures shown as images, or in paint callbacks.
+
+use egui::{pos2, Color32, Mesh, PaintCallback, PaintCallbackInfo, Rect, Shape, TextureId};
+
+/// A texture the app renders to with wgpu, shown by egui, e.g. with [`egui::Ui::image`].
+///
+/// Created by [`crate::Overlay::create_user_texture`], on the device of the overlay.
+pub struct UserTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    id: TextureId,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+}
+
+impl UserTexture {
+    /// `register` makes the view drawable by egui
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        register: impl FnOnce(&wgpu::TextureView) -> anyhow::Result<TextureId>,
+    ) -> anyhow::Result<Self> {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("user texture"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let id = register(&view)?;
+        Ok(Self {
+            texture,
+            view,
+            id,
+            size,
+            format,
+        })
+    }
+
+    /// To show it, e.g. `ui.image(texture.id(), size)`.
+    pub fn id(&self) -> TextureId {
+        self.id
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// To render to, as a color attachment.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// In pixels.
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// a quad showing the texture over `rect`
+    pub(crate) fn mesh(&self, rect: Rect) -> Mesh {
+        let mut mesh = Mesh::with_texture(self.id);
+        mesh.add_rect_with_uv(
+            rect,
+            Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+            Color32::WHITE,
+        );
+        mesh
+    }
+
+    /// clears it to transparent
+    pub(crate) fn clear(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("user texture clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+    }
+}
+
+/// What a [`paint_callback`] draws with.
+pub struct CallbackContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    /// Submitted before egui draws the frame.
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    /// Writes buffers through `encoder`, e.g. uniforms, from staging buffers reused across
+    /// frames rather than allocated by each write.
+    pub belt: &'a mut wgpu::util::StagingBelt,
+    /// Covers the callback rect, cleared to transparent: render passes load it rather than
+    /// clear it.
+    pub view: &'a wgpu::TextureView,
+    /// Of `view`, for the render pipelines.
+    pub format: wgpu::TextureFormat,
+    /// Size of `view`, in pixels.
+    pub size: (u32, u32),
+    pub info: PaintCallbackInfo,
+}
+
+/// the closure of a [`paint_callback`], found back by the overlay among the egui primitives
+pub(crate) struct WgpuCallback(pub Box<dyn Fn(&mut CallbackContext) + Send + Sync>);
+
+/// Paints `rect` with wgpu, e.g. a 3D view, when the frame is drawn. Added to a UI with
+/// `ui.painter().add(paint_callback(rect, ...))`.
+///
+/// The callback renders into a texture the size of `rect`, egui then draws it in place, in
+/// the order of the shapes, clipped like them.
+pub fn paint_callback(
+    rect: Rect,
+    paint: impl Fn(&mut CallbackContext) + Send + Sync + 'static,
+) -> Shape {
+    Shape::Callback(PaintCallback {
+        rect,
+        callback: std::sync::Arc::new(WgpuCallback(Box::new(paint))),
+    })
+}
+
+/// the format of the textures painted by the callbacks, as egui's own
+pub(crate) const CALLBACK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
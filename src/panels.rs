@@ -0,0 +1,434 @@
+//! Bars, side panels and corner HUDs placed in the work area of the desktop, next to its own
+//! panels rather than over them, whatever covers the overlay window.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::panels::{CornerHud, TopBar};
+//! use egui_wgpu_x11::{egui, Overlay, OverlayApp};
+//!
+//! struct Hud;
+//!
+//! impl OverlayApp for Hud {
+//!     fn ui(&mut self, ctx: &egui::Context) {
+//!         TopBar::new("bar").show(ctx, |ui| ui.label("12:00"));
+//!         CornerHud::new("fps", egui::Align2::RIGHT_BOTTOM).show(ctx, |ui| ui.label("60 fps"));
+//!     }
+//! }
+//!
+//! Overlay::fullscreen_overlay()?.run(Hud)?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! The overlay follows `_NET_WORKAREA` of the current desktop, as set by EWMH window managers,
+//! and shares it with the helpers through the egui context, see [`work_area`]. Without window
+//! manager, the helpers use the whole egui screen.
+
+use std::hash::Hash;
+
+use anyhow::Result;
+use egui::style::Margin;
+use egui::{
+    Align, Align2, Context, Frame, Id, InnerResponse, Pos2, Rect, Rounding, Style, Ui, Vec2,
+};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{Atom, AtomEnum, ConnectionExt as _, Rectangle, Window};
+use x11rb::protocol::Event;
+
+/// The work area of the desktop in the egui coordinates of the overlay, the part of the screen
+/// no panel reserves, within the egui screen.
+pub fn work_area(ctx: &Context) -> Rect {
+    let screen = ctx.input().screen_rect();
+    match ctx.data().get_temp::<Rect>(work_area_id()) {
+        Some(work_area) => work_area.intersect(screen),
+        None => screen,
+    }
+}
+
+/// shares the work area of the overlay with the helpers, `None` for the whole screen
+pub(crate) fn set_work_area(ctx: &Context, work_area: Option<Rect>) {
+    match work_area {
+        Some(work_area) => ctx.data().insert_temp(work_area_id(), work_area),
+        None => ctx.data().remove::<Rect>(work_area_id()),
+    }
+}
+
+fn work_area_id() -> Id {
+    Id::new("overlay work area")
+}
+
+/// A bar along the top of the work area, under the panels of the desktop, e.g. with a clock
+/// and meters.
+pub struct TopBar {
+    id: Id,
+    height: f32,
+    frame: Option<Frame>,
+}
+
+impl TopBar {
+    /// 24 points high, in a panel frame.
+    pub fn new(id: impl Hash) -> Self {
+        Self {
+            id: Id::new(id),
+            height: 24.,
+            frame: None,
+        }
+    }
+
+    pub fn height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn frame(mut self, frame: Frame) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    /// Where the bar goes in `work_area`.
+    pub fn rect(&self, work_area: Rect) -> Rect {
+        Rect::from_min_size(
+            work_area.min,
+            Vec2::new(work_area.width(), self.height.min(work_area.height())),
+        )
+    }
+
+    pub fn show<R>(
+        self,
+        ctx: &Context,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> InnerResponse<R> {
+        let rect = self.rect(work_area(ctx));
+        show_in(ctx, self.id, rect, self.frame, |ui| {
+            ui.horizontal_centered(add_contents).inner
+        })
+    }
+}
+
+/// A panel along the left or right side of the work area, as high as it is, e.g. with a
+/// player list or notes.
+pub struct SidePanelOverlay {
+    id: Id,
+    side: Align,
+    width: f32,
+    frame: Option<Frame>,
+}
+
+impl SidePanelOverlay {
+    /// 200 points wide, in a panel frame.
+    pub fn left(id: impl Hash) -> Self {
+        Self::new(id, Align::Min)
+    }
+
+    /// 200 points wide, in a panel frame.
+    pub fn right(id: impl Hash) -> Self {
+        Self::new(id, Align::Max)
+    }
+
+    fn new(id: impl Hash, side: Align) -> Self {
+        Self {
+            id: Id::new(id),
+            side,
+            width: 200.,
+            frame: None,
+        }
+    }
+
+    pub fn width(mut self, width: f32) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn frame(mut self, frame: Frame) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    /// Where the panel goes in `work_area`.
+    pub fn rect(&self, work_area: Rect) -> Rect {
+        let width = self.width.min(work_area.width());
+        let left = match self.side {
+            Align::Max => work_area.max.x - width,
+            _ => work_area.min.x,
+        };
+        Rect::from_min_size(
+            Pos2::new(left, work_area.min.y),
+            Vec2::new(width, work_area.height()),
+        )
+    }
+
+    pub fn show<R>(
+        self,
+        ctx: &Context,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> InnerResponse<R> {
+        let rect = self.rect(work_area(ctx));
+        show_in(ctx, self.id, rect, self.frame, add_contents)
+    }
+}
+
+/// A HUD sized by its contents in a corner of the work area, or the middle of one of its
+/// edges, e.g. a frame rate counter.
+pub struct CornerHud {
+    id: Id,
+    corner: Align2,
+    margin: Vec2,
+    frame: Option<Frame>,
+}
+
+impl CornerHud {
+    /// 8 points from the work area edges, in a popup frame.
+    pub fn new(id: impl Hash, corner: Align2) -> Self {
+        Self {
+            id: Id::new(id),
+            corner,
+            margin: Vec2::splat(8.),
+            frame: None,
+        }
+    }
+
+    pub fn margin(mut self, margin: impl Into<Vec2>) -> Self {
+        self.margin = margin.into();
+        self
+    }
+
+    pub fn frame(mut self, frame: Frame) -> Self {
+        self.frame = Some(frame);
+        self
+    }
+
+    pub fn show<R>(
+        self,
+        ctx: &Context,
+        add_contents: impl FnOnce(&mut Ui) -> R,
+    ) -> InnerResponse<R> {
+        let offset = self.anchor_offset(work_area(ctx), ctx.input().screen_rect());
+        egui::Area::new(self.id)
+            .anchor(self.corner, offset)
+            .show(ctx, |ui| {
+                let frame = self.frame.unwrap_or_else(|| Frame::popup(ui.style()));
+                frame.show(ui, add_contents).inner
+            })
+    }
+
+    /// egui anchors areas to the screen, offset to the work area
+    fn anchor_offset(&self, work_area: Rect, screen: Rect) -> Vec2 {
+        let offset =
+            |align: Align, margin: f32, work: (f32, f32, f32), screen: (f32, f32, f32)| match align
+            {
+                Align::Min => work.0 - screen.0 + margin,
+                Align::Center => work.1 - screen.1,
+                Align::Max => work.2 - screen.2 - margin,
+            };
+        Vec2::new(
+            offset(
+                self.corner.x(),
+                self.margin.x,
+                (work_area.min.x, work_area.center().x, work_area.max.x),
+                (screen.min.x, screen.center().x, screen.max.x),
+            ),
+            offset(
+                self.corner.y(),
+                self.margin.y,
+                (work_area.min.y, work_area.center().y, work_area.max.y),
+                (screen.min.y, screen.center().y, screen.max.y),
+            ),
+        )
+    }
+}
+
+/// `add_contents` in `frame` filling `rect`
+fn show_in<R>(
+    ctx: &Context,
+    id: Id,
+    rect: Rect,
+    frame: Option<Frame>,
+    add_contents: impl FnOnce(&mut Ui) -> R,
+) -> InnerResponse<R> {
+    egui::Area::new(id).fixed_pos(rect.min).show(ctx, |ui| {
+        let frame = frame.unwrap_or_else(|| panel_frame(ui.style()));
+        let inner_size = rect.size() - frame.inner_margin.sum() - frame.outer_margin.sum();
+        frame
+            .show(ui, |ui| {
+                ui.set_min_size(inner_size.max(Vec2::ZERO));
+                ui.set_max_size(inner_size.max(Vec2::ZERO));
+                add_contents(ui)
+            })
+            .inner
+    })
+}
+
+/// like the frame of egui's own panels
+fn panel_frame(style: &Style) -> Frame {
+    Frame {
+        inner_margin: Margin::symmetric(8., 2.),
+        rounding: Rounding::none(),
+        fill: style.visuals.window_fill(),
+        stroke: style.visuals.window_stroke(),
+        ..Default::default()
+    }
+}
+
+/// Follows `_NET_WORKAREA` of the current desktop, in pixels on the screen.
+pub(crate) struct WorkArea {
+    root: Window,
+    net_workarea: Atom,
+    pub rect: Option<Rectangle>,
+}
+
+impl WorkArea {
+    /// Needs the property changes of the root window selected.
+    pub fn new<Conn>(conn: &Conn, root: Window, desktop: Option<u32>) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let net_workarea = conn.intern_atom(false, b"_NET_WORKAREA")?.reply()?.atom;
+        let mut work_area = Self {
+            root,
+            net_workarea,
+            rect: None,
+        };
+        work_area.update(conn, desktop)?;
+        Ok(work_area)
+    }
+
+    /// Returns whether `rect` changed.
+    pub fn handle_event<Conn>(
+        &mut self,
+        conn: &Conn,
+        event: &Event,
+        desktop: Option<u32>,
+    ) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        match event {
+            Event::PropertyNotify(e) if e.window == self.root && e.atom == self.net_workarea => {
+                self.update(conn, desktop)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Reads the work area of `desktop` again, returns whether it changed.
+    pub fn update<Conn>(&mut self, conn: &Conn, desktop: Option<u32>) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        let reply = conn
+            .get_property(
+                false,
+                self.root,
+                self.net_workarea,
+                AtomEnum::CARDINAL,
+                0,
+                u32::MAX,
+            )?
+            .reply()?;
+        let areas: Vec<u32> = reply.value32().map(Iterator::collect).unwrap_or_default();
+        let rect = desktop_work_area(&areas, desktop.unwrap_or(0));
+        let changed = rect != self.rect;
+        self.rect = rect;
+        Ok(changed)
+    }
+}
+
+/// the work area of `desktop` in `_NET_WORKAREA`: x, y, width and height of each desktop
+fn desktop_work_area(areas: &[u32], desktop: u32) -> Option<Rectangle> {
+    let index = 4 * desktop as usize;
+    areas.get(index..index + 4).map(|area| Rectangle {
+        x: area[0].min(i16::MAX as u32) as i16,
+        y: area[1].min(i16::MAX as u32) as i16,
+        width: area[2].min(u16::MAX.into()) as u16,
+        height: area[3].min(u16::MAX.into()) as u16,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{pos2, vec2, RawInput};
+
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect::from_min_size(pos2(x, y), vec2(width, height))
+    }
+
+    /// runs `f` in a frame of an 800×600 screen
+    fn frame(f: impl FnOnce(&Context)) {
+        let input = RawInput {
+            screen_rect: Some(rect(0., 0., 800., 600.)),
+            ..Default::default()
+        };
+        let _ = Context::default().run(input, f);
+    }
+
+    #[test]
+    fn work_area_within_the_screen() {
+        frame(|ctx| {
+            assert_eq!(work_area(ctx), rect(0., 0., 800., 600.));
+            set_work_area(ctx, Some(rect(0., 30., 1000., 570.)));
+            assert_eq!(work_area(ctx), rect(0., 30., 800., 570.));
+            set_work_area(ctx, None);
+            assert_eq!(work_area(ctx), rect(0., 0., 800., 600.));
+        });
+    }
+
+    #[test]
+    fn bars_and_side_panels() {
+        let work_area = rect(50., 30., 750., 570.);
+        assert_eq!(
+            TopBar::new("bar").rect(work_area),
+            rect(50., 30., 750., 24.)
+        );
+        assert_eq!(TopBar::new("bar").height(1000.).rect(work_area), work_area);
+        assert_eq!(
+            SidePanelOverlay::left("notes").rect(work_area),
+            rect(50., 30., 200., 570.)
+        );
+        assert_eq!(
+            SidePanelOverlay::right("notes").width(100.).rect(work_area),
+            rect(700., 30., 100., 570.)
+        );
+        assert_eq!(
+            SidePanelOverlay::right("notes")
+                .width(1000.)
+                .rect(work_area),
+            work_area
+        );
+    }
+
+    #[test]
+    fn corner_huds_in_the_work_area() {
+        let screen = rect(0., 0., 800., 600.);
+        let work_area = rect(50., 30., 700., 540.);
+        let offset = |corner| CornerHud::new("hud", corner).anchor_offset(work_area, screen);
+        assert_eq!(offset(Align2::LEFT_TOP), vec2(58., 38.));
+        assert_eq!(offset(Align2::RIGHT_BOTTOM), vec2(-58., -38.));
+        assert_eq!(offset(Align2::CENTER_TOP), vec2(0., 38.));
+        assert_eq!(
+            CornerHud::new("hud", Align2::RIGHT_TOP)
+                .margin([0., 4.])
+                .anchor_offset(screen, screen),
+            vec2(0., 4.)
+        );
+    }
+
+    #[test]
+    fn desktop_work_areas() {
+        let areas = [0, 30, 1920, 1050, 0, 0, 1920, 1080];
+        let rect = |x, y, width, height| Rectangle {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert_eq!(desktop_work_area(&areas, 0), Some(rect(0, 30, 1920, 1050)));
+        assert_eq!(desktop_work_area(&areas, 1), Some(rect(0, 0, 1920, 1080)));
+        assert_eq!(desktop_work_area(&areas, 2), None);
+        assert_eq!(desktop_work_area(&[], 0), None);
+        // clamped to the protocol types
+        assert_eq!(
+            desktop_work_area(&[1 << 16, 0, 1 << 17, 10], 0),
+            Some(rect(i16::MAX, 0, u16::MAX, 10))
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use egui::{Align2, Context, RichText, Vec2};
+use tracing::error;
+
+use crate::OverlayApp;
+
+/// Runs the UI of the app, showing the message of its panics in a window instead of
+/// unwinding out of the overlay. The app is poisoned until retried from that window, its
+/// state may be half updated.
+pub(crate) struct PanicGuard {
+    /// off for headless overlays, whose tests should fail on panics
+    pub enabled: bool,
+    /// of the last panic, until retried
+    pub message: Option<String>,
+}
+
+impl PanicGuard {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            message: None,
+        }
+    }
+
+    pub fn ui(&mut self, app: &mut dyn OverlayApp, ctx: &Context) {
+        if !self.enabled {
+            app.ui(ctx);
+            return;
+        }
+        if self.message.is_none() {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| app.ui(ctx))) {
+                let message = panic_message(payload.as_ref());
+                error!("the overlay app panicked: {}", message);
+                self.message = Some(message);
+            }
+        }
+        let Some(message) = &self.message else {
+            return;
+        };
+
+        let mut retry = false;
+        egui::Window::new("The overlay app panicked")
+            .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let color = ui.visuals().error_fg_color;
+                ui.label(RichText::new(message).monospace().color(color));
+                ui.label("Its state may be inconsistent, it isn't run until retried.");
+                retry = ui.button("Retry").clicked();
+            });
+        if retry {
+            self.message = None;
+            ctx.request_repaint();
+        }
+    }
+}
+
+/// the payload of `panic!`, a `&str` or a `String` when formatted
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "no message".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// panics on its first frames
+    struct Panicking {
+        panics: usize,
+        frames: usize,
+    }
+
+    impl OverlayApp for Panicking {
+        fn ui(&mut self, _ctx: &Context) {
+            self.frames += 1;
+            if self.panics > 0 {
+                self.panics -= 1;
+                panic!("frame {}", self.frames);
+            }
+        }
+    }
+
+    fn frame(guard: &mut PanicGuard, app: &mut Panicking) {
+        let _ = Context::default().run(Default::default(), |ctx| guard.ui(app, ctx));
+    }
+
+    #[test]
+    fn poisoned_until_retried() {
+        let mut guard = PanicGuard::new(true);
+        let mut app = Panicking {
+            panics: 1,
+            frames: 0,
+        };
+        frame(&mut guard, &mut app);
+        assert_eq!(guard.message.as_deref(), Some("frame 1"));
+        frame(&mut guard, &mut app);
+        assert_eq!(app.frames, 1);
+
+        // as done by the retry button
+        guard.message = None;
+        frame(&mut guard, &mut app);
+        assert_eq!(app.frames, 2);
+        assert!(guard.message.is_none());
+    }
+
+    #[test]
+    fn disabled_unwinds() {
+        let mut guard = PanicGuard::new(false);
+        let mut app = Panicking {
+            panics: 1,
+            frames: 0,
+        };
+        let unwound = panic::catch_unwind(AssertUnwindSafe(|| frame(&mut guard, &mut app)));
+        assert!(unwound.is_err());
+        assert!(guard.message.is_none());
+    }
+
+    #[test]
+    fn panic_messages() {
+        let message = |payload: Box<dyn Any + Send>| panic_message(payload.as_ref());
+        assert_eq!(message(Box::new("static")), "static");
+        assert_eq!(message(Box::new(String::from("formatted"))), "formatted");
+        assert_eq!(message(Box::new(42)), "no message");
+    }
+}
@@ -0,0 +1,47 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context as _, Result};
+use tracing::warn;
+
+/// `path` if absolute, otherwise under `$XDG_DATA_HOME/egui-overlay`
+pub(crate) fn memory_path(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_owned());
+    }
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let home = env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set"))?;
+            PathBuf::from(home).join(".local/share")
+        }
+    };
+    Ok(data_home.join("egui-overlay").join(path))
+}
+
+/// Restores the memory saved at `path`, a missing file leaves the memory as is.
+pub(crate) fn load_memory(context: &egui::Context, path: &Path) {
+    let memory = match fs::read_to_string(path) {
+        Ok(memory) => memory,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+        Err(e) => return warn!("reading the egui memory {}: {}", path.display(), e),
+    };
+    match ron::from_str(&memory) {
+        Ok(memory) => *context.memory() = memory,
+        // e.g. written by another egui version, overwritten on exit
+        Err(e) => warn!("parsing the egui memory {}: {}", path.display(), e),
+    }
+}
+
+pub(crate) fn save_memory(context: &egui::Context, path: &Path) -> Result<()> {
+    let memory = ron::to_string(&*context.memory())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // not to leave a truncated file if interrupted
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, memory).with_context(|| format!("writing {}", tmp.display()))?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
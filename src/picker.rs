@@ -0,0 +1,295 @@
+use anyhow::Result;
+use egui::{Color32, Id, LayerId, Order, Sense, Stroke};
+use x11rb::connection::Connection;
+use x11rb::protocol::shape::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, MapState, Rectangle, Window, WindowClass,
+};
+use x11rb::protocol::Event;
+use x11rb::NONE;
+
+use crate::wm_query::{window_class, window_title, WmAtoms};
+use crate::{Overlay, OverlayApp};
+
+/// What [`Overlay::pick_color`] calls back, with the picked color or `None` when cancelled.
+pub(crate) type PickHandler = Box<dyn FnOnce(&mut Overlay, Option<Color32>) -> Result<()>>;
+
+/// What [`Overlay::pick_window`] calls back, with the picked window or `None` when cancelled.
+pub(crate) type WindowPickHandler =
+    Box<dyn FnOnce(&mut Overlay, Option<PickedWindow>) -> Result<()>>;
+
+/// A window of another application, picked with [`Overlay::pick_window`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PickedWindow {
+    /// The application's own window, inside the frame of reparenting window managers, e.g.
+    /// for [`Attachment::over`](crate::Attachment::over).
+    pub window: Window,
+    /// `WM_CLASS` instance name, empty when unset.
+    pub instance: String,
+    /// `WM_CLASS` class name, empty when unset.
+    pub class: String,
+    /// `_NET_WM_NAME`, or `WM_NAME`, empty when unset.
+    pub title: String,
+}
+
+/// A color pick in progress, while the overlay grabs the pointer.
+pub(crate) struct ColorPicker {
+    pub on_pick: PickHandler,
+    /// where the pointer moved on the screen, in pixels, until the color there is read
+    pub moved: Option<(i16, i16)>,
+    /// under the pointer
+    pub color: Option<Color32>,
+}
+
+/// A window pick in progress, while the overlay grabs the pointer.
+pub(crate) struct WindowPicker {
+    pub on_pick: WindowPickHandler,
+    /// where the pointer moved on the screen, in pixels, until the window there is found
+    pub moved: Option<(i16, i16)>,
+    /// under the pointer, with its frame in egui points
+    pub hovered: Option<(PickedWindow, egui::Rect)>,
+}
+
+/// What a grabbed pointer event does to the pick.
+pub(crate) enum PickEvent {
+    Moved(i16, i16),
+    /// at the position, with the primary button
+    Picked(i16, i16),
+    Cancelled,
+}
+
+impl ColorPicker {
+    pub fn new(on_pick: PickHandler, pointer: Option<(i16, i16)>) -> Self {
+        Self {
+            on_pick,
+            moved: pointer,
+            color: None,
+        }
+    }
+
+    pub fn wrap<'a>(&'a self, app: &'a mut dyn OverlayApp) -> ColorTooltip<'a> {
+        ColorTooltip { picker: self, app }
+    }
+
+    /// shows the color under the pointer in a tooltip, its hexadecimal code next to it
+    fn ui(&self, ctx: &egui::Context) {
+        let Some(color) = self.color else {
+            return;
+        };
+        egui::show_tooltip_at_pointer(ctx, Id::new("color picker"), |ui| {
+            ui.horizontal(|ui| {
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(24., 24.), Sense::hover());
+                let stroke = Stroke::new(1., ui.visuals().text_color());
+                ui.painter().rect(rect, 2., color, stroke);
+                ui.monospace(format!(
+                    "#{:02x}{:02x}{:02x}",
+                    color.r(),
+                    color.g(),
+                    color.b()
+                ));
+            });
+        });
+    }
+}
+
+impl WindowPicker {
+    pub fn new(on_pick: WindowPickHandler, pointer: Option<(i16, i16)>) -> Self {
+        Self {
+            on_pick,
+            moved: pointer,
+            hovered: None,
+        }
+    }
+
+    pub fn wrap<'a>(&'a self, app: &'a mut dyn OverlayApp) -> WindowOutline<'a> {
+        WindowOutline { picker: self, app }
+    }
+
+    /// outlines the frame of the window under the pointer, its class and title in a tooltip
+    fn ui(&self, ctx: &egui::Context) {
+        let Some((window, frame)) = &self.hovered else {
+            return;
+        };
+        let stroke = ctx.style().visuals.selection.stroke;
+        let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("window picker")));
+        painter.rect(
+            *frame,
+            0.,
+            stroke.color.linear_multiply(0.15),
+            Stroke::new(3., stroke.color),
+        );
+        egui::show_tooltip_at_pointer(ctx, Id::new("window picker"), |ui| {
+            ui.strong(&window.title);
+            ui.monospace(format!("{} ({:#x})", window.class, window.window));
+        });
+    }
+}
+
+/// The top-level window at `x`, `y` on the screen and where it is, with the application's
+/// window inside it. Skips `own` windows and the ones input goes through, like overlays.
+pub(crate) fn window_at<Conn>(
+    conn: &Conn,
+    root: Window,
+    (x, y): (i16, i16),
+    own: &[Window],
+) -> Result<Option<(PickedWindow, Rectangle)>>
+where
+    Conn: Connection,
+{
+    let tree = conn.query_tree(root)?.reply()?;
+    // topmost first
+    for &window in tree.children.iter().rev() {
+        if own.contains(&window) {
+            continue;
+        }
+        // errors for windows gone meanwhile
+        let Ok(attributes) = conn.get_window_attributes(window)?.reply() else {
+            continue;
+        };
+        if attributes.map_state != MapState::VIEWABLE || attributes.class == WindowClass::INPUT_ONLY
+        {
+            continue;
+        }
+        let Ok(geometry) = conn.get_geometry(window)?.reply() else {
+            continue;
+        };
+        let border = 2 * geometry.border_width;
+        let rect = Rectangle {
+            x: geometry.x,
+            y: geometry.y,
+            width: geometry.width + border,
+            height: geometry.height + border,
+        };
+        if !contains(rect, x, y) || !takes_input(conn, window, rect, x, y)? {
+            continue;
+        }
+        let client = client_window(conn, window)?;
+        return Ok(Some((window_info(conn, client)?, rect)));
+    }
+    Ok(None)
+}
+
+/// whether the input shape of `window` at `rect` holds `x`, `y`, e.g. not for the compositor
+/// overlay window
+fn takes_input<Conn>(conn: &Conn, window: Window, rect: Rectangle, x: i16, y: i16) -> Result<bool>
+where
+    Conn: Connection,
+{
+    let Ok(shape) = conn.shape_get_rectangles(window, shape::SK::INPUT)?.reply() else {
+        return Ok(false);
+    };
+    Ok(shape.rectangles.iter().any(|shape| {
+        let shape = Rectangle {
+            x: rect.x.saturating_add(shape.x),
+            y: rect.y.saturating_add(shape.y),
+            ..*shape
+        };
+        contains(shape, x, y)
+    }))
+}
+
+fn contains(rect: Rectangle, x: i16, y: i16) -> bool {
+    let (x, y) = (i32::from(x), i32::from(y));
+    let (left, top) = (i32::from(rect.x), i32::from(rect.y));
+    x >= left && y >= top && x < left + i32::from(rect.width) && y < top + i32::from(rect.height)
+}
+
+/// the window with a `WM_STATE` in `top_level`, the one the window manager manages, or
+/// `top_level` itself without window manager
+fn client_window<Conn>(conn: &Conn, top_level: Window) -> Result<Window>
+where
+    Conn: Connection,
+{
+    let wm_state = atom(conn, "WM_STATE")?;
+    Ok(find_client(conn, top_level, wm_state)?.unwrap_or(top_level))
+}
+
+fn find_client<Conn>(conn: &Conn, window: Window, wm_state: Atom) -> Result<Option<Window>>
+where
+    Conn: Connection,
+{
+    let Ok(state) = conn
+        .get_property(false, window, wm_state, AtomEnum::ANY, 0, 0)?
+        .reply()
+    else {
+        return Ok(None);
+    };
+    if state.type_ != NONE {
+        return Ok(Some(window));
+    }
+    let Ok(tree) = conn.query_tree(window)?.reply() else {
+        return Ok(None);
+    };
+    for &child in tree.children.iter().rev() {
+        if let Some(client) = find_client(conn, child, wm_state)? {
+            return Ok(Some(client));
+        }
+    }
+    Ok(None)
+}
+
+/// the class and title of `window`, empty for the ones unset
+fn window_info<Conn>(conn: &Conn, window: Window) -> Result<PickedWindow>
+where
+    Conn: Connection,
+{
+    let class = window_class(conn, window)?.unwrap_or_default();
+    let title = window_title(conn, &WmAtoms::new(conn)?, window)?;
+    Ok(PickedWindow {
+        window,
+        instance: class.instance,
+        class: class.class,
+        title: title.unwrap_or_default(),
+    })
+}
+
+fn atom<Conn>(conn: &Conn, name: &str) -> Result<Atom>
+where
+    Conn: Connection,
+{
+    Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+}
+
+/// the pick events among the core and XInput2 pointer ones, positions on the root window
+pub(crate) fn pick_event(event: &Event) -> Option<PickEvent> {
+    // 16.16 fixed point
+    let xi_pixels = |x: i32| (x >> 16) as i16;
+    match event {
+        Event::MotionNotify(e) => Some(PickEvent::Moved(e.root_x, e.root_y)),
+        Event::XinputMotion(e) => Some(PickEvent::Moved(xi_pixels(e.root_x), xi_pixels(e.root_y))),
+        // the wheel scrolls instead
+        Event::ButtonPress(e) if (4..=7).contains(&e.detail) => None,
+        Event::ButtonPress(e) if e.detail == 1 => Some(PickEvent::Picked(e.root_x, e.root_y)),
+        Event::ButtonPress(_) => Some(PickEvent::Cancelled),
+        Event::XinputButtonPress(e) if (4..=7).contains(&e.detail) => None,
+        Event::XinputButtonPress(e) if e.detail == 1 => {
+            Some(PickEvent::Picked(xi_pixels(e.root_x), xi_pixels(e.root_y)))
+        }
+        Event::XinputButtonPress(_) => Some(PickEvent::Cancelled),
+        _ => None,
+    }
+}
+
+pub(crate) struct ColorTooltip<'a> {
+    picker: &'a ColorPicker,
+    app: &'a mut dyn OverlayApp,
+}
+
+impl OverlayApp for ColorTooltip<'_> {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.app.ui(ctx);
+        self.picker.ui(ctx);
+    }
+}
+
+pub(crate) struct WindowOutline<'a> {
+    picker: &'a WindowPicker,
+    app: &'a mut dyn OverlayApp,
+}
+
+impl OverlayApp for WindowOutline<'_> {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.app.ui(ctx);
+        self.picker.ui(ctx);
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{bail, Result};
+use egui::{pos2, Event, Modifiers, PointerButton, Pos2, NUM_POINTER_BUTTONS};
+use x11rb::connection::Connection;
+use x11rb::protocol::xinput::{
+    self, ConnectionExt as _, Device, EventMask, PointerEventFlags, XIEventMask,
+};
+use x11rb::protocol::xproto::{ConnectionExt as _, Window};
+use x11rb::protocol::Event as XEvent;
+
+use crate::scroll::wheel_event;
+
+/// Translates X pointer events into egui events, positions going from pixels to points.
+/// The XInput2 ones replace the core ones once selected, see [`SmoothScroll`].
+///
+/// [`SmoothScroll`]: crate::scroll::SmoothScroll
+pub(crate) fn pointer_event(
+    event: &XEvent,
+    pixels_per_point: f32,
+    modifiers: Modifiers,
+) -> Option<Event> {
+    let to_points = |x: f32, y: f32| pos2(x / pixels_per_point, y / pixels_per_point);
+    // 16.16 fixed point
+    let xi_to_points = |x: i32, y: i32| to_points(x as f32 / 65536., y as f32 / 65536.);
+
+    match event {
+        XEvent::MotionNotify(e) => Some(Event::PointerMoved(to_points(
+            e.event_x.into(),
+            e.event_y.into(),
+        ))),
+        XEvent::XinputMotion(e) => Some(Event::PointerMoved(xi_to_points(e.event_x, e.event_y))),
+        XEvent::ButtonPress(e) if (4..=7).contains(&e.detail) => wheel_event(e.detail, modifiers),
+        XEvent::ButtonPress(e) | XEvent::ButtonRelease(e) => Some(Event::PointerButton {
+            pos: to_points(e.event_x.into(), e.event_y.into()),
+            button: pointer_button(e.detail)?,
+            pressed: matches!(event, XEvent::ButtonPress(_)),
+            modifiers,
+        }),
+        // emulated from the smooth scrolling, which is sent as such
+        XEvent::XinputButtonPress(e) if e.flags.contains(PointerEventFlags::POINTER_EMULATED) => {
+            None
+        }
+        XEvent::XinputButtonPress(e) if (4..=7).contains(&e.detail) => {
+            wheel_event(e.detail as u8, modifiers)
+        }
+        XEvent::XinputButtonPress(e) | XEvent::XinputButtonRelease(e) => {
+            Some(Event::PointerButton {
+                pos: xi_to_points(e.event_x, e.event_y),
+                button: pointer_button(u8::try_from(e.detail).ok()?)?,
+                pressed: matches!(event, XEvent::XinputButtonPress(_)),
+                modifiers,
+            })
+        }
+        XEvent::LeaveNotify(_) => Some(Event::PointerGone),
+        _ => None,
+    }
+}
+
+/// whether `event` presses the middle button, pasting the primary selection
+pub(crate) fn is_middle_press(event: &XEvent) -> bool {
+    match event {
+        XEvent::ButtonPress(e) => e.detail == 2,
+        XEvent::XinputButtonPress(e) => e.detail == 2,
+        _ => false,
+    }
+}
+
+fn pointer_button(detail: u8) -> Option<PointerButton> {
+    match detail {
+        1 => Some(PointerButton::Primary),
+        2 => Some(PointerButton::Middle),
+        3 => Some(PointerButton::Secondary),
+        _ => None,
+    }
+}
+
+/// Follows the pointer over the whole screen with XInput2 raw events, delivered to the root
+/// window whatever window is under the pointer, so passthrough overlays still see it move.
+pub(crate) struct PointerTracker {
+    root: Window,
+    /// raw motion since the position was last queried
+    moved: bool,
+    /// indexed by `PointerButton`
+    down: [bool; NUM_POINTER_BUTTONS],
+}
+
+impl PointerTracker {
+    pub fn new<Conn>(conn: &Conn, root: Window) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        if conn
+            .extension_information(xinput::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            bail!("XInput extension not available");
+        }
+        // raw events reach the root window regardless of grabs since 2.1
+        let version = conn.xinput_xi_query_version(2, 2)?.reply()?;
+        if (version.major_version, version.minor_version) < (2, 1) {
+            bail!(
+                "XInput {}.{} is too old to track the pointer, 2.1 is needed",
+                version.major_version,
+                version.minor_version
+            );
+        }
+        select_raw_events(
+            conn,
+            root,
+            XIEventMask::RAW_MOTION
+                | XIEventMask::RAW_BUTTON_PRESS
+                | XIEventMask::RAW_BUTTON_RELEASE,
+        )?;
+        Ok(Self {
+            root,
+            moved: true,
+            down: [false; NUM_POINTER_BUTTONS],
+        })
+    }
+
+    /// Unsubscribes from the raw events, for all the overlays on the connection.
+    pub fn stop<Conn>(self, conn: &Conn) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        select_raw_events(conn, self.root, XIEventMask::default())
+    }
+
+    pub fn handle_event(&mut self, event: &XEvent) {
+        match event {
+            XEvent::XinputRawMotion(_) => self.moved = true,
+            XEvent::XinputRawButtonPress(e) | XEvent::XinputRawButtonRelease(e) => {
+                if let Some(button) = u8::try_from(e.detail).ok().and_then(pointer_button) {
+                    self.down[button as usize] = matches!(event, XEvent::XinputRawButtonPress(_));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The pointer position relative to `win_id` in points, if it moved since the last call.
+    pub fn moved<Conn>(
+        &mut self,
+        conn: &Conn,
+        win_id: Window,
+        pixels_per_point: f32,
+    ) -> Result<Option<Pos2>>
+    where
+        Conn: Connection,
+    {
+        if !std::mem::take(&mut self.moved) {
+            return Ok(None);
+        }
+        let pointer = conn.query_pointer(win_id)?.reply()?;
+        // on another screen, there is no position relative to the window
+        Ok(pointer.same_screen.then(|| {
+            pos2(
+                pointer.win_x as f32 / pixels_per_point,
+                pointer.win_y as f32 / pixels_per_point,
+            )
+        }))
+    }
+
+    pub fn is_down(&self, button: PointerButton) -> bool {
+        self.down[button as usize]
+    }
+}
+
+fn select_raw_events<Conn>(conn: &Conn, root: Window, mask: XIEventMask) -> Result<()>
+where
+    Conn: Connection,
+{
+    conn.xinput_xi_select_events(
+        root,
+        &[EventMask {
+            deviceid: Device::ALL_MASTER.into(),
+            mask: vec![mask],
+        }],
+    )?;
+    Ok(())
+}
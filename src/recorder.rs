@@ -0,0 +1,484 @@
+//! Recording the overlay to a GIF or a video, e.g. to demo its UI, started and stopped with
+//! [`Overlay::start_recording`] and [`Overlay::stop_recording`], or a hotkey.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::{egui::Modifiers, keysyms, recorder, Overlay};
+//! # use egui_wgpu_x11::OverlayApp;
+//! # struct Hud;
+//! # impl OverlayApp for Hud {
+//! #     fn ui(&mut self, _ctx: &egui_wgpu_x11::egui::Context) {}
+//! # }
+//!
+//! let mut overlay = Overlay::new()?;
+//! recorder::toggle_with(&mut overlay, Modifiers::NONE, keysyms::KEY_F10, "hud.gif")?;
+//! overlay.run(Hud)?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! The frames are read back from the GPU as they are drawn, at most [`RECORDING_FPS`] per
+//! second, and encoded on a thread of their own: GIFs by the `gif` crate, on a fixed palette
+//! with 1-bit transparency, videos by `ffmpeg`, which must be in the `PATH`. The
+//! recording keeps the size of its first frame, the frames of another size are dropped.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use egui::{ColorImage, Modifiers};
+use tracing::{debug, info};
+
+use crate::Overlay;
+
+/// The frame rate cap of the recordings, and the constant frame rate of the videos.
+pub const RECORDING_FPS: f32 = 30.;
+
+/// What a recording is encoded to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// Looping, transparent where the overlay is.
+    Gif,
+    /// VP9 with an alpha channel.
+    WebM,
+    /// H.264, opaque.
+    Mp4,
+}
+
+impl RecordingFormat {
+    /// From the extension of `path`: `gif`, `webm` or `mp4`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "gif" => Some(Self::Gif),
+            "webm" => Some(Self::WebM),
+            "mp4" => Some(Self::Mp4),
+            _ => None,
+        }
+    }
+}
+
+/// Makes `modifiers` + `keysym` start recording `overlay` to `path`, then stop, replacing the
+/// file each time.
+pub fn toggle_with(
+    overlay: &mut Overlay,
+    modifiers: Modifiers,
+    keysym: u32,
+    path: impl Into<PathBuf>,
+) -> Result<()> {
+    let path = path.into();
+    overlay.register_hotkey(modifiers, keysym, move |overlay| {
+        match overlay.stop_recording()? {
+            Some(path) => info!("recorded {}", path.display()),
+            None => overlay.start_recording(&path)?,
+        }
+        Ok(())
+    })
+}
+
+enum Message {
+    Frame(ColorImage, Instant),
+    Stop(Instant),
+}
+
+/// An ongoing recording, fed the frames of the overlay.
+pub(crate) struct Recording {
+    path: PathBuf,
+    sender: Sender<Message>,
+    encoder: JoinHandle<Result<()>>,
+    last_frame: Option<Instant>,
+}
+
+impl Recording {
+    pub fn start(path: PathBuf) -> Result<Self> {
+        let format = RecordingFormat::from_path(&path)
+            .ok_or_else(|| anyhow!("{}: expected a .gif, .webm or .mp4 file", path.display()))?;
+        let encoder: Box<dyn Encoder> = match format {
+            RecordingFormat::Gif => Box::new(GifEncoder::new(&path)?),
+            RecordingFormat::WebM | RecordingFormat::Mp4 => {
+                Box::new(VideoEncoder::new(&path, format))
+            }
+        };
+        let (sender, receiver) = mpsc::channel();
+        let encoder = thread::Builder::new()
+            .name("egui-overlay-recorder".to_owned())
+            .spawn(move || encode(receiver, encoder))?;
+        Ok(Self {
+            path,
+            sender,
+            encoder,
+            last_frame: None,
+        })
+    }
+
+    /// Whether a frame drawn at `now` is recorded, under the frame rate cap.
+    pub fn wants_frame(&self, now: Instant) -> bool {
+        self.last_frame
+            .is_none_or(|last| now - last >= Duration::from_secs_f32(1. / RECORDING_FPS))
+    }
+
+    /// Fails once the encoder stopped, e.g. on a write error.
+    pub fn push(&mut self, frame: ColorImage, now: Instant) -> Result<()> {
+        self.last_frame = Some(now);
+        self.sender
+            .send(Message::Frame(frame, now))
+            .map_err(|_| anyhow!("the recording encoder stopped"))
+    }
+
+    /// Waits for the encoder to finish the file.
+    pub fn finish(self) -> Result<PathBuf> {
+        let _ = self.sender.send(Message::Stop(Instant::now()));
+        drop(self.sender);
+        self.encoder
+            .join()
+            .map_err(|_| anyhow!("the recording encoder panicked"))??;
+        Ok(self.path)
+    }
+}
+
+/// encodes each frame once the next one comes, to know how long it shows
+fn encode(receiver: Receiver<Message>, mut encoder: Box<dyn Encoder>) -> Result<()> {
+    let mut pending: Option<(ColorImage, Instant)> = None;
+    let mut size = None;
+    loop {
+        let (next, at) = match receiver.recv() {
+            Ok(Message::Frame(frame, at)) => (Some(frame), at),
+            Ok(Message::Stop(at)) => (None, at),
+            // dropped without stopping
+            Err(_) => (None, Instant::now()),
+        };
+        if let Some((frame, shown)) = pending.take() {
+            encoder.frame(&frame, at - shown)?;
+        }
+        let Some(next) = next else {
+            break;
+        };
+        match size {
+            None => size = Some(next.size),
+            Some(size) if size != next.size => {
+                debug!(
+                    "{:?} frame dropped from the {:?} recording",
+                    next.size, size
+                );
+                continue;
+            }
+            Some(_) => {}
+        }
+        pending = Some((next, at));
+    }
+    encoder.finish()
+}
+
+trait Encoder: Send {
+    /// Adds `frame`, shown for `duration`.
+    fn frame(&mut self, frame: &ColorImage, duration: Duration) -> Result<()>;
+
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// palette index of the transparent pixels, followed by a 6x6x6 color cube
+const TRANSPARENT: u8 = 0;
+
+struct GifEncoder {
+    /// created up front, to fail early
+    out: Option<BufWriter<File>>,
+    /// started with the first frame, its size known
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl GifEncoder {
+    fn new(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("{}", path.display()))?;
+        Ok(Self {
+            out: Some(BufWriter::new(file)),
+            gif: None,
+        })
+    }
+
+    /// the header, the palette, and the extension looping the animation
+    fn start(&mut self, size: [usize; 2]) -> Result<()> {
+        let (width, height) = gif_size(size)?;
+        let out = self.out.take().context("GIF already started")?;
+        let mut gif = gif::Encoder::new(out, width, height, &palette())?;
+        gif.set_repeat(gif::Repeat::Infinite)?;
+        self.gif = Some(gif);
+        Ok(())
+    }
+}
+
+impl Encoder for GifEncoder {
+    fn frame(&mut self, frame: &ColorImage, duration: Duration) -> Result<()> {
+        if self.gif.is_none() {
+            self.start(frame.size)?;
+        }
+        let Some(gif) = &mut self.gif else {
+            return Ok(());
+        };
+        let (width, height) = gif_size(frame.size)?;
+        let indices: Vec<u8> = frame
+            .pixels
+            .iter()
+            .map(|pixel| palette_index(pixel.to_srgba_unmultiplied()))
+            .collect();
+        gif.write_frame(&gif::Frame {
+            // in hundredths of a second, browsers showing shorter delays slower
+            delay: (duration.as_millis() / 10).clamp(2, u16::MAX.into()) as u16,
+            // cleared before the next frame, its transparent pixels not showing this one
+            dispose: gif::DisposalMethod::Background,
+            transparent: Some(TRANSPARENT),
+            width,
+            height,
+            buffer: indices.into(),
+            ..Default::default()
+        })?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        // after the trailer, or empty without frames
+        let out = match self.gif {
+            Some(gif) => Some(gif.into_inner()?),
+            None => self.out,
+        };
+        if let Some(mut out) = out {
+            out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn gif_size(size: [usize; 2]) -> Result<(u16, u16)> {
+    match size.map(u16::try_from) {
+        [Ok(width), Ok(height)] => Ok((width, height)),
+        _ => bail!("{:?} is too large for a GIF", size),
+    }
+}
+
+/// transparent, then the levels of the cube
+fn palette() -> [u8; 256 * 3] {
+    let mut palette = [0; 256 * 3];
+    for i in 0..216 {
+        let rgb = [i / 36, i / 6 % 6, i % 6].map(|level| (level * 51) as u8);
+        palette[(i + 1) * 3..(i + 2) * 3].copy_from_slice(&rgb);
+    }
+    palette
+}
+
+/// the nearest color of the cube, transparent under half opacity
+fn palette_index([r, g, b, a]: [u8; 4]) -> u8 {
+    if a < 128 {
+        return TRANSPARENT;
+    }
+    let level = |channel: u8| (u16::from(channel) * 5 + 127) / 255;
+    1 + (level(r) * 36 + level(g) * 6 + level(b)) as u8
+}
+
+/// raw frames piped to `ffmpeg`, repeated for a constant frame rate
+struct VideoEncoder {
+    path: PathBuf,
+    format: RecordingFormat,
+    /// started with the first frame, its size known
+    ffmpeg: Option<(Child, ChildStdin)>,
+    /// of the frames encoded so far
+    duration: Duration,
+    written: u64,
+}
+
+impl VideoEncoder {
+    fn new(path: &Path, format: RecordingFormat) -> Self {
+        Self {
+            path: path.to_owned(),
+            format,
+            ffmpeg: None,
+            duration: Duration::ZERO,
+            written: 0,
+        }
+    }
+
+    fn start(&self, size: [usize; 2]) -> Result<(Child, ChildStdin)> {
+        let codec: &[&str] = match self.format {
+            RecordingFormat::Mp4 => &[
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+                // x264 wants even sizes
+                "-vf",
+                "pad=ceil(iw/2)*2:ceil(ih/2)*2",
+            ],
+            _ => &[
+                "-c:v",
+                "libvpx-vp9",
+                "-pix_fmt",
+                "yuva420p",
+                "-b:v",
+                "0",
+                "-crf",
+                "32",
+            ],
+        };
+        let mut child = Command::new("ffmpeg")
+            .args([
+                "-loglevel",
+                "error",
+                "-y",
+                "-f",
+                "rawvideo",
+                "-pix_fmt",
+                "rgba",
+            ])
+            .args(["-s", &format!("{}x{}", size[0], size[1])])
+            .args(["-r", &RECORDING_FPS.to_string(), "-i", "-"])
+            .args(codec)
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => {
+                    anyhow!("ffmpeg isn't in the PATH, it encodes the .webm and .mp4 recordings")
+                }
+                _ => anyhow::Error::new(e).context("could not run ffmpeg"),
+            })?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("no stdin to pipe the frames to ffmpeg"))?;
+        Ok((child, stdin))
+    }
+}
+
+impl Encoder for VideoEncoder {
+    fn frame(&mut self, frame: &ColorImage, duration: Duration) -> Result<()> {
+        if self.ffmpeg.is_none() {
+            self.ffmpeg = Some(self.start(frame.size)?);
+        }
+        let Some((_, stdin)) = &mut self.ffmpeg else {
+            return Ok(());
+        };
+        self.duration += duration;
+        // at least the first frame
+        let frames = ((self.duration.as_secs_f32() * RECORDING_FPS).round() as u64).max(1);
+        if frames <= self.written {
+            return Ok(());
+        }
+        let pixels: Vec<u8> = frame
+            .pixels
+            .iter()
+            .flat_map(|pixel| pixel.to_srgba_unmultiplied())
+            .collect();
+        for _ in self.written..frames {
+            stdin.write_all(&pixels).context("ffmpeg stopped")?;
+        }
+        self.written = frames;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        let Some((mut child, stdin)) = self.ffmpeg else {
+            return Ok(());
+        };
+        // the end of the input
+        drop(stdin);
+        let status = child.wait()?;
+        if !status.success() {
+            bail!(
+                "ffmpeg failed to encode {}: {}",
+                self.path.display(),
+                status
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Color32;
+
+    /// `frames` encoded, then decoded: the delay and the palette indices of each frame
+    fn round_trip(name: &str, frames: &[(ColorImage, Duration)]) -> Vec<(u16, Vec<u8>)> {
+        let path =
+            std::env::temp_dir().join(format!("egui-overlay-{}-{}", std::process::id(), name));
+        let mut encoder = Box::new(GifEncoder::new(&path).unwrap());
+        for (frame, duration) in frames {
+            encoder.frame(frame, *duration).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(File::open(&path).unwrap()).unwrap();
+        assert_eq!(decoder.global_palette(), Some(&palette()[..]));
+        let mut decoded = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.transparent, Some(TRANSPARENT));
+            assert_eq!(frame.dispose, gif::DisposalMethod::Background);
+            decoded.push((frame.delay, frame.buffer.to_vec()));
+        }
+        std::fs::remove_file(&path).unwrap();
+        decoded
+    }
+
+    fn indices(frame: &ColorImage) -> Vec<u8> {
+        frame
+            .pixels
+            .iter()
+            .map(|pixel| palette_index(pixel.to_srgba_unmultiplied()))
+            .collect()
+    }
+
+    #[test]
+    fn frames_keep_their_delay_and_pixels() {
+        let red = ColorImage::new([4, 3], Color32::RED);
+        let mut half = ColorImage::new([4, 3], Color32::BLUE);
+        half.pixels[..6].fill(Color32::TRANSPARENT);
+        let decoded = round_trip(
+            "frames.gif",
+            &[
+                (red.clone(), Duration::from_millis(100)),
+                (half.clone(), Duration::from_millis(500)),
+                // browsers slow down shorter delays
+                (red.clone(), Duration::from_millis(1)),
+            ],
+        );
+        assert_eq!(
+            decoded,
+            [
+                (10, indices(&red)),
+                (50, indices(&half)),
+                (2, indices(&red)),
+            ]
+        );
+        assert_eq!(indices(&red)[0], 1 + 5 * 36);
+        assert_eq!(decoded[1].1[..6], [TRANSPARENT; 6]);
+        assert_eq!(decoded[1].1[6], 1 + 5);
+    }
+
+    #[test]
+    fn noisy_frames_outgrow_the_lzw_dictionary() {
+        // pseudo-random colors of the cube, far more than the 4096 codes before a reset
+        let mut seed = 1u32;
+        let mut noise = ColorImage::new([128, 128], Color32::BLACK);
+        for pixel in &mut noise.pixels {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let level = |shift: u32| ((seed >> shift) % 6 * 51) as u8;
+            *pixel = Color32::from_rgb(level(8), level(16), level(24));
+        }
+        let decoded = round_trip("noise.gif", &[(noise.clone(), Duration::from_millis(40))]);
+        assert_eq!(decoded, [(4, indices(&noise))]);
+    }
+
+    #[test]
+    fn transparent_under_half_opacity() {
+        assert_eq!(palette_index([255, 255, 255, 127]), TRANSPARENT);
+        assert_eq!(palette_index([0, 0, 0, 128]), 1);
+        assert_eq!(palette_index([255, 255, 255, 255]), 216);
+        let index = usize::from(palette_index([255, 102, 0, 255]));
+        assert_eq!(palette()[index * 3..index * 3 + 3], [255, 102, 0]);
+    }
+}
@@ -0,0 +1,199 @@
+//! The egui renderers drawing the overlays, picked with
+//! [`OverlayBuilder::renderer`](crate::OverlayBuilder::renderer).
+
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::Result;
+use egui::{epaint::ClippedPrimitive, TextureId, TexturesDelta};
+
+/// the closure of a [`RendererFactory`]
+type BuildRenderer =
+    dyn Fn(&wgpu::Device, wgpu::TextureFormat, u32) -> Box<dyn Renderer> + Send + Sync;
+
+/// Builds the renderer of an overlay, for its device, surface format and MSAA sample count,
+/// again on a new device after a GPU reset. The closure may capture the settings of the
+/// renderer.
+#[derive(Clone)]
+pub struct RendererFactory(Arc<BuildRenderer>);
+
+impl RendererFactory {
+    pub fn new(
+        build: impl Fn(&wgpu::Device, wgpu::TextureFormat, u32) -> Box<dyn Renderer>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self(Arc::new(build))
+    }
+
+    pub fn build(
+        &self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        msaa_samples: u32,
+    ) -> Box<dyn Renderer> {
+        (self.0)(device, format, msaa_samples)
+    }
+}
+
+/// [`egui_wgpu_backend`]
+impl Default for RendererFactory {
+    fn default() -> Self {
+        Self::new(egui_wgpu_backend)
+    }
+}
+
+impl fmt::Debug for RendererFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RendererFactory").finish_non_exhaustive()
+    }
+}
+
+/// The size of the surface drawn into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenDescriptor {
+    pub size_in_pixels: [u32; 2],
+    pub pixels_per_point: f32,
+}
+
+/// Draws the egui primitives of an overlay with wgpu.
+///
+/// The overlay tessellates the frames, runs the paint callbacks into textures, and hands
+/// renderers meshes only. Their surface is cleared beforehand, in the color space of the
+/// overlay.
+pub trait Renderer {
+    /// Uploads the textures set by egui, the ones of `delta.free` being freed after the frame.
+    fn update_textures(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        delta: &TexturesDelta,
+    ) -> Result<()>;
+
+    /// Frees egui and native textures.
+    fn free_textures(&mut self, ids: &[TextureId]) -> Result<()>;
+
+    /// Makes a texture of the device drawable by egui, without copying it. The ids are
+    /// [`TextureId::User`], numbered from 0 in the order of the registrations.
+    fn register_native_texture(
+        &mut self,
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+    ) -> TextureId;
+
+    /// Draws `view` instead of the native texture `id`.
+    fn update_native_texture(
+        &mut self,
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+        id: TextureId,
+    ) -> Result<()>;
+
+    /// Uploads the vertices, indices and uniforms of a frame, before it's rendered.
+    fn update_buffers(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paint_jobs: &[ClippedPrimitive],
+        screen: &ScreenDescriptor,
+    );
+
+    /// Records the drawing of a frame into `pass`.
+    fn render<'rpass>(
+        &'rpass self,
+        pass: &mut wgpu::RenderPass<'rpass>,
+        paint_jobs: &[ClippedPrimitive],
+        screen: &ScreenDescriptor,
+    ) -> Result<()>;
+}
+
+/// The default renderer, from the `egui_wgpu_backend` crate.
+pub fn egui_wgpu_backend(
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+    msaa_samples: u32,
+) -> Box<dyn Renderer> {
+    Box::new(egui_wgpu_backend::RenderPass::new(
+        device,
+        format,
+        msaa_samples,
+    ))
+}
+
+/// in the terms of egui_wgpu_backend
+fn backend_screen(screen: &ScreenDescriptor) -> egui_wgpu_backend::ScreenDescriptor {
+    egui_wgpu_backend::ScreenDescriptor {
+        physical_width: screen.size_in_pixels[0],
+        physical_height: screen.size_in_pixels[1],
+        scale_factor: screen.pixels_per_point,
+    }
+}
+
+impl Renderer for egui_wgpu_backend::RenderPass {
+    fn update_textures(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        delta: &TexturesDelta,
+    ) -> Result<()> {
+        self.add_textures(device, queue, delta)?;
+        Ok(())
+    }
+
+    fn free_textures(&mut self, ids: &[TextureId]) -> Result<()> {
+        self.remove_textures(TexturesDelta {
+            set: Vec::new(),
+            free: ids.to_vec(),
+        })?;
+        Ok(())
+    }
+
+    fn register_native_texture(
+        &mut self,
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+    ) -> TextureId {
+        self.egui_texture_from_wgpu_texture(device, view, filter)
+    }
+
+    fn update_native_texture(
+        &mut self,
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+        id: TextureId,
+    ) -> Result<()> {
+        self.update_egui_texture_from_wgpu_texture(device, view, filter, id)?;
+        Ok(())
+    }
+
+    fn update_buffers(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paint_jobs: &[ClippedPrimitive],
+        screen: &ScreenDescriptor,
+    ) {
+        egui_wgpu_backend::RenderPass::update_buffers(
+            self,
+            device,
+            queue,
+            paint_jobs,
+            &backend_screen(screen),
+        )
+    }
+
+    fn render<'rpass>(
+        &'rpass self,
+        pass: &mut wgpu::RenderPass<'rpass>,
+        paint_jobs: &[ClippedPrimitive],
+        screen: &ScreenDescriptor,
+    ) -> Result<()> {
+        self.execute_with_renderpass(pass, paint_jobs, &backend_screen(screen))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,401 @@
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context as _, Error, Result};
+use tracing::{debug, debug_span, info, warn};
+use x11rb::connection::Connection;
+use x11rb::errors::{ConnectionError, ReplyError, ReplyOrIdError};
+use x11rb::protocol::Event;
+use x11rb::xcb_ffi::XCBConnection;
+
+use crate::{
+    connect, event_window, repaints, signals, Overlay, OverlayApp, OverlayBuilder, OverlayError,
+    Schedule,
+};
+
+/// how long to try connecting again after the X connection broke
+const RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// between attempts to get a new GPU device after losing it
+pub(crate) const GPU_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// between checks whether the GPU finished a frame
+const GPU_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// later than due by more, a frame counts as dropped, unless the max fps is lower
+const LATE_FRAME: Duration = Duration::from_micros(16_667);
+
+/// What the loop does after a turn.
+pub(crate) enum Turn {
+    /// Until an event, a wake-up, or the given time if any.
+    Wait(Option<Instant>),
+    Stop,
+}
+
+/// Drives sibling overlays, one turn at a time, for the blocking and the async loops.
+pub(crate) struct Runner<'a> {
+    overlays: Vec<(Overlay, &'a mut dyn OverlayApp)>,
+    schedules: Vec<Schedule>,
+    conn: Rc<XCBConnection>,
+    /// readable after SIGINT or SIGTERM, if any overlay stops on them
+    signals: Option<RawFd>,
+}
+
+impl<'a> Runner<'a> {
+    /// `None` without overlays to run.
+    pub fn new(mut overlays: Vec<(Overlay, &'a mut dyn OverlayApp)>) -> Result<Option<Self>> {
+        let Some((first, _)) = overlays.first() else {
+            return Ok(None);
+        };
+        let conn = first.conn.clone();
+        if overlays
+            .iter()
+            .any(|(overlay, _)| !Rc::ptr_eq(&overlay.conn, &conn))
+        {
+            bail!("overlays run together must be siblings");
+        }
+
+        let signals = overlays
+            .iter()
+            .any(|(overlay, _)| overlay.exit_on_signal)
+            .then(signals::watch)
+            .transpose()?;
+        // flushed at the frame boundaries from now on
+        for (overlay, _) in &mut overlays {
+            overlay.running = true;
+        }
+        let schedules = overlays.iter().map(|_| Schedule::new()).collect();
+        Ok(Some(Self {
+            overlays,
+            schedules,
+            conn,
+            signals,
+        }))
+    }
+
+    pub fn conn(&self) -> &XCBConnection {
+        &self.conn
+    }
+
+    /// The wake-up sockets of the overlays, and the signals one.
+    pub fn wakers(&self) -> Vec<RawFd> {
+        self.overlays
+            .iter()
+            .map(|(overlay, _)| overlay.wake_receiver.as_raw_fd())
+            .chain(self.signals)
+            .collect()
+    }
+
+    /// Handles the pending events and wake-ups, renders the due frames.
+    /// Rebuilds the overlays on a new connection if the X one broke.
+    pub fn turn(&mut self) -> Result<Turn> {
+        match self.step() {
+            Err(e) if is_connection_error(&e) => {
+                warn!("lost the X connection: {:#}", e);
+                self.reconnect()?;
+                Ok(Turn::Wait(Some(Instant::now())))
+            }
+            turn => turn,
+        }
+    }
+
+    /// tries connecting again until [`RECONNECT_TIMEOUT`], then moves the overlays over
+    fn reconnect(&mut self) -> Result<()> {
+        let deadline = Instant::now() + RECONNECT_TIMEOUT;
+        loop {
+            match self.rebuild() {
+                Ok(()) => {
+                    info!("reconnected to the X server");
+                    return Ok(());
+                }
+                Err(e) if Instant::now() < deadline => {
+                    warn!("reconnecting: {:#}", e);
+                    std::thread::sleep(RECONNECT_INTERVAL);
+                }
+                Err(e) => return Err(e.context("could not reconnect to the X server")),
+            }
+        }
+    }
+
+    fn rebuild(&mut self) -> Result<()> {
+        // the same display as the first overlay, they share the connection
+        let settings = self.overlays.first().map(|(overlay, _)| &overlay.settings);
+        let (conn, screen_num) = connect(settings.unwrap_or(&OverlayBuilder::new()))?;
+        let conn = Rc::new(conn);
+        for (overlay, _) in &mut self.overlays {
+            overlay
+                .reconnect(conn.clone(), screen_num)
+                .context("rebuilding the overlay")?;
+        }
+        self.conn = conn;
+        self.schedules = self.overlays.iter().map(|_| Schedule::new()).collect();
+        Ok(())
+    }
+
+    fn step(&mut self) -> Result<Turn> {
+        if self.signals.is_some() && signals::received() {
+            return Ok(Turn::Stop);
+        }
+        let Self {
+            overlays,
+            schedules,
+            conn,
+            ..
+        } = self;
+
+        dispatch_events(conn, overlays, schedules)?;
+
+        for ((overlay, app), schedule) in overlays.iter_mut().zip(schedules.iter_mut()) {
+            if overlay.wake_receiver.drain() {
+                overlay.handle_config_changes();
+                #[cfg(any(feature = "ipc", feature = "dbus"))]
+                overlay.handle_commands()?;
+                #[cfg(feature = "dbus")]
+                overlay.handle_tray()?;
+                app.woken(overlay)?;
+                schedule.repaint_at = Some(Instant::now());
+            }
+        }
+
+        let now = Instant::now();
+        for i in 0..overlays.len() {
+            let (overlay, schedule) = (&mut overlays[i].0, &mut schedules[i]);
+            let Some(at) = schedule
+                .frame_at(overlay.frame_interval())
+                .filter(|at| *at <= now)
+            else {
+                continue;
+            };
+            if now - at > overlay.frame_interval().max(LATE_FRAME) {
+                overlay.state.frames.dropped();
+            }
+            schedule.last_frame = Some(now);
+            overlay.step_fade(now)?;
+            if !overlay.visible && overlay.fade.is_none()
+                || overlay.occlusion.hidden()
+                || overlay.paused()
+            {
+                // until shown or seen again
+                schedule.repaint_at = None;
+                continue;
+            }
+            // late-latching: the input that came meanwhile makes it into this frame
+            dispatch_events(conn, overlays, schedules)?;
+            let ((overlay, app), schedule) = (&mut overlays[i], &mut schedules[i]);
+            overlay.update_tracked_pointer()?;
+            overlay.update_picked_color()?;
+            overlay.update_picked_window()?;
+            let _span = debug_span!("frame", window = overlay.win_id).entered();
+            let (mut picking, mut picking_window, mut debugging);
+            let mut wrapped: &mut dyn OverlayApp = *app;
+            let mut toasting = overlay.toasts.wrap(overlay.state.context(), now, wrapped);
+            wrapped = &mut toasting;
+            if let Some(picker) = &overlay.picker {
+                picking = picker.wrap(wrapped);
+                wrapped = &mut picking;
+            }
+            if let Some(picker) = &overlay.window_picker {
+                picking_window = picker.wrap(wrapped);
+                wrapped = &mut picking_window;
+            }
+            if let Some(debug) = &overlay.debug {
+                debugging = debug.wrap(wrapped);
+                wrapped = &mut debugging;
+            }
+            let rendered = overlay.state.render(wrapped);
+            if let Some(debug) = &mut overlay.debug {
+                debug.record_frame(now, overlay.state.texture_bytes());
+            }
+            match rendered {
+                Ok(frame) => {
+                    schedule.repaint_at = now.checked_add(frame.repaint_after);
+                    if overlay.fade.is_some() {
+                        schedule.repaint_at = Some(now);
+                    }
+                    if overlay.update_crop(&frame.painted)? {
+                        schedule.repaint_at = Some(now);
+                    }
+                    overlay.update_input_region(&frame.painted)?;
+                    overlay.update_keyboard_grab()?;
+                    if !frame.copied_text.is_empty() {
+                        overlay.clipboard.copy(&**conn, frame.copied_text)?;
+                    }
+                    overlay.cursors.set(&**conn, frame.cursor_icon)?;
+                    if let Some(open_url) = frame.open_url {
+                        overlay.open_url(&open_url.url);
+                    }
+                    if let Some(memory) = frame.over_budget {
+                        overlay.texture_budget_exceeded(&memory);
+                    }
+                    #[cfg(feature = "recorder")]
+                    overlay.record_frame(now);
+                    app.update(overlay)?;
+                    #[cfg(feature = "dbus")]
+                    overlay.signal_notifications()?;
+                }
+                // Reconfigure the surface if it's lost or outdated, on a new device if lost too
+                Err(
+                    OverlayError::SurfaceLost | OverlayError::Surface(wgpu::SurfaceError::Outdated),
+                ) => {
+                    overlay.state.frames.dropped();
+                    if let Err(e) = overlay.state.recover() {
+                        // e.g. while the driver resets
+                        warn!("no GPU to draw on: {:#}", e);
+                        schedule.repaint_at = now.checked_add(GPU_RETRY_INTERVAL);
+                    }
+                }
+                // The system is out of memory, we should probably quit
+                Err(OverlayError::Surface(wgpu::SurfaceError::OutOfMemory)) => {
+                    return Ok(Turn::Stop)
+                }
+
+                Err(OverlayError::Surface(wgpu::SurfaceError::Timeout)) => {
+                    overlay.state.frames.dropped();
+                    warn!("surface timeout")
+                }
+                Err(e) => return Err(e.into()),
+            }
+            // the requests of this frame, before the next overlay blocks on its own
+            conn.flush()?;
+        }
+
+        for ((overlay, _), schedule) in overlays.iter_mut().zip(schedules.iter_mut()) {
+            // unless drawn anew in this turn
+            if !std::mem::take(&mut schedule.exposed) || schedule.last_frame == Some(now) {
+                continue;
+            }
+            match overlay.state.present_cached() {
+                Ok(()) => {}
+                Err(OverlayError::Surface(wgpu::SurfaceError::OutOfMemory)) => {
+                    return Ok(Turn::Stop)
+                }
+                Err(OverlayError::Other(e)) => return Err(e),
+                // drawn anew, after recovering
+                Err(e) => {
+                    debug!("cached frame: {}", e);
+                    schedule.repaint_at = Some(now);
+                }
+            }
+        }
+
+        if overlays.iter().any(|(overlay, _)| overlay.closed) {
+            return Ok(Turn::Stop);
+        }
+
+        let visible = overlays
+            .iter_mut()
+            .filter(|(overlay, _)| overlay.visible && overlay.always_on_top);
+        for (overlay, _) in visible {
+            overlay
+                .stacking
+                .keep_on_top(&**conn, overlay.root, overlay.win_id)?;
+        }
+
+        // the events read meanwhile by the round trips of this turn: queued by XCB, the socket
+        // won't report them again
+        dispatch_events(conn, overlays, schedules)?;
+        conn.flush()?;
+        // restacked at once if those events may have covered an overlay
+        let restack = overlays.iter().any(|(overlay, _)| {
+            overlay.visible && overlay.always_on_top && overlay.stacking.pending()
+        });
+        // soon again while the GPU draws, for the frame latency
+        let drawing = overlays
+            .iter()
+            .filter(|(overlay, _)| overlay.state.poll_frames())
+            .map(|_| Instant::now() + GPU_POLL_INTERVAL);
+        let wake_at = overlays
+            .iter()
+            .zip(schedules.iter())
+            .filter_map(|((overlay, _), schedule)| schedule.frame_at(overlay.frame_interval()))
+            .chain(drawing)
+            .chain(restack.then(Instant::now))
+            .min();
+        Ok(Turn::Wait(wake_at))
+    }
+}
+
+/// Handles the pending events, each by the overlay it is addressed to, or by all of them.
+fn dispatch_events(
+    conn: &XCBConnection,
+    overlays: &mut [(Overlay, &mut dyn OverlayApp)],
+    schedules: &mut [Schedule],
+) -> Result<()> {
+    while let Some(event) = conn.poll_for_event()? {
+        let now = Instant::now();
+        let repaint = repaints(&event);
+        let exposed = matches!(event, Event::Expose(_));
+        let target = match &event {
+            // reported once
+            Event::Error(e) => overlays
+                .iter()
+                .position(|(overlay, _)| overlay.win_id == e.bad_value)
+                .or(Some(0)),
+            _ => event_window(&event).and_then(|window| {
+                overlays
+                    .iter()
+                    .position(|(overlay, _)| overlay.win_id == window)
+            }),
+        };
+        match target {
+            Some(i) => {
+                overlays[i].0.handle_event(event)?;
+                // the cached frame instead of a new one
+                if exposed && overlays[i].0.ui_rate.is_some() {
+                    schedules[i].exposed = true;
+                } else if repaint {
+                    schedules[i].repaint_at = Some(now);
+                }
+            }
+            // global events, or events on windows of other clients
+            None => {
+                for ((overlay, _), schedule) in overlays.iter_mut().zip(schedules.iter_mut()) {
+                    overlay.handle_event(event.clone())?;
+                    if repaint {
+                        schedule.repaint_at = Some(now);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// whether `e` comes from the X connection breaking, rather than from a request
+pub(crate) fn is_connection_error(e: &Error) -> bool {
+    e.chain().any(|cause| {
+        cause.is::<ConnectionError>()
+            || matches!(cause.downcast_ref(), Some(ReplyError::ConnectionError(_)))
+            || matches!(
+                cause.downcast_ref(),
+                Some(ReplyOrIdError::ConnectionError(_))
+            )
+    })
+}
+
+/// Waits like [`crate::x11::wait_for_event`] on the connection and the wake-up sockets,
+/// without blocking the runtime. The events queued by XCB must have been handled first.
+#[cfg(feature = "tokio")]
+pub(crate) async fn wait_async(
+    fds: &[tokio::io::unix::AsyncFd<RawFd>],
+    wake_at: Option<Instant>,
+) -> Result<()> {
+    use std::future::Future;
+    use std::task::Poll;
+
+    let mut sleep = wake_at.map(|at| Box::pin(tokio::time::sleep_until(at.into())));
+    std::future::poll_fn(|cx| {
+        for fd in fds {
+            if let Poll::Ready(guard) = fd.poll_read_ready(cx) {
+                // the next turn reads everything, the fd is reported again once more arrives
+                guard?.clear_ready();
+                return Poll::Ready(Ok::<_, std::io::Error>(()));
+            }
+        }
+        match &mut sleep {
+            Some(sleep) => sleep.as_mut().poll(cx).map(Ok),
+            None => Poll::Pending,
+        }
+    })
+    .await?;
+    Ok(())
+}
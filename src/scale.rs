@@ -0,0 +1,133 @@
+use std::env;
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, Window};
+
+use crate::monitors::Monitor;
+
+/// the DPI at which egui points are pixels
+const BASE_DPI: f32 = 96.;
+
+/// Picks the scale factor from, by priority:
+/// `GDK_SCALE` or `QT_SCALE_FACTOR`, `Xft.dpi` in the X resources, the monitor physical size.
+/// Defaults to 1.
+pub(crate) fn detect_scale_factor<Conn>(
+    conn: &Conn,
+    root: Window,
+    monitor: Option<&Monitor>,
+) -> Result<f32>
+where
+    Conn: Connection,
+{
+    if let Some(scale) = env_scale_factor() {
+        return Ok(scale);
+    }
+    if let Some(dpi) = xft_dpi(conn, root)? {
+        return Ok(dpi / BASE_DPI);
+    }
+    Ok(monitor.and_then(physical_scale_factor).unwrap_or(1.))
+}
+
+fn env_scale_factor() -> Option<f32> {
+    ["GDK_SCALE", "QT_SCALE_FACTOR"]
+        .iter()
+        .find_map(|var| parse_scale(&env::var(var).ok()?))
+}
+
+/// A positive scale factor.
+fn parse_scale(value: &str) -> Option<f32> {
+    value.trim().parse::<f32>().ok().filter(|&scale| scale > 0.)
+}
+
+/// `Xft.dpi`, as set by desktop environments or `xrdb`
+fn xft_dpi<Conn>(conn: &Conn, root: Window) -> Result<Option<f32>>
+where
+    Conn: Connection,
+{
+    let resources = conn
+        .get_property(
+            false,
+            root,
+            AtomEnum::RESOURCE_MANAGER,
+            AtomEnum::STRING,
+            0,
+            u32::MAX,
+        )?
+        .reply()?
+        .value;
+
+    Ok(parse_xft_dpi(&resources))
+}
+
+/// The first positive `Xft.dpi` of a resource database.
+fn parse_xft_dpi(resources: &[u8]) -> Option<f32> {
+    String::from_utf8_lossy(resources)
+        .lines()
+        .filter_map(|line| line.strip_prefix("Xft.dpi:"))
+        .filter_map(|dpi| dpi.trim().parse::<f32>().ok())
+        .find(|&dpi| dpi > 0.)
+}
+
+/// From the monitor DPI, rounded to a quarter as the reported physical sizes are approximate.
+fn physical_scale_factor(monitor: &Monitor) -> Option<f32> {
+    if monitor.width_mm == 0 {
+        return None;
+    }
+    let dpi = monitor.width as f32 / (monitor.width_mm as f32 / 25.4);
+    let scale = (dpi / BASE_DPI * 4.).round() / 4.;
+    Some(scale.max(1.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(width: u16, width_mm: u32) -> Monitor {
+        Monitor {
+            name: "DP-1".to_owned(),
+            primary: true,
+            x: 0,
+            y: 0,
+            width,
+            height: width / 16 * 9,
+            width_mm,
+            height_mm: width_mm / 16 * 9,
+        }
+    }
+
+    #[test]
+    fn scale_values() {
+        assert_eq!(parse_scale("2"), Some(2.));
+        assert_eq!(parse_scale(" 1.5\n"), Some(1.5));
+        assert_eq!(parse_scale("0"), None);
+        assert_eq!(parse_scale("-1"), None);
+        assert_eq!(parse_scale("auto"), None);
+        assert_eq!(parse_scale(""), None);
+    }
+
+    #[test]
+    fn xft_dpi_resources() {
+        let resources = b"Xcursor.size:\t24\nXft.antialias:\t1\nXft.dpi:\t144\n";
+        assert_eq!(parse_xft_dpi(resources), Some(144.));
+        // the first valid entry
+        assert_eq!(
+            parse_xft_dpi(b"Xft.dpi: 0\nXft.dpi: x\nXft.dpi: 192"),
+            Some(192.)
+        );
+        // other resources only match on the full name
+        assert_eq!(parse_xft_dpi(b"Xft.dpix: 144\n*Xft.dpi: 144"), None);
+        assert_eq!(parse_xft_dpi(b""), None);
+    }
+
+    #[test]
+    fn physical_scale_factors() {
+        // 27" 4K, 163 DPI
+        assert_eq!(physical_scale_factor(&monitor(3840, 597)), Some(1.75));
+        // 13" 2560, 227 DPI
+        assert_eq!(physical_scale_factor(&monitor(2560, 286)), Some(2.25));
+        // low DPI never scales down
+        assert_eq!(physical_scale_factor(&monitor(1920, 600)), Some(1.));
+        assert_eq!(physical_scale_factor(&monitor(1920, 0)), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Representative egui scenes, drawn by the benchmarks of the frame pipeline and by the
+//! `--bench-scene` flag of the demo binary, so that regressions in the render path show.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::scenes::Scene;
+//! use egui_wgpu_x11::HeadlessOverlay;
+//!
+//! let mut overlay = HeadlessOverlay::new(1280, 720)?;
+//! let mut app = Scene::Shapes.app();
+//! for _ in 0..100 {
+//!     overlay.render(&mut app)?;
+//! }
+//! # anyhow::Ok(())
+//! ```
+
+use std::f32::consts::TAU;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use egui::{pos2, vec2, Color32, ColorImage, Context, Shape, Stroke, TextureFilter, TextureHandle};
+
+use crate::OverlayApp;
+
+/// What a scene mostly stresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scene {
+    /// Windows of labels, text layout and glyph meshes.
+    Text,
+    /// Sliders, buttons, checkboxes and progress bars in grids.
+    Widgets,
+    /// Circles, lines and curves from the painter, anti-aliased tessellation.
+    Shapes,
+    /// Many small images, a draw call each.
+    Images,
+}
+
+impl Scene {
+    pub const ALL: [Scene; 4] = [Scene::Text, Scene::Widgets, Scene::Shapes, Scene::Images];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scene::Text => "text",
+            Scene::Widgets => "widgets",
+            Scene::Shapes => "shapes",
+            Scene::Images => "images",
+        }
+    }
+
+    /// The scene as an app, animated so that every frame is laid out and tessellated again.
+    pub fn app(self) -> SceneApp {
+        SceneApp {
+            scene: self,
+            frame: 0,
+            images: Vec::new(),
+            checked: [false; 32],
+        }
+    }
+}
+
+impl fmt::Display for Scene {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Scene {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        Scene::ALL
+            .into_iter()
+            .find(|scene| scene.name() == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown scene {:?}, one of text, widgets, shapes, images",
+                    name
+                )
+            })
+    }
+}
+
+/// Draws a [`Scene`], repainting continuously.
+pub struct SceneApp {
+    scene: Scene,
+    frame: u64,
+    /// loaded on the first frame
+    images: Vec<TextureHandle>,
+    checked: [bool; 32],
+}
+
+impl SceneApp {
+    pub fn scene(&self) -> Scene {
+        self.scene
+    }
+
+    fn text(&mut self, ctx: &Context) {
+        for w in 0..4 {
+            egui::Window::new(format!("Text {}", w))
+                .default_pos(pos2(20. + w as f32 * 300., 20.))
+                .default_size(vec2(280., 600.))
+                .show(ctx, |ui| {
+                    for line in 0..40 {
+                        ui.label(format!(
+                            "line {} of window {}, frame {}: the quick brown fox",
+                            line, w, self.frame
+                        ));
+                    }
+                });
+        }
+    }
+
+    fn widgets(&mut self, ctx: &Context) {
+        let t = self.frame as f32 / 60.;
+        for w in 0..2 {
+            egui::Window::new(format!("Widgets {}", w))
+                .default_pos(pos2(20. + w as f32 * 420., 20.))
+                .show(ctx, |ui| {
+                    egui::Grid::new(w).striped(true).show(ui, |ui| {
+                        for row in 0..16 {
+                            let mut value = (t + row as f32 / 4.).sin() * 50. + 50.;
+                            ui.add(egui::Slider::new(&mut value, 0.0..=100.));
+                            ui.checkbox(&mut self.checked[w * 16 + row], "check");
+                            let _ = ui.button(format!("button {}", row));
+                            ui.add(egui::ProgressBar::new(value / 100.).desired_width(80.));
+                            ui.end_row();
+                        }
+                    });
+                });
+        }
+    }
+
+    fn shapes(&mut self, ctx: &Context) {
+        let t = self.frame as f32 / 60.;
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none())
+            .show(ctx, |ui| {
+                let rect = ui.max_rect();
+                let painter = ui.painter();
+                for i in 0..200 {
+                    let angle = i as f32 / 200. * TAU + t;
+                    let radius = rect.height() * (0.1 + 0.35 * (i % 10) as f32 / 10.);
+                    let center = rect.center() + vec2(angle.cos(), angle.sin()) * radius;
+                    let color = Color32::from_rgb((i * 7 % 256) as u8, (i * 13 % 256) as u8, 200);
+                    painter.circle(center, 8., color, Stroke::new(1., Color32::WHITE));
+                    painter.line_segment(
+                        [rect.center(), center],
+                        Stroke::new(1.5, color.linear_multiply(0.5)),
+                    );
+                }
+                let points: Vec<_> = (0..500)
+                    .map(|i| {
+                        let x = rect.left() + rect.width() * i as f32 / 499.;
+                        let y = rect.center().y + (i as f32 / 20. + t * 3.).sin() * 100.;
+                        pos2(x, y)
+                    })
+                    .collect();
+                painter.add(Shape::line(points, Stroke::new(2., Color32::YELLOW)));
+            });
+    }
+
+    fn images(&mut self, ctx: &Context) {
+        if self.images.is_empty() {
+            self.images = (0..64)
+                .map(|i| {
+                    let color = Color32::from_rgb((i * 4) as u8, 255 - (i * 4) as u8, 128);
+                    let image = ColorImage::new([32, 32], color);
+                    ctx.load_texture(format!("scene {}", i), image, TextureFilter::Linear)
+                })
+                .collect();
+        }
+        let t = self.frame as f32 / 60.;
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none())
+            .show(ctx, |ui| {
+                let rect = ui.max_rect();
+                for (i, image) in self.images.iter().enumerate() {
+                    let x = (i % 8) as f32 * 40. + (t + i as f32).sin() * 10.;
+                    let y = (i / 8) as f32 * 40.;
+                    let at = egui::Rect::from_min_size(rect.min + vec2(x, y), vec2(32., 32.));
+                    ui.put(at, egui::Image::new(image, vec2(32., 32.)));
+                }
+            });
+    }
+}
+
+impl OverlayApp for SceneApp {
+    fn ui(&mut self, ctx: &Context) {
+        match self.scene {
+            Scene::Text => self.text(ctx),
+            Scene::Widgets => self.widgets(ctx),
+            Scene::Shapes => self.shapes(ctx),
+            Scene::Images => self.images(ctx),
+        }
+        self.frame += 1;
+        ctx.request_repaint();
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::{bail, Result};
+use egui::{Color32, ColorImage};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    ConnectionExt as _, Drawable, ImageFormat, ImageOrder, Rectangle, Visualid,
+};
+
+/// Reads `rect` of the root window of `screen_num`, windows and overlays included, clamped to
+/// the screen.
+pub(crate) fn grab_screen<Conn>(
+    conn: &Conn,
+    screen_num: usize,
+    rect: Rectangle,
+) -> Result<ColorImage>
+where
+    Conn: Connection,
+{
+    let screen = &conn.setup().roots[screen_num];
+    let x0 = rect.x.clamp(0, screen.width_in_pixels as i16);
+    let y0 = rect.y.clamp(0, screen.height_in_pixels as i16);
+    let x1 =
+        (i32::from(rect.x) + i32::from(rect.width)).clamp(x0.into(), screen.width_in_pixels.into());
+    let y1 = (i32::from(rect.y) + i32::from(rect.height))
+        .clamp(y0.into(), screen.height_in_pixels.into());
+    let rect = Rectangle {
+        x: x0,
+        y: y0,
+        width: (x1 - i32::from(x0)) as u16,
+        height: (y1 - i32::from(y0)) as u16,
+    };
+    read_image(
+        conn,
+        screen.root,
+        screen.root_visual,
+        screen.root_depth,
+        rect,
+    )
+}
+
+/// Reads `rect` of `drawable`, of `visual` and `depth`. Handles the usual 24 and 32 bits
+/// deep ones, stored as 32 bits BGRX or premultiplied BGRA pixels.
+pub(crate) fn read_image<Conn>(
+    conn: &Conn,
+    drawable: Drawable,
+    visual: Visualid,
+    depth: u8,
+    rect: Rectangle,
+) -> Result<ColorImage>
+where
+    Conn: Connection,
+{
+    let setup = conn.setup();
+    let bgrx = setup
+        .roots
+        .iter()
+        .flat_map(|screen| &screen.allowed_depths)
+        .flat_map(|depth| &depth.visuals)
+        .find(|candidate| candidate.visual_id == visual)
+        .is_some_and(|visual| {
+            (visual.red_mask, visual.green_mask, visual.blue_mask) == (0xff0000, 0xff00, 0xff)
+        });
+    let bits_per_pixel = setup
+        .pixmap_formats
+        .iter()
+        .find(|format| format.depth == depth)
+        .map(|format| format.bits_per_pixel);
+    if !bgrx || bits_per_pixel != Some(32) || setup.image_byte_order != ImageOrder::LSB_FIRST {
+        bail!(
+            "can't read a {} bits deep image with {:?} bits per pixel",
+            depth,
+            bits_per_pixel
+        );
+    }
+    if rect.width == 0 || rect.height == 0 {
+        return Ok(ColorImage::new([0, 0], Color32::TRANSPARENT));
+    }
+
+    let image = conn
+        .get_image(
+            ImageFormat::Z_PIXMAP,
+            drawable,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            !0,
+        )?
+        .reply()?;
+    let pixels = image
+        .data
+        .chunks_exact(4)
+        .map(|bgra| match depth {
+            32 => Color32::from_rgba_premultiplied(bgra[2], bgra[1], bgra[0], bgra[3]),
+            _ => Color32::from_rgb(bgra[2], bgra[1], bgra[0]),
+        })
+        .collect();
+    Ok(ColorImage {
+        size: [rect.width.into(), rect.height.into()],
+        pixels,
+    })
+}
@@ -0,0 +1,442 @@
+//! HUD layouts written in [Rhai](https://rhai.rs) scripts, reloaded when the script file
+//! changes.
+//!
+//! The script's `ui` function returns the content of the overlay, a list of elements built
+//! by the functions below, drawn from top to bottom. Other values are shown as labels. The
+//! functions of a script share `this`, an object map kept between calls and reloads, e.g.
+//! for samples:
+//!
+//! ```rhai
+//! fn ui() {
+//!     let capacity = read("/sys/class/power_supply/BAT0/capacity").parse_float();
+//!     if this.samples == () { this.samples = []; }
+//!     this.samples.push(capacity);
+//!     if capacity < 10.0 { notify("Battery", "plug me in"); }
+//!     [
+//!         heading("Battery"),
+//!         progress(capacity / 100.0, `${capacity}%`),
+//!         plot("battery", this.samples),
+//!         row(["logo", image("logo", 16.0, 16.0)]),
+//!     ]
+//! }
+//! ```
+//!
+//! | Function | Element |
+//! |-|-|
+//! | `label(text)`, `heading(text)`, `small(text)` | text |
+//! | `colored(text, "#rrggbb")` | colored text |
+//! | `separator()`, `space(points)` | spacing |
+//! | `progress(fraction)`, `progress(fraction, text)` | a progress bar |
+//! | `plot(name, values)` | a line of the values, evenly spaced |
+//! | `image(name, width, height)` | an image added with [`ScriptedHud::image`] |
+//! | `row(elements)`, `column(elements)`, `panel(elements)` | a layout of other elements |
+//!
+//! Scripts drive the overlay with `show()`, `hide()`, `toggle()`, `toggle_passthrough()`,
+//! `set_opacity(opacity)`, `notify(summary, body)` and `quit()`, and read data with
+//! `read(path)`, the trimmed content of a file, and `timestamp()`, the seconds since the
+//! Unix epoch.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context as _, Result};
+use egui::plot::{Line, Plot, PlotPoints};
+use egui::{Color32, ColorImage, Frame, RichText, TextureHandle};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use tracing::{debug, warn};
+
+use crate::command::Command;
+use crate::config::Config;
+use crate::{Overlay, OverlayApp};
+
+/// Bounds the work of a call, so that a script looping forever doesn't freeze the overlay.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// An overlay app drawing what a Rhai script lays out, see the [module](self) docs.
+///
+/// ```no_run
+/// use egui_wgpu_x11::scripting::ScriptedHud;
+/// use egui_wgpu_x11::Overlay;
+///
+/// let hud = ScriptedHud::new("hud.rhai")?;
+/// Overlay::builder().run(hud)?;
+/// # anyhow::Ok(())
+/// ```
+pub struct ScriptedHud {
+    path: PathBuf,
+    engine: Engine,
+    script: Option<AST>,
+    modified: Option<SystemTime>,
+    /// of the last load, the previous script running meanwhile
+    load_error: Option<String>,
+    /// of the last call
+    run_error: Option<String>,
+    /// `this`
+    state: Dynamic,
+    refresh: Duration,
+    last_refresh: Option<Instant>,
+    elements: Vec<Element>,
+    commands: Rc<RefCell<Vec<Command>>>,
+    images: Vec<(String, ColorImage)>,
+    textures: HashMap<String, TextureHandle>,
+}
+
+impl ScriptedHud {
+    /// Loads the script at `path`, relative to `$XDG_CONFIG_HOME/egui-overlay` unless
+    /// absolute. Only a missing file fails, errors in the script are shown in the overlay
+    /// until fixed.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = script_path(path.as_ref())?;
+        if !path.is_file() {
+            return Err(anyhow!("no script at {}", path.display()));
+        }
+        let commands = Rc::new(RefCell::new(Vec::new()));
+        let mut hud = Self {
+            path,
+            engine: engine(&commands),
+            script: None,
+            modified: None,
+            load_error: None,
+            run_error: None,
+            state: Dynamic::from_map(Map::new()),
+            refresh: Duration::from_secs(1),
+            last_refresh: None,
+            elements: Vec::new(),
+            commands,
+            images: Vec::new(),
+            textures: HashMap::new(),
+        };
+        hud.reload();
+        Ok(hud)
+    }
+
+    /// Names an image for the `image` function of the script.
+    pub fn image(mut self, name: impl Into<String>, image: ColorImage) -> Self {
+        self.images.push((name.into(), image));
+        self
+    }
+
+    /// How often the script's `ui` runs, every second by default. The overlay draws the
+    /// last elements in between.
+    pub fn refresh(mut self, refresh: Duration) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// compiles the script again if it changed since the last time
+    fn reload(&mut self) {
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified());
+        let modified = match modified {
+            Ok(modified) => Some(modified),
+            // e.g. while an editor replaces the file, the previous script stays
+            Err(_) if self.script.is_some() => return,
+            Err(_) => None,
+        };
+        if self.modified.is_some() && self.modified == modified {
+            return;
+        }
+        self.modified = modified;
+        debug!("loading {}", self.path.display());
+        let result = fs::read_to_string(&self.path)
+            .with_context(|| format!("reading {}", self.path.display()))
+            .and_then(|source| {
+                let script = self
+                    .engine
+                    .compile(source)
+                    .map_err(|e| anyhow!("{}: {}", self.path.display(), e))?;
+                self.engine
+                    .run_ast_with_scope(&mut Scope::new(), &script)
+                    .map_err(|e| anyhow!("{}: {}", self.path.display(), e))?;
+                Ok(script)
+            });
+        match result {
+            Ok(script) => {
+                self.script = Some(script);
+                self.load_error = None;
+            }
+            Err(e) => report(&mut self.load_error, e),
+        }
+        self.last_refresh = None;
+    }
+
+    /// runs `ui` when due
+    fn run(&mut self) {
+        let due = self
+            .last_refresh
+            .is_none_or(|last| last.elapsed() >= self.refresh);
+        let Some(script) = self.script.as_ref().filter(|_| due) else {
+            return;
+        };
+        self.last_refresh = Some(Instant::now());
+        let options = CallFnOptions::new()
+            .bind_this_ptr(&mut self.state)
+            .eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut Scope::new(),
+            script,
+            "ui",
+            (),
+        );
+        match result {
+            Ok(elements) => {
+                self.elements = elements_of(elements);
+                self.run_error = None;
+            }
+            Err(e) => report(
+                &mut self.run_error,
+                anyhow!("{}: {}", self.path.display(), e),
+            ),
+        }
+    }
+}
+
+/// keeps `error` in `slot`, logged unless it already was
+fn report(slot: &mut Option<String>, error: anyhow::Error) {
+    let error = format!("{:#}", error);
+    if slot.as_ref() != Some(&error) {
+        warn!("{}", error);
+    }
+    *slot = Some(error);
+}
+
+impl OverlayApp for ScriptedHud {
+    fn ui(&mut self, ctx: &egui::Context) {
+        for (name, image) in self.images.drain(..) {
+            let texture = ctx.load_texture(&name, image, egui::TextureFilter::Linear);
+            self.textures.insert(name, texture);
+        }
+        self.reload();
+        self.run();
+        ctx.request_repaint_after(self.refresh);
+
+        egui::CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                for error in self.load_error.iter().chain(&self.run_error) {
+                    Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.colored_label(ui.visuals().error_fg_color, error);
+                    });
+                }
+                for element in &self.elements {
+                    element.ui(ui, &self.textures);
+                }
+            });
+    }
+
+    fn update(&mut self, overlay: &mut Overlay) -> Result<()> {
+        let commands = self.commands.take();
+        for command in commands {
+            command.run(overlay)?;
+        }
+        Ok(())
+    }
+}
+
+/// What a script lays out.
+#[derive(Clone)]
+enum Element {
+    Label(RichText),
+    Separator,
+    Space(f32),
+    Progress(f32, Option<String>),
+    Plot(String, Vec<f64>),
+    Image(String, egui::Vec2),
+    Row(Vec<Element>),
+    Column(Vec<Element>),
+    Panel(Vec<Element>),
+}
+
+impl Element {
+    fn ui(&self, ui: &mut egui::Ui, textures: &HashMap<String, TextureHandle>) {
+        match self {
+            Self::Label(text) => {
+                ui.label(text.clone());
+            }
+            Self::Separator => {
+                ui.separator();
+            }
+            Self::Space(points) => ui.add_space(*points),
+            Self::Progress(fraction, text) => {
+                let mut bar = egui::ProgressBar::new(*fraction);
+                if let Some(text) = text {
+                    bar = bar.text(text.as_str());
+                }
+                ui.add(bar);
+            }
+            Self::Plot(name, values) => {
+                Plot::new(("script", name.as_str()))
+                    .height(48.)
+                    .show_axes([false, false])
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .allow_scroll(false)
+                    .show(ui, |plot| {
+                        plot.line(Line::new(PlotPoints::from_ys_f64(values)))
+                    });
+            }
+            Self::Image(name, size) => match textures.get(name) {
+                Some(texture) => {
+                    ui.image(texture, *size);
+                }
+                None => {
+                    ui.colored_label(
+                        ui.visuals().error_fg_color,
+                        format!("no image named {:?}", name),
+                    );
+                }
+            },
+            Self::Row(elements) => {
+                ui.horizontal(|ui| elements.iter().for_each(|e| e.ui(ui, textures)));
+            }
+            Self::Column(elements) => {
+                ui.vertical(|ui| elements.iter().for_each(|e| e.ui(ui, textures)));
+            }
+            Self::Panel(elements) => {
+                Frame::popup(ui.style())
+                    .show(ui, |ui| elements.iter().for_each(|e| e.ui(ui, textures)));
+            }
+        }
+    }
+}
+
+/// the elements returned by a script, the other values as labels
+fn elements_of(value: Dynamic) -> Vec<Element> {
+    if value.is_unit() {
+        return Vec::new();
+    }
+    match value.try_cast::<Array>() {
+        Some(values) => values.into_iter().map(element_of).collect(),
+        None => Vec::new(),
+    }
+}
+
+fn element_of(value: Dynamic) -> Element {
+    if value.is::<Element>() {
+        return value.cast();
+    }
+    match value.into_string() {
+        Ok(text) => Element::Label(text.into()),
+        Err(type_name) => Element::Label(format!("<{}>", type_name).into()),
+    }
+}
+
+/// the numbers of an array, as floats
+fn numbers(values: Array) -> Vec<f64> {
+    values
+        .into_iter()
+        .filter_map(|value| {
+            value
+                .as_float()
+                .ok()
+                .or_else(|| value.as_int().ok().map(|int| int as f64))
+        })
+        .collect()
+}
+
+/// `#rrggbb`, white otherwise
+fn color(hex: &str) -> Color32 {
+    let hex = hex.trim_start_matches('#');
+    match u32::from_str_radix(hex, 16) {
+        Ok(rgb) if hex.len() == 6 => {
+            Color32::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+        }
+        _ => Color32::WHITE,
+    }
+}
+
+/// the engine with the layout, control and data functions registered, the controls queued
+/// in `commands`
+fn engine(commands: &Rc<RefCell<Vec<Command>>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.register_type_with_name::<Element>("Element");
+
+    engine
+        .register_fn("label", |text: &str| Element::Label(text.into()))
+        .register_fn("heading", |text: &str| {
+            Element::Label(RichText::new(text).heading())
+        })
+        .register_fn("small", |text: &str| {
+            Element::Label(RichText::new(text).small())
+        })
+        .register_fn("colored", |text: &str, hex: &str| {
+            Element::Label(RichText::new(text).color(color(hex)))
+        })
+        .register_fn("separator", || Element::Separator)
+        .register_fn("space", |points: f64| Element::Space(points as f32))
+        .register_fn("progress", |fraction: f64| {
+            Element::Progress(fraction as f32, None)
+        })
+        .register_fn("progress", |fraction: f64, text: &str| {
+            Element::Progress(fraction as f32, Some(text.to_owned()))
+        })
+        .register_fn("plot", |name: &str, values: Array| {
+            Element::Plot(name.to_owned(), numbers(values))
+        })
+        .register_fn("image", |name: &str, width: f64, height: f64| {
+            Element::Image(name.to_owned(), egui::vec2(width as f32, height as f32))
+        })
+        .register_fn("row", |elements: Array| {
+            Element::Row(elements.into_iter().map(element_of).collect())
+        })
+        .register_fn("column", |elements: Array| {
+            Element::Column(elements.into_iter().map(element_of).collect())
+        })
+        .register_fn("panel", |elements: Array| {
+            Element::Panel(elements.into_iter().map(element_of).collect())
+        });
+
+    let queue = |command: Command| {
+        let commands = commands.clone();
+        move || commands.borrow_mut().push(command.clone())
+    };
+    engine
+        .register_fn("show", queue(Command::Show))
+        .register_fn("hide", queue(Command::Hide))
+        .register_fn("toggle", queue(Command::Toggle))
+        .register_fn("toggle_passthrough", queue(Command::TogglePassthrough))
+        .register_fn("quit", queue(Command::Quit));
+    let commands_ = commands.clone();
+    engine.register_fn("set_opacity", move |opacity: f64| {
+        commands_.borrow_mut().push(Command::SetOpacity {
+            opacity: opacity as f32,
+        })
+    });
+    let commands = commands.clone();
+    engine.register_fn("notify", move |summary: &str, body: &str| {
+        commands.borrow_mut().push(Command::Notify {
+            summary: summary.to_owned(),
+            body: body.to_owned(),
+            timeout_ms: None,
+        })
+    });
+
+    engine
+        .register_fn("read", |path: &str| {
+            fs::read_to_string(path)
+                .map(|content| content.trim().to_owned())
+                .unwrap_or_default()
+        })
+        .register_fn("timestamp", || {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64()
+        });
+    engine
+}
+
+/// `path` if absolute, otherwise under `$XDG_CONFIG_HOME/egui-overlay`
+fn script_path(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_owned());
+    }
+    let config = Config::default_path()?;
+    let dir = config.parent().context("no config directory")?;
+    Ok(dir.join(path))
+}
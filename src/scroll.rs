@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use egui::{vec2, Event, Modifiers, Vec2};
+use x11rb::connection::Connection;
+use x11rb::protocol::xinput::{
+    self, ConnectionExt as _, Device, DeviceClassData, DeviceId, EventMask, Fp3232, ScrollType,
+    XIEventMask,
+};
+use x11rb::protocol::xproto::Window;
+use x11rb::protocol::Event as XEvent;
+
+/// points scrolled by a wheel notch, as in egui-winit
+const POINTS_PER_NOTCH: f32 = 50.;
+
+/// The egui event of a wheel notch, X buttons 4 to 7, `None` for other buttons.
+pub(crate) fn wheel_event(button: u8, modifiers: Modifiers) -> Option<Event> {
+    let notches = match button {
+        4 => vec2(0., 1.),
+        5 => vec2(0., -1.),
+        6 => vec2(1., 0.),
+        7 => vec2(-1., 0.),
+        _ => return None,
+    };
+    Some(scroll_event(notches * POINTS_PER_NOTCH, modifiers))
+}
+
+/// Zooms with ctrl held, scrolls horizontally with shift, like egui-winit.
+fn scroll_event(delta: Vec2, modifiers: Modifiers) -> Event {
+    if modifiers.ctrl || modifiers.command {
+        Event::Zoom((delta.y / 200.).exp())
+    } else if modifiers.shift {
+        Event::Scroll(vec2(delta.x + delta.y, 0.))
+    } else {
+        Event::Scroll(delta)
+    }
+}
+
+/// A scroll valuator of an input device.
+struct Valuator {
+    horizontal: bool,
+    /// the change of value of one wheel notch, negative if inverted
+    increment: f64,
+    /// unknown until the first motion, and after the pointer left the window
+    last: Option<f64>,
+}
+
+/// Smooth scrolling of touchpads and high resolution wheels, from the XInput2 scroll valuators
+/// of the motion events, see [`select_xi2_events`].
+pub(crate) struct SmoothScroll {
+    /// by slave device and valuator number
+    valuators: HashMap<(DeviceId, u16), Valuator>,
+}
+
+impl SmoothScroll {
+    pub fn new<Conn>(conn: &Conn) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let mut scroll = Self {
+            valuators: HashMap::new(),
+        };
+        scroll.query_devices(conn)?;
+        Ok(scroll)
+    }
+
+    /// The scroll of a motion event, if any.
+    pub fn handle_event<Conn>(
+        &mut self,
+        conn: &Conn,
+        event: &XEvent,
+        modifiers: Modifiers,
+    ) -> Result<Option<Event>>
+    where
+        Conn: Connection,
+    {
+        match event {
+            XEvent::XinputMotion(e) => {
+                let mut delta = Vec2::ZERO;
+                for (number, value) in set_valuators(&e.valuator_mask).zip(&e.axisvalues) {
+                    let Some(valuator) = self.valuators.get_mut(&(e.sourceid, number)) else {
+                        continue;
+                    };
+                    let value = fp3232(*value);
+                    let Some(last) = valuator.last.replace(value) else {
+                        continue;
+                    };
+                    // the values grow scrolling down or right, unlike egui's
+                    let notches = -((value - last) / valuator.increment) as f32;
+                    if valuator.horizontal {
+                        delta.x += notches;
+                    } else {
+                        delta.y += notches;
+                    }
+                }
+                if delta != Vec2::ZERO {
+                    return Ok(Some(scroll_event(delta * POINTS_PER_NOTCH, modifiers)));
+                }
+            }
+            // e.g. another touchpad plugged in
+            XEvent::XinputDeviceChanged(_) => self.query_devices(conn)?,
+            // the values change meanwhile, as the pointer moves over other windows
+            XEvent::EnterNotify(_) | XEvent::LeaveNotify(_) => {
+                for valuator in self.valuators.values_mut() {
+                    valuator.last = None;
+                }
+            }
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn query_devices<Conn>(&mut self, conn: &Conn) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        let devices = conn.xinput_xi_query_device(Device::ALL)?.reply()?;
+        self.valuators = devices
+            .infos
+            .iter()
+            .flat_map(|device| {
+                device.classes.iter().filter_map(|class| match &class.data {
+                    DeviceClassData::Scroll(scroll) if fp3232(scroll.increment) != 0. => Some((
+                        (device.deviceid, scroll.number),
+                        Valuator {
+                            horizontal: scroll.scroll_type == ScrollType::HORIZONTAL,
+                            increment: fp3232(scroll.increment),
+                            last: None,
+                        },
+                    )),
+                    _ => None,
+                })
+            })
+            .collect();
+        Ok(())
+    }
+}
+
+/// Gets the XInput2 pointer events of `window` instead of the core ones, which then stop
+/// coming, see [`crate::pointer::pointer_event`] for their translation. Touch events too, from
+/// XInput 2.2. Returns whether they are.
+pub(crate) fn select_xi2_events<Conn>(conn: &Conn, window: Window) -> Result<bool>
+where
+    Conn: Connection,
+{
+    if conn
+        .extension_information(xinput::X11_EXTENSION_NAME)?
+        .is_none()
+    {
+        bail!("XInput extension not available");
+    }
+    // scroll classes came with 2.1, touch events with 2.2
+    let version = conn.xinput_xi_query_version(2, 2)?.reply()?;
+    let version = (version.major_version, version.minor_version);
+    if version < (2, 1) {
+        bail!(
+            "XInput {}.{} is too old for smooth scrolling, 2.1 is needed",
+            version.0,
+            version.1
+        );
+    }
+    let touch = version >= (2, 2);
+    let mut mask = XIEventMask::MOTION
+        | XIEventMask::BUTTON_PRESS
+        | XIEventMask::BUTTON_RELEASE
+        | XIEventMask::DEVICE_CHANGED;
+    if touch {
+        mask |= XIEventMask::TOUCH_BEGIN | XIEventMask::TOUCH_UPDATE | XIEventMask::TOUCH_END;
+    }
+    conn.xinput_xi_select_events(
+        window,
+        &[EventMask {
+            deviceid: Device::ALL_MASTER.into(),
+            mask: vec![mask],
+        }],
+    )?;
+    Ok(touch)
+}
+
+/// the numbers of the valuators set in `mask`, in the order of their values
+fn set_valuators(mask: &[u32]) -> impl Iterator<Item = u16> + '_ {
+    mask.iter().enumerate().flat_map(|(word, bits)| {
+        (0..32)
+            .filter(move |bit| bits & (1 << bit) != 0)
+            .map(move |bit| (word * 32 + bit) as u16)
+    })
+}
+
+fn fp3232(value: Fp3232) -> f64 {
+    f64::from(value.integral) + f64::from(value.frac) / 2f64.powi(32)
+}
@@ -0,0 +1,62 @@
+//! SIGINT and SIGTERM stopping the loops, which sleep on a socket written by the handler.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::OnceLock;
+
+use nix::libc::c_int;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+/// the end of the socket written by the handler
+static SENDER: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_signal(_: c_int) {
+    RECEIVED.store(true, Ordering::SeqCst);
+    let fd = SENDER.load(Ordering::SeqCst);
+    if fd >= 0 {
+        // async-signal-safe, unlike most of std
+        unsafe { nix::libc::write(fd, [0u8].as_ptr().cast(), 1) };
+    }
+}
+
+/// Installs the handlers once, returns the socket readable after a signal. It is never
+/// drained, so that it wakes every loop polling it.
+///
+/// The handlers are reset once called: a second signal kills a stuck process.
+pub(crate) fn watch() -> io::Result<RawFd> {
+    static SOCKETS: OnceLock<(UnixStream, UnixStream)> = OnceLock::new();
+    static INSTALLED: OnceLock<()> = OnceLock::new();
+
+    let (sender, receiver) = match SOCKETS.get() {
+        Some(sockets) => sockets,
+        None => {
+            let (sender, receiver) = UnixStream::pair()?;
+            sender.set_nonblocking(true)?;
+            SOCKETS.get_or_init(|| (sender, receiver))
+        }
+    };
+    SENDER.store(sender.as_raw_fd(), Ordering::SeqCst);
+
+    let mut installed: io::Result<()> = Ok(());
+    INSTALLED.get_or_init(|| {
+        let action = SigAction::new(
+            SigHandler::Handler(on_signal),
+            SaFlags::SA_RESTART | SaFlags::SA_RESETHAND,
+            SigSet::empty(),
+        );
+        for signal in [Signal::SIGINT, Signal::SIGTERM] {
+            if let Err(e) = unsafe { sigaction(signal, &action) } {
+                installed = Err(e.into());
+            }
+        }
+    });
+    installed?;
+    Ok(receiver.as_raw_fd())
+}
+
+pub(crate) fn received() -> bool {
+    RECEIVED.load(Ordering::SeqCst)
+}
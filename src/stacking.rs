@@ -0,0 +1,176 @@
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{
+    AtomEnum, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt as _, EventMask,
+    MapState, StackMode, Window,
+};
+use x11rb::protocol::Event;
+
+/// Keeps the overlay above the other windows, the way the window manager allows.
+pub(crate) struct Stacking {
+    /// `_NET_WM_NAME` of the EWMH window manager, if any
+    wm_name: Option<String>,
+    /// the window manager keeps the managed overlay above, through `_NET_WM_STATE_ABOVE`
+    by_wm: bool,
+    /// another window may cover the overlay since the last check
+    check: bool,
+}
+
+impl Stacking {
+    /// Otherwise subscribes to the changes of the windows stacking, see [`Stacking::handle_event`].
+    pub fn new<Conn>(conn: &Conn, root: Window, managed: bool) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let atom = |name: &str| -> Result<_> {
+            Ok(conn.intern_atom(false, name.as_bytes())?.reply()?.atom)
+        };
+
+        // the window manager sets its check window on both the root and itself,
+        // a stale one left by a dead window manager lacks the latter
+        let check = atom("_NET_SUPPORTING_WM_CHECK")?;
+        let check_window = |window| -> Result<Option<Window>> {
+            Ok(conn
+                .get_property(false, window, check, AtomEnum::WINDOW, 0, 1)?
+                .reply()
+                .ok()
+                .and_then(|reply| reply.value32()?.next()))
+        };
+        let wm_window = check_window(root)?
+            .filter(|&window| check_window(window).ok().flatten() == Some(window));
+
+        let wm_name = match wm_window {
+            Some(window) => {
+                let name = conn
+                    .get_property(
+                        false,
+                        window,
+                        atom("_NET_WM_NAME")?,
+                        atom("UTF8_STRING")?,
+                        0,
+                        256,
+                    )?
+                    .reply()?;
+                Some(String::from_utf8_lossy(&name.value).into_owned())
+            }
+            None => None,
+        };
+
+        let by_wm = managed && wm_window.is_some() && {
+            let above = atom("_NET_WM_STATE_ABOVE")?;
+            let supported = conn
+                .get_property(
+                    false,
+                    root,
+                    atom("_NET_SUPPORTED")?,
+                    AtomEnum::ATOM,
+                    0,
+                    u32::MAX,
+                )?
+                .reply()?;
+            supported
+                .value32()
+                .is_some_and(|mut atoms| atoms.any(|atom| atom == above))
+        };
+
+        if !by_wm {
+            conn.change_window_attributes(
+                root,
+                &ChangeWindowAttributesAux::new().event_mask(EventMask::SUBSTRUCTURE_NOTIFY),
+            )?;
+        }
+
+        Ok(Self {
+            wm_name,
+            by_wm,
+            check: true,
+        })
+    }
+
+    pub fn wm_name(&self) -> Option<&str> {
+        self.wm_name.as_deref()
+    }
+
+    /// Notes windows mapped or restacked, which may now cover the overlay.
+    pub fn handle_event(&mut self, event: &Event, root: Window) {
+        match event {
+            Event::MapNotify(e) if e.event == root => self.check = true,
+            Event::CirculateNotify(e) if e.event == root => self.check = true,
+            Event::ConfigureNotify(e) if e.event == root => self.check = true,
+            _ => {}
+        }
+    }
+
+    /// Whether the next [`Stacking::keep_on_top`] has a check to do.
+    pub fn pending(&self) -> bool {
+        self.check && !self.by_wm
+    }
+
+    /// Forces the next [`Stacking::keep_on_top`] to check, e.g. once the window is mapped again.
+    pub fn recheck(&mut self) {
+        self.check = true;
+    }
+
+    /// Restacks the window just above the highest window covering it, unless the window
+    /// manager does it or nothing changed since the last call.
+    ///
+    /// Only mapped, non override-redirect windows count: other overlays, menus and tooltips
+    /// are left above, rather than fighting over the top. With a reparenting window manager,
+    /// the frame holding the window is restacked.
+    pub fn keep_on_top<Conn>(&mut self, conn: &Conn, root: Window, win_id: Window) -> Result<()>
+    where
+        Conn: Connection,
+    {
+        if self.by_wm || !std::mem::take(&mut self.check) {
+            return Ok(());
+        }
+
+        let top_level = top_level(conn, root, win_id)?;
+        // bottom to top
+        let children = conn.query_tree(root)?.reply()?.children;
+        let Some(position) = children.iter().position(|&window| window == top_level) else {
+            return Ok(());
+        };
+        let above = &children[position + 1..];
+
+        // pipelined, not to wait for each reply in turn
+        let cookies = above
+            .iter()
+            .map(|&window| conn.get_window_attributes(window))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut highest = None;
+        for (&window, cookie) in above.iter().zip(cookies) {
+            // destroyed meanwhile
+            let Ok(attributes) = cookie.reply() else {
+                continue;
+            };
+            if attributes.map_state == MapState::VIEWABLE && !attributes.override_redirect {
+                highest = Some(window);
+            }
+        }
+
+        if let Some(sibling) = highest {
+            let values = ConfigureWindowAux::new()
+                .sibling(sibling)
+                .stack_mode(StackMode::ABOVE);
+            conn.configure_window(top_level, &values)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The child of the root holding `window`: itself, or the frame a window manager put it in.
+fn top_level<Conn>(conn: &Conn, root: Window, window: Window) -> Result<Window>
+where
+    Conn: Connection,
+{
+    let mut window = window;
+    loop {
+        let parent = conn.query_tree(window)?.reply()?.parent;
+        if parent == root || parent == x11rb::NONE {
+            return Ok(window);
+        }
+        window = parent;
+    }
+}
@@ -0,0 +1,1864 @@
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    env, error, fmt, iter,
+    panic::{self, AssertUnwindSafe},
+    rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Once,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use egui::{
+    epaint::{ImageDelta, Primitive},
+    vec2, ClippedPrimitive, ColorImage, Context, Pos2, RawInput, Rect, TextureId, TexturesDelta,
+    Vec2,
+};
+
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use tracing::{debug, debug_span, warn};
+use wgpu_core::device::queue::{QueueSubmitError, QueueWriteError};
+use wgpu_core::device::DeviceError;
+use wgpu_core::present::{ConfigureSurfaceError, SurfaceError as CoreSurfaceError};
+use x11rb::protocol::xproto::Rectangle;
+
+use crate::budget::{TextureBudget, TextureMemory};
+use crate::capture::{offscreen_texture, read_texture};
+use crate::geometry::Geometry;
+use crate::paint::{CallbackContext, UserTexture, WgpuCallback, CALLBACK_FORMAT};
+use crate::panics::PanicGuard;
+use crate::renderer::{Renderer, RendererFactory, ScreenDescriptor};
+use crate::stats::FrameRecorder;
+use crate::uploads;
+use crate::watchdog::{Incident, Watchdog, WatchdogTimer};
+use crate::{OverlayApp, OverlayError};
+
+/// what the window shows where egui paints nothing, with straight alpha
+const CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.2,
+    g: 0.1,
+    b: 0.3,
+    a: 0.2,
+};
+
+/// bytes of the staging buffers of the paint callbacks, larger writes getting their own
+const STAGING_CHUNK_SIZE: wgpu::BufferAddress = 1 << 16;
+
+/// What a rendered frame reports back to the overlay.
+pub(crate) struct Frame {
+    /// Areas painted by egui, in points.
+    pub painted: Vec<Rect>,
+    /// When egui wants the next frame, `Duration::MAX` for never.
+    pub repaint_after: Duration,
+    /// Copied or cut text, empty if none.
+    pub copied_text: String,
+    /// The mouse cursor egui wants over the window.
+    pub cursor_icon: egui::CursorIcon,
+    /// The link clicked, if any.
+    pub open_url: Option<egui::output::OpenUrl>,
+    /// The texture memory, when the textures drawn alone just went over the budget.
+    pub over_budget: Option<TextureMemory>,
+}
+
+/// How the overlay surface encodes colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// An sRGB surface format: egui's colors and blending come out as designed.
+    /// Falls back to the other formats when the driver offers none.
+    #[default]
+    Srgb,
+    /// A plain `Unorm` surface format, egui_wgpu_backend's linear colors written as is:
+    /// they look darker and translucent areas blend differently. Only for drivers whose sRGB
+    /// surfaces misbehave.
+    Linear,
+}
+
+impl std::str::FromStr for ColorSpace {
+    type Err = anyhow::Error;
+
+    /// `srgb` or `linear`
+    fn from_str(color_space: &str) -> anyhow::Result<Self> {
+        match color_space.to_lowercase().as_str() {
+            "srgb" => Ok(Self::Srgb),
+            "linear" => Ok(Self::Linear),
+            _ => anyhow::bail!("expected srgb or linear, got {:?}", color_space),
+        }
+    }
+}
+
+/// How frames are drawn and presented, set when building the overlay.
+#[derive(Clone, Debug)]
+pub(crate) struct SurfaceOptions {
+    pub present_mode: wgpu::PresentMode,
+    pub msaa_samples: u32,
+    pub color_space: ColorSpace,
+    pub renderer: RendererFactory,
+    /// `false` for windows without an alpha channel, e.g. on 24-bit visuals
+    pub transparent: bool,
+    pub watchdog: Option<Watchdog>,
+}
+
+/// How the GPU adapter is picked.
+///
+/// The overlays of a thread picked with the same options share their device and queue, unless
+/// it can't present to their window. Each keeps its own egui render pass, as the textures of
+/// separate egui contexts share their ids, and its own surface format.
+///
+/// `OVERLAY_WGPU_BACKEND` (e.g. `vulkan`, `gl`) and `OVERLAY_WGPU_POWER` (`low` or `high`)
+/// override these settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GpuOptions {
+    /// `None` tries Vulkan, then GL, through EGL on the overlay window, then a software
+    /// adapter.
+    pub backends: Option<wgpu::Backends>,
+    /// Low power picks the integrated GPU of hybrid laptops, high performance the discrete one.
+    pub power_preference: wgpu::PowerPreference,
+    /// Only consider software adapters, e.g. llvmpipe.
+    pub force_fallback_adapter: bool,
+}
+
+impl GpuOptions {
+    /// Applies the environment overrides.
+    pub(crate) fn with_env(mut self) -> Result<Self, OverlayError> {
+        if let Ok(backend) = env::var("OVERLAY_WGPU_BACKEND") {
+            self.backends = Some(
+                parse_backends(&backend)
+                    .map_err(|e| OverlayError::config("OVERLAY_WGPU_BACKEND", e))?,
+            );
+        }
+        if let Ok(power) = env::var("OVERLAY_WGPU_POWER") {
+            self.power_preference = parse_power_preference(&power)
+                .map_err(|e| OverlayError::config("OVERLAY_WGPU_POWER", e))?;
+        }
+        Ok(self)
+    }
+
+    /// the backends to try in order, with whether to force a fallback adapter
+    fn candidates(&self) -> Vec<(String, wgpu::Backends, bool)> {
+        match self.backends {
+            Some(backends) => vec![(
+                format!("{:?}", backends),
+                backends,
+                self.force_fallback_adapter,
+            )],
+            None if self.force_fallback_adapter => {
+                vec![("fallback adapter".into(), wgpu::Backends::all(), true)]
+            }
+            None => vec![
+                ("Vulkan".into(), wgpu::Backends::VULKAN, false),
+                ("GL".into(), wgpu::Backends::GL, false),
+                ("fallback adapter".into(), wgpu::Backends::all(), true),
+            ],
+        }
+    }
+}
+
+/// e.g. `vulkan`, `gl`, or a comma separated list of both
+pub(crate) fn parse_backends(backends: &str) -> anyhow::Result<wgpu::Backends> {
+    let parsed = wgpu::util::parse_backends_from_comma_list(&backends.to_lowercase());
+    if parsed.is_empty() {
+        anyhow::bail!("unknown backend {:?}", backends);
+    }
+    Ok(parsed)
+}
+
+/// `low` or `high`
+pub(crate) fn parse_power_preference(power: &str) -> anyhow::Result<wgpu::PowerPreference> {
+    match power.to_lowercase().as_str() {
+        "low" => Ok(wgpu::PowerPreference::LowPower),
+        "high" => Ok(wgpu::PowerPreference::HighPerformance),
+        _ => anyhow::bail!("expected low or high, got {:?}", power),
+    }
+}
+
+/// No GPU could draw the overlay, see [`GpuError::attempts`] for what was tried.
+#[derive(Debug)]
+pub struct GpuError {
+    pub attempts: Vec<GpuAttempt>,
+}
+
+impl GpuError {
+    /// whether an adapter was found, but can't present transparent frames to the window
+    pub(crate) fn opaque(&self) -> bool {
+        self.attempts
+            .iter()
+            .any(|attempt| matches!(attempt.failure, GpuFailure::Opaque))
+    }
+}
+
+/// A backend tried while looking for a GPU, and why it was rejected.
+#[derive(Debug)]
+pub struct GpuAttempt {
+    /// e.g. `Vulkan`
+    pub backend: String,
+    pub failure: GpuFailure,
+}
+
+#[derive(Debug)]
+pub enum GpuFailure {
+    /// No adapter able to present to the overlay window.
+    NoAdapter,
+    /// The adapter was found but refused to create a device.
+    Device(wgpu::RequestDeviceError),
+    /// The adapter can only present opaque frames to the window.
+    Opaque,
+}
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no usable GPU")?;
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            let separator = if i == 0 { ", tried " } else { ", " };
+            match &attempt.failure {
+                GpuFailure::NoAdapter => write!(f, "{separator}{}: no adapter", attempt.backend)?,
+                GpuFailure::Device(e) => write!(f, "{separator}{}: {e}", attempt.backend)?,
+                GpuFailure::Opaque => write!(
+                    f,
+                    "{separator}{}: no transparent surface format",
+                    attempt.backend
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for GpuError {}
+
+thread_local! {
+    /// the devices of the overlays of this thread, with the options they were picked with,
+    /// for the overlays built later to share them
+    static SHARED_GPUS: RefCell<Vec<(GpuOptions, Weak<Gpu>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The GPU device, shared by the overlays of a thread: its handles are `Rc`s, and overlays
+/// run on the thread building them.
+pub(crate) struct Gpu {
+    /// shared with the replacement device, the surfaces belong to it
+    instance: Rc<wgpu::Instance>,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    /// how the adapter was requested, to request a replacement
+    power_preference: wgpu::PowerPreference,
+    force_fallback_adapter: bool,
+    /// set once the device is lost, e.g. by a driver reset or suspend/resume
+    lost: Arc<AtomicBool>,
+    /// the device replacing this one once lost, for the overlays sharing it
+    replacement: RefCell<Option<Rc<Gpu>>>,
+}
+
+impl Gpu {
+    fn with_device(
+        instance: Rc<wgpu::Instance>,
+        (adapter, device, queue): (wgpu::Adapter, wgpu::Device, wgpu::Queue),
+        power_preference: wgpu::PowerPreference,
+        force_fallback_adapter: bool,
+    ) -> Self {
+        quiet_device_lost_panics();
+        let lost = Arc::new(AtomicBool::new(false));
+        device.on_uncaptured_error({
+            let lost = lost.clone();
+            move |error| {
+                if is_device_lost(&error) {
+                    lost.store(true, Ordering::Relaxed);
+                } else {
+                    // like the default handler
+                    panic!("wgpu error: {}", error);
+                }
+            }
+        });
+        Self {
+            instance,
+            adapter,
+            device,
+            queue,
+            power_preference,
+            force_fallback_adapter,
+            lost,
+            replacement: RefCell::new(None),
+        }
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Gives up on the device, e.g. hung, replaced like a lost one by every overlay on it.
+    fn give_up(&self) {
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    /// A new device replacing this lost one, the same for every overlay sharing this one.
+    /// `surface`, if any, must be one the new adapter can present to, with transparency if
+    /// `transparent`.
+    fn replacement(
+        &self,
+        surface: Option<&wgpu::Surface>,
+        transparent: bool,
+    ) -> Result<Rc<Self>, GpuError> {
+        if let Some(gpu) = &*self.replacement.borrow() {
+            return Ok(gpu.clone());
+        }
+        let request = Self::request(
+            &self.instance,
+            surface,
+            transparent,
+            self.power_preference,
+            self.force_fallback_adapter,
+        );
+        let gpu = match request {
+            Ok(request) => Rc::new(Self::with_device(
+                self.instance.clone(),
+                request,
+                self.power_preference,
+                self.force_fallback_adapter,
+            )),
+            Err(failure) => {
+                return Err(GpuError {
+                    attempts: vec![GpuAttempt {
+                        backend: format!("{:?}", self.adapter.get_info().backend),
+                        failure,
+                    }],
+                })
+            }
+        };
+        *self.replacement.borrow_mut() = Some(gpu.clone());
+        Ok(gpu)
+    }
+
+    /// Runs `f`, turning the panics of wgpu reporting the device lost into
+    /// `SurfaceError::Lost`, other panics carry on. The former aren't printed.
+    fn guard<T>(&self, f: impl FnOnce() -> T) -> Result<T, wgpu::SurfaceError> {
+        // restored after, should guards nest
+        let outer = GUARDED.with(|guarded| guarded.replace(true));
+        let result = panic::catch_unwind(AssertUnwindSafe(f));
+        GUARDED.with(|guarded| guarded.set(outer));
+        result.map_err(|payload| {
+            if !panic_message(&*payload).is_some_and(panic_reports_device_lost) {
+                panic::resume_unwind(payload);
+            }
+            self.lost.store(true, Ordering::Relaxed);
+            wgpu::SurfaceError::Lost
+        })
+    }
+
+    /// A device of another overlay of the thread, picked with the same `options` and able to
+    /// present to `window`, or a new one, shared from then on. Returns the surface of `window`.
+    fn shared<W>(
+        window: &W,
+        options: &GpuOptions,
+        transparent: bool,
+    ) -> Result<(Rc<Self>, wgpu::Surface), GpuError>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        let shared = SHARED_GPUS.with(|gpus| {
+            let mut gpus = gpus.borrow_mut();
+            gpus.retain(|(_, gpu)| gpu.strong_count() > 0);
+            gpus.iter()
+                .filter(|(picked_with, _)| picked_with == options)
+                .filter_map(|(_, gpu)| gpu.upgrade())
+                .find(|gpu| !gpu.is_lost())
+        });
+        if let Some(gpu) = shared {
+            let surface = unsafe { gpu.instance.create_surface(window) };
+            if gpu.adapter.is_surface_supported(&surface) {
+                return Ok((gpu, surface));
+            }
+        }
+        let (gpu, surface) = Self::new(window, options, transparent)?;
+        let gpu = Rc::new(gpu);
+        SHARED_GPUS.with(|gpus| gpus.borrow_mut().push((*options, Rc::downgrade(&gpu))));
+        Ok((gpu, surface))
+    }
+
+    /// Tries the backends allowed by `options`, for an adapter able to present to `window`,
+    /// with transparency if `transparent`. Returns the surface of `window` on the chosen
+    /// backend.
+    fn new<W>(
+        window: &W,
+        options: &GpuOptions,
+        transparent: bool,
+    ) -> Result<(Self, wgpu::Surface), GpuError>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        let mut attempts = Vec::new();
+        for (backend, backends, force_fallback_adapter) in options.candidates() {
+            // The instance is a handle to our GPU
+            let instance = Rc::new(wgpu::Instance::new(backends));
+            let surface = unsafe { instance.create_surface(window) };
+            let request = Self::request(
+                &instance,
+                Some(&surface),
+                transparent,
+                options.power_preference,
+                force_fallback_adapter,
+            );
+            match request {
+                Ok(request) => {
+                    let gpu = Self::with_device(
+                        instance,
+                        request,
+                        options.power_preference,
+                        force_fallback_adapter,
+                    );
+                    return Ok((gpu, surface));
+                }
+                Err(failure) => attempts.push(GpuAttempt { backend, failure }),
+            }
+        }
+        Err(GpuError { attempts })
+    }
+
+    /// Tries the backends allowed by `options`, for any adapter, to render offscreen.
+    fn headless(options: &GpuOptions) -> Result<Self, GpuError> {
+        let mut attempts = Vec::new();
+        for (backend, backends, force_fallback_adapter) in options.candidates() {
+            let instance = Rc::new(wgpu::Instance::new(backends));
+            let request = Self::request(
+                &instance,
+                None,
+                true,
+                options.power_preference,
+                force_fallback_adapter,
+            );
+            match request {
+                Ok(request) => {
+                    return Ok(Self::with_device(
+                        instance,
+                        request,
+                        options.power_preference,
+                        force_fallback_adapter,
+                    ))
+                }
+                Err(failure) => attempts.push(GpuAttempt { backend, failure }),
+            }
+        }
+        Err(GpuError { attempts })
+    }
+
+    /// Without `surface`, any adapter will do.
+    fn request(
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+        transparent: bool,
+        power_preference: wgpu::PowerPreference,
+        force_fallback_adapter: bool,
+    ) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), GpuFailure> {
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference,
+            compatible_surface: surface,
+            force_fallback_adapter,
+        }))
+        .ok_or(GpuFailure::NoAdapter)?;
+        if surface.is_some_and(|surface| {
+            surface_config(surface, &adapter, ColorSpace::default(), transparent).is_none()
+        }) {
+            return Err(GpuFailure::Opaque);
+        }
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::default(),
+                limits: wgpu::Limits::default(),
+            },
+            None,
+        ))
+        .map_err(GpuFailure::Device)?;
+
+        Ok((adapter, device, queue))
+    }
+}
+
+/// A copy of the frames in another window, at its own scale factor.
+struct Mirror {
+    surface: wgpu::Surface,
+    config: wgpu::SurfaceConfiguration,
+    msaa_view: Option<wgpu::TextureView>,
+    pixels_per_point: f32,
+}
+
+/// Where the frames are drawn.
+enum Target {
+    Surface(wgpu::Surface),
+    /// of the headless overlays, recreated when resized
+    Offscreen(wgpu::Texture),
+}
+
+pub(crate) struct State {
+    target: Target,
+    gpu: Rc<Gpu>,
+    config: wgpu::SurfaceConfiguration,
+    /// of the surface
+    pub size: (u32, u32),
+    /// of the egui screen, larger than the surface when cropped
+    area: (u32, u32),
+    /// of the surface in the egui screen, in pixels
+    origin: (u16, u16),
+    context: Context,
+    pub raw_input: RawInput,
+    start_time: Instant,
+    /// places the egui screen in the window
+    viewport: Geometry,
+    renderer: Box<dyn Renderer>,
+    /// builds `renderer` again on a new device
+    new_renderer: RendererFactory,
+    /// 1 without multisampling
+    msaa_samples: u32,
+    /// drawn to then resolved into the frame, when multisampling
+    msaa_view: Option<wgpu::TextureView>,
+    /// picks the surface format, again on a new device
+    color_space: ColorSpace,
+    /// copies of the egui textures on the GPU, to upload them again to a new device
+    textures: HashMap<TextureId, ImageDelta>,
+    /// evicts the copies on the GPU past a memory budget
+    budget: TextureBudget,
+    /// of the last frame, painted again to capture it
+    last_paint_jobs: Vec<ClippedPrimitive>,
+    /// the wgpu textures registered, from [`TextureId::User`] 0 to `next_user_texture`
+    user_textures: HashSet<TextureId>,
+    next_user_texture: u64,
+    /// painted by the paint callbacks, in the order of the callbacks
+    callback_textures: Vec<UserTexture>,
+    /// frames are drawn into `cache` then onto the surface, see [`State::set_caching`]
+    caching: bool,
+    /// the last frame, the size of the surface, presented again without running the UI
+    cache: Option<UserTexture>,
+    /// bumped when drawing on a new device
+    device_generation: u32,
+    /// whether the window has an alpha channel, frames being opaque otherwise
+    transparent: bool,
+    /// staging buffers of the paint callbacks, reused across frames
+    belt: wgpu::util::StagingBelt,
+    pub frames: FrameRecorder,
+    /// drawn with the same primitives after the frame, see [`State::add_mirror`]
+    mirrors: Vec<Mirror>,
+    /// catches the panics of the app
+    pub panics: PanicGuard,
+    /// gives up on the device when it seems hung
+    pub watchdog: WatchdogTimer,
+}
+
+impl State {
+    /// Draws into `window`, on the device of another overlay if `gpu` is given,
+    /// otherwise on a new device picked according to `options`.
+    pub fn new<W>(
+        window: &W,
+        size: (u32, u32),
+        gpu: Option<Rc<Gpu>>,
+        options: &GpuOptions,
+        surface_options: SurfaceOptions,
+        scale_factor: f32,
+    ) -> Result<Self, GpuError>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        // wgpu stuff
+
+        let (gpu, surface) = match gpu {
+            Some(gpu) => {
+                let surface = unsafe { gpu.instance.create_surface(window) };
+                if !gpu.adapter.is_surface_supported(&surface) {
+                    return Err(GpuError {
+                        attempts: vec![GpuAttempt {
+                            backend: gpu.adapter.get_info().name,
+                            failure: GpuFailure::NoAdapter,
+                        }],
+                    });
+                }
+                (gpu, surface)
+            }
+            None => Gpu::shared(window, options, surface_options.transparent)?,
+        };
+        let SurfaceOptions {
+            present_mode,
+            msaa_samples,
+            color_space,
+            renderer,
+            transparent,
+            watchdog,
+        } = surface_options;
+        let (format, alpha_mode) = surface_config(&surface, &gpu.adapter, color_space, transparent)
+            .ok_or_else(|| GpuError {
+                attempts: vec![GpuAttempt {
+                    backend: gpu.adapter.get_info().name,
+                    failure: GpuFailure::Opaque,
+                }],
+            })?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.0,
+            height: size.1,
+            present_mode: supported_present_mode(&surface, &gpu.adapter, present_mode),
+            alpha_mode,
+        };
+        surface.configure(&gpu.device, &config);
+
+        let msaa_samples = supported_msaa_samples(&gpu.adapter, format, msaa_samples);
+        let mut state = Self::with_target(
+            Target::Surface(surface),
+            gpu,
+            config,
+            msaa_samples,
+            color_space,
+            renderer,
+            scale_factor,
+        );
+        state.transparent = transparent;
+        state.watchdog.watchdog = watchdog;
+        Ok(state)
+    }
+
+    /// Draws into an offscreen texture instead of a window, on a new device.
+    pub fn headless(
+        size: (u32, u32),
+        options: &GpuOptions,
+        scale_factor: f32,
+    ) -> Result<Self, GpuError> {
+        let gpu = Rc::new(Gpu::headless(options)?);
+        // what X surfaces mostly pick, so that captures look alike
+        let format = wgpu::TextureFormat::Bgra8UnormSrgb;
+        let texture = offscreen_texture(&gpu.device, size, format);
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: size.0,
+            height: size.1,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::PreMultiplied,
+        };
+
+        let mut state = Self::with_target(
+            Target::Offscreen(texture),
+            gpu,
+            config,
+            1,
+            ColorSpace::default(),
+            RendererFactory::default(),
+            scale_factor,
+        );
+        // tests fail on the panics of their app
+        state.panics.enabled = false;
+        Ok(state)
+    }
+
+    fn with_target(
+        target: Target,
+        gpu: Rc<Gpu>,
+        config: wgpu::SurfaceConfiguration,
+        msaa_samples: u32,
+        color_space: ColorSpace,
+        new_renderer: RendererFactory,
+        scale_factor: f32,
+    ) -> Self {
+        let size = (config.width, config.height);
+
+        // egui stuff
+
+        let raw_input = egui::RawInput {
+            screen_rect: Some(screen_rect(size, scale_factor, &Geometry::fill())),
+            pixels_per_point: Some(scale_factor),
+            ..Default::default()
+        };
+
+        let renderer = new_renderer.build(&gpu.device, config.format, msaa_samples);
+        let msaa_view = msaa_view(&gpu.device, size, config.format, msaa_samples);
+
+        let context = Context::default();
+
+        Self {
+            target,
+            gpu,
+            config,
+            size,
+            area: size,
+            origin: (0, 0),
+            context,
+            raw_input,
+            start_time: Instant::now(),
+            viewport: Geometry::fill(),
+            renderer,
+            new_renderer,
+            msaa_samples,
+            msaa_view,
+            color_space,
+            textures: HashMap::new(),
+            budget: TextureBudget::new(),
+            last_paint_jobs: Vec::new(),
+            user_textures: HashSet::new(),
+            next_user_texture: 0,
+            callback_textures: Vec::new(),
+            caching: false,
+            cache: None,
+            device_generation: 0,
+            transparent: true,
+            belt: wgpu::util::StagingBelt::new(STAGING_CHUNK_SIZE),
+            frames: FrameRecorder::default(),
+            mirrors: Vec::new(),
+            panics: PanicGuard::new(true),
+            watchdog: WatchdogTimer::new(Some(Watchdog::default())),
+        }
+    }
+
+    /// Draws the frames into `window` too, `size` pixels at `pixels_per_point`, from the
+    /// primitives of the overlay. Its surface must take the format of the overlay's.
+    pub fn add_mirror<W>(
+        &mut self,
+        window: &W,
+        size: (u32, u32),
+        pixels_per_point: f32,
+    ) -> anyhow::Result<()>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        let surface = unsafe { self.gpu.instance.create_surface(window) };
+        let mut mirror = Mirror {
+            surface,
+            config: self.config.clone(),
+            msaa_view: None,
+            pixels_per_point,
+        };
+        self.configure_mirror(&mut mirror, size)?;
+        self.mirrors.push(mirror);
+        Ok(())
+    }
+
+    /// Resizes the `index`th mirror, in the order they were added.
+    pub fn resize_mirror(
+        &mut self,
+        index: usize,
+        size: (u32, u32),
+        pixels_per_point: f32,
+    ) -> anyhow::Result<()> {
+        let mut mirror = self.mirrors.remove(index);
+        mirror.pixels_per_point = pixels_per_point;
+        self.configure_mirror(&mut mirror, size)?;
+        self.mirrors.insert(index, mirror);
+        Ok(())
+    }
+
+    /// Stops drawing the mirrors, before their windows are destroyed.
+    pub fn remove_mirrors(&mut self) {
+        self.mirrors.clear();
+    }
+
+    /// configures the surface of `mirror` like the overlay's, on the current device
+    fn configure_mirror(&self, mirror: &mut Mirror, size: (u32, u32)) -> anyhow::Result<()> {
+        let adapter = &self.gpu.adapter;
+        let (format, alpha_mode) =
+            surface_config(&mirror.surface, adapter, self.color_space, self.transparent)
+                .filter(|(format, _)| *format == self.config.format)
+                .ok_or_else(|| anyhow!("the mirror can't be drawn like the overlay"))?;
+        // without waiting for the vertical blank of each monitor in turn
+        let present_mode = match mirror.surface.get_supported_present_modes(adapter) {
+            modes if modes.contains(&wgpu::PresentMode::Mailbox) => wgpu::PresentMode::Mailbox,
+            _ => wgpu::PresentMode::Fifo,
+        };
+        mirror.config = wgpu::SurfaceConfiguration {
+            format,
+            alpha_mode,
+            present_mode,
+            width: size.0.max(1),
+            height: size.1.max(1),
+            ..self.config.clone()
+        };
+        mirror.surface.configure(&self.gpu.device, &mirror.config);
+        mirror.msaa_view = msaa_view(
+            &self.gpu.device,
+            (mirror.config.width, mirror.config.height),
+            format,
+            self.msaa_samples,
+        );
+        Ok(())
+    }
+
+    /// draws `paint_jobs` again on every mirror, skipping the ones without a frame to draw on
+    fn draw_mirrors(&mut self, paint_jobs: &[ClippedPrimitive]) -> anyhow::Result<()> {
+        let gpu = self.gpu.clone();
+        for mirror in &self.mirrors {
+            let frame = match mirror.surface.get_current_texture() {
+                Ok(frame) => frame,
+                Err(e) => {
+                    debug!("mirror frame: {}", e);
+                    mirror.surface.configure(&gpu.device, &mirror.config);
+                    continue;
+                }
+            };
+            let view = frame
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            let screen_descriptor = ScreenDescriptor {
+                size_in_pixels: [mirror.config.width, mirror.config.height],
+                pixels_per_point: mirror.pixels_per_point,
+            };
+            // written before the submission, after the previous one used the buffers
+            self.renderer
+                .update_buffers(&gpu.device, &gpu.queue, paint_jobs, &screen_descriptor);
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("mirror encoder"),
+                });
+            draw_frame(
+                &*self.renderer,
+                &mut encoder,
+                &view,
+                mirror.msaa_view.as_ref(),
+                &self.config,
+                paint_jobs,
+                &screen_descriptor,
+            )?;
+            gpu.queue.submit(iter::once(encoder.finish()));
+            frame.present();
+        }
+        Ok(())
+    }
+
+    /// Checks whether the GPU finished the frames drawn, recording their latency. Returns
+    /// whether some are left.
+    pub fn poll_frames(&self) -> bool {
+        if !self.frames.pending() {
+            return false;
+        }
+        self.gpu.device.poll(wgpu::Maintain::Poll);
+        self.frames.pending()
+    }
+
+    pub fn gpu(&self) -> &Rc<Gpu> {
+        &self.gpu
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.gpu.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.gpu.queue
+    }
+
+    pub fn device_generation(&self) -> u32 {
+        self.device_generation
+    }
+
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
+
+    /// Makes `texture`, on the device of the overlay, drawable by egui, without copying it.
+    pub fn register_texture(
+        &mut self,
+        texture: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+    ) -> TextureId {
+        let id = self
+            .renderer
+            .register_native_texture(&self.gpu.device, texture, filter);
+        self.user_textures.insert(id);
+        self.next_user_texture += 1;
+        id
+    }
+
+    pub fn update_texture(
+        &mut self,
+        id: TextureId,
+        texture: &wgpu::TextureView,
+        filter: wgpu::FilterMode,
+    ) -> anyhow::Result<()> {
+        if !self.user_textures.contains(&id) {
+            bail!("{:?} is not a registered wgpu texture", id);
+        }
+        self.renderer
+            .update_native_texture(&self.gpu.device, texture, filter, id)?;
+        Ok(())
+    }
+
+    /// A texture to render to, drawable by egui.
+    pub fn create_user_texture(
+        &mut self,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+    ) -> anyhow::Result<UserTexture> {
+        self.new_user_texture(size, format, None)
+    }
+
+    /// Replaces `texture` by a new one of `size`, on the current device, keeping its id.
+    pub fn resize_user_texture(
+        &mut self,
+        texture: &mut UserTexture,
+        size: (u32, u32),
+    ) -> anyhow::Result<()> {
+        *texture = self.new_user_texture(size, texture.format(), Some(texture.id()))?;
+        Ok(())
+    }
+
+    /// registered as `id` if given, as a new texture otherwise
+    fn new_user_texture(
+        &mut self,
+        size: (u32, u32),
+        format: wgpu::TextureFormat,
+        id: Option<TextureId>,
+    ) -> anyhow::Result<UserTexture> {
+        let gpu = self.gpu.clone();
+        UserTexture::new(&gpu.device, size, format, |view| match id {
+            Some(id) => {
+                self.update_texture(id, view, wgpu::FilterMode::Linear)?;
+                Ok(id)
+            }
+            None => Ok(self.register_texture(view, wgpu::FilterMode::Linear)),
+        })
+    }
+
+    pub fn free_texture(&mut self, id: TextureId) -> anyhow::Result<()> {
+        if !self.user_textures.remove(&id) {
+            bail!("{:?} is not a registered wgpu texture", id);
+        }
+        self.renderer.free_textures(&[id])?;
+        Ok(())
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// bytes of the egui textures on the GPU
+    pub fn texture_bytes(&self) -> usize {
+        self.budget.bytes(&self.textures)
+    }
+
+    pub fn texture_memory(&self) -> TextureMemory {
+        self.budget.memory(&self.textures)
+    }
+
+    /// Evicts the least recently drawn egui images past `budget` bytes, see
+    /// [`TextureBudget`].
+    pub fn set_texture_budget(&mut self, budget: Option<usize>) {
+        self.budget.budget = budget;
+    }
+
+    pub fn pixels_per_point(&self) -> f32 {
+        self.raw_input.pixels_per_point.unwrap_or(1.)
+    }
+
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.raw_input.pixels_per_point = Some(pixels_per_point);
+        self.raw_input.screen_rect = Some(screen_rect(self.area, pixels_per_point, &self.viewport));
+    }
+
+    /// Places the egui screen in the window, see [`Viewport`](crate::geometry::Viewport).
+    pub fn set_viewport(&mut self, viewport: Geometry) {
+        self.viewport = viewport;
+        self.raw_input.screen_rect =
+            Some(screen_rect(self.area, self.pixels_per_point(), &viewport));
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        let Target::Surface(surface) = &self.target else {
+            return;
+        };
+        let present_mode = supported_present_mode(surface, &self.gpu.adapter, present_mode);
+        if present_mode != self.config.present_mode {
+            self.config.present_mode = present_mode;
+            surface.configure(&self.gpu.device, &self.config);
+        }
+    }
+
+    /// Resizes the egui screen, and the surface to cover it all.
+    pub fn resize(&mut self, new_size: (u32, u32)) {
+        if new_size.0 > 0 && new_size.1 > 0 {
+            self.area = new_size;
+            self.origin = (0, 0);
+            self.configure(new_size);
+            self.raw_input.screen_rect = Some(screen_rect(
+                new_size,
+                self.pixels_per_point(),
+                &self.viewport,
+            ));
+        }
+    }
+
+    /// Restricts the surface to `rect` of the egui screen, in pixels, the whole screen if
+    /// `None`. Input positions are taken relative to `rect`.
+    pub fn crop(&mut self, rect: Option<Rectangle>) {
+        match rect {
+            Some(rect) => {
+                self.origin = (rect.x as u16, rect.y as u16);
+                self.configure((rect.width.into(), rect.height.into()));
+            }
+            None => {
+                self.origin = (0, 0);
+                self.configure(self.area);
+            }
+        }
+    }
+
+    /// sets the size of the surface
+    fn configure(&mut self, size: (u32, u32)) {
+        if size.0 > 0 && size.1 > 0 {
+            self.size = size;
+            self.config.width = size.0;
+            self.config.height = size.1;
+            match &mut self.target {
+                Target::Surface(surface) => surface.configure(&self.gpu.device, &self.config),
+                Target::Offscreen(texture) => {
+                    *texture = offscreen_texture(&self.gpu.device, size, self.config.format)
+                }
+            }
+            self.msaa_view = msaa_view(
+                &self.gpu.device,
+                size,
+                self.config.format,
+                self.msaa_samples,
+            );
+        }
+    }
+
+    /// After `SurfaceError::Lost`: reconfigures the surface, on a new device if the current one
+    /// was lost, e.g. by a driver reset or suspend/resume, uploading the egui textures again.
+    pub fn recover(&mut self) -> anyhow::Result<()> {
+        if !self.gpu.is_lost() {
+            self.configure(self.size);
+            return Ok(());
+        }
+        let surface = match &self.target {
+            Target::Surface(surface) => Some(surface),
+            Target::Offscreen(_) => None,
+        };
+        let gpu = self.gpu.replacement(surface, self.transparent)?;
+        match &mut self.target {
+            Target::Surface(surface) => {
+                let (format, alpha_mode) =
+                    surface_config(surface, &gpu.adapter, self.color_space, self.transparent)
+                        .ok_or_else(|| anyhow!("the new device can't draw transparent frames"))?;
+                self.config.format = format;
+                self.config.alpha_mode = alpha_mode;
+                self.config.present_mode =
+                    supported_present_mode(surface, &gpu.adapter, self.config.present_mode);
+                self.msaa_samples = supported_msaa_samples(&gpu.adapter, format, self.msaa_samples);
+                surface.configure(&gpu.device, &self.config);
+            }
+            Target::Offscreen(texture) => {
+                *texture = offscreen_texture(&gpu.device, self.size, self.config.format)
+            }
+        }
+
+        self.renderer = self
+            .new_renderer
+            .build(&gpu.device, self.config.format, self.msaa_samples);
+        self.msaa_view = msaa_view(
+            &gpu.device,
+            self.size,
+            self.config.format,
+            self.msaa_samples,
+        );
+        let textures = TexturesDelta {
+            set: self
+                .textures
+                .iter()
+                .map(|(id, texture)| (*id, texture.clone()))
+                .collect(),
+            free: Vec::new(),
+        };
+        self.renderer
+            .update_textures(&gpu.device, &gpu.queue, &textures)?;
+        self.budget.reset();
+        self.gpu = gpu;
+        self.device_generation += 1;
+        self.belt = wgpu::util::StagingBelt::new(STAGING_CHUNK_SIZE);
+        // painted again on the new device, into new textures
+        for texture in self.callback_textures.drain(..) {
+            self.user_textures.remove(&texture.id());
+        }
+        if let Some(cache) = self.cache.take() {
+            self.user_textures.remove(&cache.id());
+        }
+        self.replace_user_textures()?;
+        let mirrors = std::mem::take(&mut self.mirrors);
+        for mut mirror in mirrors {
+            let size = (mirror.config.width, mirror.config.height);
+            match self.configure_mirror(&mut mirror, size) {
+                Ok(()) => self.mirrors.push(mirror),
+                Err(e) => warn!("{:#}, no longer drawn", e),
+            }
+        }
+        warn!(
+            "GPU device lost, now drawing on {}",
+            self.gpu.adapter.get_info().name
+        );
+        Ok(())
+    }
+
+    /// Paints the last frame again into an offscreen texture, and reads it back.
+    pub fn capture(&mut self) -> anyhow::Result<ColorImage> {
+        let device = &self.gpu.device;
+        let texture = offscreen_texture(device, self.size, self.config.format);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [self.config.width, self.config.height],
+            pixels_per_point: self.pixels_per_point(),
+        };
+        self.renderer.update_buffers(
+            device,
+            &self.gpu.queue,
+            &self.last_paint_jobs,
+            &screen_descriptor,
+        );
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("capture encoder"),
+        });
+        self.draw(
+            &mut encoder,
+            &view,
+            &self.last_paint_jobs,
+            &screen_descriptor,
+        )?;
+        self.gpu.queue.submit(iter::once(encoder.finish()));
+
+        read_texture(
+            device,
+            &self.gpu.queue,
+            &texture,
+            self.size,
+            self.config.format,
+        )
+    }
+
+    /// Fails with [`OverlayError::SurfaceLost`] or [`OverlayError::Surface`] if the surface
+    /// needs [`State::recover`], [`OverlayError::Other`] if the renderer did.
+    pub fn render(&mut self, app: &mut dyn OverlayApp) -> Result<Frame, OverlayError> {
+        // until recovered
+        if self.gpu.is_lost() {
+            return Err(OverlayError::SurfaceLost);
+        }
+        let gpu = self.gpu.clone();
+        let start = Instant::now();
+        self.frames.begin(start);
+
+        // presented once drawn, offscreen textures are kept as is
+        let (output_frame, output_view) = match &self.target {
+            Target::Surface(surface) => {
+                let output_frame = match gpu.guard(|| surface.get_current_texture())? {
+                    Ok(output_frame) => output_frame,
+                    Err(wgpu::SurfaceError::Timeout) => {
+                        if let Some(incident) = self.watchdog.timeout() {
+                            self.reset_gpu(incident);
+                        }
+                        return Err(OverlayError::Surface(wgpu::SurfaceError::Timeout));
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let output_view = output_frame
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(output_frame), output_view)
+            }
+            Target::Offscreen(texture) => (
+                None,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
+
+        let acquired = Instant::now();
+
+        // Begin to draw the UI frame.
+        let scale_factor = self.raw_input.pixels_per_point.unwrap_or(1.);
+        let origin = vec2(self.origin.0.into(), self.origin.1.into()) / scale_factor;
+        if origin != Vec2::ZERO {
+            for event in &mut self.raw_input.events {
+                translate_event(event, origin);
+            }
+        }
+        // frames are irregular, egui animations need the actual time
+        self.raw_input.time = Some(self.start_time.elapsed().as_secs_f64());
+        self.context.begin_frame(self.raw_input.take());
+        self.raw_input.pixels_per_point = Some(scale_factor);
+
+        // Draw the application, or its panic.
+        self.panics.ui(app, &self.context);
+
+        // End the UI frame. We could now handle the output and draw the UI with the backend.
+        let full_output = self.context.end_frame();
+        let mut paint_jobs = self.context.tessellate(full_output.shapes);
+        let painted = paint_jobs
+            .iter()
+            .map(|job| match &job.primitive {
+                Primitive::Mesh(mesh) => mesh.calc_bounds().intersect(job.clip_rect),
+                Primitive::Callback(_) => job.clip_rect,
+            })
+            .filter(Rect::is_positive)
+            .collect();
+
+        // kept first, a lost device would miss them otherwise
+        let mut tdelta = uploads::compact(full_output.textures_delta, &mut self.textures);
+        let over_budget = self.budget.apply(&paint_jobs, &mut tdelta, &self.textures);
+
+        gpu.guard(|| -> anyhow::Result<()> {
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("encoder"),
+                });
+
+            self.paint_callbacks(&mut encoder, &mut paint_jobs, scale_factor)?;
+            // the mirrors show the whole egui screen, uncropped
+            let mirror_jobs =
+                (!self.mirrors.is_empty() && origin != Vec2::ZERO).then(|| paint_jobs.clone());
+            if origin != Vec2::ZERO {
+                for job in &mut paint_jobs {
+                    job.clip_rect = job.clip_rect.translate(-origin);
+                    if let Primitive::Mesh(mesh) = &mut job.primitive {
+                        mesh.translate(-origin);
+                    }
+                }
+            }
+
+            // Upload all resources for the GPU.
+            let screen_descriptor = ScreenDescriptor {
+                size_in_pixels: [self.config.width, self.config.height],
+                pixels_per_point: scale_factor,
+            };
+            self.renderer
+                .update_textures(&gpu.device, &gpu.queue, &tdelta)?;
+            self.renderer
+                .update_buffers(&gpu.device, &gpu.queue, &paint_jobs, &screen_descriptor);
+
+            // Record all render passes, into the cache if any, copied onto the surface below
+            if self.caching && output_frame.is_some() {
+                self.update_cache()?;
+            }
+            let view = self.cache.as_ref().map_or(&output_view, UserTexture::view);
+            self.draw(&mut encoder, view, &paint_jobs, &screen_descriptor)?;
+            {
+                let _span = debug_span!("submit").entered();
+                let submitted = Instant::now();
+                // Submit the commands.
+                self.belt.finish();
+                gpu.queue.submit(iter::once(encoder.finish()));
+                self.belt.recall();
+                self.draw_cache(&output_view)?;
+
+                // Redraw egui
+                if let Some(output_frame) = output_frame {
+                    output_frame.present();
+                }
+                self.frames
+                    .submitted(&gpu.queue, submitted - start, submitted, Instant::now());
+            }
+            self.draw_mirrors(mirror_jobs.as_deref().unwrap_or(&paint_jobs))?;
+
+            self.renderer.free_textures(&tdelta.free)
+        })??;
+        self.last_paint_jobs = paint_jobs;
+        // waiting on the GPU, not on the app
+        let gpu_time = acquired.duration_since(start) + self.frames.stats().submit_time;
+        if let Some(incident) = self.watchdog.frame(gpu_time) {
+            self.reset_gpu(incident);
+        }
+
+        Ok(Frame {
+            painted,
+            repaint_after: full_output.repaint_after,
+            copied_text: full_output.platform_output.copied_text,
+            cursor_icon: full_output.platform_output.cursor_icon,
+            open_url: full_output.platform_output.open_url,
+            over_budget,
+        })
+    }
+
+    /// Draws the next frames on a new device, the watchdog giving up on this one.
+    fn reset_gpu(&mut self, incident: Incident) {
+        warn!("{}, drawing on a new GPU device", incident);
+        self.gpu.give_up();
+        self.frames.gpu_reset();
+    }
+
+    /// Draws the frames into a texture kept until the next one, presented again by
+    /// [`State::present_cached`] without running the UI. Drawing on an offscreen target
+    /// doesn't cache anything.
+    pub fn set_caching(&mut self, caching: bool) -> anyhow::Result<()> {
+        self.caching = caching;
+        match self.cache.take() {
+            Some(cache) if !caching => self.free_texture(cache.id()),
+            cache => {
+                self.cache = cache;
+                Ok(())
+            }
+        }
+    }
+
+    /// Presents the last frame again, e.g. as the window is exposed, if cached.
+    pub fn present_cached(&mut self) -> Result<(), OverlayError> {
+        let (Target::Surface(surface), Some(cache)) = (&self.target, &self.cache) else {
+            return Ok(());
+        };
+        if self.gpu.is_lost() {
+            return Err(OverlayError::SurfaceLost);
+        }
+        // resized since, the next frame draws it
+        if cache.size() != self.size {
+            return Ok(());
+        }
+        let gpu = self.gpu.clone();
+        let output_frame = gpu.guard(|| surface.get_current_texture())??;
+        let output_view = output_frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        gpu.guard(|| self.draw_cache(&output_view))??;
+        output_frame.present();
+        Ok(())
+    }
+
+    /// the cache texture, made again when the surface is resized or its format changes
+    fn update_cache(&mut self) -> anyhow::Result<()> {
+        let current = self
+            .cache
+            .as_ref()
+            .map(|cache| (cache.size(), cache.format()));
+        if current == Some((self.size, self.config.format)) {
+            return Ok(());
+        }
+        let id = self.cache.as_ref().map(UserTexture::id);
+        self.cache = Some(self.new_user_texture(self.size, self.config.format, id)?);
+        Ok(())
+    }
+
+    /// copies the cache, if any, onto `view`, in a submission of its own as the renderer
+    /// buffers hold the frame until then
+    fn draw_cache(&mut self, view: &wgpu::TextureView) -> anyhow::Result<()> {
+        let Some(cache) = &self.cache else {
+            return Ok(());
+        };
+        // in pixels
+        let rect = Rect::from_min_size(Pos2::ZERO, vec2(self.size.0 as f32, self.size.1 as f32));
+        let paint_jobs = [ClippedPrimitive {
+            clip_rect: rect,
+            primitive: Primitive::Mesh(cache.mesh(rect)),
+        }];
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [self.config.width, self.config.height],
+            pixels_per_point: 1.,
+        };
+        let gpu = &self.gpu;
+        self.renderer
+            .update_buffers(&gpu.device, &gpu.queue, &paint_jobs, &screen_descriptor);
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("cache encoder"),
+            });
+        self.draw(&mut encoder, view, &paint_jobs, &screen_descriptor)?;
+        gpu.queue.submit(iter::once(encoder.finish()));
+        Ok(())
+    }
+
+    /// Clears `view` and draws `paint_jobs` on it, through the multisampled texture if any.
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        paint_jobs: &[ClippedPrimitive],
+        screen_descriptor: &ScreenDescriptor,
+    ) -> anyhow::Result<()> {
+        draw_frame(
+            &*self.renderer,
+            encoder,
+            view,
+            self.msaa_view.as_ref(),
+            &self.config,
+            paint_jobs,
+            screen_descriptor,
+        )
+    }
+
+    /// Runs the paint callbacks, each into its texture, and replaces them by meshes drawing
+    /// the textures, renderers only drawing meshes.
+    fn paint_callbacks(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        paint_jobs: &mut [ClippedPrimitive],
+        pixels_per_point: f32,
+    ) -> anyhow::Result<()> {
+        let max_size = self.gpu.device.limits().max_texture_dimension_2d as f32;
+        let mut painted = 0;
+        for job in paint_jobs {
+            let Primitive::Callback(callback) = &job.primitive else {
+                continue;
+            };
+            let callback = callback.clone();
+            let Some(WgpuCallback(paint)) = callback.callback.downcast_ref::<WgpuCallback>() else {
+                continue;
+            };
+            let size = (
+                (callback.rect.width() * pixels_per_point)
+                    .round()
+                    .clamp(1., max_size) as u32,
+                (callback.rect.height() * pixels_per_point)
+                    .round()
+                    .clamp(1., max_size) as u32,
+            );
+            let id = self.callback_textures.get(painted).map(UserTexture::id);
+            if self.callback_textures.get(painted).map(UserTexture::size) != Some(size) {
+                let texture = self.new_user_texture(size, CALLBACK_FORMAT, id)?;
+                match self.callback_textures.get_mut(painted) {
+                    Some(old) => *old = texture,
+                    None => self.callback_textures.push(texture),
+                }
+            }
+
+            let texture = &self.callback_textures[painted];
+            texture.clear(encoder);
+            paint(&mut CallbackContext {
+                device: &self.gpu.device,
+                queue: &self.gpu.queue,
+                encoder,
+                belt: &mut self.belt,
+                view: texture.view(),
+                format: texture.format(),
+                size,
+                info: egui::PaintCallbackInfo {
+                    viewport: callback.rect,
+                    clip_rect: job.clip_rect,
+                    pixels_per_point,
+                    screen_size_px: [self.config.width, self.config.height],
+                },
+            });
+            job.primitive = Primitive::Mesh(texture.mesh(callback.rect));
+            painted += 1;
+        }
+        // the textures of callbacks gone since the last frame
+        let unused: Vec<_> = self.callback_textures.drain(painted..).collect();
+        for texture in unused {
+            self.free_texture(texture.id())?;
+        }
+        Ok(())
+    }
+
+    /// Points the registered wgpu textures, gone with the former device, to an empty texture
+    /// until the app registers new ones, egui failing to draw unknown textures otherwise.
+    /// The new render pass numbers its textures from 0 again, so all the former ids are taken
+    /// first, to keep the live ones.
+    fn replace_user_textures(&mut self) -> anyhow::Result<()> {
+        if self.next_user_texture == 0 {
+            return Ok(());
+        }
+        let placeholder = self
+            .gpu
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("placeholder"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut freed = Vec::new();
+        for _ in 0..self.next_user_texture {
+            let id = self.renderer.register_native_texture(
+                &self.gpu.device,
+                &placeholder,
+                wgpu::FilterMode::Nearest,
+            );
+            if !self.user_textures.contains(&id) {
+                freed.push(id);
+            }
+        }
+        self.renderer.free_textures(&freed)?;
+        Ok(())
+    }
+}
+
+/// clears `view` of `format` and draws `paint_jobs` on it, through `msaa_view` if any
+fn draw_frame(
+    renderer: &dyn Renderer,
+    encoder: &mut wgpu::CommandEncoder,
+    view: &wgpu::TextureView,
+    msaa_view: Option<&wgpu::TextureView>,
+    config: &wgpu::SurfaceConfiguration,
+    paint_jobs: &[ClippedPrimitive],
+    screen_descriptor: &ScreenDescriptor,
+) -> anyhow::Result<()> {
+    let (view, resolve_target) = match msaa_view {
+        Some(msaa_view) => (msaa_view, Some(view)),
+        None => (view, None),
+    };
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("egui"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(clear_color(config)),
+                // resolved, the samples themselves aren't needed afterwards
+                store: resolve_target.is_none(),
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+    renderer.render(&mut pass, paint_jobs, screen_descriptor)
+}
+
+/// moves the position of pointer and touch events by `delta`
+fn translate_event(event: &mut egui::Event, delta: Vec2) {
+    match event {
+        egui::Event::PointerMoved(pos)
+        | egui::Event::PointerButton { pos, .. }
+        | egui::Event::Touch { pos, .. } => *pos += delta,
+        _ => {}
+    }
+}
+
+/// whether `error`, or one of its causes, is the device lost: `DeviceError::Lost` as is, or
+/// wrapped by the queue and surface errors, which hide it from the causes
+fn is_device_lost(error: &(dyn error::Error + 'static)) -> bool {
+    let lost = |e: &DeviceError| matches!(e, DeviceError::Lost);
+    iter::successors(Some(error), |cause| cause.source()).any(|cause| {
+        cause.downcast_ref().is_some_and(lost)
+            || matches!(cause.downcast_ref(), Some(QueueSubmitError::Queue(e)) if lost(e))
+            || matches!(cause.downcast_ref(), Some(QueueWriteError::Queue(e)) if lost(e))
+            || matches!(cause.downcast_ref(), Some(CoreSurfaceError::Device(e)) if lost(e))
+            || matches!(cause.downcast_ref(), Some(ConfigureSurfaceError::Device(e)) if lost(e))
+    })
+}
+
+/// Whether a panic of wgpu reports the device lost. The errors of the wgpu calls without a
+/// `Result` only reach us formatted into a panic, so this is the one place matching on text,
+/// on the message of `DeviceError::Lost` itself rather than a copy of its wording.
+fn panic_reports_device_lost(message: &str) -> bool {
+    message.contains(&DeviceError::Lost.to_string())
+}
+
+thread_local! {
+    /// inside [`Gpu::guard`], its device lost panics caught
+    static GUARDED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Wraps the panic hook, once, not to print the device lost panics caught by [`Gpu::guard`].
+fn quiet_device_lost_panics() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let caught = GUARDED.with(Cell::get)
+                && info.payload_as_str().is_some_and(panic_reports_device_lost);
+            if !caught {
+                hook(info);
+            }
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    payload
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| payload.downcast_ref::<&str>().copied())
+}
+
+/// the egui screen, placed by `viewport` in a window of `size` pixels
+fn screen_rect(size: (u32, u32), scale_factor: f32, viewport: &Geometry) -> Rect {
+    let window = Rectangle {
+        x: 0,
+        y: 0,
+        width: size.0.min(u16::MAX.into()) as u16,
+        height: size.1.min(u16::MAX.into()) as u16,
+    };
+    let rect = viewport.place(window);
+    Rect::from_min_size(
+        Pos2::new(
+            f32::from(rect.x) / scale_factor,
+            f32::from(rect.y) / scale_factor,
+        ),
+        vec2(rect.width.into(), rect.height.into()) / scale_factor,
+    )
+}
+
+/// `samples` if `format` supports multisampling on `adapter`, 1 otherwise.
+fn supported_msaa_samples(
+    adapter: &wgpu::Adapter,
+    format: wgpu::TextureFormat,
+    samples: u32,
+) -> u32 {
+    let needed = wgpu::TextureFormatFeatureFlags::MULTISAMPLE
+        | wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE;
+    if samples > 1
+        && !adapter
+            .get_texture_format_features(format)
+            .flags
+            .contains(needed)
+    {
+        warn!("{:?} can't be multisampled, MSAA is off", format);
+        return 1;
+    }
+    samples
+}
+
+/// the texture drawn to before resolving into `size` frames, `None` without multisampling
+fn msaa_view(
+    device: &wgpu::Device,
+    size: (u32, u32),
+    format: wgpu::TextureFormat,
+    samples: u32,
+) -> Option<wgpu::TextureView> {
+    if samples <= 1 {
+        return None;
+    }
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("msaa"),
+        size: wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: samples,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    });
+    Some(texture.create_view(&wgpu::TextureViewDescriptor::default()))
+}
+
+/// A surface format with a full alpha channel, and an alpha mode blending it with the windows
+/// underneath, or an opaque one unless `transparent`. `None` if the surface can't do that.
+fn surface_config(
+    surface: &wgpu::Surface,
+    adapter: &wgpu::Adapter,
+    color_space: ColorSpace,
+    transparent: bool,
+) -> Option<(wgpu::TextureFormat, wgpu::CompositeAlphaMode)> {
+    const SRGB_FORMATS: [wgpu::TextureFormat; 2] = [
+        wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ];
+    const LINEAR_FORMATS: [wgpu::TextureFormat; 2] = [
+        wgpu::TextureFormat::Bgra8Unorm,
+        wgpu::TextureFormat::Rgba8Unorm,
+    ];
+    // linear colors, like the sRGB formats once decoded
+    const FLOAT_FORMATS: [wgpu::TextureFormat; 1] = [wgpu::TextureFormat::Rgba16Float];
+    // egui outputs premultiplied colors, and X compositors treat ARGB windows as premultiplied
+    const ALPHA_MODES: [wgpu::CompositeAlphaMode; 3] = [
+        wgpu::CompositeAlphaMode::PreMultiplied,
+        wgpu::CompositeAlphaMode::Inherit,
+        wgpu::CompositeAlphaMode::PostMultiplied,
+    ];
+    // the alpha is dropped by windows without an alpha channel anyway
+    const OPAQUE_MODES: [wgpu::CompositeAlphaMode; 2] = [
+        wgpu::CompositeAlphaMode::Opaque,
+        wgpu::CompositeAlphaMode::Inherit,
+    ];
+
+    let preferred: [&[wgpu::TextureFormat]; 3] = match color_space {
+        ColorSpace::Srgb => [&SRGB_FORMATS, &FLOAT_FORMATS, &LINEAR_FORMATS],
+        ColorSpace::Linear => [&LINEAR_FORMATS, &SRGB_FORMATS, &FLOAT_FORMATS],
+    };
+    // the driver lists its preferred format first, among equally preferred ones
+    let supported = surface.get_supported_formats(adapter);
+    let format = preferred.into_iter().find_map(|formats| {
+        supported
+            .iter()
+            .find(|format| formats.contains(format))
+            .copied()
+    })?;
+    if color_space == ColorSpace::Srgb && LINEAR_FORMATS.contains(&format) {
+        warn!("no sRGB surface format, colors will look off: {:?}", format);
+    }
+    let supported_modes = surface.get_supported_alpha_modes(adapter);
+    let alpha_modes: &[_] = if transparent {
+        &ALPHA_MODES
+    } else {
+        &OPAQUE_MODES
+    };
+    let alpha_mode = alpha_modes
+        .iter()
+        .find(|mode| supported_modes.contains(mode))
+        .copied()?;
+    Some((format, alpha_mode))
+}
+
+/// [`CLEAR_COLOR`], premultiplied unless the surface takes straight alpha or drops it, and
+/// made linear for sRGB formats encoding it back: premultiplied in sRGB, like egui colors.
+fn clear_color(config: &wgpu::SurfaceConfiguration) -> wgpu::Color {
+    let alpha = match config.alpha_mode {
+        wgpu::CompositeAlphaMode::PostMultiplied | wgpu::CompositeAlphaMode::Opaque => 1.,
+        _ => CLEAR_COLOR.a,
+    };
+    let linear = |c: f64| {
+        if !config.format.describe().srgb {
+            c
+        } else if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    wgpu::Color {
+        r: linear(CLEAR_COLOR.r * alpha),
+        g: linear(CLEAR_COLOR.g * alpha),
+        b: linear(CLEAR_COLOR.b * alpha),
+        a: CLEAR_COLOR.a,
+    }
+}
+
+/// `requested` if the surface supports it, otherwise `Fifo` which is always available.
+fn supported_present_mode(
+    surface: &wgpu::Surface,
+    adapter: &wgpu::Adapter,
+    requested: wgpu::PresentMode,
+) -> wgpu::PresentMode {
+    let auto = matches!(
+        requested,
+        wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync
+    );
+    if auto
+        || surface
+            .get_supported_present_modes(adapter)
+            .contains(&requested)
+    {
+        requested
+    } else {
+        warn!("present mode {:?} unsupported, using Fifo", requested);
+        wgpu::PresentMode::Fifo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::pos2;
+    use wgpu_core::error::ContextError;
+    use x11rb::protocol::xproto::MotionNotifyEvent;
+
+    use super::*;
+
+    /// `cause` as the uncaptured error handler gets it
+    fn uncaptured(cause: impl error::Error + Send + Sync + 'static) -> wgpu::Error {
+        wgpu::Error::Validation {
+            source: Box::new(ContextError {
+                string: "Queue::submit",
+                cause: Box::new(cause),
+                label_key: "label",
+                label: String::new(),
+            }),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn viewport_transform() {
+        let inset = Geometry {
+            margin: crate::geometry::Margin::uniform(20),
+            ..Geometry::fill()
+        };
+        // in points, at twice the scale
+        let screen = screen_rect((320, 180), 2., &inset);
+        assert_eq!(screen, Rect::from_min_max(pos2(10., 10.), pos2(150., 80.)));
+        assert_eq!(
+            screen_rect((320, 180), 1., &Geometry::fill()),
+            Rect::from_min_max(pos2(0., 0.), pos2(320., 180.))
+        );
+
+        // the pointer in the points of the whole window, at the same scale
+        let motion = x11rb::protocol::Event::MotionNotify(MotionNotifyEvent {
+            event_x: 20,
+            event_y: 30,
+            ..Default::default()
+        });
+        let pointer = crate::pointer::pointer_event(&motion, 2., egui::Modifiers::NONE);
+        assert_eq!(pointer, Some(egui::Event::PointerMoved(pos2(10., 15.))));
+        assert!(screen.contains(pos2(10., 15.)));
+
+        // cropped, the surface starts further in the window
+        let mut event = egui::Event::PointerMoved(pos2(5., 5.));
+        translate_event(&mut event, vec2(10., 20.));
+        assert_eq!(event, egui::Event::PointerMoved(pos2(15., 25.)));
+        let mut key = egui::Event::Text("a".to_owned());
+        translate_event(&mut key, vec2(10., 20.));
+        assert_eq!(key, egui::Event::Text("a".to_owned()));
+    }
+
+    #[test]
+    fn clear_colors() {
+        let config = |format, alpha_mode| wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: 1,
+            height: 1,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode,
+        };
+        let premultiplied = clear_color(&config(
+            wgpu::TextureFormat::Bgra8Unorm,
+            wgpu::CompositeAlphaMode::PreMultiplied,
+        ));
+        assert!((premultiplied.r - CLEAR_COLOR.r * CLEAR_COLOR.a).abs() < 1e-9);
+        assert!((premultiplied.b - CLEAR_COLOR.b * CLEAR_COLOR.a).abs() < 1e-9);
+        assert_eq!(premultiplied.a, CLEAR_COLOR.a);
+        // encoded back by the surface, then premultiplied as the compositor expects
+        let srgb = clear_color(&config(
+            wgpu::TextureFormat::Bgra8UnormSrgb,
+            wgpu::CompositeAlphaMode::Inherit,
+        ));
+        let encoded = match srgb.r {
+            c if c <= 0.0031308 => c * 12.92,
+            c => 1.055 * c.powf(1. / 2.4) - 0.055,
+        };
+        assert!((encoded - CLEAR_COLOR.r * CLEAR_COLOR.a).abs() < 1e-6);
+        for alpha_mode in [
+            wgpu::CompositeAlphaMode::PostMultiplied,
+            wgpu::CompositeAlphaMode::Opaque,
+        ] {
+            let straight = clear_color(&config(wgpu::TextureFormat::Bgra8Unorm, alpha_mode));
+            assert_eq!(straight, CLEAR_COLOR);
+        }
+    }
+
+    #[test]
+    fn device_lost_errors() {
+        assert!(is_device_lost(&uncaptured(DeviceError::Lost)));
+        assert!(is_device_lost(&uncaptured(QueueSubmitError::Queue(
+            DeviceError::Lost
+        ))));
+        assert!(is_device_lost(&uncaptured(ConfigureSurfaceError::Device(
+            DeviceError::Lost
+        ))));
+        assert!(!is_device_lost(&uncaptured(QueueSubmitError::Queue(
+            DeviceError::Invalid
+        ))));
+        assert!(!is_device_lost(&uncaptured(
+            ConfigureSurfaceError::ZeroArea
+        )));
+    }
+
+    #[test]
+    fn device_lost_panics() {
+        let message = format!(
+            "Error in Queue::submit: {}",
+            QueueSubmitError::Queue(DeviceError::Lost)
+        );
+        assert!(panic_reports_device_lost(&message));
+        let message = format!(
+            "Error in Surface::configure: {}",
+            ConfigureSurfaceError::ZeroArea
+        );
+        assert!(!panic_reports_device_lost(&message));
+    }
+}
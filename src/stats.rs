@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// over which the frame rate is counted
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Timing of the frames of the overlay itself, updated every frame, see
+/// [`Overlay::frame_stats`](crate::Overlay::frame_stats).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Building the UI, tessellating it and recording the GPU commands, for the last frame.
+    pub cpu_time: Duration,
+    /// Submitting the commands of the last frame to the GPU and presenting it.
+    pub submit_time: Duration,
+    /// From the submission until the GPU was done drawing, for the last frame it finished.
+    /// `None` until it finished one.
+    pub present_latency: Option<Duration>,
+    /// From the oldest input event the frame handled until the GPU was done drawing it, for
+    /// the last such frame it finished. The compositor and the display add their own delay.
+    pub input_latency: Option<Duration>,
+    /// Frames rendered over the last second.
+    pub fps: f32,
+    /// Frames rendered since the overlay was built.
+    pub frames: u64,
+    /// Frames the surface failed to render, and frames started more than a frame interval
+    /// late, at the max fps or 60 fps without one.
+    pub dropped_frames: u64,
+    /// Devices given up on by the [`Watchdog`](crate::Watchdog), the frames drawn on new ones.
+    pub gpu_resets: u64,
+}
+
+/// Measures the frames into [`FrameStats`].
+#[derive(Default)]
+pub(crate) struct FrameRecorder {
+    stats: FrameStats,
+    /// start of the frames of the last [`FPS_WINDOW`], oldest first
+    starts: VecDeque<Instant>,
+    /// oldest input event not handled by a frame yet
+    input: Option<Instant>,
+    /// present and input latencies, set by wgpu once the GPU is done with a frame
+    latency: Arc<Mutex<(Option<Duration>, Option<Duration>)>>,
+    /// frames the GPU is not done with yet
+    pending: Arc<AtomicUsize>,
+}
+
+impl FrameRecorder {
+    pub fn stats(&self) -> FrameStats {
+        let (present_latency, input_latency) = *self.latency.lock().unwrap();
+        FrameStats {
+            present_latency,
+            input_latency,
+            ..self.stats
+        }
+    }
+
+    /// Notes an input event received `at`, handled by the next frame.
+    pub fn input(&mut self, at: Instant) {
+        self.input.get_or_insert(at);
+    }
+
+    pub fn begin(&mut self, start: Instant) {
+        while self
+            .starts
+            .front()
+            .is_some_and(|first| start.duration_since(*first) > FPS_WINDOW)
+        {
+            self.starts.pop_front();
+        }
+        self.starts.push_back(start);
+        self.stats.fps = match (self.starts.front(), self.starts.back()) {
+            (Some(first), Some(last)) if self.starts.len() > 1 => {
+                (self.starts.len() - 1) as f32 / last.duration_since(*first).as_secs_f32()
+            }
+            _ => 0.,
+        };
+    }
+
+    /// Records a frame submitted at `submitted` after `cpu_time`, the latency then known
+    /// once `queue` is done with it.
+    pub fn submitted(
+        &mut self,
+        queue: &wgpu::Queue,
+        cpu_time: Duration,
+        submitted: Instant,
+        presented: Instant,
+    ) {
+        self.stats.cpu_time = cpu_time;
+        self.stats.submit_time = presented - submitted;
+        self.stats.frames += 1;
+        let latency = self.latency.clone();
+        let input = self.input.take();
+        let pending = self.pending.clone();
+        pending.fetch_add(1, Ordering::Relaxed);
+        queue.on_submitted_work_done(move || {
+            pending.fetch_sub(1, Ordering::Relaxed);
+            let mut latency = latency.lock().unwrap();
+            latency.0 = Some(submitted.elapsed());
+            if let Some(input) = input {
+                latency.1 = Some(input.elapsed());
+            }
+        });
+    }
+
+    /// Whether the GPU is still drawing frames, their latency known once the device is polled.
+    pub fn pending(&self) -> bool {
+        self.pending.load(Ordering::Relaxed) > 0
+    }
+
+    pub fn dropped(&mut self) {
+        self.stats.dropped_frames += 1;
+    }
+
+    pub fn gpu_reset(&mut self) {
+        self.stats.gpu_resets += 1;
+    }
+}
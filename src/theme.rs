@@ -0,0 +1,109 @@
+use std::env;
+use std::process::Command;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use tracing::debug;
+
+/// Dark or light egui visuals.
+///
+/// `OVERLAY_THEME` (`dark` or `light`) overrides [`Theme::System`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    /// The color scheme preferred by the desktop, from the freedesktop settings portal or
+    /// GNOME's settings, dark if neither tells.
+    System,
+}
+
+impl Theme {
+    pub(crate) fn visuals(self) -> egui::Visuals {
+        let dark = match self {
+            Self::Dark => true,
+            Self::Light => false,
+            Self::System => prefers_dark(),
+        };
+        if dark {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = Error;
+
+    /// `dark`, `light` or `system`
+    fn from_str(theme: &str) -> Result<Self, Error> {
+        match theme.to_lowercase().as_str() {
+            "dark" => Ok(Self::Dark),
+            "light" => Ok(Self::Light),
+            "system" => Ok(Self::System),
+            _ => Err(anyhow!("expected dark, light or system, got {:?}", theme)),
+        }
+    }
+}
+
+fn prefers_dark() -> bool {
+    if let Ok(theme) = env::var("OVERLAY_THEME") {
+        match theme.to_lowercase().as_str() {
+            "dark" => return true,
+            "light" => return false,
+            _ => debug!("OVERLAY_THEME: expected dark or light, got {:?}", theme),
+        }
+    }
+    portal_prefers_dark()
+        .or_else(gsettings_prefers_dark)
+        .unwrap_or(true)
+}
+
+/// `org.freedesktop.appearance color-scheme`: 0 no preference, 1 dark, 2 light
+fn portal_prefers_dark() -> Option<bool> {
+    let reply = output(
+        "gdbus",
+        &[
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.portal.Desktop",
+            "--object-path",
+            "/org/freedesktop/portal/desktop",
+            "--method",
+            "org.freedesktop.portal.Settings.Read",
+            "org.freedesktop.appearance",
+            "color-scheme",
+        ],
+    )?;
+    // e.g. `(<<uint32 1>>,)`
+    let value = reply
+        .trim_matches(|c: char| !c.is_ascii_digit())
+        .rsplit(|c: char| !c.is_ascii_digit())
+        .next()?;
+    match value {
+        "1" => Some(true),
+        "2" => Some(false),
+        _ => None,
+    }
+}
+
+/// GNOME's color scheme, or its GTK theme on versions before 42
+fn gsettings_prefers_dark() -> Option<bool> {
+    let get = |key| output("gsettings", &["get", "org.gnome.desktop.interface", key]);
+    match get("color-scheme").as_deref().map(str::trim) {
+        Some("'prefer-dark'") => Some(true),
+        Some("'prefer-light'") => Some(false),
+        _ => get("gtk-theme").map(|theme| theme.to_lowercase().contains("dark")),
+    }
+}
+
+/// the standard output of the command, `None` if it is missing or fails
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8(output.stdout).ok()
+}
@@ -0,0 +1,427 @@
+//! Timed messages shown in a corner of an overlay, e.g. OSD-style volume changes or errors,
+//! pushed with [`Overlay::push_toast`](crate::Overlay::push_toast), or from other threads
+//! with a [`ToastSender`].
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use egui_wgpu_x11::toasts::Toast;
+//!
+//! # let overlay = egui_wgpu_x11::Overlay::new()?;
+//! let toasts = overlay.toast_sender();
+//! std::thread::spawn(move || {
+//!     toasts.push(Toast::new("Volume 40%").duration(Duration::from_secs(1)));
+//! });
+//! # anyhow::Ok(())
+//! ```
+//!
+//! Clicking a toast dismisses it. The actions of toasts are shown as buttons, their clicks
+//! sent to [`Toast::events`].
+
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use egui::{Align2, Color32, ColorImage, Frame, RichText, Sense, TextureHandle};
+
+use crate::{OverlayApp, Waker};
+
+/// shown at once, the oldest ones going first
+const MAX_TOASTS: usize = 5;
+/// between the toasts and the corner of the overlay
+const MARGIN: f32 = 10.;
+/// at the end of their duration
+const FADE_OUT: Duration = Duration::from_millis(500);
+/// of the text, wrapped beyond
+const MAX_WIDTH: f32 = 320.;
+/// side of the icons, in points
+const ICON_SIZE: f32 = 32.;
+/// the action of clicking the toast itself
+const DEFAULT_ACTION: &str = "default";
+
+/// How a toast is highlighted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToastKind {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+/// A message shown for a few seconds, then fading out.
+#[derive(Clone)]
+pub struct Toast {
+    summary: String,
+    body: String,
+    kind: ToastKind,
+    duration: Option<Duration>,
+    id: u64,
+    icon: Option<Arc<ColorImage>>,
+    actions: Vec<(String, String)>,
+    events: Option<Sender<ToastEvent>>,
+}
+
+/// What happened to a toast, sent to [`Toast::events`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ToastEvent {
+    /// The action with this key was clicked, `default` for the toast itself.
+    Action {
+        id: u64,
+        key: String,
+    },
+    Closed {
+        id: u64,
+        reason: CloseReason,
+    },
+}
+
+/// Why a toast went away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CloseReason {
+    Expired,
+    /// Clicked, or one of its actions.
+    Dismissed,
+    /// With [`ToastSender::close`] or [`Overlay::close_toast`](crate::Overlay::close_toast).
+    Closed,
+    /// Pushed out by newer toasts, when too many are shown.
+    Dropped,
+}
+
+impl Toast {
+    /// Shown for 5 seconds, see [`Toast::duration`].
+    pub fn new(summary: impl Into<String>) -> Self {
+        Self {
+            summary: summary.into(),
+            body: String::new(),
+            kind: ToastKind::Info,
+            duration: Some(Duration::from_secs(5)),
+            id: 0,
+            icon: None,
+            actions: Vec::new(),
+            events: None,
+        }
+    }
+
+    pub fn warning(summary: impl Into<String>) -> Self {
+        Self::new(summary).kind(ToastKind::Warning)
+    }
+
+    pub fn error(summary: impl Into<String>) -> Self {
+        Self::new(summary).kind(ToastKind::Error)
+    }
+
+    /// Shown under the summary, none by default.
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn kind(mut self, kind: ToastKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Fade-out included.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Shown until dismissed or closed.
+    pub fn persistent(mut self) -> Self {
+        self.duration = None;
+        self
+    }
+
+    /// Replaces the toast shown with the same id, if any, and tells the [`ToastEvent`]s
+    /// apart. 0, the default, replaces none.
+    pub fn id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Shown left of the text.
+    pub fn icon(mut self, icon: ColorImage) -> Self {
+        self.icon = Some(Arc::new(icon));
+        self
+    }
+
+    /// A button labeled `label`, sending its `key` when clicked. The `default` action is
+    /// clicking the toast itself instead.
+    pub fn action(mut self, key: impl Into<String>, label: impl Into<String>) -> Self {
+        self.actions.push((key.into(), label.into()));
+        self
+    }
+
+    /// Where the clicks and the closing of the toast are sent.
+    pub fn events(mut self, events: Sender<ToastEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn send(&self, event: ToastEvent) {
+        if let Some(events) = &self.events {
+            // nobody listens anymore otherwise
+            let _ = events.send(event);
+        }
+    }
+
+    fn closed(&self, reason: CloseReason) {
+        self.send(ToastEvent::Closed {
+            id: self.id,
+            reason,
+        });
+    }
+}
+
+enum Message {
+    Push(Toast),
+    Close(u64),
+}
+
+/// Pushes toasts to an overlay from any thread, see
+/// [`Overlay::toast_sender`](crate::Overlay::toast_sender).
+#[derive(Clone, Debug)]
+pub struct ToastSender {
+    sender: Sender<Message>,
+    waker: Waker,
+}
+
+impl ToastSender {
+    pub fn push(&self, toast: Toast) {
+        self.send(Message::Push(toast));
+    }
+
+    /// Closes the toasts with this [`Toast::id`].
+    pub fn close(&self, id: u64) {
+        self.send(Message::Close(id));
+    }
+
+    fn send(&self, message: Message) {
+        // the overlay is gone otherwise
+        if self.sender.send(message).is_ok() {
+            self.waker.wake();
+        }
+    }
+}
+
+struct Shown {
+    toast: Toast,
+    until: Option<Instant>,
+    /// loaded when first drawn
+    icon: Option<TextureHandle>,
+}
+
+/// The toasts of an overlay, oldest first.
+pub(crate) struct Toasts {
+    shown: VecDeque<Shown>,
+    pub sender: ToastSender,
+    received: Receiver<Message>,
+    pub corner: Align2,
+}
+
+impl Toasts {
+    pub fn new(waker: Waker, corner: Align2) -> Self {
+        let (sender, received) = mpsc::channel();
+        Self {
+            shown: VecDeque::new(),
+            sender: ToastSender { sender, waker },
+            received,
+            corner,
+        }
+    }
+
+    pub fn push(&mut self, toast: Toast) {
+        let shown = Shown {
+            until: toast.duration.map(|duration| Instant::now() + duration),
+            toast,
+            icon: None,
+        };
+        let id = shown.toast.id;
+        if let Some(replaced) = self
+            .shown
+            .iter_mut()
+            .find(|replaced| id != 0 && replaced.toast.id == id)
+        {
+            *replaced = shown;
+            return;
+        }
+        if self.shown.len() == MAX_TOASTS {
+            if let Some(dropped) = self.shown.pop_front() {
+                dropped.toast.closed(CloseReason::Dropped);
+            }
+        }
+        self.shown.push_back(shown);
+    }
+
+    pub fn close(&mut self, id: u64, reason: CloseReason) {
+        self.shown.retain(|shown| {
+            let closed = shown.toast.id == id;
+            if closed {
+                shown.toast.closed(reason);
+            }
+            !closed
+        });
+    }
+
+    /// Draws the toasts not expired at `now` over `app`, the ones sent from other threads
+    /// included.
+    pub fn wrap<'a>(
+        &'a mut self,
+        ctx: &egui::Context,
+        now: Instant,
+        app: &'a mut dyn OverlayApp,
+    ) -> ToastLayer<'a> {
+        while let Ok(message) = self.received.try_recv() {
+            match message {
+                Message::Push(toast) => self.push(toast),
+                Message::Close(id) => self.close(id, CloseReason::Closed),
+            }
+        }
+        self.shown.retain(|shown| {
+            let expired = shown.until.is_some_and(|until| until <= now);
+            if expired {
+                shown.toast.closed(CloseReason::Expired);
+            }
+            !expired
+        });
+        for shown in &mut self.shown {
+            if let (Some(image), None) = (&shown.toast.icon, &shown.icon) {
+                let image = ColorImage::clone(image);
+                shown.icon = Some(ctx.load_texture("toast icon", image, Default::default()));
+            }
+        }
+        ToastLayer {
+            toasts: self,
+            now,
+            app,
+        }
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, now: Instant) {
+        if self.shown.is_empty() {
+            return;
+        }
+        if let Some(next_expiry) = self.shown.iter().filter_map(|shown| shown.until).min() {
+            let left = next_expiry.saturating_duration_since(now);
+            if left < FADE_OUT {
+                ctx.request_repaint();
+            } else {
+                ctx.request_repaint_after(left - FADE_OUT);
+            }
+        }
+
+        let offset = [
+            -self.corner.x().to_sign() * MARGIN,
+            -self.corner.y().to_sign() * MARGIN,
+        ];
+        let mut clicked = None;
+        egui::Area::new("overlay toasts")
+            .anchor(self.corner, offset)
+            .show(ctx, |ui| {
+                ui.with_layout(egui::Layout::top_down(self.corner.x()), |ui| {
+                    for (i, shown) in self.shown.iter().enumerate() {
+                        let alpha = shown.until.map_or(1., |until| {
+                            let left = until.saturating_duration_since(now);
+                            (left.as_secs_f32() / FADE_OUT.as_secs_f32()).min(1.)
+                        });
+                        if let Some(action) = toast_ui(ui, shown, alpha) {
+                            clicked = Some((i, action));
+                        }
+                    }
+                });
+            });
+
+        let Some((i, action)) = clicked else {
+            return;
+        };
+        if let Some(shown) = self.shown.remove(i) {
+            if let Some(key) = action {
+                shown.toast.send(ToastEvent::Action {
+                    id: shown.toast.id,
+                    key,
+                });
+            }
+            shown.toast.closed(CloseReason::Dismissed);
+        }
+    }
+}
+
+/// a toast, its colors faded to `alpha`, returns whether it was clicked, with the key of the
+/// action if any
+fn toast_ui(ui: &mut egui::Ui, shown: &Shown, alpha: f32) -> Option<Option<String>> {
+    let toast = &shown.toast;
+    let visuals = ui.visuals();
+    let accent = match toast.kind {
+        ToastKind::Info => visuals.widgets.noninteractive.bg_stroke.color,
+        ToastKind::Warning => visuals.warn_fg_color,
+        ToastKind::Error => visuals.error_fg_color,
+    };
+    let summary = match toast.kind {
+        ToastKind::Info => visuals.strong_text_color(),
+        _ => accent,
+    };
+    let body = visuals.text_color();
+    let mut frame = Frame::popup(ui.style());
+    frame.fill = frame.fill.linear_multiply(alpha);
+    frame.stroke.color = accent.linear_multiply(alpha);
+    frame.shadow.color = frame.shadow.color.linear_multiply(alpha);
+
+    let mut clicked_action = None;
+    let framed = frame.show(ui, |ui| {
+        ui.set_max_width(MAX_WIDTH);
+        ui.horizontal(|ui| {
+            if let Some(icon) = &shown.icon {
+                ui.add(
+                    egui::Image::new(icon.id(), [ICON_SIZE, ICON_SIZE])
+                        .tint(Color32::WHITE.linear_multiply(alpha)),
+                );
+            }
+            ui.vertical(|ui| {
+                ui.label(RichText::new(&toast.summary).color(summary.linear_multiply(alpha)));
+                if !toast.body.is_empty() {
+                    ui.label(RichText::new(&toast.body).color(body.linear_multiply(alpha)));
+                }
+                let buttons: Vec<_> = toast
+                    .actions
+                    .iter()
+                    .filter(|(key, _)| key != DEFAULT_ACTION)
+                    .collect();
+                if !buttons.is_empty() {
+                    ui.horizontal_wrapped(|ui| {
+                        for (key, label) in buttons {
+                            if ui.button(label).clicked() {
+                                clicked_action = Some(key.clone());
+                            }
+                        }
+                    });
+                }
+            });
+        });
+    });
+    if clicked_action.is_some() {
+        return Some(clicked_action);
+    }
+    let response = ui.interact(
+        framed.response.rect,
+        framed.response.id.with("toast"),
+        Sense::click(),
+    );
+    let default = toast.actions.iter().any(|(key, _)| key == DEFAULT_ACTION);
+    response
+        .clicked()
+        .then(|| default.then(|| DEFAULT_ACTION.to_owned()))
+}
+
+pub(crate) struct ToastLayer<'a> {
+    toasts: &'a mut Toasts,
+    now: Instant,
+    app: &'a mut dyn OverlayApp,
+}
+
+impl OverlayApp for ToastLayer<'_> {
+    fn ui(&mut self, ctx: &egui::Context) {
+        self.app.ui(ctx);
+        self.toasts.ui(ctx, self.now);
+    }
+}
@@ -0,0 +1,74 @@
+use egui::{pos2, Event, Modifiers, PointerButton, Pos2, TouchDeviceId, TouchId, TouchPhase};
+use x11rb::protocol::Event as XEvent;
+
+/// Translates XInput2 touch events, selected by [`crate::scroll::select_xi2_events`].
+///
+/// egui widgets only react to the pointer, so the first finger down also drives it, like
+/// egui-winit does. The touch events make multitouch gestures, e.g. pinch-zoom.
+#[derive(Default)]
+pub(crate) struct Touches {
+    /// the touch driving the pointer
+    pointer: Option<u32>,
+}
+
+impl Touches {
+    pub fn handle_event(
+        &mut self,
+        event: &XEvent,
+        pixels_per_point: f32,
+        modifiers: Modifiers,
+        events: &mut Vec<Event>,
+    ) {
+        let (e, phase) = match event {
+            XEvent::XinputTouchBegin(e) => (e, TouchPhase::Start),
+            XEvent::XinputTouchUpdate(e) => (e, TouchPhase::Move),
+            XEvent::XinputTouchEnd(e) => (e, TouchPhase::End),
+            _ => return,
+        };
+        // 16.16 fixed point
+        let pos = pos2(
+            e.event_x as f32 / 65536. / pixels_per_point,
+            e.event_y as f32 / 65536. / pixels_per_point,
+        );
+        events.push(Event::Touch {
+            device_id: TouchDeviceId(e.sourceid.into()),
+            id: TouchId(e.detail.into()),
+            phase,
+            pos,
+            // pressure valuators are not standard
+            force: 0.,
+        });
+
+        if phase == TouchPhase::Start && self.pointer.is_none() {
+            self.pointer = Some(e.detail);
+        }
+        if self.pointer == Some(e.detail) {
+            self.drive_pointer(phase, pos, modifiers, events);
+        }
+        if phase == TouchPhase::End && self.pointer == Some(e.detail) {
+            self.pointer = None;
+        }
+    }
+
+    fn drive_pointer(
+        &self,
+        phase: TouchPhase,
+        pos: Pos2,
+        modifiers: Modifiers,
+        events: &mut Vec<Event>,
+    ) {
+        let button = |pressed| Event::PointerButton {
+            pos,
+            button: PointerButton::Primary,
+            pressed,
+            modifiers,
+        };
+        match phase {
+            TouchPhase::Start => events.extend([Event::PointerMoved(pos), button(true)]),
+            TouchPhase::Move => events.push(Event::PointerMoved(pos)),
+            TouchPhase::End | TouchPhase::Cancel => {
+                events.extend([button(false), Event::PointerGone])
+            }
+        }
+    }
+}
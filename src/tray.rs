@@ -0,0 +1,417 @@
+//! A status icon in the panel, to manage long-running overlays: clicking it shows or hides
+//! the overlay, its menu has the app's items and quits, see
+//! [`Overlay::set_tray`](crate::Overlay::set_tray).
+//!
+//! ```no_run
+//! use egui_wgpu_x11::tray::Tray;
+//!
+//! # let mut overlay = egui_wgpu_x11::Overlay::new()?;
+//! overlay.set_tray(Some(Tray::new("HUD").item("Debug panel", |overlay| {
+//!     overlay.set_debug_panel(!overlay.debug_panel());
+//!     Ok(())
+//! })))?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! The icon follows the StatusNotifierItem specification, shown by KDE, waybar, polybar
+//! and the GNOME AppIndicator extension among others, with the menu served as dbusmenu.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use anyhow::{Context as _, Result};
+use egui::ColorImage;
+use tracing::trace;
+use zbus::blocking::{Connection, ConnectionBuilder};
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+use zbus::{dbus_interface, SignalContext};
+
+use crate::{Overlay, Waker};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+
+/// menu item ids, the app's items following
+const ROOT: i32 = 0;
+const VISIBLE: i32 = 1;
+const SEPARATOR: i32 = 2;
+const QUIT: i32 = 3;
+const FIRST_ITEM: i32 = 10;
+
+/// of the tray icons of the process, for their bus names
+static TRAYS: AtomicU32 = AtomicU32::new(0);
+
+type TrayCallback = Box<dyn FnMut(&mut Overlay) -> Result<()>>;
+
+/// The icon and menu of an overlay in the panel.
+pub struct Tray {
+    title: String,
+    icon_name: String,
+    icon: Option<ColorImage>,
+    items: Vec<(String, TrayCallback)>,
+}
+
+impl Tray {
+    /// With the `video-display` icon of the theme.
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            icon_name: "video-display".to_owned(),
+            icon: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// An icon of the theme.
+    pub fn icon_name(mut self, name: impl Into<String>) -> Self {
+        self.icon_name = name.into();
+        self
+    }
+
+    /// Drawn instead of the icon of the theme.
+    pub fn icon(mut self, icon: ColorImage) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// A menu item running `callback` on the overlay when clicked, after the visibility
+    /// toggle and before quitting, e.g. to show settings.
+    pub fn item(
+        mut self,
+        label: impl Into<String>,
+        callback: impl FnMut(&mut Overlay) -> Result<()> + 'static,
+    ) -> Self {
+        self.items.push((label.into(), Box::new(callback)));
+        self
+    }
+}
+
+/// What was clicked.
+enum Click {
+    Toggle,
+    Quit,
+    Item(usize),
+}
+
+/// A tray icon registered, running the clicks on the overlay loop.
+pub(crate) struct TrayIcon {
+    connection: Connection,
+    clicks: Receiver<Click>,
+    callbacks: Vec<TrayCallback>,
+    /// of the overlay, checked in the menu
+    visible: Arc<AtomicBool>,
+    revision: Arc<AtomicU32>,
+}
+
+impl TrayIcon {
+    pub fn start(tray: Tray, waker: Waker, visible: bool) -> Result<Self> {
+        let (sender, clicks) = mpsc::channel();
+        let visible = Arc::new(AtomicBool::new(visible));
+        let revision = Arc::new(AtomicU32::new(1));
+        let (labels, callbacks) = tray.items.into_iter().unzip();
+        let item = StatusNotifierItem {
+            title: tray.title,
+            icon_name: tray.icon_name,
+            icon_pixmap: tray.icon.iter().map(argb_pixmap).collect(),
+            clicks: sender.clone(),
+            waker: waker.clone(),
+        };
+        let menu = DbusMenu {
+            labels,
+            visible: visible.clone(),
+            revision: revision.clone(),
+            clicks: sender,
+            waker,
+        };
+        let name = format!(
+            "org.kde.StatusNotifierItem-{}-{}",
+            std::process::id(),
+            TRAYS.fetch_add(1, Ordering::Relaxed) + 1
+        );
+        let connection = ConnectionBuilder::session()?
+            .name(name.as_str())?
+            .serve_at(ITEM_PATH, item)?
+            .serve_at(MENU_PATH, menu)?
+            .build()?;
+        connection
+            .call_method(
+                Some("org.kde.StatusNotifierWatcher"),
+                "/StatusNotifierWatcher",
+                Some("org.kde.StatusNotifierWatcher"),
+                "RegisterStatusNotifierItem",
+                &(name.as_str(),),
+            )
+            .context("registering the tray icon, is there a tray running?")?;
+        Ok(Self {
+            connection,
+            clicks,
+            callbacks,
+            visible,
+            revision,
+        })
+    }
+
+    /// Runs the clicks received since the last call.
+    pub fn handle_clicks(&mut self, overlay: &mut Overlay) -> Result<()> {
+        for click in self.clicks.try_iter().collect::<Vec<_>>() {
+            match click {
+                Click::Toggle => overlay.set_visible(!overlay.visible())?,
+                Click::Quit => overlay.close(),
+                Click::Item(i) => {
+                    if let Some(callback) = self.callbacks.get_mut(i) {
+                        callback(overlay)?
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the visibility toggle of the menu or not.
+    pub fn set_visible(&self, visible: bool) -> Result<()> {
+        if self.visible.swap(visible, Ordering::Relaxed) == visible {
+            return Ok(());
+        }
+        let revision = self.revision.fetch_add(1, Ordering::Relaxed) + 1;
+        let context = SignalContext::new(self.connection.inner(), MENU_PATH)?;
+        zbus::block_on(DbusMenu::layout_updated(&context, revision, ROOT))?;
+        Ok(())
+    }
+}
+
+/// `org.kde.StatusNotifierItem`
+struct StatusNotifierItem {
+    title: String,
+    icon_name: String,
+    icon_pixmap: Vec<(i32, i32, Vec<u8>)>,
+    clicks: Sender<Click>,
+    waker: Waker,
+}
+
+#[dbus_interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    fn activate(&self, x: i32, y: i32) {
+        trace!(x, y, "tray icon activated");
+        send(&self.clicks, &self.waker, Click::Toggle);
+    }
+
+    fn secondary_activate(&self, x: i32, y: i32) {
+        trace!(x, y, "tray icon middle-clicked");
+    }
+
+    fn context_menu(&self, x: i32, y: i32) {
+        trace!(x, y, "tray menu requested outside of dbusmenu");
+    }
+
+    fn scroll(&self, delta: i32, orientation: &str) {
+        trace!(delta, orientation, "tray icon scrolled");
+    }
+
+    #[dbus_interface(property)]
+    fn category(&self) -> &str {
+        "ApplicationStatus"
+    }
+
+    #[dbus_interface(property)]
+    fn id(&self) -> &str {
+        &self.title
+    }
+
+    #[dbus_interface(property)]
+    fn title(&self) -> &str {
+        &self.title
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        "Active"
+    }
+
+    #[dbus_interface(property)]
+    fn icon_name(&self) -> &str {
+        &self.icon_name
+    }
+
+    #[dbus_interface(property)]
+    fn icon_pixmap(&self) -> Vec<(i32, i32, Vec<u8>)> {
+        self.icon_pixmap.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[dbus_interface(property)]
+    fn menu(&self) -> ObjectPath<'_> {
+        ObjectPath::from_static_str_unchecked(MENU_PATH)
+    }
+}
+
+/// `com.canonical.dbusmenu`, the menu of the icon
+struct DbusMenu {
+    labels: Vec<String>,
+    visible: Arc<AtomicBool>,
+    revision: Arc<AtomicU32>,
+    clicks: Sender<Click>,
+    waker: Waker,
+}
+
+type Properties = HashMap<String, OwnedValue>;
+
+impl DbusMenu {
+    fn ids(&self) -> impl Iterator<Item = i32> {
+        let items = (0..self.labels.len() as i32).map(|i| FIRST_ITEM + i);
+        std::iter::once(VISIBLE)
+            .chain(items)
+            .chain([SEPARATOR, QUIT])
+    }
+
+    fn properties(&self, id: i32) -> Properties {
+        let mut properties = Properties::new();
+        let mut set = |name: &str, value: Value<'_>| {
+            properties.insert(name.to_owned(), value.into());
+        };
+        match id {
+            ROOT => set("children-display", "submenu".into()),
+            VISIBLE => {
+                set("label", "Visible".into());
+                set("toggle-type", "checkmark".into());
+                let visible = self.visible.load(Ordering::Relaxed);
+                set("toggle-state", i32::from(visible).into());
+            }
+            SEPARATOR => set("type", "separator".into()),
+            QUIT => set("label", "Quit".into()),
+            id => {
+                let label = self.labels.get((id - FIRST_ITEM) as usize);
+                set("label", label.map_or("", String::as_str).into());
+            }
+        }
+        properties
+    }
+}
+
+#[dbus_interface(name = "com.canonical.dbusmenu")]
+impl DbusMenu {
+    /// The whole menu, it has no submenus.
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        recursion_depth: i32,
+        property_names: Vec<String>,
+    ) -> (u32, (i32, Properties, Vec<OwnedValue>)) {
+        trace!(
+            parent_id,
+            recursion_depth,
+            ?property_names,
+            "tray menu layout"
+        );
+        let children = self
+            .ids()
+            .map(|id| {
+                let child = (id, self.properties(id), Vec::<OwnedValue>::new());
+                Value::from(child).into()
+            })
+            .collect();
+        let revision = self.revision.load(Ordering::Relaxed);
+        (revision, (ROOT, self.properties(ROOT), children))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        property_names: Vec<String>,
+    ) -> Vec<(i32, Properties)> {
+        trace!(?ids, ?property_names, "tray menu properties");
+        ids.into_iter()
+            .map(|id| (id, self.properties(id)))
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: &str) -> OwnedValue {
+        let mut properties = self.properties(id);
+        properties
+            .remove(name)
+            .unwrap_or_else(|| Value::from("").into())
+    }
+
+    fn event(&self, id: i32, event_id: &str, data: OwnedValue, timestamp: u32) {
+        trace!(id, event_id, ?data, timestamp, "tray menu event");
+        if event_id != "clicked" {
+            return;
+        }
+        let click = match id {
+            VISIBLE => Click::Toggle,
+            QUIT => Click::Quit,
+            id if id >= FIRST_ITEM => Click::Item((id - FIRST_ITEM) as usize),
+            _ => return,
+        };
+        send(&self.clicks, &self.waker, click);
+    }
+
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        for (id, event_id, data, timestamp) in events {
+            self.event(id, &event_id, data, timestamp);
+        }
+        Vec::new()
+    }
+
+    fn about_to_show(&self, id: i32) -> bool {
+        trace!(id, "tray menu about to show");
+        false
+    }
+
+    fn about_to_show_group(&self, ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        trace!(?ids, "tray menus about to show");
+        (Vec::new(), Vec::new())
+    }
+
+    #[dbus_interface(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[dbus_interface(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[dbus_interface(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[dbus_interface(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[dbus_interface(signal)]
+    async fn layout_updated(
+        context: &SignalContext<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+fn send(clicks: &Sender<Click>, waker: &Waker, click: Click) {
+    // the overlay is gone otherwise
+    if clicks.send(click).is_ok() {
+        waker.wake();
+    }
+}
+
+/// `image` as ARGB32 in network byte order, for `IconPixmap`
+fn argb_pixmap(image: &ColorImage) -> (i32, i32, Vec<u8>) {
+    let argb = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| {
+            let [r, g, b, a] = pixel.to_srgba_unmultiplied();
+            [a, r, g, b]
+        })
+        .collect();
+    (image.size[0] as i32, image.size[1] as i32, argb)
+}
@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+
+use egui::epaint::ImageDelta;
+use egui::{ColorImage, FontImage, ImageData, TextureId, TexturesDelta};
+use tracing::warn;
+
+/// Applies `delta` to the copies of the egui textures, and returns what is left to upload:
+/// the partial updates writing the pixels already there are dropped, the others of a texture
+/// merged into one covering them all, e.g. the glyphs added to the font atlas in a frame.
+pub(crate) fn compact(
+    delta: TexturesDelta,
+    textures: &mut HashMap<TextureId, ImageDelta>,
+) -> TexturesDelta {
+    // in the order of the delta, `None` for the whole texture
+    let mut changed: Vec<(TextureId, Option<Region>)> = Vec::new();
+    for (id, set) in delta.set {
+        let region = match (set.pos, textures.get_mut(&id)) {
+            (Some(pos), Some(texture)) => {
+                if same_pixels(&texture.image, &set.image, pos) {
+                    continue;
+                }
+                patch(&mut texture.image, &set.image, pos);
+                Some(Region::new(pos, set.image.size()))
+            }
+            _ => {
+                textures.insert(id, set);
+                None
+            }
+        };
+        match changed.iter_mut().find(|(changed_id, _)| *changed_id == id) {
+            Some((_, merged)) => {
+                *merged = match (*merged, region) {
+                    (Some(merged), Some(region)) => Some(merged.union(region)),
+                    _ => None,
+                }
+            }
+            None => changed.push((id, region)),
+        }
+    }
+
+    let set = changed
+        .into_iter()
+        .filter_map(|(id, region)| {
+            let texture = textures.get(&id)?;
+            let delta = match region {
+                Some(region) if texture.pos.is_none() => {
+                    ImageDelta::partial(region.min, crop(&texture.image, region), texture.filter)
+                }
+                _ => texture.clone(),
+            };
+            Some((id, delta))
+        })
+        .collect();
+    for id in &delta.free {
+        textures.remove(id);
+    }
+    TexturesDelta {
+        set,
+        free: delta.free,
+    }
+}
+
+/// A rectangle of texels, `max` excluded.
+#[derive(Clone, Copy)]
+struct Region {
+    min: [usize; 2],
+    max: [usize; 2],
+}
+
+impl Region {
+    fn new(pos: [usize; 2], size: [usize; 2]) -> Self {
+        Self {
+            min: pos,
+            max: [pos[0] + size[0], pos[1] + size[1]],
+        }
+    }
+
+    fn union(self, other: Region) -> Self {
+        Self {
+            min: [self.min[0].min(other.min[0]), self.min[1].min(other.min[1])],
+            max: [self.max[0].max(other.max[0]), self.max[1].max(other.max[1])],
+        }
+    }
+
+    fn size(&self) -> [usize; 2] {
+        [self.max[0] - self.min[0], self.max[1] - self.min[1]]
+    }
+}
+
+/// whether `image` already holds the pixels of `patch` at `pos`
+fn same_pixels(image: &ImageData, patch: &ImageData, pos: [usize; 2]) -> bool {
+    match (image, patch) {
+        (ImageData::Color(image), ImageData::Color(patch)) => {
+            same_rows(&image.pixels, image.size, &patch.pixels, patch.size[0], pos)
+        }
+        (ImageData::Font(image), ImageData::Font(patch)) => {
+            same_rows(&image.pixels, image.size, &patch.pixels, patch.size[0], pos)
+        }
+        _ => false,
+    }
+}
+
+fn same_rows<T: PartialEq>(
+    pixels: &[T],
+    [width, height]: [usize; 2],
+    patch: &[T],
+    patch_width: usize,
+    [x, y]: [usize; 2],
+) -> bool {
+    if x + patch_width > width || y + patch.len() / patch_width.max(1) > height {
+        return false;
+    }
+    patch
+        .chunks_exact(patch_width.max(1))
+        .enumerate()
+        .all(|(row, patch_row)| {
+            let start = (y + row) * width + x;
+            pixels[start..start + patch_row.len()] == *patch_row
+        })
+}
+
+/// copies the pixels of `patch` into `image`, its top-left corner at `pos`
+fn patch(image: &mut ImageData, patch: &ImageData, pos: [usize; 2]) {
+    match (image, patch) {
+        (ImageData::Color(image), ImageData::Color(patch)) => patch_rows(
+            &mut image.pixels,
+            image.size[0],
+            &patch.pixels,
+            patch.size[0],
+            pos,
+        ),
+        (ImageData::Font(image), ImageData::Font(patch)) => patch_rows(
+            &mut image.pixels,
+            image.size[0],
+            &patch.pixels,
+            patch.size[0],
+            pos,
+        ),
+        _ => warn!("texture patched with another kind of image"),
+    }
+}
+
+fn patch_rows<T: Copy>(
+    pixels: &mut [T],
+    width: usize,
+    patch: &[T],
+    patch_width: usize,
+    [x, y]: [usize; 2],
+) {
+    for (row, patch_row) in patch.chunks_exact(patch_width.max(1)).enumerate() {
+        let start = (y + row) * width + x;
+        pixels[start..start + patch_row.len()].copy_from_slice(patch_row);
+    }
+}
+
+/// the pixels of `region` of `image`
+fn crop(image: &ImageData, region: Region) -> ImageData {
+    let size = region.size();
+    match image {
+        ImageData::Color(image) => ImageData::Color(ColorImage {
+            size,
+            pixels: crop_rows(&image.pixels, image.size[0], region),
+        }),
+        ImageData::Font(image) => ImageData::Font(FontImage {
+            size,
+            pixels: crop_rows(&image.pixels, image.size[0], region),
+        }),
+    }
+}
+
+fn crop_rows<T: Copy>(pixels: &[T], width: usize, region: Region) -> Vec<T> {
+    (region.min[1]..region.max[1])
+        .flat_map(|y| &pixels[y * width + region.min[0]..y * width + region.max[0]])
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use egui::{Color32, TextureFilter};
+
+    use super::*;
+
+    const ID: TextureId = TextureId::Managed(1);
+
+    fn image(size: [usize; 2], color: Color32) -> ColorImage {
+        ColorImage::new(size, color)
+    }
+
+    /// `ID`, a 4x4 black texture, uploaded
+    fn textures() -> HashMap<TextureId, ImageDelta> {
+        let mut textures = HashMap::new();
+        let full = ImageDelta::full(image([4, 4], Color32::BLACK), TextureFilter::Nearest);
+        let delta = TexturesDelta {
+            set: vec![(ID, full.clone())],
+            free: vec![],
+        };
+        let uploads = compact(delta, &mut textures);
+        assert!(uploads.set == [(ID, full)]);
+        textures
+    }
+
+    fn partial(pos: [usize; 2], size: [usize; 2], color: Color32) -> (TextureId, ImageDelta) {
+        (
+            ID,
+            ImageDelta::partial(pos, image(size, color), TextureFilter::Nearest),
+        )
+    }
+
+    fn color(texture: &ImageDelta, [x, y]: [usize; 2]) -> Color32 {
+        match &texture.image {
+            ImageData::Color(image) => image.pixels[y * image.size[0] + x],
+            ImageData::Font(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn adjacent_updates_merge() {
+        let mut textures = textures();
+        let delta = TexturesDelta {
+            set: vec![
+                partial([0, 0], [2, 1], Color32::RED),
+                partial([2, 0], [2, 1], Color32::GREEN),
+            ],
+            free: vec![],
+        };
+        let uploads = compact(delta, &mut textures);
+        let expected = {
+            let mut row = image([4, 1], Color32::RED);
+            row.pixels[2..].fill(Color32::GREEN);
+            ImageDelta::partial([0, 0], row, TextureFilter::Nearest)
+        };
+        assert!(uploads.set == [(ID, expected)]);
+        // the copy kept up to date
+        assert_eq!(color(&textures[&ID], [3, 0]), Color32::GREEN);
+        assert_eq!(color(&textures[&ID], [3, 1]), Color32::BLACK);
+    }
+
+    #[test]
+    fn overlapping_updates_merge_into_their_bounding_box() {
+        let mut textures = textures();
+        let delta = TexturesDelta {
+            set: vec![
+                partial([0, 0], [2, 2], Color32::RED),
+                partial([1, 1], [2, 2], Color32::BLUE),
+            ],
+            free: vec![],
+        };
+        let uploads = compact(delta, &mut textures);
+        let [(id, upload)] = &uploads.set[..] else {
+            panic!("one upload expected, got {}", uploads.set.len());
+        };
+        assert_eq!(*id, ID);
+        assert_eq!(upload.pos, Some([0, 0]));
+        assert_eq!(upload.image.size(), [3, 3]);
+        // the later update on top, the untouched corners as they were
+        assert_eq!(color(upload, [0, 0]), Color32::RED);
+        assert_eq!(color(upload, [1, 1]), Color32::BLUE);
+        assert_eq!(color(upload, [2, 0]), Color32::BLACK);
+        assert_eq!(color(upload, [0, 2]), Color32::BLACK);
+    }
+
+    #[test]
+    fn unchanged_pixels_and_freed_textures() {
+        let mut textures = textures();
+        let delta = TexturesDelta {
+            set: vec![partial([1, 1], [2, 2], Color32::BLACK)],
+            free: vec![],
+        };
+        assert!(compact(delta, &mut textures).set.is_empty());
+
+        // a whole texture replaced is uploaded whole, even after partial updates
+        let whole = ImageDelta::full(image([2, 2], Color32::WHITE), TextureFilter::Nearest);
+        let delta = TexturesDelta {
+            set: vec![partial([0, 0], [1, 1], Color32::RED), (ID, whole.clone())],
+            free: vec![],
+        };
+        assert!(compact(delta, &mut textures).set == [(ID, whole)]);
+
+        let delta = TexturesDelta {
+            set: vec![],
+            free: vec![ID],
+        };
+        assert!(compact(delta, &mut textures).free == [ID]);
+        assert!(textures.is_empty());
+    }
+}
@@ -0,0 +1,35 @@
+use std::process::{Command, Stdio};
+use std::thread;
+
+use tracing::{debug, warn};
+
+/// Given each link clicked, before it is opened. Returns whether it handled the URL, which
+/// is then not opened.
+pub(crate) type UrlHandler = Box<dyn FnMut(&str) -> bool>;
+
+/// The default command opening links.
+pub(crate) const DEFAULT_URL_COMMAND: &str = "xdg-open";
+
+/// Runs `command` with `url` as its last argument, without waiting for it.
+/// `command` may hold arguments, separated by spaces, e.g. `firefox --new-tab`.
+pub(crate) fn open(command: &str, url: &str) {
+    let mut args = command.split_whitespace();
+    let Some(program) = args.next() else {
+        return;
+    };
+    let spawned = Command::new(program)
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match spawned {
+        Ok(mut child) => {
+            debug!("opening {} with {}", url, program);
+            // reaped once done, not to leave a zombie
+            thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!("could not open {} with {}: {}", url, program, e),
+    }
+}
@@ -0,0 +1,56 @@
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::Arc;
+
+/// Wakes a sleeping overlay loop to render a frame, from any thread.
+///
+/// ```no_run
+/// # let overlay = egui_wgpu_x11::Overlay::new()?;
+/// let waker = overlay.waker();
+/// std::thread::spawn(move || loop {
+///     std::thread::sleep(std::time::Duration::from_secs(1));
+///     waker.wake();
+/// });
+/// # anyhow::Ok(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Waker(Arc<UnixStream>);
+
+impl Waker {
+    pub fn wake(&self) {
+        // a full socket already holds a pending wake-up
+        let _ = (&*self.0).write(&[0]);
+    }
+}
+
+/// The end of the wake-up socket watched by the loop.
+pub(crate) struct WakeReceiver(UnixStream);
+
+impl WakeReceiver {
+    /// Consumes the pending wake-ups, returns whether there were any.
+    pub fn drain(&self) -> bool {
+        let mut buf = [0; 64];
+        let mut woken = false;
+        while let Ok(n @ 1..) = (&self.0).read(&mut buf) {
+            woken = true;
+            if n < buf.len() {
+                break;
+            }
+        }
+        woken
+    }
+}
+
+impl AsRawFd for WakeReceiver {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+pub(crate) fn wake_channel() -> io::Result<(Waker, WakeReceiver)> {
+    let (sender, receiver) = UnixStream::pair()?;
+    sender.set_nonblocking(true)?;
+    receiver.set_nonblocking(true)?;
+    Ok((Waker(Arc::new(sender)), WakeReceiver(receiver)))
+}
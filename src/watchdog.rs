@@ -0,0 +1,119 @@
+use std::fmt;
+use std::time::Duration;
+
+/// When to give up on a GPU device that seems hung and draw on a new one, see
+/// [`OverlayBuilder::watchdog`](crate::OverlayBuilder::watchdog). The resets are logged and
+/// counted in [`FrameStats::gpu_resets`](crate::FrameStats::gpu_resets).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchdog {
+    /// Surface timeouts in a row, e.g. while the GPU doesn't finish the frames.
+    pub max_timeouts: u32,
+    /// Spent by a frame waiting on the GPU: acquiring the surface texture, then submitting
+    /// and presenting it. The time building the UI doesn't count.
+    pub max_frame_time: Duration,
+}
+
+impl Default for Watchdog {
+    /// 3 timeouts, or a 2 second frame.
+    fn default() -> Self {
+        Self {
+            max_timeouts: 3,
+            max_frame_time: Duration::from_secs(2),
+        }
+    }
+}
+
+/// why the watchdog reset the device
+pub(crate) enum Incident {
+    Timeouts(u32),
+    SlowFrame(Duration),
+}
+
+impl fmt::Display for Incident {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Timeouts(timeouts) => write!(f, "{} surface timeouts in a row", timeouts),
+            Self::SlowFrame(time) => write!(f, "a frame took {:?}", time),
+        }
+    }
+}
+
+/// Counts the timeouts in a row and times the frames against the [`Watchdog`], if any.
+pub(crate) struct WatchdogTimer {
+    pub watchdog: Option<Watchdog>,
+    timeouts: u32,
+}
+
+impl WatchdogTimer {
+    pub fn new(watchdog: Option<Watchdog>) -> Self {
+        Self {
+            watchdog,
+            timeouts: 0,
+        }
+    }
+
+    /// After a surface timeout.
+    pub fn timeout(&mut self) -> Option<Incident> {
+        self.timeouts += 1;
+        let watchdog = self.watchdog?;
+        (self.timeouts >= watchdog.max_timeouts)
+            .then(|| Incident::Timeouts(std::mem::take(&mut self.timeouts)))
+    }
+
+    /// After a frame presented in `time`.
+    pub fn frame(&mut self, time: Duration) -> Option<Incident> {
+        self.timeouts = 0;
+        let watchdog = self.watchdog?;
+        (time > watchdog.max_frame_time).then_some(Incident::SlowFrame(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incident(incident: Option<Incident>) -> Option<String> {
+        incident.map(|incident| incident.to_string())
+    }
+
+    #[test]
+    fn timeouts_in_a_row() {
+        let mut timer = WatchdogTimer::new(Some(Watchdog::default()));
+        assert!(timer.timeout().is_none());
+        assert!(timer.timeout().is_none());
+        assert_eq!(
+            incident(timer.timeout()).as_deref(),
+            Some("3 surface timeouts in a row")
+        );
+        // counted again after the reset
+        assert!(timer.timeout().is_none());
+
+        // and after a frame presented
+        assert!(timer.frame(Duration::from_millis(16)).is_none());
+        assert!(timer.timeout().is_none());
+        assert!(timer.timeout().is_none());
+        assert!(timer.timeout().is_some());
+    }
+
+    #[test]
+    fn slow_frames() {
+        let mut timer = WatchdogTimer::new(Some(Watchdog {
+            max_timeouts: 3,
+            max_frame_time: Duration::from_millis(100),
+        }));
+        assert!(timer.frame(Duration::from_millis(100)).is_none());
+        assert_eq!(
+            incident(timer.frame(Duration::from_millis(150))).as_deref(),
+            Some("a frame took 150ms")
+        );
+    }
+
+    #[test]
+    fn without_watchdog() {
+        let mut timer = WatchdogTimer::new(None);
+        for _ in 0..10 {
+            assert!(timer.timeout().is_none());
+        }
+        assert!(timer.frame(Duration::from_secs(60)).is_none());
+    }
+}
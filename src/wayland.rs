@@ -0,0 +1,353 @@
+//! The overlay on Wayland, as a wlr-layer-shell surface (sway, Hyprland, KDE...).
+//!
+//! Same rendering and input shapes as on X11. Keyboard, clipboard and cursor icons are not
+//! supported, and [`OverlayApp::update`] is not called as there is no X [`Overlay`].
+//!
+//! [`Overlay`]: crate::Overlay
+
+use std::{cell::RefCell, ffi::c_void, os::unix::io::RawFd, rc::Rc, time::Instant};
+
+use anyhow::{bail, Context as _, Result};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use raw_window_handle::{
+    HasRawDisplayHandle, HasRawWindowHandle, RawDisplayHandle, RawWindowHandle,
+    WaylandDisplayHandle, WaylandWindowHandle,
+};
+use tracing::warn;
+use wayland_client::{
+    protocol::{wl_compositor::WlCompositor, wl_pointer, wl_seat, wl_surface::WlSurface},
+    Display, EventQueue, GlobalManager, Main,
+};
+use wayland_protocols::wlr::unstable::layer_shell::v1::client::{
+    zwlr_layer_shell_v1::{Layer, ZwlrLayerShellV1},
+    zwlr_layer_surface_v1::{self, Anchor},
+};
+use x11rb::protocol::xproto::Rectangle;
+
+use crate::{
+    min_frame_time, pixel_rects, state::State, InputShape, OverlayApp, OverlayBuilder,
+    OverlayError, Schedule,
+};
+
+/// Linux input event codes of the mouse buttons
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+
+struct WaylandWindow {
+    display: *mut c_void,
+    surface: *mut c_void,
+}
+
+unsafe impl HasRawWindowHandle for WaylandWindow {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        let mut handle = WaylandWindowHandle::empty();
+        handle.surface = self.surface;
+        RawWindowHandle::Wayland(handle)
+    }
+}
+unsafe impl HasRawDisplayHandle for WaylandWindow {
+    fn raw_display_handle(&self) -> RawDisplayHandle {
+        let mut handle = WaylandDisplayHandle::empty();
+        handle.display = self.display;
+        RawDisplayHandle::Wayland(handle)
+    }
+}
+
+/// What the protocol callbacks report to the loop.
+#[derive(Default)]
+struct Shared {
+    /// size given by the last configure, taken by the loop
+    configured: Option<(u32, u32)>,
+    closed: bool,
+    events: Vec<egui::Event>,
+    pixels_per_point: f32,
+    pointer_pos: egui::Pos2,
+}
+
+/// Maps a layer surface as configured by `builder`, then runs `app` in it until the compositor
+/// closes it or the GPU gives up.
+pub(crate) fn run(builder: OverlayBuilder, app: &mut dyn OverlayApp) -> Result<()> {
+    let display = Display::connect_to_env()?;
+    let mut queue = display.create_event_queue();
+    let attached = display.attach(queue.token());
+    let globals = GlobalManager::new(&attached);
+    queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+
+    let compositor = globals.instantiate_range::<WlCompositor>(1, 4)?;
+    let layer_shell = globals
+        .instantiate_exact::<ZwlrLayerShellV1>(1)
+        .context("the compositor does not implement wlr-layer-shell")?;
+
+    let scale_factor = builder.scale_factor.unwrap_or(1.) * builder.viewport.scale;
+    let shared = Rc::new(RefCell::new(Shared {
+        pixels_per_point: scale_factor,
+        ..Shared::default()
+    }));
+    if let Ok(seat) = globals.instantiate_range::<wl_seat::WlSeat>(1, 5) {
+        let shared = shared.clone();
+        seat.quick_assign(move |seat, event, _| {
+            if let wl_seat::Event::Capabilities { capabilities } = event {
+                if capabilities.contains(wl_seat::Capability::Pointer) {
+                    let shared = shared.clone();
+                    seat.get_pointer().quick_assign(move |_, event, _| {
+                        handle_pointer(&mut shared.borrow_mut(), event)
+                    });
+                }
+            }
+        });
+    }
+
+    let surface = compositor.create_surface();
+    let layer_surface =
+        layer_shell.get_layer_surface(&surface, None, Layer::Overlay, "egui-overlay".to_owned());
+    {
+        let shared = shared.clone();
+        layer_surface.quick_assign(move |layer_surface, event, _| match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer_surface.ack_configure(serial);
+                shared.borrow_mut().configured = Some((width, height));
+            }
+            zwlr_layer_surface_v1::Event::Closed => shared.borrow_mut().closed = true,
+            _ => {}
+        });
+    }
+
+    // spanning the whole output, the first configure tells its size
+    layer_surface.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+    layer_surface.set_exclusive_zone(-1);
+    set_input_region(&compositor, &surface, Some(&[]));
+    surface.commit();
+    let (output_width, output_height) = wait_for_configure(&mut queue, &shared)?;
+
+    let rect = builder.geometry.place(Rectangle {
+        x: 0,
+        y: 0,
+        width: output_width as u16,
+        height: output_height as u16,
+    });
+    layer_surface.set_anchor(Anchor::Top | Anchor::Left);
+    layer_surface.set_margin(rect.y.into(), 0, 0, rect.x.into());
+    layer_surface.set_size(rect.width.into(), rect.height.into());
+    surface.commit();
+    let size = wait_for_configure(&mut queue, &shared)?;
+
+    let window = WaylandWindow {
+        display: display.get_display_ptr() as *mut c_void,
+        surface: surface.as_ref().c_ptr() as *mut c_void,
+    };
+    let mut state = State::new(
+        &window,
+        size,
+        None,
+        &builder.gpu.with_env()?,
+        builder.surface_options(),
+        scale_factor,
+    )?;
+    state.set_viewport(builder.viewport.geometry);
+    builder.setup_context(state.context())?;
+
+    let signals = builder
+        .exit_on_signal
+        .then(crate::signals::watch)
+        .transpose()?;
+    let min_frame_time = min_frame_time(builder.max_fps).max(min_frame_time(builder.ui_rate));
+    let mut schedule = Schedule::new();
+    let mut input_region: Option<Vec<Rectangle>> = None;
+    loop {
+        queue.dispatch_pending(&mut (), |_, _, _| {})?;
+        {
+            let mut shared = shared.borrow_mut();
+            if shared.closed || signals.is_some() && crate::signals::received() {
+                break;
+            }
+            if let Some(size) = shared.configured.take() {
+                state.resize(size);
+                schedule.repaint_at = Some(Instant::now());
+            }
+            if !shared.events.is_empty() {
+                state.raw_input.events.append(&mut shared.events);
+                schedule.repaint_at = Some(Instant::now());
+            }
+        }
+
+        let now = Instant::now();
+        if schedule
+            .frame_at(min_frame_time)
+            .is_some_and(|at| at <= now)
+        {
+            schedule.last_frame = Some(now);
+            match state.render(app) {
+                Ok(frame) => {
+                    schedule.repaint_at = now.checked_add(frame.repaint_after);
+                    if let (Some(open_url), Some(command)) = (&frame.open_url, &builder.url_command)
+                    {
+                        crate::url::open(command, &open_url.url);
+                    }
+                    let rects = match builder.input_shape {
+                        InputShape::Passthrough => Some(Vec::new()),
+                        InputShape::Window => None,
+                        InputShape::Content => {
+                            Some(pixel_rects(&frame.painted, state.pixels_per_point()))
+                        }
+                    };
+                    if input_region != rects {
+                        set_input_region(&compositor, &surface, rects.as_deref());
+                        surface.commit();
+                        input_region = rects;
+                    }
+                }
+                // Reconfigure the surface if it's lost or outdated, on a new device if lost too
+                Err(
+                    OverlayError::SurfaceLost | OverlayError::Surface(wgpu::SurfaceError::Outdated),
+                ) => {
+                    if let Err(e) = state.recover() {
+                        // e.g. while the driver resets
+                        warn!("no GPU to draw on: {:#}", e);
+                        schedule.repaint_at = now.checked_add(crate::runner::GPU_RETRY_INTERVAL);
+                    }
+                }
+                // The system is out of memory, we should probably quit
+                Err(OverlayError::Surface(wgpu::SurfaceError::OutOfMemory)) => break,
+
+                Err(OverlayError::Surface(wgpu::SurfaceError::Timeout)) => warn!("surface timeout"),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let timeout = schedule
+            .frame_at(min_frame_time)
+            .map(|at| at.saturating_duration_since(Instant::now()));
+        wait_for_events(&display, &queue, signals, timeout)?;
+    }
+
+    #[cfg(feature = "persistence")]
+    if let Some(path) = &builder.memory_path {
+        crate::persistence::save_memory(state.context(), &crate::persistence::memory_path(path)?)?;
+    }
+
+    // the wgpu surface first, then the surface it draws to
+    drop(state);
+    layer_surface.destroy();
+    surface.destroy();
+    display.flush()?;
+    Ok(())
+}
+
+fn wait_for_configure(queue: &mut EventQueue, shared: &Rc<RefCell<Shared>>) -> Result<(u32, u32)> {
+    loop {
+        queue.dispatch(&mut (), |_, _, _| {})?;
+        let mut shared = shared.borrow_mut();
+        if shared.closed {
+            bail!("the compositor closed the overlay surface");
+        }
+        if let Some(size) = shared.configured.take() {
+            return Ok(size);
+        }
+    }
+}
+
+/// Flushes, then reads the socket once readable, `signals` is, or after `timeout`, forever
+/// if `None`.
+fn wait_for_events(
+    display: &Display,
+    queue: &EventQueue,
+    signals: Option<RawFd>,
+    timeout: Option<std::time::Duration>,
+) -> Result<()> {
+    display.flush()?;
+    // events already queued, nothing to wait for
+    let Some(guard) = queue.prepare_read() else {
+        return Ok(());
+    };
+    let mut fds: Vec<_> = std::iter::once(display.get_connection_fd())
+        .chain(signals)
+        .map(|fd| PollFd::new(fd, PollFlags::POLLIN))
+        .collect();
+    // rounded up, not to spin on sub-millisecond timeouts
+    let timeout_ms = timeout.map_or(-1, |timeout| {
+        timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+    });
+    match poll(&mut fds, timeout_ms) {
+        Ok(_) if fds[0].revents().is_some_and(|r| !r.is_empty()) => guard.read_events()?,
+        Ok(_) | Err(Errno::EINTR) => guard.cancel(),
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+/// `None` restores the default shape: the whole surface catches input
+fn set_input_region(
+    compositor: &Main<WlCompositor>,
+    surface: &Main<WlSurface>,
+    rects: Option<&[Rectangle]>,
+) {
+    match rects {
+        Some(rects) => {
+            let region = compositor.create_region();
+            for rect in rects {
+                region.add(
+                    rect.x.into(),
+                    rect.y.into(),
+                    rect.width.into(),
+                    rect.height.into(),
+                );
+            }
+            surface.set_input_region(Some(&region));
+            region.destroy();
+        }
+        None => surface.set_input_region(None),
+    }
+}
+
+fn handle_pointer(shared: &mut Shared, event: wl_pointer::Event) {
+    let pixels_per_point = shared.pixels_per_point;
+    let event = match event {
+        wl_pointer::Event::Enter {
+            surface_x,
+            surface_y,
+            ..
+        }
+        | wl_pointer::Event::Motion {
+            surface_x,
+            surface_y,
+            ..
+        } => {
+            shared.pointer_pos = egui::pos2(
+                surface_x as f32 / pixels_per_point,
+                surface_y as f32 / pixels_per_point,
+            );
+            egui::Event::PointerMoved(shared.pointer_pos)
+        }
+        wl_pointer::Event::Leave { .. } => egui::Event::PointerGone,
+        wl_pointer::Event::Button { button, state, .. } => {
+            let button = match button {
+                BTN_LEFT => egui::PointerButton::Primary,
+                BTN_RIGHT => egui::PointerButton::Secondary,
+                BTN_MIDDLE => egui::PointerButton::Middle,
+                _ => return,
+            };
+            egui::Event::PointerButton {
+                pos: shared.pointer_pos,
+                button,
+                pressed: state == wl_pointer::ButtonState::Pressed,
+                modifiers: egui::Modifiers::default(),
+            }
+        }
+        wl_pointer::Event::Axis { axis, value, .. } => {
+            // positive values scroll down or right, the opposite of egui
+            let delta = -value as f32 / pixels_per_point;
+            egui::Event::Scroll(match axis {
+                wl_pointer::Axis::HorizontalScroll => egui::vec2(delta, 0.),
+                _ => egui::vec2(0., delta),
+            })
+        }
+        _ => return,
+    };
+    shared.events.push(event);
+}
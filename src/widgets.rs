@@ -0,0 +1,272 @@
+//! HUD widgets composed into one overlay from the config file, e.g.
+//!
+//! ```toml
+//! [[widgets]]
+//! kind = "cpu"
+//! history = 120
+//!
+//! [[widgets]]
+//! kind = "network"
+//! interface = "wlan0"
+//! ```
+//!
+//! Each widget kind is registered in a [`WidgetRegistry`] with a name, crates providing
+//! widgets implement [`Widget`]. The crate ships `cpu`, `memory` and `network`:
+//!
+//! ```no_run
+//! use egui_wgpu_x11::config::Config;
+//! use egui_wgpu_x11::widgets::WidgetRegistry;
+//! use egui_wgpu_x11::Overlay;
+//!
+//! let config = Config::load()?;
+//! let mut overlay = config.apply(Overlay::builder()).build()?;
+//! let hud = WidgetRegistry::builtin().build(&config.widgets, &mut overlay)?;
+//! overlay.run(hud)?;
+//! # anyhow::Ok(())
+//! ```
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context as _, Result};
+use egui::plot::PlotPoints;
+use egui::Frame;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::{Overlay, OverlayApp, Toast};
+
+mod cpu;
+mod memory;
+mod network;
+
+pub use cpu::CpuMeter;
+pub use memory::MemoryMeter;
+pub use network::NetworkGraph;
+
+/// A component of a HUD, built from its table in the config file.
+pub trait Widget: 'static {
+    /// The settings of the widget, its `kind` aside: the schema of its table, e.g. a struct
+    /// deriving `Deserialize` with `deny_unknown_fields` and `default`.
+    type Config: DeserializeOwned;
+
+    /// Builds the widget once the overlay is, e.g. to load textures.
+    fn init(config: Self::Config, overlay: &mut Overlay) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Called after each frame, see [`OverlayApp::update`].
+    fn update(&mut self, _overlay: &mut Overlay) -> Result<()> {
+        Ok(())
+    }
+
+    /// Draws the widget, below the previous ones of the HUD.
+    fn ui(&mut self, ui: &mut egui::Ui);
+}
+
+/// The table of a widget in the config file: its kind, and its own settings.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct WidgetConfig {
+    pub kind: String,
+    #[serde(flatten)]
+    pub settings: toml::value::Table,
+}
+
+/// [`Widget`]s without their config type, to be stored together
+trait AnyWidget {
+    fn update(&mut self, overlay: &mut Overlay) -> Result<()>;
+    fn ui(&mut self, ui: &mut egui::Ui);
+}
+
+impl<W: Widget> AnyWidget for W {
+    fn update(&mut self, overlay: &mut Overlay) -> Result<()> {
+        Widget::update(self, overlay)
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        Widget::ui(self, ui)
+    }
+}
+
+type WidgetFactory = fn(toml::value::Table, &mut Overlay) -> Result<Box<dyn AnyWidget>>;
+
+fn build<W: Widget>(
+    settings: toml::value::Table,
+    overlay: &mut Overlay,
+) -> Result<Box<dyn AnyWidget>> {
+    let config = W::Config::deserialize(toml::Value::Table(settings))?;
+    Ok(Box::new(W::init(config, overlay)?))
+}
+
+/// The widget kinds the config file can use, by name.
+#[derive(Clone, Default)]
+pub struct WidgetRegistry {
+    kinds: BTreeMap<String, WidgetFactory>,
+}
+
+impl WidgetRegistry {
+    /// Without any widget kind.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// With the widgets of the crate: `cpu`, `memory` and `network`.
+    pub fn builtin() -> Self {
+        Self::new()
+            .register::<CpuMeter>("cpu")
+            .register::<MemoryMeter>("memory")
+            .register::<NetworkGraph>("network")
+    }
+
+    /// Makes `W` available as `kind`, replacing the widget registered so before if any.
+    pub fn register<W: Widget>(mut self, kind: impl Into<String>) -> Self {
+        self.kinds.insert(kind.into(), build::<W>);
+        self
+    }
+
+    pub fn kinds(&self) -> impl Iterator<Item = &str> {
+        self.kinds.keys().map(String::as_str)
+    }
+
+    /// The HUD of the widgets in `configs`, in order, failing on unknown kinds and
+    /// invalid settings.
+    ///
+    /// With [`OverlayBuilder::watch_config`](crate::OverlayBuilder::watch_config), the HUD is
+    /// built again when the widgets of the config change.
+    pub fn build(&self, configs: &[WidgetConfig], overlay: &mut Overlay) -> Result<Hud> {
+        Ok(Hud {
+            widgets: self.widgets(configs, overlay)?,
+            registry: self.clone(),
+            configs: configs.to_vec(),
+        })
+    }
+
+    fn widgets(
+        &self,
+        configs: &[WidgetConfig],
+        overlay: &mut Overlay,
+    ) -> Result<Vec<Box<dyn AnyWidget>>> {
+        let mut widgets = Vec::with_capacity(configs.len());
+        for (i, config) in configs.iter().enumerate() {
+            let factory = self.kinds.get(&config.kind).ok_or_else(|| {
+                let kinds: Vec<_> = self.kinds().collect();
+                anyhow!(
+                    "unknown widget kind {:?}, expected one of {}",
+                    config.kind,
+                    kinds.join(", ")
+                )
+            })?;
+            let widget = factory(config.settings.clone(), overlay)
+                .with_context(|| format!("widget {} ({})", i + 1, config.kind))?;
+            widgets.push(widget);
+        }
+        Ok(widgets)
+    }
+}
+
+/// Widgets stacked in an overlay, built with [`WidgetRegistry::build`].
+pub struct Hud {
+    widgets: Vec<Box<dyn AnyWidget>>,
+    /// to build the widgets again when the config changes
+    registry: WidgetRegistry,
+    configs: Vec<WidgetConfig>,
+}
+
+impl OverlayApp for Hud {
+    fn ui(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default()
+            .frame(Frame::none())
+            .show(ctx, |ui| {
+                for widget in &mut self.widgets {
+                    Frame::popup(ui.style()).show(ui, |ui| widget.ui(ui));
+                }
+            });
+    }
+
+    fn update(&mut self, overlay: &mut Overlay) -> Result<()> {
+        let changed = overlay
+            .config()
+            .map(|config| &config.widgets)
+            .filter(|configs| **configs != self.configs)
+            .cloned();
+        if let Some(configs) = changed {
+            match self.registry.widgets(&configs, overlay) {
+                Ok(widgets) => self.widgets = widgets,
+                Err(e) => {
+                    overlay.push_toast(Toast::error("Invalid widgets").body(format!("{:#}", e)))
+                }
+            }
+            // not built again until they change, even if invalid
+            self.configs = configs;
+        }
+        for widget in &mut self.widgets {
+            widget.update(overlay)?;
+        }
+        Ok(())
+    }
+}
+
+/// When to sample, every `interval`.
+struct Sampling {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl Sampling {
+    /// In seconds.
+    fn every(interval: f32) -> Self {
+        Self {
+            interval: Duration::from_secs_f32(interval.max(0.05)),
+            last: None,
+        }
+    }
+
+    /// Whether a sample is due, repainting when the next one is otherwise.
+    fn due(&mut self, ctx: &egui::Context) -> bool {
+        let now = Instant::now();
+        let due = self.last.is_none_or(|last| now >= last + self.interval);
+        if due {
+            self.last = Some(now);
+        }
+        ctx.request_repaint_after(self.interval);
+        due
+    }
+}
+
+/// The last samples, the oldest dropped beyond `capacity`.
+struct History {
+    capacity: usize,
+    samples: VecDeque<f64>,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, sample: f64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn last(&self) -> Option<f64> {
+        self.samples.back().copied()
+    }
+
+    /// as plot points `interval` apart, the last sample at 0 and the older ones in the
+    /// negative seconds
+    fn points(&self, interval: Duration) -> PlotPoints {
+        let interval = interval.as_secs_f64();
+        let len = self.samples.len();
+        self.samples
+            .iter()
+            .enumerate()
+            .map(|(i, sample)| [(i as f64 - (len - 1) as f64) * interval, *sample])
+            .collect()
+    }
+}
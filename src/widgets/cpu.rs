@@ -0,0 +1,97 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use egui::plot::{Line, Plot};
+use serde::Deserialize;
+
+use super::{History, Sampling, Widget};
+use crate::Overlay;
+
+/// The CPU usage of the whole system, from `/proc/stat`, with a graph of the last samples.
+pub struct CpuMeter {
+    config: CpuConfig,
+    sampling: Sampling,
+    history: History,
+    /// busy and total jiffies at the last sample
+    last: Option<(u64, u64)>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CpuConfig {
+    pub label: String,
+    /// Seconds between samples.
+    pub interval: f32,
+    /// Samples in the graph, 0 for none.
+    pub history: usize,
+}
+
+impl Default for CpuConfig {
+    fn default() -> Self {
+        Self {
+            label: "CPU".to_owned(),
+            interval: 1.,
+            history: 60,
+        }
+    }
+}
+
+impl Widget for CpuMeter {
+    type Config = CpuConfig;
+
+    fn init(config: CpuConfig, _overlay: &mut Overlay) -> Result<Self> {
+        let last = Some(jiffies()?);
+        Ok(Self {
+            sampling: Sampling::every(config.interval),
+            history: History::new(config.history),
+            config,
+            last,
+        })
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.sampling.due(ui.ctx()) {
+            if let Ok((busy, total)) = jiffies() {
+                if let Some((last_busy, last_total)) = self.last {
+                    let elapsed = total.saturating_sub(last_total).max(1);
+                    let usage = busy.saturating_sub(last_busy) as f64 / elapsed as f64;
+                    self.history.push(usage * 100.);
+                }
+                self.last = Some((busy, total));
+            }
+        }
+        let usage = self.history.last().unwrap_or(0.);
+        ui.label(format!("{} {:.0}%", self.config.label, usage));
+        if self.config.history > 0 {
+            Plot::new(("cpu", self.config.label.as_str()))
+                .height(48.)
+                .include_y(0.)
+                .include_y(100.)
+                .show_axes([false, false])
+                .allow_drag(false)
+                .allow_zoom(false)
+                .allow_scroll(false)
+                .show(ui, |plot| {
+                    plot.line(Line::new(self.history.points(self.sampling.interval)))
+                });
+        }
+    }
+}
+
+/// the busy and total jiffies of all the CPUs since boot
+fn jiffies() -> Result<(u64, u64)> {
+    let stat = fs::read_to_string("/proc/stat")?;
+    let line = stat
+        .lines()
+        .find(|line| line.starts_with("cpu "))
+        .ok_or_else(|| anyhow!("no cpu line in /proc/stat"))?;
+    let times: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .map(str::parse)
+        .collect::<Result<_, _>>()?;
+    let total = times.iter().sum::<u64>();
+    // idle and iowait
+    let idle = times.iter().skip(3).take(2).sum::<u64>();
+    Ok((total - idle, total))
+}
@@ -0,0 +1,78 @@
+use std::fs;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use super::{Sampling, Widget};
+use crate::Overlay;
+
+/// The memory used by the system, from `/proc/meminfo`, the caches counted as free.
+pub struct MemoryMeter {
+    config: MemoryConfig,
+    sampling: Sampling,
+    /// used and total bytes at the last sample
+    usage: (u64, u64),
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MemoryConfig {
+    pub label: String,
+    /// Seconds between samples.
+    pub interval: f32,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            label: "Memory".to_owned(),
+            interval: 2.,
+        }
+    }
+}
+
+impl Widget for MemoryMeter {
+    type Config = MemoryConfig;
+
+    fn init(config: MemoryConfig, _overlay: &mut Overlay) -> Result<Self> {
+        Ok(Self {
+            sampling: Sampling::every(config.interval),
+            usage: usage()?,
+            config,
+        })
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.sampling.due(ui.ctx()) {
+            if let Ok(usage) = usage() {
+                self.usage = usage;
+            }
+        }
+        let (used, total) = self.usage;
+        let gib = |bytes: u64| bytes as f64 / (1024. * 1024. * 1024.);
+        ui.add(
+            egui::ProgressBar::new(used as f32 / total.max(1) as f32).text(format!(
+                "{} {:.1} / {:.1} GiB",
+                self.config.label,
+                gib(used),
+                gib(total)
+            )),
+        );
+    }
+}
+
+/// the used and total bytes of memory
+fn usage() -> Result<(u64, u64)> {
+    let meminfo = fs::read_to_string("/proc/meminfo")?;
+    let field = |name: &str| -> Result<u64> {
+        let line = meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .ok_or_else(|| anyhow!("no {} in /proc/meminfo", name))?;
+        let kib: u64 = line.trim().trim_end_matches("kB").trim().parse()?;
+        Ok(kib * 1024)
+    };
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable")?;
+    Ok((total.saturating_sub(available), total))
+}
@@ -0,0 +1,135 @@
+use std::fs;
+
+use anyhow::{bail, Result};
+use egui::plot::{Legend, Line, Plot};
+use serde::Deserialize;
+
+use super::{History, Sampling, Widget};
+use crate::Overlay;
+
+/// The download and upload rates, from `/proc/net/dev`, graphed over the last samples.
+pub struct NetworkGraph {
+    config: NetworkConfig,
+    sampling: Sampling,
+    received: History,
+    sent: History,
+    /// bytes received and sent at the last sample
+    last: (u64, u64),
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    pub label: String,
+    /// e.g. `eth0`, all of them but the loopback by default
+    pub interface: Option<String>,
+    /// Seconds between samples.
+    pub interval: f32,
+    /// Samples in the graph, 0 for none.
+    pub history: usize,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            label: "Network".to_owned(),
+            interface: None,
+            interval: 1.,
+            history: 60,
+        }
+    }
+}
+
+impl Widget for NetworkGraph {
+    type Config = NetworkConfig;
+
+    fn init(config: NetworkConfig, _overlay: &mut Overlay) -> Result<Self> {
+        let last = bytes(config.interface.as_deref())?;
+        Ok(Self {
+            sampling: Sampling::every(config.interval),
+            received: History::new(config.history),
+            sent: History::new(config.history),
+            config,
+            last,
+        })
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        if self.sampling.due(ui.ctx()) {
+            if let Ok((received, sent)) = bytes(self.config.interface.as_deref()) {
+                let seconds = self.sampling.interval.as_secs_f64();
+                let rate = |now: u64, last: u64| now.saturating_sub(last) as f64 / seconds;
+                self.received.push(rate(received, self.last.0));
+                self.sent.push(rate(sent, self.last.1));
+                self.last = (received, sent);
+            }
+        }
+        ui.label(format!(
+            "{} ↓ {} ↑ {}",
+            self.config.label,
+            human_rate(self.received.last().unwrap_or(0.)),
+            human_rate(self.sent.last().unwrap_or(0.))
+        ));
+        if self.config.history > 0 {
+            Plot::new(("network", self.config.label.as_str()))
+                .height(48.)
+                .include_y(0.)
+                .show_axes([false, false])
+                .allow_drag(false)
+                .allow_zoom(false)
+                .allow_scroll(false)
+                .legend(Legend::default())
+                .show(ui, |plot| {
+                    let interval = self.sampling.interval;
+                    plot.line(Line::new(self.received.points(interval)).name("down"));
+                    plot.line(Line::new(self.sent.points(interval)).name("up"));
+                });
+        }
+    }
+}
+
+/// the bytes received and sent by `interface`, or by all of them but `lo`
+fn bytes(interface: Option<&str>) -> Result<(u64, u64)> {
+    let dev = fs::read_to_string("/proc/net/dev")?;
+    let mut total = (0, 0);
+    let mut found = false;
+    // after two header lines, `name: rx_bytes ... (8 fields) tx_bytes ...`
+    for line in dev.lines().skip(2) {
+        let Some((name, counters)) = line.split_once(':') else {
+            continue;
+        };
+        let name = name.trim();
+        let counted = match interface {
+            Some(interface) => name == interface,
+            None => name != "lo",
+        };
+        if !counted {
+            continue;
+        }
+        let counters: Vec<u64> = counters
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+        if let (Some(received), Some(sent)) = (counters.first(), counters.get(8)) {
+            total.0 += received;
+            total.1 += sent;
+            found = true;
+        }
+    }
+    if let (Some(interface), false) = (interface, found) {
+        bail!("no network interface {:?}", interface);
+    }
+    Ok(total)
+}
+
+/// e.g. `1.2 MB/s`
+fn human_rate(bytes_per_second: f64) -> String {
+    let units = ["B/s", "kB/s", "MB/s", "GB/s"];
+    let mut rate = bytes_per_second;
+    let mut unit = 0;
+    while rate >= 1000. && unit < units.len() - 1 {
+        rate /= 1000.;
+        unit += 1;
+    }
+    format!("{:.1} {}", rate, units[unit])
+}
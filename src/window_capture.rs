@@ -0,0 +1,138 @@
+//! Live images of other windows, e.g. for window switchers or picture-in-picture.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::window_capture::WindowCapture;
+//! use egui_wgpu_x11::{egui, Overlay, OverlayApp};
+//!
+//! struct Preview(WindowCapture);
+//!
+//! impl OverlayApp for Preview {
+//!     fn ui(&mut self, ctx: &egui::Context) {
+//!         egui::Window::new("Preview").show(ctx, |ui| {
+//!             if let Some(texture) = self.0.texture() {
+//!                 ui.image(texture, texture.size_vec2() / 4.);
+//!             }
+//!         });
+//!     }
+//!
+//!     fn update(&mut self, overlay: &mut Overlay) -> anyhow::Result<()> {
+//!         self.0.update(overlay)?;
+//!         // follows the window contents
+//!         overlay.context().request_repaint();
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let overlay = Overlay::new()?;
+//! let window = 0x3a00007; // e.g. from `xwininfo`
+//! let capture = WindowCapture::new(&overlay, window)?;
+//! overlay.run(Preview(capture))?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! The window is read back with `GetImage` on each update: small windows or a low
+//! [`OverlayBuilder::max_fps`](crate::OverlayBuilder::max_fps) keep it cheap.
+
+use std::rc::Rc;
+
+use anyhow::{bail, Result};
+use egui::{TextureFilter, TextureHandle};
+use x11rb::connection::{Connection, RequestConnection as _};
+use x11rb::protocol::composite::{self, ConnectionExt as _, Redirect};
+use x11rb::protocol::xproto::{ConnectionExt as _, Rectangle, Window};
+use x11rb::xcb_ffi::XCBConnection;
+
+use crate::screen::read_image;
+use crate::Overlay;
+
+/// The contents of a window as an egui texture, even while covered by other windows.
+///
+/// The window is redirected offscreen with XComposite, as compositing managers do; it still
+/// shows on the screen as before. Dropping the capture ends the redirection.
+pub struct WindowCapture {
+    conn: Rc<XCBConnection>,
+    window: Window,
+    texture: Option<TextureHandle>,
+}
+
+impl WindowCapture {
+    /// Captures `window`, on the X server of `overlay`. Needs XComposite 0.2.
+    pub fn new(overlay: &Overlay, window: Window) -> Result<Self> {
+        let conn = overlay.conn.clone();
+        if conn
+            .extension_information(composite::X11_EXTENSION_NAME)?
+            .is_none()
+        {
+            bail!("XComposite extension not available");
+        }
+        let version = conn.composite_query_version(0, 4)?.reply()?;
+        if (version.major_version, version.minor_version) < (0, 2) {
+            bail!(
+                "XComposite {}.{} can't name window pixmaps, 0.2 is needed",
+                version.major_version,
+                version.minor_version
+            );
+        }
+        conn.composite_redirect_window(window, Redirect::AUTOMATIC)?
+            .check()?;
+        Ok(Self {
+            conn,
+            window,
+            texture: None,
+        })
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    /// The window contents as of the last [`WindowCapture::update`], in pixels.
+    pub fn texture(&self) -> Option<&TextureHandle> {
+        self.texture.as_ref()
+    }
+
+    /// Reads the window contents again. Fails while it is unmapped, e.g. minimized, and once
+    /// destroyed.
+    pub fn update(&mut self, overlay: &Overlay) -> Result<()> {
+        let conn = &*self.conn;
+        let attributes = conn.get_window_attributes(self.window)?.reply()?;
+        let geometry = conn.get_geometry(self.window)?.reply()?;
+        // follows the window size, named again every time
+        let pixmap = conn.generate_id()?;
+        conn.composite_name_window_pixmap(self.window, pixmap)?
+            .check()?;
+        let rect = Rectangle {
+            x: 0,
+            y: 0,
+            // with the borders
+            width: geometry.width + 2 * geometry.border_width,
+            height: geometry.height + 2 * geometry.border_width,
+        };
+        let image = read_image(conn, pixmap, attributes.visual, geometry.depth, rect);
+        conn.free_pixmap(pixmap)?;
+        let image = image?;
+
+        match &mut self.texture {
+            Some(texture) => texture.set(image, TextureFilter::Linear),
+            None => {
+                let name = format!("window {:#x}", self.window);
+                self.texture = Some(overlay.context().load_texture(
+                    name,
+                    image,
+                    TextureFilter::Linear,
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for WindowCapture {
+    fn drop(&mut self) {
+        // fails once the window is destroyed, nothing left to undo then
+        let _ = self
+            .conn
+            .composite_unredirect_window(self.window, Redirect::AUTOMATIC);
+        let _ = self.conn.flush();
+    }
+}
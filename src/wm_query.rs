@@ -0,0 +1,383 @@
+//! The windows of other clients and the desktops, as EWMH window managers publish them, e.g.
+//! to show the focused window's title or build taskbar-like widgets.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::wm_query::{WmChange, WmQuery};
+//! use egui_wgpu_x11::{egui, Overlay, OverlayApp};
+//!
+//! struct Title {
+//!     wm: WmQuery,
+//!     title: String,
+//! }
+//!
+//! impl OverlayApp for Title {
+//!     fn ui(&mut self, ctx: &egui::Context) {
+//!         egui::Area::new("title").show(ctx, |ui| ui.heading(&self.title));
+//!     }
+//!
+//!     fn update(&mut self, _overlay: &mut Overlay) -> anyhow::Result<()> {
+//!         for change in self.wm.changes() {
+//!             if let WmChange::ActiveWindow(window) = change {
+//!                 self.title = match window {
+//!                     Some(window) => self.wm.title(window)?.unwrap_or_default(),
+//!                     None => String::new(),
+//!                 };
+//!             }
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut overlay = Overlay::new()?;
+//! let wm = WmQuery::new(&mut overlay)?;
+//! overlay.run(Title { wm, title: String::new() })?;
+//! # anyhow::Ok(())
+//! ```
+//!
+//! Changes of the root window properties are always reported. Title and desktop changes of
+//! a window are only once it is watched, see [`WmQuery::watch_window`].
+
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use anyhow::Result;
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ConnectionExt as _, EventMask, GetPropertyReply, Window,
+};
+use x11rb::protocol::Event;
+use x11rb::xcb_ffi::XCBConnection;
+use x11rb::NONE;
+
+use crate::x11::{intern_atoms, select_more_events, unselect_events};
+use crate::Overlay;
+
+/// `_NET_WM_DESKTOP` of the windows shown on every desktop.
+pub const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// The `WM_CLASS` of a window, matched by window manager rules.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WmClass {
+    pub instance: String,
+    pub class: String,
+}
+
+/// A change of what [`WmQuery`] reads, with the new value when cheap to carry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WmChange {
+    /// `_NET_ACTIVE_WINDOW`, `None` when no window has the focus.
+    ActiveWindow(Option<Window>),
+    /// `_NET_CLIENT_LIST` or `_NET_CLIENT_LIST_STACKING`, windows were mapped, unmapped or
+    /// restacked.
+    ClientList,
+    /// `_NET_CURRENT_DESKTOP`.
+    CurrentDesktop(Option<u32>),
+    /// `_NET_NUMBER_OF_DESKTOPS` or `_NET_DESKTOP_NAMES`.
+    Desktops,
+    /// `_NET_WM_NAME` or `WM_NAME` of a watched window.
+    Title(Window),
+    /// `_NET_WM_DESKTOP` of a watched window.
+    WindowDesktop(Window),
+}
+
+/// Typed reads of the EWMH and ICCCM properties of the root window and of other clients'
+/// windows, on the X server of an overlay, with their changes.
+pub struct WmQuery {
+    conn: Rc<XCBConnection>,
+    root: Window,
+    atoms: WmAtoms,
+    changes: Receiver<WmChange>,
+}
+
+impl WmQuery {
+    /// Reads the window manager state of `overlay`'s screen, its changes reported by
+    /// [`WmQuery::changes`] from now on.
+    pub fn new(overlay: &mut Overlay) -> Result<Self> {
+        let conn = overlay.conn.clone();
+        let root = overlay.root;
+        let atoms = WmAtoms::new(&*conn)?;
+        let (sender, changes) = mpsc::channel();
+        match &mut overlay.wm_watch {
+            Some(watch) => watch.listeners.push(sender),
+            None => {
+                select_more_events(&*conn, root, EventMask::PROPERTY_CHANGE)?;
+                overlay.wm_watch = Some(WmWatch {
+                    root,
+                    atoms: atoms.clone(),
+                    listeners: vec![sender],
+                });
+            }
+        }
+        Ok(Self {
+            conn,
+            root,
+            atoms,
+            changes,
+        })
+    }
+
+    /// The changes since the last call, as the overlay got them from the X server.
+    pub fn changes(&self) -> impl Iterator<Item = WmChange> + '_ {
+        self.changes.try_iter()
+    }
+
+    /// Reports the title and desktop changes of `window`, until
+    /// [`WmQuery::unwatch_window`]. Fails once the window is gone.
+    pub fn watch_window(&self, window: Window) -> Result<()> {
+        select_more_events(&*self.conn, window, EventMask::PROPERTY_CHANGE)?;
+        Ok(())
+    }
+
+    /// Stops reporting the changes of `window`, which may be gone already.
+    pub fn unwatch_window(&self, window: Window) {
+        let _ = unselect_events(&*self.conn, window, EventMask::PROPERTY_CHANGE);
+    }
+
+    /// The focused window, `None` when none has it or without EWMH window manager.
+    pub fn active_window(&self) -> Result<Option<Window>> {
+        let active = self.root_u32(self.atoms.net_active_window, AtomEnum::WINDOW.into())?;
+        Ok(active.filter(|window| *window != NONE))
+    }
+
+    /// The managed windows, oldest first.
+    pub fn client_list(&self) -> Result<Vec<Window>> {
+        self.root_u32s(self.atoms.net_client_list, AtomEnum::WINDOW.into())
+    }
+
+    /// The managed windows, bottom to top.
+    pub fn client_list_stacking(&self) -> Result<Vec<Window>> {
+        self.root_u32s(self.atoms.net_client_list_stacking, AtomEnum::WINDOW.into())
+    }
+
+    /// The desktop shown, counted from 0.
+    pub fn current_desktop(&self) -> Result<Option<u32>> {
+        self.root_u32(self.atoms.net_current_desktop, AtomEnum::CARDINAL.into())
+    }
+
+    pub fn number_of_desktops(&self) -> Result<Option<u32>> {
+        self.root_u32(self.atoms.net_number_of_desktops, AtomEnum::CARDINAL.into())
+    }
+
+    /// The names of the desktops, in order, possibly fewer than there are desktops.
+    pub fn desktop_names(&self) -> Result<Vec<String>> {
+        let Some(names) = property(
+            &*self.conn,
+            self.root,
+            self.atoms.net_desktop_names,
+            self.atoms.utf8_string,
+        )?
+        else {
+            return Ok(Vec::new());
+        };
+        let mut names: Vec<_> = names
+            .value
+            .split(|byte| *byte == 0)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .collect();
+        // each name is null-terminated
+        if names.last().is_some_and(String::is_empty) {
+            names.pop();
+        }
+        Ok(names)
+    }
+
+    /// `_NET_WM_NAME`, or `WM_NAME`, of `window`.
+    pub fn title(&self, window: Window) -> Result<Option<String>> {
+        window_title(&*self.conn, &self.atoms, window)
+    }
+
+    pub fn class(&self, window: Window) -> Result<Option<WmClass>> {
+        window_class(&*self.conn, window)
+    }
+
+    /// The desktop `window` is on, [`ALL_DESKTOPS`] for sticky windows.
+    pub fn window_desktop(&self, window: Window) -> Result<Option<u32>> {
+        let desktop = property(
+            &*self.conn,
+            window,
+            self.atoms.net_wm_desktop,
+            AtomEnum::CARDINAL.into(),
+        )?;
+        Ok(desktop.and_then(first_u32))
+    }
+
+    fn root_u32(&self, property_name: Atom, type_: Atom) -> Result<Option<u32>> {
+        let value = property(&*self.conn, self.root, property_name, type_)?;
+        Ok(value.and_then(first_u32))
+    }
+
+    fn root_u32s(&self, property_name: Atom, type_: Atom) -> Result<Vec<u32>> {
+        let value = property(&*self.conn, self.root, property_name, type_)?;
+        Ok(value
+            .and_then(|value| value.value32().map(Iterator::collect))
+            .unwrap_or_default())
+    }
+}
+
+/// Turns the property changes of the root window and the watched windows into
+/// [`WmChange`]s, for every [`WmQuery`] of the overlay.
+pub(crate) struct WmWatch {
+    root: Window,
+    atoms: WmAtoms,
+    listeners: Vec<Sender<WmChange>>,
+}
+
+impl WmWatch {
+    /// Returns whether a change was reported, dropping the queries gone meanwhile.
+    pub fn handle_event<Conn>(&mut self, conn: &Conn, event: &Event) -> Result<bool>
+    where
+        Conn: Connection,
+    {
+        let Event::PropertyNotify(e) = event else {
+            return Ok(false);
+        };
+        let atoms = &self.atoms;
+        let change = if e.window == self.root {
+            let read_u32 = |type_: AtomEnum| -> Result<Option<u32>> {
+                let value = property(conn, self.root, e.atom, type_.into())?;
+                Ok(value.and_then(first_u32))
+            };
+            match e.atom {
+                atom if atom == atoms.net_active_window => WmChange::ActiveWindow(
+                    read_u32(AtomEnum::WINDOW)?.filter(|window| *window != NONE),
+                ),
+                atom if atom == atoms.net_client_list || atom == atoms.net_client_list_stacking => {
+                    WmChange::ClientList
+                }
+                atom if atom == atoms.net_current_desktop => {
+                    WmChange::CurrentDesktop(read_u32(AtomEnum::CARDINAL)?)
+                }
+                atom if atom == atoms.net_number_of_desktops || atom == atoms.net_desktop_names => {
+                    WmChange::Desktops
+                }
+                _ => return Ok(false),
+            }
+        } else {
+            match e.atom {
+                atom if atom == atoms.net_wm_name || atom == u32::from(AtomEnum::WM_NAME) => {
+                    WmChange::Title(e.window)
+                }
+                atom if atom == atoms.net_wm_desktop => WmChange::WindowDesktop(e.window),
+                _ => return Ok(false),
+            }
+        };
+        self.listeners
+            .retain(|listener| listener.send(change.clone()).is_ok());
+        Ok(true)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct WmAtoms {
+    net_active_window: Atom,
+    net_client_list: Atom,
+    net_client_list_stacking: Atom,
+    net_current_desktop: Atom,
+    net_number_of_desktops: Atom,
+    net_desktop_names: Atom,
+    net_wm_name: Atom,
+    net_wm_desktop: Atom,
+    utf8_string: Atom,
+}
+
+impl WmAtoms {
+    pub fn new<Conn>(conn: &Conn) -> Result<Self>
+    where
+        Conn: Connection,
+    {
+        let [active, clients, stacking, desktop, desktops, names, wm_name, wm_desktop, utf8] =
+            intern_atoms(
+                conn,
+                [
+                    "_NET_ACTIVE_WINDOW",
+                    "_NET_CLIENT_LIST",
+                    "_NET_CLIENT_LIST_STACKING",
+                    "_NET_CURRENT_DESKTOP",
+                    "_NET_NUMBER_OF_DESKTOPS",
+                    "_NET_DESKTOP_NAMES",
+                    "_NET_WM_NAME",
+                    "_NET_WM_DESKTOP",
+                    "UTF8_STRING",
+                ],
+            )?;
+        Ok(Self {
+            net_active_window: active,
+            net_client_list: clients,
+            net_client_list_stacking: stacking,
+            net_current_desktop: desktop,
+            net_number_of_desktops: desktops,
+            net_desktop_names: names,
+            net_wm_name: wm_name,
+            net_wm_desktop: wm_desktop,
+            utf8_string: utf8,
+        })
+    }
+}
+
+/// `_NET_WM_NAME`, or `WM_NAME` of any text type
+pub(crate) fn window_title<Conn>(
+    conn: &Conn,
+    atoms: &WmAtoms,
+    window: Window,
+) -> Result<Option<String>>
+where
+    Conn: Connection,
+{
+    let title = match property(conn, window, atoms.net_wm_name, atoms.utf8_string)? {
+        Some(title) => Some(title),
+        None => property(conn, window, AtomEnum::WM_NAME.into(), AtomEnum::ANY.into())?,
+    };
+    Ok(title.map(|title| String::from_utf8_lossy(&title.value).into_owned()))
+}
+
+pub(crate) fn window_class<Conn>(conn: &Conn, window: Window) -> Result<Option<WmClass>>
+where
+    Conn: Connection,
+{
+    let wm_class = property(
+        conn,
+        window,
+        AtomEnum::WM_CLASS.into(),
+        AtomEnum::STRING.into(),
+    )?;
+    Ok(wm_class.map(|wm_class| {
+        let mut names = wm_class
+            .value
+            .split(|byte| *byte == 0)
+            .map(|name| String::from_utf8_lossy(name).into_owned());
+        WmClass {
+            instance: names.next().unwrap_or_default(),
+            class: names.next().unwrap_or_default(),
+        }
+    }))
+}
+
+/// the whole property, `None` when unset, of another type, or the window is gone. Fails if
+/// the connection broke.
+fn property<Conn>(
+    conn: &Conn,
+    window: Window,
+    property: Atom,
+    type_: Atom,
+) -> Result<Option<GetPropertyReply>>
+where
+    Conn: Connection,
+{
+    let reply = match conn
+        .get_property(false, window, property, type_, 0, u32::MAX)?
+        .reply()
+    {
+        Ok(reply) => reply,
+        // e.g. `BadWindow`, as the window went away
+        Err(ReplyError::X11Error(_)) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let matches =
+        reply.type_ != NONE && (type_ == u32::from(AtomEnum::ANY) || reply.type_ == type_);
+    Ok(matches.then_some(reply))
+}
+
+fn first_u32(reply: GetPropertyReply) -> Option<u32> {
+    reply.value32()?.next()
+}
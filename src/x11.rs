@@ -1,26 +1,323 @@
-use anyhow::Result;
+//! The X11 plumbing of the overlay windows, usable without egui or wgpu: transparent
+//! windows letting input through, kept above the others, shaped, dimmed or blurred.
+//!
+//! ```no_run
+//! use egui_wgpu_x11::x11::{create_overlay_window, WindowOptions};
+//! use x11rb::connection::Connection;
+//! use x11rb::protocol::xproto::{ConnectionExt as _, Rectangle};
+//!
+//! let (conn, screen_num) = x11rb::connect(None)?;
+//! let screen = &conn.setup().roots[screen_num];
+//! let rect = Rectangle { x: 0, y: 0, width: 400, height: 300 };
+//! let window = create_overlay_window(&conn, screen, rect, &WindowOptions::default())?;
+//! conn.map_window(window.window)?;
+//! conn.flush()?;
+//! # anyhow::Ok(())
+//! ```
+
+use std::ffi::CString;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, io, ptr};
+
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::unistd::gethostname;
 use x11rb::connection::Connection;
+use x11rb::errors::ConnectError;
+use x11rb::properties::{WmSizeHints, WmSizeHintsSpecification};
 use x11rb::protocol::shape;
-use x11rb::protocol::xfixes::{
-    destroy_region, ConnectionExt as _, RegionWrapper, SetWindowShapeRegionRequest,
-};
+use x11rb::protocol::xfixes::{ConnectionExt as _, RegionWrapper, SetWindowShapeRegionRequest};
 use x11rb::protocol::xproto::{
-    ClientMessageEvent, ColormapAlloc, ColormapWrapper, ConfigureWindowAux, ConnectionExt as _,
-    CreateWindowAux, EventMask, Screen, StackMode, Window, WindowClass,
+    Atom, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, Colormap, ColormapAlloc,
+    ColormapWrapper, ConfigureWindowAux, ConnectionExt as _, CreateWindowAux, EventMask, PropMode,
+    Rectangle, Screen, VisualClass, Visualid, Visualtype, Window, WindowClass,
 };
+use x11rb::reexports::x11rb_protocol::parse_display::{parse_display, ParsedDisplay};
+use x11rb::wrapper::ConnectionExt as _;
+use x11rb::xcb_ffi::XCBConnection;
+
+use crate::OverlayError;
+
+pub type Result<T, E = OverlayError> = std::result::Result<T, E>;
+
+/// How [`connect`] authenticates to the X server.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum XAuth {
+    /// From `$XAUTHORITY`, or `~/.Xauthority`, like every X client.
+    #[default]
+    Default,
+    /// The entry of this Xauthority file for the host and display number, e.g. one written
+    /// with `xauth -f` for a nested Xephyr.
+    File(PathBuf),
+    /// An `MIT-MAGIC-COOKIE-1`, e.g. one listed by `xauth list`, decoded from hex.
+    Cookie(Vec<u8>),
+}
+
+/// the families of the Xauthority entries, those of `Xauth.h`
+const FAMILY_INTERNET: u16 = 0;
+const FAMILY_INTERNET6: u16 = 6;
+const FAMILY_LOCAL: u16 = 256;
+const FAMILY_WILD: u16 = 65535;
+
+/// the error codes of libxcb connections, those of `xcb.h`
+const XCB_CONN_ERROR: c_int = 1;
+const XCB_CONN_CLOSED_MEM_INSUFFICIENT: c_int = 3;
+const XCB_CONN_CLOSED_PARSE_ERR: c_int = 5;
+const XCB_CONN_CLOSED_INVALID_SCREEN: c_int = 6;
+
+/// the authentication of libxcb
+#[repr(C)]
+struct XcbAuthInfo {
+    namelen: c_int,
+    name: *const c_char,
+    datalen: c_int,
+    data: *const c_char,
+}
+
+#[link(name = "xcb")]
+extern "C" {
+    fn xcb_connect_to_display_with_auth_info(
+        display: *const c_char,
+        auth: *mut XcbAuthInfo,
+        screen: *mut c_int,
+    ) -> *mut c_void;
+    fn xcb_connection_has_error(conn: *mut c_void) -> c_int;
+    fn xcb_disconnect(conn: *mut c_void);
+}
+
+/// Connects to `display`, `$DISPLAY` if `None`, e.g. `:1` for a nested X server or `host:0`
+/// for a remote one over TCP. Returns the connection and its default screen number.
+pub fn connect(display: Option<&str>, auth: &XAuth) -> Result<(XCBConnection, usize)> {
+    let c_display = display
+        .map(CString::new)
+        .transpose()
+        .map_err(|_| ConnectError::DisplayParsingError)?;
+    let (name, data) = match auth {
+        XAuth::Default => return Ok(XCBConnection::connect(c_display.as_deref())?),
+        XAuth::File(path) => {
+            let parsed = parse_display(display).ok_or(ConnectError::DisplayParsingError)?;
+            xauthority_entry(path, &xauthority_addresses(&parsed), parsed.display)
+                .map_err(|e| OverlayError::config(path.display(), e))?
+                .ok_or_else(|| {
+                    let e = anyhow::anyhow!("no entry for {}:{}", parsed.host, parsed.display);
+                    OverlayError::config(path.display(), e)
+                })?
+        }
+        XAuth::Cookie(cookie) => (b"MIT-MAGIC-COOKIE-1".to_vec(), cookie.clone()),
+    };
+    let mut auth_info = XcbAuthInfo {
+        namelen: name.len() as c_int,
+        name: name.as_ptr().cast(),
+        datalen: data.len() as c_int,
+        data: data.as_ptr().cast(),
+    };
+    let mut screen: c_int = 0;
+    // libxcb copies the auth info, and returns a connection in error rather than null
+    unsafe {
+        let c_display = c_display.as_ref().map_or(ptr::null(), |d| d.as_ptr());
+        let conn = xcb_connect_to_display_with_auth_info(c_display, &mut auth_info, &mut screen);
+        let error = xcb_connection_has_error(conn);
+        if error != 0 {
+            xcb_disconnect(conn);
+            return Err(connect_error(error).into());
+        }
+        let conn = XCBConnection::from_raw_xcb_connection(conn, true)?;
+        Ok((conn, screen as usize))
+    }
+}
+
+/// the `ConnectError` of the error code of a libxcb connection, as x11rb maps them
+fn connect_error(code: c_int) -> ConnectError {
+    match code {
+        XCB_CONN_ERROR => io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            "the X server is unreachable, or refused the authorization",
+        )
+        .into(),
+        XCB_CONN_CLOSED_MEM_INSUFFICIENT => ConnectError::InsufficientMemory,
+        XCB_CONN_CLOSED_PARSE_ERR => ConnectError::DisplayParsingError,
+        XCB_CONN_CLOSED_INVALID_SCREEN => ConnectError::InvalidScreen,
+        _ => ConnectError::UnknownError,
+    }
+}
+
+/// the families and addresses of the host of `display` in Xauthority files, as libxcb
+/// matches them: the hostname for local displays and loopback addresses, the bytes of the
+/// addresses the host name resolves to otherwise
+fn xauthority_addresses(display: &ParsedDisplay) -> Vec<(u16, Vec<u8>)> {
+    let hostname = gethostname().map(OsStringExt::into_vec).unwrap_or_default();
+    let local = display.host.is_empty()
+        || display.protocol.as_deref() == Some("unix")
+        || display.host.as_bytes() == hostname;
+    if local {
+        return vec![(FAMILY_LOCAL, hostname)];
+    }
+    // the port only completes the lookup, of the host name or a literal address, IPv6 ones
+    // between brackets
+    let host = display.host.trim_start_matches('[').trim_end_matches(']');
+    let resolved = (host, 6000u16.saturating_add(display.display)).to_socket_addrs();
+    resolved
+        .into_iter()
+        .flatten()
+        .map(|addr| match addr.ip() {
+            ip if ip.is_loopback() => (FAMILY_LOCAL, hostname.clone()),
+            IpAddr::V4(ip) => (FAMILY_INTERNET, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (FAMILY_INTERNET6, ip.octets().to_vec()),
+        })
+        .collect()
+}
+
+/// the name and data of the first entry of the Xauthority file at `path` for the display
+/// `number` of a host at one of `addresses`, or of any host for wildcard entries. Entries
+/// without a number apply to all.
+fn xauthority_entry(
+    path: &Path,
+    addresses: &[(u16, Vec<u8>)],
+    number: u16,
+) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let file = fs::read(path)?;
+    let mut rest = &file[..];
+    let number = number.to_string().into_bytes();
+    while !rest.is_empty() {
+        let family = take(&mut rest, 2)?;
+        let family = u16::from_be_bytes([family[0], family[1]]);
+        let entry_address = counted(&mut rest)?;
+        let entry_number = counted(&mut rest)?;
+        let name = counted(&mut rest)?;
+        let data = counted(&mut rest)?;
+        if !entry_number.is_empty() && entry_number != number {
+            continue;
+        }
+        let host = family == FAMILY_WILD
+            || addresses
+                .iter()
+                .any(|(f, a)| family == *f && entry_address == &a[..]);
+        if host {
+            return Ok(Some((name.to_vec(), data.to_vec())));
+        }
+    }
+    Ok(None)
+}
+
+/// the next `len` bytes of an Xauthority file
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if rest.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "truncated entry",
+        ));
+    }
+    let (taken, left) = rest.split_at(len);
+    *rest = left;
+    Ok(taken)
+}
+
+/// a field of an Xauthority file, after its big endian length
+fn counted<'a>(rest: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = take(rest, 2)?;
+    take(rest, u16::from_be_bytes([len[0], len[1]]).into())
+}
 
-pub fn xfixes_init<Conn>(conn: &Conn)
+/// Interns `names` in a single round trip instead of one per atom, which adds up with
+/// remote X servers.
+pub fn intern_atoms<Conn, const N: usize>(conn: &Conn, names: [&str; N]) -> Result<[Atom; N]>
 where
     Conn: Connection,
 {
-    conn.xfixes_query_version(100, 0).unwrap();
+    let mut cookies = Vec::with_capacity(N);
+    for name in names {
+        cookies.push(conn.intern_atom(false, name.as_bytes())?);
+    }
+    let mut atoms = [0; N];
+    for (atom, cookie) in atoms.iter_mut().zip(cookies) {
+        *atom = cookie.reply()?.atom;
+    }
+    Ok(atoms)
+}
+
+/// How [`create_overlay_window`] sets up a window.
+#[derive(Clone, Copy, Debug)]
+pub struct WindowOptions {
+    /// Bits per pixel, 32 by default for per-pixel transparency, see [`visual_for_depth`].
+    pub depth: u8,
+    /// A visual of `depth`, e.g. one of [`argb_visuals`], the one of [`visual_for_depth`]
+    /// by default.
+    pub visual: Option<Visualid>,
+    /// The events selected on the window, [`overlay_events`] and [`input_events`] by default.
+    pub event_mask: EventMask,
+    /// A colormap of the visual of `depth` to share, instead of creating one for the window.
+    pub colormap: Option<Colormap>,
+    /// Goes through the window manager, undecorated and not focused when mapped. The window
+    /// is override-redirect otherwise, placed and stacked by the client alone.
+    pub managed: bool,
 }
 
-/// from <https://stackoverflow.com/a/33735384>
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self {
+            depth: 32,
+            visual: None,
+            event_mask: overlay_events() | input_events(),
+            colormap: None,
+            managed: false,
+        }
+    }
+}
+
+/// A window made by [`create_overlay_window`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OverlayWindow {
+    pub window: Window,
+    pub visual: Visualid,
+    pub depth: u8,
+    /// To free once the window is destroyed, unless given by [`WindowOptions::colormap`].
+    pub colormap: Colormap,
+}
+
+/// The events overlays handle on their window whatever their input: mapping, geometry,
+/// visibility, exposure and property changes. Unlike all of them, leaves out the redirections
+/// reserved to window managers, e.g. `SUBSTRUCTURE_REDIRECT`.
+pub fn overlay_events() -> EventMask {
+    EventMask::EXPOSURE
+        | EventMask::STRUCTURE_NOTIFY
+        | EventMask::VISIBILITY_CHANGE
+        | EventMask::PROPERTY_CHANGE
+        | EventMask::FOCUS_CHANGE
+}
+
+/// The keyboard and pointer events, for windows catching input.
+pub fn input_events() -> EventMask {
+    EventMask::KEY_PRESS
+        | EventMask::KEY_RELEASE
+        | EventMask::BUTTON_PRESS
+        | EventMask::BUTTON_RELEASE
+        | EventMask::POINTER_MOTION
+        | EventMask::ENTER_WINDOW
+        | EventMask::LEAVE_WINDOW
+}
+
+/// Initializes XFixes, needed by the shape functions, e.g. [`input_passthrough`].
+pub fn xfixes_init<Conn>(conn: &Conn) -> Result<()>
+where
+    Conn: Connection,
+{
+    conn.xfixes_query_version(100, 0)?;
+    Ok(())
+}
+
+/// Lets all the input through the window, to the windows below.
+///
+/// From <https://stackoverflow.com/a/33735384>.
 pub fn input_passthrough<Conn>(conn: &Conn, win_id: u32) -> Result<()>
 where
     Conn: Connection,
 {
+    // the wrapper destroys the region when dropped, the window keeps its own copy of the shape
     let rw = RegionWrapper::create_region(conn, &[])?;
 
     let set_shape_request = SetWindowShapeRegionRequest {
@@ -41,26 +338,93 @@ where
     };
     conn.send_trait_request_without_reply(set_shape_request)?;
 
-    // TODO: does not fail but now triggers an error event, though it did not when it was inlined in main, ??
-    destroy_region(conn, rw.region())?;
+    Ok(())
+}
+
+/// Restricts the input shape to `rects`, the rest of the window lets input through.
+/// `None` restores the default shape: the whole window catches input.
+pub fn set_input_region<Conn>(conn: &Conn, win_id: u32, rects: Option<&[Rectangle]>) -> Result<()>
+where
+    Conn: Connection,
+{
+    set_shape_region(conn, win_id, shape::SK::INPUT, rects)
+}
+
+/// Restricts the window to `rects`, the rest is not drawn and lets input through.
+/// `None` restores the default shape: the whole window.
+pub fn set_bounding_region<Conn>(
+    conn: &Conn,
+    win_id: u32,
+    rects: Option<&[Rectangle]>,
+) -> Result<()>
+where
+    Conn: Connection,
+{
+    set_shape_region(conn, win_id, shape::SK::BOUNDING, rects)
+}
+
+fn set_shape_region<Conn>(
+    conn: &Conn,
+    win_id: u32,
+    kind: shape::SK,
+    rects: Option<&[Rectangle]>,
+) -> Result<()>
+where
+    Conn: Connection,
+{
+    // the wrapper destroys the region when dropped, the window keeps its own copy of the shape
+    let rw = rects
+        .map(|rects| RegionWrapper::create_region(conn, rects))
+        .transpose()?;
+
+    let set_shape_request = SetWindowShapeRegionRequest {
+        dest: win_id,
+        dest_kind: kind,
+        x_offset: 0,
+        y_offset: 0,
+        region: rw.as_ref().map_or(0, RegionWrapper::region),
+    };
+    conn.send_trait_request_without_reply(set_shape_request)?;
 
     Ok(())
 }
 
-/// from <https://stackoverflow.com/a/16235920>
-/// possible alt: <https://github.com/libsdl-org/SDL/blob/85e6500065bbe37e9131c0ff9cd7e5af6d256730/src/video/x11/SDL_x11window.c#L153-L175>
+/// flushes, then blocks until the server sends something, one of `wakers` is readable,
+/// or `timeout` elapses, never if `None`. Only the socket is polled: the events XCB already
+/// queued, e.g. while waiting for a reply, must have been handled first
+pub(crate) fn wait_for_event<Conn>(
+    conn: &Conn,
+    wakers: &[RawFd],
+    timeout: Option<Duration>,
+) -> anyhow::Result<()>
+where
+    Conn: Connection + AsRawFd,
+{
+    conn.flush()?;
+
+    let mut fds: Vec<_> = [conn.as_raw_fd()]
+        .iter()
+        .chain(wakers)
+        .map(|&fd| PollFd::new(fd, PollFlags::POLLIN))
+        .collect();
+    // rounded up, not to spin on sub-millisecond timeouts
+    let timeout_ms = timeout.map_or(-1, |timeout| {
+        timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32
+    });
+    match poll(&mut fds, timeout_ms) {
+        Ok(_) | Err(Errno::EINTR) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Asks the window manager to keep the window above the others.
+///
+/// From <https://stackoverflow.com/a/16235920>, possible alt: <https://github.com/libsdl-org/SDL/blob/85e6500065bbe37e9131c0ff9cd7e5af6d256730/src/video/x11/SDL_x11window.c#L153-L175>
 pub fn always_on_top<Conn>(conn: &Conn, root_win_id: u32, win_id: u32) -> Result<()>
 where
     Conn: Connection,
 {
-    let wm_state = conn
-        .intern_atom(false, "_NET_WM_STATE".as_bytes())?
-        .reply()?
-        .atom;
-    let wm_state_above = conn
-        .intern_atom(false, "_NET_WM_STATE_ABOVE".as_bytes())?
-        .reply()?
-        .atom;
+    let [wm_state, wm_state_above] = intern_atoms(conn, ["_NET_WM_STATE", "_NET_WM_STATE_ABOVE"])?;
 
     const _NET_WM_STATE_ADD: u32 = 1;
     let event_always_on_top = ClientMessageEvent::new(
@@ -79,67 +443,459 @@ where
     Ok(())
 }
 
-/// original hack, as `always_on_top` patterns are not fully effective with Xmonad
-/// not tested on other WMs yet
-pub fn raise_if_not_top<Conn>(conn: &Conn, root_win_id: u32, win_id: u32) -> Result<()>
+/// Moves and resizes the window.
+pub fn set_window_geometry<Conn>(
+    conn: &Conn,
+    win_id: u32,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+) -> Result<()>
 where
     Conn: Connection,
 {
-    let tree = conn.query_tree(root_win_id)?.reply()?.children;
-    // runs on the assumption that the top most window is the last of the root's children
-    if tree.last() != Some(&win_id) {
-        let values = ConfigureWindowAux::default().stack_mode(StackMode::ABOVE);
-        conn.configure_window(win_id, &values)?;
-    }
+    let values = ConfigureWindowAux::new()
+        .x(i32::from(x))
+        .y(i32::from(y))
+        .width(u32::from(width))
+        .height(u32::from(height));
+    conn.configure_window(win_id, &values)?;
 
     Ok(())
 }
 
+/// A 32-bit true color visual, the 8 bits left by the color masks holding the alpha.
+pub fn argb_visual(screen: &Screen) -> Result<Visualid> {
+    visual_for_depth(screen, 32)
+}
+
+/// The 32-bit true color visuals of the screen, the 8 bits left by the color masks holding
+/// the alpha. The BGRA ones come first, laid out like the usual surface formats of GPUs.
+pub fn argb_visuals(screen: &Screen) -> Vec<Visualid> {
+    let mut visuals: Vec<_> = true_color_visuals(screen, 32).collect();
+    // stable, keeps the server order otherwise
+    visuals.sort_by_key(|visual| {
+        (visual.red_mask, visual.green_mask, visual.blue_mask) != (0xff0000, 0xff00, 0xff)
+    });
+    visuals.iter().map(|visual| visual.visual_id).collect()
+}
+
+/// A true color visual of `depth` with 8 bits per color: for 32 bits the first of
+/// [`argb_visuals`], otherwise the root visual if deep enough.
+pub fn visual_for_depth(screen: &Screen, depth: u8) -> Result<Visualid> {
+    let visual = match depth {
+        32 => argb_visuals(screen).first().copied(),
+        _ => {
+            let visuals: Vec<_> = true_color_visuals(screen, depth)
+                .map(|visual| visual.visual_id)
+                .collect();
+            visuals
+                .iter()
+                .find(|visual| **visual == screen.root_visual)
+                .or(visuals.first())
+                .copied()
+        }
+    };
+    visual.ok_or(match depth {
+        32 => OverlayError::NoArgbVisual,
+        depth => OverlayError::NoVisual { depth },
+    })
+}
+
+/// the visuals of `depth` with 8 bits per color
+fn true_color_visuals(screen: &Screen, depth: u8) -> impl Iterator<Item = &Visualtype> {
+    screen
+        .allowed_depths
+        .iter()
+        .filter(move |candidate| candidate.depth == depth)
+        .flat_map(|candidate| &candidate.visuals)
+        .filter(|visual| {
+            visual.class == VisualClass::TRUE_COLOR
+                && visual.bits_per_rgb_value == 8
+                && (visual.red_mask | visual.green_mask | visual.blue_mask).count_ones() == 24
+        })
+}
+
+/// Creates an unmapped window at `rect` letting input through, see [`WindowOptions`].
 pub fn create_overlay_window<Conn>(
     conn: &Conn,
     screen: &Screen,
-    x: i16,
-    y: i16,
-    width: u16,
-    height: u16,
-) -> Result<Window>
+    rect: Rectangle,
+    options: &WindowOptions,
+) -> Result<OverlayWindow>
 where
     Conn: Connection,
 {
-    let depths = &screen.allowed_depths;
-    let visuals = &depths.iter().find(|&d| d.depth == 32).unwrap().visuals;
-
-    let cw = ColormapWrapper::create_colormap(
-        conn,
-        ColormapAlloc::NONE,
-        screen.root,
-        visuals.first().unwrap().visual_id,
-    )?;
+    let visual = match options.visual {
+        Some(visual) => visual,
+        None => visual_for_depth(screen, options.depth)?,
+    };
+    let colormap = match options.colormap {
+        Some(colormap) => colormap,
+        None => ColormapWrapper::create_colormap(conn, ColormapAlloc::NONE, screen.root, visual)?
+            .into_colormap(),
+    };
 
     let win_id = conn.generate_id()?;
 
     conn.create_window(
-        32,
+        options.depth,
         win_id,
         screen.root,
-        x,
-        y,
-        width,
-        height,
+        rect.x,
+        rect.y,
+        rect.width,
+        rect.height,
         0,
         WindowClass::INPUT_OUTPUT,
-        visuals.first().unwrap().visual_id,
+        visual,
         &CreateWindowAux::new()
             .background_pixel(0x00000000)
-            .colormap(Some(cw.into_colormap()))
-            .override_redirect(Some(1))
+            .colormap(Some(colormap))
+            .override_redirect(Some(u32::from(!options.managed)))
             .border_pixel(Some(1))
-            .event_mask(Some(0b1_1111_1111_1111_1111_1111_1111u32.into())),
+            .event_mask(Some(options.event_mask)),
     )?;
 
     input_passthrough(conn, win_id)?;
 
-    always_on_top(conn, screen.root, win_id)?;
+    if options.managed {
+        managed_hints(conn, win_id, rect)?;
+    }
+
+    Ok(OverlayWindow {
+        window: win_id,
+        visual,
+        depth: options.depth,
+        colormap,
+    })
+}
+
+/// Lets window managers ask to close the window, instead of killing the client. Returns
+/// the `WM_DELETE_WINDOW` atom of their requests.
+pub fn set_wm_protocols<Conn>(conn: &Conn, win_id: u32) -> Result<Atom>
+where
+    Conn: Connection,
+{
+    let [wm_protocols, wm_delete_window] =
+        intern_atoms(conn, ["WM_PROTOCOLS", "WM_DELETE_WINDOW"])?;
+    conn.change_property32(
+        PropMode::REPLACE,
+        win_id,
+        wm_protocols,
+        AtomEnum::ATOM,
+        &[wm_delete_window],
+    )?;
+    Ok(wm_delete_window)
+}
+
+/// Reserves screen space like a panel, `None` releases it. Window managers only read
+/// struts from the windows they manage.
+pub fn set_strut<Conn>(conn: &Conn, win_id: u32, strut_partial: Option<[u32; 12]>) -> Result<()>
+where
+    Conn: Connection,
+{
+    let [strut_atom, strut_partial_atom] =
+        intern_atoms(conn, ["_NET_WM_STRUT", "_NET_WM_STRUT_PARTIAL"])?;
+
+    match strut_partial {
+        Some(strut_partial) => {
+            // older window managers only know the 4 first values
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                strut_atom,
+                AtomEnum::CARDINAL,
+                &strut_partial[..4],
+            )?;
+            conn.change_property32(
+                PropMode::REPLACE,
+                win_id,
+                strut_partial_atom,
+                AtomEnum::CARDINAL,
+                &strut_partial,
+            )?;
+        }
+        None => {
+            conn.delete_property(win_id, strut_atom)?;
+            conn.delete_property(win_id, strut_partial_atom)?;
+        }
+    }
+
+    Ok(())
+}
 
-    Ok(win_id)
+/// Dims the whole window, from 0 transparent to 1 opaque, done by the compositing manager.
+pub fn set_window_opacity<Conn>(conn: &Conn, win_id: u32, opacity: f32) -> Result<()>
+where
+    Conn: Connection,
+{
+    let [opacity_atom] = intern_atoms(conn, [OPACITY])?;
+    write_window_opacity(conn, win_id, opacity_atom, opacity)
+}
+
+/// `_NET_WM_WINDOW_OPACITY`, for [`write_window_opacity`]
+pub(crate) const OPACITY: &str = "_NET_WM_WINDOW_OPACITY";
+
+/// [`set_window_opacity`] with the atom interned beforehand, without a round trip
+pub(crate) fn write_window_opacity<Conn>(
+    conn: &Conn,
+    win_id: u32,
+    opacity_atom: Atom,
+    opacity: f32,
+) -> Result<()>
+where
+    Conn: Connection,
+{
+    if opacity >= 1. {
+        // the default, compositors may then skip blending the window
+        conn.delete_property(win_id, opacity_atom)?;
+    } else {
+        let opacity = (f64::from(opacity.max(0.)) * f64::from(u32::MAX)) as u32;
+        conn.change_property32(
+            PropMode::REPLACE,
+            win_id,
+            opacity_atom,
+            AtomEnum::CARDINAL,
+            &[opacity],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Asks compositors to blur what is behind `rects` of the window, or nothing if empty.
+/// KDE reads `_KDE_NET_WM_BLUR_BEHIND_REGION`, an empty one meaning the whole window.
+/// The parts of `rects` left or above the window are cut off.
+pub fn set_blur_region<Conn>(conn: &Conn, win_id: u32, rects: &[Rectangle]) -> Result<()>
+where
+    Conn: Connection,
+{
+    let [blur_atom] = intern_atoms(conn, [BLUR_REGION])?;
+    write_blur_region(conn, win_id, blur_atom, rects)
+}
+
+/// `_KDE_NET_WM_BLUR_BEHIND_REGION`, for [`write_blur_region`]
+pub(crate) const BLUR_REGION: &str = "_KDE_NET_WM_BLUR_BEHIND_REGION";
+
+/// [`set_blur_region`] with the atom interned beforehand, without a round trip
+pub(crate) fn write_blur_region<Conn>(
+    conn: &Conn,
+    win_id: u32,
+    blur_atom: Atom,
+    rects: &[Rectangle],
+) -> Result<()>
+where
+    Conn: Connection,
+{
+    let region = blur_region(rects);
+    if region.is_empty() {
+        conn.delete_property(win_id, blur_atom)?;
+    } else {
+        conn.change_property32(
+            PropMode::REPLACE,
+            win_id,
+            blur_atom,
+            AtomEnum::CARDINAL,
+            &region,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// `rects` as x, y, width and height cardinals, cut to the positive coordinates the property
+/// holds, without those left empty
+fn blur_region(rects: &[Rectangle]) -> Vec<u32> {
+    rects
+        .iter()
+        .filter_map(|rect| {
+            let cut = |start: i16, len: u16| {
+                let len = u32::from(len).saturating_sub(start.min(0).unsigned_abs().into());
+                (start.max(0) as u32, len)
+            };
+            let ((x, width), (y, height)) = (cut(rect.x, rect.width), cut(rect.y, rect.height));
+            (width > 0 && height > 0).then_some([x, y, width, height])
+        })
+        .flatten()
+        .collect()
+}
+
+/// asks the window manager for an undecorated window at `rect`, not focused when mapped
+fn managed_hints<Conn>(conn: &Conn, win_id: u32, rect: Rectangle) -> Result<()>
+where
+    Conn: Connection,
+{
+    let [motif_hints, user_time] = intern_atoms(conn, ["_MOTIF_WM_HINTS", "_NET_WM_USER_TIME"])?;
+
+    // flags: decorations, functions, decorations: none, input mode, status
+    const MWM_HINTS_DECORATIONS: u32 = 1 << 1;
+    conn.change_property32(
+        PropMode::REPLACE,
+        win_id,
+        motif_hints,
+        motif_hints,
+        &[MWM_HINTS_DECORATIONS, 0, 0, 0, 0],
+    )?;
+
+    let mut size_hints = WmSizeHints::new();
+    size_hints.position = Some((
+        WmSizeHintsSpecification::UserSpecified,
+        rect.x.into(),
+        rect.y.into(),
+    ));
+    size_hints.size = Some((
+        WmSizeHintsSpecification::UserSpecified,
+        rect.width.into(),
+        rect.height.into(),
+    ));
+    size_hints.set_normal_hints(conn, win_id)?;
+
+    // 0 prevents focusing the window when it is mapped
+    conn.change_property32(
+        PropMode::REPLACE,
+        win_id,
+        user_time,
+        AtomEnum::CARDINAL,
+        &[0],
+    )?;
+
+    Ok(())
+}
+
+/// Adds `mask` to the events of `window` this client selected, keeping the others, e.g. on
+/// the root window shared by several parts of a program.
+pub fn select_more_events<Conn>(conn: &Conn, window: Window, mask: EventMask) -> Result<()>
+where
+    Conn: Connection,
+{
+    let selected = conn.get_window_attributes(window)?.reply()?.your_event_mask;
+    if !selected.contains(mask) {
+        conn.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new().event_mask(selected | mask),
+        )?;
+    }
+    Ok(())
+}
+
+/// Removes `mask` from the events of `window` this client selected, keeping the others, e.g.
+/// on the window of another client followed by several parts of a program.
+pub fn unselect_events<Conn>(conn: &Conn, window: Window, mask: EventMask) -> Result<()>
+where
+    Conn: Connection,
+{
+    let selected = conn.get_window_attributes(window)?.reply()?.your_event_mask;
+    if selected.intersects(mask) {
+        conn.change_window_attributes(
+            window,
+            &ChangeWindowAttributesAux::new()
+                .event_mask(EventMask::from(u32::from(selected) & !u32::from(mask))),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// an Xauthority entry, as `xauth` writes them
+    fn entry(family: u16, address: &[u8], number: &[u8], cookie: &[u8]) -> Vec<u8> {
+        let mut entry = family.to_be_bytes().to_vec();
+        for field in [address, number, b"MIT-MAGIC-COOKIE-1", cookie] {
+            entry.extend((field.len() as u16).to_be_bytes());
+            entry.extend(field);
+        }
+        entry
+    }
+
+    #[test]
+    fn xauthority_entries() {
+        let path = env::temp_dir().join(format!("egui-overlay-{}-xauthority", std::process::id()));
+        let file = [
+            entry(FAMILY_LOCAL, b"laptop", b"0", b"laptop"),
+            entry(FAMILY_LOCAL, b"desktop", b"0", b"desktop"),
+            entry(FAMILY_INTERNET, &[10, 0, 0, 2], b"0", b"remote"),
+            entry(FAMILY_WILD, b"", b"5", b"wild"),
+        ]
+        .concat();
+        fs::write(&path, file).unwrap();
+        let cookie = |family, address: &[u8], number| {
+            xauthority_entry(&path, &[(family, address.to_vec())], number)
+                .unwrap()
+                .map(|(name, data)| {
+                    assert_eq!(name, b"MIT-MAGIC-COOKIE-1");
+                    data
+                })
+        };
+
+        // the entry of the host, even after another
+        assert_eq!(cookie(FAMILY_LOCAL, b"desktop", 0).unwrap(), b"desktop");
+        assert_eq!(
+            cookie(FAMILY_INTERNET, &[10, 0, 0, 2], 0).unwrap(),
+            b"remote"
+        );
+        // never the cookie of another host
+        assert_eq!(cookie(FAMILY_LOCAL, b"server", 0), None);
+        assert_eq!(cookie(FAMILY_INTERNET, &[10, 0, 0, 3], 0), None);
+        assert_eq!(xauthority_entry(&path, &[], 0).unwrap(), None);
+        assert_eq!(cookie(FAMILY_LOCAL, b"desktop", 1), None);
+        // wildcards match any host
+        assert_eq!(cookie(FAMILY_LOCAL, b"server", 5).unwrap(), b"wild");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn blur_regions() {
+        let rect = |x, y, width, height| Rectangle {
+            x,
+            y,
+            width,
+            height,
+        };
+        assert!(blur_region(&[]).is_empty());
+        assert_eq!(
+            blur_region(&[rect(10, 20, 30, 40), rect(0, 0, 5, 5)]),
+            [10, 20, 30, 40, 0, 0, 5, 5]
+        );
+        // cut at the window edges rather than wrapped around
+        assert_eq!(blur_region(&[rect(-10, -5, 30, 40)]), [0, 0, 20, 35]);
+        assert_eq!(blur_region(&[rect(-32768, 0, 65535, 1)]), [0, 0, 32767, 1]);
+        // entirely outside, dropped
+        assert!(blur_region(&[rect(-30, 0, 30, 40), rect(5, -50, 10, 20)]).is_empty());
+    }
+
+    #[test]
+    fn xauthority_host_addresses() {
+        let hostname = gethostname().unwrap().into_vec();
+        let display = |name: &str| parse_display(Some(name)).unwrap();
+        assert_eq!(
+            xauthority_addresses(&display(":1")),
+            [(FAMILY_LOCAL, hostname.clone())]
+        );
+        assert_eq!(
+            xauthority_addresses(&display("127.0.0.1:1")),
+            [(FAMILY_LOCAL, hostname)]
+        );
+        assert_eq!(
+            xauthority_addresses(&display("10.1.2.3:0")),
+            [(FAMILY_INTERNET, vec![10, 1, 2, 3])]
+        );
+        assert_eq!(
+            xauthority_addresses(&display("[fd00::1]:0")),
+            [(
+                FAMILY_INTERNET6,
+                "fd00::1"
+                    .parse::<std::net::Ipv6Addr>()
+                    .unwrap()
+                    .octets()
+                    .to_vec()
+            )]
+        );
+    }
 }
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use tracing::error;
+use x11rb::x11_utils::X11Error;
+
+/// An X protocol error, caused by a request sent without waiting for its reply.
+///
+/// Requests waiting for their reply return their errors instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XError {
+    /// E.g. `Window` or `Match`.
+    pub kind: String,
+    /// The failed request, e.g. `ConfigureWindow` or `XFIXES::DestroyRegion`.
+    pub request: String,
+    /// Of the failed request, its low 16 bits.
+    pub sequence: u16,
+    /// The resource id or value the server rejected.
+    pub bad_value: u32,
+}
+
+impl From<&X11Error> for XError {
+    fn from(error: &X11Error) -> Self {
+        let name = error.request_name.map_or_else(
+            || format!("{}.{}", error.major_opcode, error.minor_opcode),
+            str::to_owned,
+        );
+        let request = match &error.extension_name {
+            Some(extension) => format!("{}::{}", extension, name),
+            None => name,
+        };
+        Self {
+            kind: format!("{:?}", error.error_kind),
+            request,
+            sequence: error.sequence,
+            bad_value: error.bad_value,
+        }
+    }
+}
+
+impl fmt::Display for XError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "X error {} in {} (sequence {}, bad value {:#x})",
+            self.kind, self.request, self.sequence, self.bad_value
+        )
+    }
+}
+
+pub(crate) type XErrorHandler = Box<dyn FnMut(&XError)>;
+
+pub(crate) fn log_x_error(error: &XError) {
+    error!("{}", error);
+}
+
+#[cfg(test)]
+mod tests {
+    use x11rb::protocol::ErrorKind;
+
+    use super::*;
+
+    fn x11_error(extension_name: Option<&str>, request_name: Option<&'static str>) -> X11Error {
+        X11Error {
+            error_kind: ErrorKind::Window,
+            error_code: 3,
+            sequence: 42,
+            bad_value: 0x0120_0003,
+            minor_opcode: 0,
+            major_opcode: 12,
+            extension_name: extension_name.map(str::to_owned),
+            request_name,
+        }
+    }
+
+    #[test]
+    fn core_request_errors() {
+        let error = XError::from(&x11_error(None, Some("ConfigureWindow")));
+        assert_eq!(
+            error,
+            XError {
+                kind: "Window".to_owned(),
+                request: "ConfigureWindow".to_owned(),
+                sequence: 42,
+                bad_value: 0x0120_0003,
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "X error Window in ConfigureWindow (sequence 42, bad value 0x1200003)"
+        );
+    }
+
+    #[test]
+    fn extension_request_errors() {
+        let error = XError::from(&x11_error(Some("XFIXES"), Some("DestroyRegion")));
+        assert_eq!(error.request, "XFIXES::DestroyRegion");
+    }
+
+    #[test]
+    fn unknown_requests_by_opcode() {
+        let mut x11_error = x11_error(None, None);
+        x11_error.major_opcode = 140;
+        x11_error.minor_opcode = 7;
+        assert_eq!(XError::from(&x11_error).request, "140.7");
+    }
+}
@@ -0,0 +1,234 @@
+//! Runs overlays against a virtual X server, `Xvfb`, and pokes at them from another client.
+//!
+//! The server is started once per test binary, on a free display which becomes `$DISPLAY`.
+//! Tests holding a [`Server`] run one at a time, as they share the screen. Without `Xvfb`
+//! installed, [`Server::start`] returns `None` and the tests pass without checking anything,
+//! unless `REQUIRE_XVFB` is set, e.g. on CI: they then fail.
+//! `Xephyr` works too, to watch the tests run: set `HARNESS_XSERVER=Xephyr`.
+//!
+//! The overlays render with whatever wgpu finds on the machine, e.g. llvmpipe through GL.
+
+#![allow(dead_code)]
+
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use x11rb::connection::Connection;
+use x11rb::properties::WmClass;
+use x11rb::protocol::shape::{ConnectionExt as _, SK};
+use x11rb::protocol::xproto::{
+    ConnectionExt as _, CreateWindowAux, MapState, Rectangle, Window, WindowClass,
+    BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT, MOTION_NOTIFY_EVENT,
+};
+use x11rb::protocol::xtest::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+use x11rb::CURRENT_TIME;
+
+/// How long to wait for the overlay thread to catch up.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+const SCREEN: &str = "1280x720x24";
+
+struct Display {
+    /// killed with the test binary, as the process exit closes its last client
+    _server: Child,
+    /// kept open, not to reset the server between tests
+    conn: RustConnection,
+    root: Window,
+}
+
+/// A test's exclusive access to the X server.
+pub struct Server {
+    display: MutexGuard<'static, Display>,
+}
+
+impl Server {
+    /// The shared server, once the previous test using it is done.
+    pub fn start() -> Option<Self> {
+        static DISPLAY: OnceLock<Option<Mutex<Display>>> = OnceLock::new();
+        let display = DISPLAY
+            .get_or_init(|| spawn_server().map(Mutex::new))
+            .as_ref();
+        let Some(display) = display else {
+            // a skip must not pass for a success there
+            if std::env::var_os("REQUIRE_XVFB").is_some_and(|require| !require.is_empty()) {
+                panic!("REQUIRE_XVFB is set, but no X server could be started");
+            }
+            return None;
+        };
+        // a failed test poisons the lock, the next ones can still use the server
+        let display = display.lock().unwrap_or_else(|e| e.into_inner());
+        Some(Self { display })
+    }
+
+    pub fn conn(&self) -> &RustConnection {
+        &self.display.conn
+    }
+
+    pub fn root(&self) -> Window {
+        self.display.root
+    }
+
+    /// The top-level window whose `WM_CLASS` class is `class`, once mapped.
+    pub fn wait_for_window(&self, class: &str) -> Option<Window> {
+        let mut found = None;
+        wait_until(|| {
+            found = self.find_window(class);
+            found.is_some()
+        });
+        found
+    }
+
+    pub fn find_window(&self, class: &str) -> Option<Window> {
+        self.children().into_iter().find(|&window| {
+            let attributes = self.conn().get_window_attributes(window).unwrap().reply();
+            let class_matches = WmClass::get(self.conn(), window)
+                .unwrap()
+                .reply()
+                .is_ok_and(|wm_class| wm_class.class() == class.as_bytes());
+            class_matches && attributes.is_ok_and(|a| a.map_state == MapState::VIEWABLE)
+        })
+    }
+
+    /// The top-level windows, bottom first.
+    pub fn children(&self) -> Vec<Window> {
+        self.conn()
+            .query_tree(self.root())
+            .unwrap()
+            .reply()
+            .unwrap()
+            .children
+    }
+
+    pub fn geometry(&self, window: Window) -> Rectangle {
+        let geometry = self.conn().get_geometry(window).unwrap().reply().unwrap();
+        Rectangle {
+            x: geometry.x,
+            y: geometry.y,
+            width: geometry.width,
+            height: geometry.height,
+        }
+    }
+
+    pub fn override_redirect(&self, window: Window) -> bool {
+        self.conn()
+            .get_window_attributes(window)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .override_redirect
+    }
+
+    /// The input shape of `window`, relative to it.
+    pub fn input_region(&self, window: Window) -> Vec<Rectangle> {
+        self.conn()
+            .shape_get_rectangles(window, SK::INPUT)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .rectangles
+    }
+
+    /// Maps a plain window at `rect`, as another program would.
+    pub fn map_window(&self, rect: Rectangle) -> Window {
+        let conn = self.conn();
+        let window = conn.generate_id().unwrap();
+        conn.create_window(
+            x11rb::COPY_DEPTH_FROM_PARENT,
+            window,
+            self.root(),
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &CreateWindowAux::new().background_pixel(0xffffff),
+        )
+        .unwrap();
+        conn.map_window(window).unwrap();
+        conn.flush().unwrap();
+        window
+    }
+
+    pub fn destroy_window(&self, window: Window) {
+        self.conn().destroy_window(window).unwrap();
+        self.conn().flush().unwrap();
+    }
+
+    /// Moves the pointer to `(x, y)` of the screen, through XTest.
+    pub fn move_pointer(&self, x: i16, y: i16) {
+        self.conn()
+            .xtest_fake_input(MOTION_NOTIFY_EVENT, 0, CURRENT_TIME, self.root(), x, y, 0)
+            .unwrap();
+        self.conn().flush().unwrap();
+    }
+
+    /// Left clicks at `(x, y)` of the screen, through XTest.
+    pub fn click(&self, x: i16, y: i16) {
+        self.move_pointer(x, y);
+        for event in [BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT] {
+            self.conn()
+                .xtest_fake_input(event, 1, CURRENT_TIME, self.root(), 0, 0, 0)
+                .unwrap();
+        }
+        self.conn().flush().unwrap();
+    }
+}
+
+/// Polls `condition` until it holds, false after [`TIMEOUT`].
+pub fn wait_until(mut condition: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < TIMEOUT {
+        if condition() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+/// Starts the server on a free display, which it reports through `-displayfd`.
+fn spawn_server() -> Option<Display> {
+    let program = std::env::var("HARNESS_XSERVER").unwrap_or_else(|_| "Xvfb".into());
+    let mut command = Command::new(&program);
+    match program.as_str() {
+        "Xephyr" => command.args(["-screen", "1280x720"]),
+        _ => command.args(["-screen", "0", SCREEN]),
+    };
+    let mut server = match command
+        .args(["-displayfd", "1", "-nolisten", "tcp", "+extension", "XTEST"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("{} unavailable, skipping the X tests: {}", program, e);
+            return None;
+        }
+    };
+
+    // the display number then a newline, once ready
+    let mut stdout = server.stdout.take()?;
+    let mut number = String::new();
+    let mut byte = [0];
+    while stdout.read(&mut byte).ok()? == 1 && byte[0] != b'\n' {
+        number.push(byte[0] as char);
+    }
+    let display = format!(":{}", number.trim());
+    // before any overlay connects, the tests then run their overlays on the server
+    std::env::set_var("DISPLAY", &display);
+
+    let (conn, screen_num) = RustConnection::connect(Some(&display)).ok()?;
+    let root = conn.setup().roots[screen_num].root;
+    Some(Display {
+        _server: server,
+        conn,
+        root,
+    })
+}
@@ -0,0 +1,119 @@
+//! Frames rendered offscreen, without an X server.
+
+use egui_wgpu_x11::egui::{self, pos2, vec2, Color32, Pos2, Rect};
+use egui_wgpu_x11::geometry::Viewport;
+use egui_wgpu_x11::{GpuOptions, HeadlessOverlay, OverlayApp};
+
+/// Paints a red square near the top-left corner of the egui screen.
+struct Square;
+
+impl Square {
+    /// in points
+    fn rect(ctx: &egui::Context) -> Rect {
+        let screen = ctx.input().screen_rect();
+        Rect::from_min_size(screen.min + vec2(10., 10.), vec2(40., 40.))
+    }
+}
+
+impl OverlayApp for Square {
+    fn ui(&mut self, ctx: &egui::Context) {
+        ctx.layer_painter(egui::LayerId::background()).rect_filled(
+            Self::rect(ctx),
+            0.,
+            Color32::RED,
+        );
+    }
+}
+
+/// On the GPU, or the software adapter. Without either, the test passes without checking
+/// anything, unless `REQUIRE_GPU` is set, e.g. on CI.
+fn overlay(width: u32, height: u32) -> Option<HeadlessOverlay> {
+    let fallback = GpuOptions {
+        force_fallback_adapter: true,
+        ..Default::default()
+    };
+    let overlay = HeadlessOverlay::new(width, height)
+        .or_else(|_| HeadlessOverlay::with_gpu(width, height, fallback));
+    match overlay {
+        Ok(overlay) => Some(overlay),
+        Err(e) if std::env::var_os("REQUIRE_GPU").is_some_and(|require| !require.is_empty()) => {
+            panic!("REQUIRE_GPU is set, but no adapter was found: {}", e)
+        }
+        Err(e) => {
+            eprintln!("no adapter, skipping the headless tests: {}", e);
+            None
+        }
+    }
+}
+
+/// `frame` at `pos`, in pixels
+fn pixel(frame: &egui::ColorImage, pos: Pos2) -> Color32 {
+    frame.pixels[pos.y as usize * frame.size[0] + pos.x as usize]
+}
+
+/// give or take the blending and format conversions of the backend
+fn is_red(color: Color32) -> bool {
+    let [r, g, b, a] = color.to_array();
+    r > 240 && g < 16 && b < 16 && a > 240
+}
+
+#[test]
+fn renders_a_frame() {
+    let Some(mut overlay) = overlay(320, 180) else {
+        return;
+    };
+    overlay.render(&mut Square).unwrap();
+    let frame = overlay.capture_frame().unwrap();
+
+    assert_eq!(frame.size, [320, 180]);
+    let square = Square::rect(overlay.context());
+    let center = pixel(&frame, square.center());
+    assert!(is_red(center), "{:?}", center);
+    // the translucent background around it
+    let outside = pixel(&frame, square.right_bottom() + vec2(20., 20.));
+    assert!(outside.a() < 128, "{:?}", outside);
+}
+
+#[test]
+fn renders_at_the_new_size_and_scale() {
+    let Some(mut overlay) = overlay(320, 180) else {
+        return;
+    };
+    overlay.render(&mut Square).unwrap();
+    overlay.resize(640, 360);
+    overlay.set_pixels_per_point(2.);
+    overlay.render(&mut Square).unwrap();
+    let frame = overlay.capture_frame().unwrap();
+
+    assert_eq!(frame.size, [640, 360]);
+    // twice as large in pixels: inside its bottom-right corner only at this scale
+    let corner = (Square::rect(overlay.context()).right_bottom() - vec2(2., 2.)).to_vec2() * 2.;
+    let inside = pixel(&frame, corner.to_pos2());
+    assert!(is_red(inside), "{:?}", inside);
+    let outside = pixel(&frame, corner.to_pos2() + vec2(8., 8.));
+    assert!(!is_red(outside), "{:?}", outside);
+}
+
+#[test]
+fn renders_and_points_in_the_viewport() {
+    let Some(mut overlay) = overlay(320, 180) else {
+        return;
+    };
+    overlay.set_viewport(Viewport::inset(20).scale(2.));
+    // in the points of the whole frame, zoomed
+    let pointer = pos2(30., 30.);
+    overlay.push_event(egui::Event::PointerMoved(pointer));
+    overlay.render(&mut Square).unwrap();
+    let frame = overlay.capture_frame().unwrap();
+
+    let ctx = overlay.context();
+    assert_eq!(ctx.input().screen_rect().min, pos2(10., 10.));
+    let square = Square::rect(ctx);
+    assert_eq!(ctx.input().pointer.hover_pos(), Some(pointer));
+    assert!(square.contains(pointer));
+    let center = pixel(&frame, (square.center().to_vec2() * 2.).to_pos2());
+    assert!(is_red(center), "{:?}", center);
+    // the inset stays clear
+    let inset = pixel(&frame, pos2(10., 10.));
+    assert!(!is_red(inset) && inset.a() < 128, "{:?}", inset);
+}
@@ -0,0 +1,224 @@
+//! The overlay on a virtual X server, see the [`harness`].
+
+mod harness;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+
+use egui_wgpu_x11::egui::{self, pos2, vec2, Color32, ColorImage, Rect};
+use egui_wgpu_x11::geometry::Geometry;
+use egui_wgpu_x11::{Handle, InputShape, Overlay, OverlayApp, OverlayBuilder};
+use harness::{wait_until, Server, TIMEOUT};
+use x11rb::protocol::xproto::{Rectangle, Window};
+
+/// where the probe paints its red square, in points, at a scale factor of 1
+const SQUARE: Rect = Rect::from_min_max(pos2(200., 100.), pos2(250., 150.));
+const BUTTON_POS: egui::Pos2 = pos2(60., 60.);
+/// where the overlays are placed on the screen
+const RECT: Rectangle = Rectangle {
+    x: 100,
+    y: 100,
+    width: 400,
+    height: 300,
+};
+
+/// Paints a red square and a button, reports clicks and captured frames to the test.
+struct Probe {
+    clicked: Arc<AtomicBool>,
+    frames: Sender<ColorImage>,
+    capture: bool,
+}
+
+impl OverlayApp for Probe {
+    fn ui(&mut self, ctx: &egui::Context) {
+        ctx.layer_painter(egui::LayerId::background())
+            .rect_filled(SQUARE, 0., Color32::RED);
+        egui::Area::new("probe")
+            .fixed_pos(BUTTON_POS)
+            .show(ctx, |ui| {
+                if ui.button("Click").clicked() {
+                    self.clicked.store(true, Ordering::SeqCst);
+                }
+            });
+    }
+
+    fn update(&mut self, overlay: &mut Overlay) -> anyhow::Result<()> {
+        if self.capture {
+            self.capture = false;
+            self.frames.send(overlay.capture_frame()?)?;
+        }
+        Ok(())
+    }
+}
+
+struct Spawned {
+    handle: Handle<Probe>,
+    window: Window,
+    clicked: Arc<AtomicBool>,
+    frames: Receiver<ColorImage>,
+}
+
+/// Runs a probe in an overlay configured by `builder`, placed at [`RECT`], with the test name
+/// as `WM_CLASS` to find its window.
+fn spawn(server: &Server, name: &str, builder: OverlayBuilder) -> Spawned {
+    let clicked = Arc::new(AtomicBool::new(false));
+    let (sender, frames) = mpsc::channel();
+    let probe_clicked = clicked.clone();
+    let handle = builder
+        .geometry(Geometry::absolute(
+            RECT.x as u16,
+            RECT.y as u16,
+            RECT.width,
+            RECT.height,
+        ))
+        .scale_factor(1.)
+        .class(name, name)
+        .spawn(move || Probe {
+            clicked: probe_clicked,
+            frames: sender,
+            capture: false,
+        })
+        .unwrap();
+    let window = server
+        .wait_for_window(name)
+        .expect("the overlay window is not mapped");
+    Spawned {
+        handle,
+        window,
+        clicked,
+        frames,
+    }
+}
+
+#[test]
+fn creates_an_override_redirect_window() {
+    let Some(server) = Server::start() else {
+        return;
+    };
+    let overlay = spawn(&server, "creates", OverlayBuilder::new());
+
+    assert_eq!(server.geometry(overlay.window), RECT);
+    assert!(server.override_redirect(overlay.window));
+
+    overlay.handle.close().unwrap();
+    assert!(server.find_window("creates").is_none());
+}
+
+#[test]
+fn lets_input_through_by_default() {
+    let Some(server) = Server::start() else {
+        return;
+    };
+    let overlay = spawn(&server, "passthrough", OverlayBuilder::new());
+
+    assert!(server.input_region(overlay.window).is_empty());
+
+    overlay.handle.close().unwrap();
+}
+
+#[test]
+fn window_input_shape_covers_the_window() {
+    let Some(server) = Server::start() else {
+        return;
+    };
+    let builder = OverlayBuilder::new().input_shape(InputShape::Window);
+    let overlay = spawn(&server, "window_shape", builder);
+
+    let whole = Rectangle { x: 0, y: 0, ..RECT };
+    assert!(wait_until(|| server.input_region(overlay.window) == [whole]));
+
+    overlay.handle.close().unwrap();
+}
+
+#[test]
+fn content_input_shape_follows_the_ui() {
+    let Some(server) = Server::start() else {
+        return;
+    };
+    let builder = OverlayBuilder::new().input_shape(InputShape::Content);
+    let overlay = spawn(&server, "content_shape", builder);
+
+    let contains = |rects: &[Rectangle], x: i16, y: i16| {
+        rects.iter().any(|rect| {
+            (rect.x..rect.x + rect.width as i16).contains(&x)
+                && (rect.y..rect.y + rect.height as i16).contains(&y)
+        })
+    };
+    let center = SQUARE.center();
+    assert!(wait_until(|| {
+        let rects = server.input_region(overlay.window);
+        contains(&rects, center.x as i16, center.y as i16)
+    }));
+    // the corners are left empty by the probe
+    let rects = server.input_region(overlay.window);
+    assert!(!contains(&rects, 5, 5));
+    assert!(!contains(
+        &rects,
+        RECT.width as i16 - 5,
+        RECT.height as i16 - 5
+    ));
+
+    overlay.handle.close().unwrap();
+}
+
+#[test]
+fn stays_above_windows_mapped_later() {
+    let Some(server) = Server::start() else {
+        return;
+    };
+    let overlay = spawn(&server, "restacking", OverlayBuilder::new());
+
+    let other = server.map_window(Rectangle {
+        x: 50,
+        y: 50,
+        width: 300,
+        height: 300,
+    });
+    assert!(wait_until(
+        || server.children().last() == Some(&overlay.window)
+    ));
+
+    server.destroy_window(other);
+    overlay.handle.close().unwrap();
+}
+
+#[test]
+fn clicks_reach_the_ui() {
+    let Some(server) = Server::start() else {
+        return;
+    };
+    let builder = OverlayBuilder::new().input_shape(InputShape::Content);
+    let overlay = spawn(&server, "clicks", builder);
+
+    // inside the button, once the content shape lets the pointer hit it
+    let target = BUTTON_POS + vec2(10., 8.);
+    let (x, y) = (RECT.x + target.x as i16, RECT.y + target.y as i16);
+    assert!(wait_until(|| !server
+        .input_region(overlay.window)
+        .is_empty()));
+    server.click(x, y);
+
+    assert!(wait_until(|| overlay.clicked.load(Ordering::SeqCst)));
+    overlay.handle.close().unwrap();
+}
+
+#[test]
+fn captures_the_rendered_frame() {
+    let Some(server) = Server::start() else {
+        return;
+    };
+    let overlay = spawn(&server, "capture", OverlayBuilder::new());
+
+    overlay.handle.update(|probe| probe.capture = true);
+    let frame = overlay.frames.recv_timeout(TIMEOUT).unwrap();
+
+    assert_eq!(frame.size, [RECT.width as usize, RECT.height as usize]);
+    let center = SQUARE.center();
+    let pixel = frame.pixels[center.y as usize * frame.size[0] + center.x as usize];
+    // give or take the blending and format conversions of the backend
+    let [r, g, b, a] = pixel.to_array();
+    assert!(r > 240 && g < 16 && b < 16 && a > 240, "{:?}", pixel);
+
+    overlay.handle.close().unwrap();
+}